@@ -0,0 +1,186 @@
+//! Request throughput, from encoding events to decoding results.
+//!
+//! Run with `cargo bench --features bench`. Each benchmark submits
+//! transfers between accounts that don't exist, so the cluster rejects
+//! every event and the client decodes a result for each, without the
+//! cluster's state growing between iterations.
+//!
+//! Benchmarks run against the in-memory client and the simulated cluster,
+//! and against a real cluster if `TIGERBEETLE_ADDRESSES` is set (with
+//! cluster id 0). `TIGERBEETLE_BENCH_SECONDS` sets how long each runs.
+//!
+//! Results can be saved as a baseline and compared against later:
+//!
+//! ```text
+//! cargo bench --features bench --bench throughput -- --save-baseline main.txt
+//! cargo bench --features bench --bench throughput -- --baseline main.txt
+//! ```
+
+use futures::executor::block_on;
+use futures::future::join_all;
+
+use tigerbeetle as tb;
+use tigerbeetle::sim::{Faults, SimulatedCluster};
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::time::{Duration, Instant};
+
+/// Requests submitted at once by the pipelined benchmarks.
+const PIPELINE_DEPTH: usize = 64;
+
+struct Bench {
+    duration: Duration,
+    baseline: HashMap<String, f64>,
+    results: Vec<(String, f64)>,
+    next_id: u128,
+}
+
+impl Bench {
+    fn transfers(&mut self, count: usize) -> Vec<tb::Transfer> {
+        (0..count)
+            .map(|_| {
+                self.next_id += 1;
+                tb::Transfer {
+                    id: self.next_id,
+                    debit_account_id: 1,
+                    credit_account_id: 2,
+                    amount: 1,
+                    ledger: 1,
+                    code: 1,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Run `iteration`, which processes `events` events, until the
+    /// benchmark's duration has passed, and report events per second.
+    fn run(&mut self, name: &str, events: usize, mut iteration: impl FnMut(&mut Bench)) {
+        // Warm up.
+        iteration(self);
+
+        let started = Instant::now();
+        let mut iterations = 0;
+        while started.elapsed() < self.duration {
+            iteration(self);
+            iterations += 1;
+        }
+        let events_per_second = (iterations * events) as f64 / started.elapsed().as_secs_f64();
+
+        let change = match self.baseline.get(name) {
+            Some(baseline) => format!("{:+7.1}%", (events_per_second / baseline - 1.0) * 100.0),
+            None => String::new(),
+        };
+        println!("{name:<48} {events_per_second:>14.0} events/s {change}");
+        self.results.push((name.to_owned(), events_per_second));
+    }
+}
+
+fn create_transfers(bench: &mut Bench, client: &tb::Client, prefix: &str) {
+    for batch in [1, 100, tb::BATCH_MAX] {
+        bench.run(
+            &format!("{prefix}/create_transfers/{batch}"),
+            batch,
+            |bench| {
+                let transfers = bench.transfers(batch);
+                let results = block_on(client.create_transfers(&transfers)).unwrap();
+                assert_eq!(results.len(), batch);
+            },
+        );
+    }
+
+    let batch = 100;
+    bench.run(
+        &format!("{prefix}/create_transfers/{batch}x{PIPELINE_DEPTH}"),
+        batch * PIPELINE_DEPTH,
+        |bench| {
+            let requests: Vec<_> = (0..PIPELINE_DEPTH)
+                .map(|_| client.create_transfers(&bench.transfers(batch)))
+                .collect();
+            for results in block_on(join_all(requests)) {
+                assert_eq!(results.unwrap().len(), batch);
+            }
+        },
+    );
+}
+
+fn lookup_accounts(bench: &mut Bench, client: &tb::Client, prefix: &str) {
+    for batch in [1, tb::BATCH_MAX] {
+        let ids: Vec<u128> = (1..=batch as u128).collect();
+        bench.run(&format!("{prefix}/lookup_accounts/{batch}"), batch, |_| {
+            block_on(client.lookup_accounts(&ids)).unwrap();
+        });
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut baseline = None;
+    let mut save_baseline = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--baseline" => baseline = args.next(),
+            "--save-baseline" => save_baseline = args.next(),
+            // Passed by `cargo bench`.
+            _ => {}
+        }
+    }
+
+    let seconds = env::var("TIGERBEETLE_BENCH_SECONDS")
+        .ok()
+        .map(|seconds| seconds.parse().expect("TIGERBEETLE_BENCH_SECONDS"))
+        .unwrap_or(1.0);
+    let mut bench = Bench {
+        duration: Duration::from_secs_f64(seconds),
+        baseline: baseline
+            .map(|path| {
+                let baseline = fs::read_to_string(&path).expect("baseline");
+                baseline
+                    .lines()
+                    .filter_map(|line| {
+                        let (name, events_per_second) = line.split_once(' ')?;
+                        Some((name.to_owned(), events_per_second.parse().ok()?))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        results: Vec::new(),
+        next_id: 0,
+    };
+
+    let in_memory = tb::InMemoryClient::new();
+    for batch in [1, 100, tb::BATCH_MAX] {
+        bench.run(
+            &format!("in_memory/create_transfers/{batch}"),
+            batch,
+            |bench| {
+                let transfers = bench.transfers(batch);
+                block_on(in_memory.create_transfers(&transfers)).unwrap();
+            },
+        );
+    }
+
+    let cluster = SimulatedCluster::new(0, Faults::default());
+    let client = cluster.client();
+    create_transfers(&mut bench, &client, "sim");
+    lookup_accounts(&mut bench, &client, "sim");
+    block_on(client.close());
+
+    if let Ok(addresses) = env::var("TIGERBEETLE_ADDRESSES") {
+        let client = tb::Client::new(0, &addresses).expect("client");
+        create_transfers(&mut bench, &client, "native");
+        lookup_accounts(&mut bench, &client, "native");
+        block_on(client.close());
+    }
+
+    if let Some(path) = save_baseline {
+        let baseline: String = bench
+            .results
+            .iter()
+            .map(|(name, events_per_second)| format!("{name} {events_per_second}\n"))
+            .collect();
+        fs::write(path, baseline).expect("baseline");
+    }
+}
@@ -1,6 +1,20 @@
-use std::{env, path::Path};
+use std::{env, path::Path, process::Command};
 
 fn main() -> anyhow::Result<()> {
+    let minor = rustc_minor_version();
+    if minor >= Some(80) {
+        println!("cargo:rustc-check-cfg=cfg(tb_offset_of)");
+    }
+    // `core::mem::offset_of`, for the layout assertions in lib.rs.
+    if minor >= Some(77) {
+        println!("cargo:rustc-cfg=tb_offset_of");
+    }
+
+    // Without the client there is nothing to link.
+    if env::var_os("CARGO_FEATURE_STD").is_none() {
+        return Ok(());
+    }
+
     let cargo_manifest_dir = env::var("CARGO_MANIFEST_DIR")?;
 
     if !Path::new(&format!("{cargo_manifest_dir}/assets/tb_client.h")).try_exists()? {
@@ -63,3 +77,17 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// The minor version of the compiler, e.g. 77 for `rustc 1.77.2`.
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    version
+        .split_whitespace()
+        .nth(1)?
+        .split('.')
+        .nth(1)?
+        .parse()
+        .ok()
+}
@@ -6,6 +6,12 @@ const TmpTigerBeetle = @import("../../testing/tmp_tigerbeetle.zig");
 pub fn tests(shell: *Shell, gpa: std.mem.Allocator) !void {
     try shell.exec_zig("build clients:rust -Drelease", .{});
     try shell.exec("cargo test --all", .{});
+    try shell.exec("cargo check --no-default-features", .{});
+    try shell.exec("cargo test --features cli,exporter,gateway --bins", .{});
+    try shell.exec("cargo test --lib --features testing,cdc", .{});
+    try shell.exec("cargo test --lib --all-features", .{});
+    try shell.exec("cargo clippy --all-features --lib --bins -- -D warnings", .{});
+    try shell.exec("cargo check --features bench --bench throughput", .{});
     try shell.exec("cargo fmt --check", .{});
     try shell.exec("cargo clippy -- -D clippy::all", .{});
 
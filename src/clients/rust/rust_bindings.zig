@@ -73,7 +73,7 @@ fn resolve_rust_type(comptime Type: type) []const u8 {
 
             return comptime "*mut " ++ resolve_rust_type(info.child);
         },
-        .void, .@"opaque" => return "::std::os::raw::c_void",
+        .void, .@"opaque" => return "::core::ffi::c_void",
         else => @compileError("Unhandled type: " ++ @typeName(Type)),
     }
 }
@@ -232,10 +232,10 @@ pub fn main() !void {
         \\        client_out: *mut tb_client_t,
         \\        // 128-bit unsigned integer represented as a 16-byte little-endian array.
         \\        cluster_id: *const [u8; 16],
-        \\        address_ptr: *const ::std::os::raw::c_char,
+        \\        address_ptr: *const u8,
         \\        address_len: u32,
         \\        completion_ctx: usize,
-        \\        completion_callback: ::std::option::Option<
+        \\        completion_callback: ::core::option::Option<
         \\            unsafe extern "C" fn(
         \\                arg1: usize,
         \\                arg3: *mut tb_packet_t,
@@ -251,10 +251,10 @@ pub fn main() !void {
         \\        client_out: *mut tb_client_t,
         \\        // 128-bit unsigned integer represented as a 16-byte little-endian array.
         \\        cluster_id: *const [u8; 16],
-        \\        address_ptr: *const ::std::os::raw::c_char,
+        \\        address_ptr: *const u8,
         \\        address_len: u32,
         \\        completion_ctx: usize,
-        \\        completion_callback: ::std::option::Option<
+        \\        completion_callback: ::core::option::Option<
         \\            unsafe extern "C" fn(
         \\                arg1: usize,
         \\                arg3: *mut tb_packet_t,
@@ -289,7 +289,7 @@ pub fn main() !void {
         \\
         \\    // Registers or unregisters the application log callback.
         \\    pub fn register_log_callback(
-        \\        callback: ::std::option::Option<
+        \\        callback: ::core::option::Option<
         \\            unsafe extern "C" fn(
         \\                TB_LOG_LEVEL,
         \\                *const u8,
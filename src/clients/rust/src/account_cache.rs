@@ -0,0 +1,293 @@
+use super::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A cache of accounts looked up from a cluster, for accounts that are
+/// looked up often but change rarely, such as fee accounts.
+///
+/// Accounts are cached for a fixed time to live, and up to a capacity,
+/// beyond which the least recently used are evicted. Accounts that aren't
+/// found are not cached.
+///
+/// A cached account's balances are those at its lookup, so are stale once
+/// a transfer touches it. Pass the transfers the application creates to
+/// [`AccountCache::invalidate_transfers`] to look their accounts up again;
+/// the time to live bounds the staleness from transfers made elsewhere.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use tigerbeetle as tb;
+///
+/// # async fn example(client: &tb::Client, transfers: &[tb::Transfer]) -> Result<(), tb::PacketStatus> {
+/// let cache = tb::AccountCache::new(1024, Duration::from_secs(5));
+/// let fees = cache.lookup_accounts(client, &[42]).await?;
+///
+/// client.create_transfers(transfers).await?;
+/// cache.invalidate_transfers(transfers);
+/// # Ok(())
+/// # }
+/// ```
+pub struct AccountCache {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    accounts: HashMap<u128, CacheEntry>,
+    /// The ids of the cached accounts, least recently used first.
+    used: BTreeMap<u64, u128>,
+    use_next: u64,
+    /// Incremented by each invalidation, so that lookups made before it
+    /// aren't cached.
+    generation: u64,
+}
+
+struct CacheEntry {
+    account: Account,
+    expires: Instant,
+    used: u64,
+}
+
+impl AccountCache {
+    /// A cache of up to `capacity` accounts, each cached for `ttl`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize, ttl: Duration) -> AccountCache {
+        assert!(capacity > 0, "capacity must not be zero");
+        AccountCache {
+            capacity,
+            ttl,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Look up accounts, from the cache if they were looked up within the
+    /// time to live, and otherwise from the cluster.
+    ///
+    /// As with [`Client::lookup_accounts`], the accounts are returned in the
+    /// order of `ids`, and ids that aren't found are omitted.
+    pub async fn lookup_accounts(
+        &self,
+        client: &dyn TigerBeetleClient,
+        ids: &[u128],
+    ) -> Result<Vec<Account>, PacketStatus> {
+        let (mut found, missing, generation) = self.cached(ids, Instant::now());
+        if !missing.is_empty() {
+            let accounts = client.lookup_accounts(&missing).await?;
+            self.insert(&accounts, generation, Instant::now());
+            found.extend(accounts.iter().map(|account| (account.id, *account)));
+        }
+        Ok(ids.iter().filter_map(|id| found.get(id).copied()).collect())
+    }
+
+    /// Remove an account from the cache.
+    pub fn invalidate(&self, id: u128) {
+        let mut state = self.state();
+        state.generation += 1;
+        state.remove(id);
+    }
+
+    /// Remove the debit and credit accounts of `transfers` from the cache.
+    ///
+    /// A transfer that posts or voids a pending transfer may leave its
+    /// accounts zero; invalidate the pending transfer's accounts instead.
+    pub fn invalidate_transfers(&self, transfers: &[Transfer]) {
+        let mut state = self.state();
+        state.generation += 1;
+        for transfer in transfers {
+            state.remove(transfer.debit_account_id);
+            state.remove(transfer.credit_account_id);
+        }
+    }
+
+    /// Remove all accounts from the cache.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        let generation = state.generation + 1;
+        *state = CacheState {
+            generation,
+            ..CacheState::default()
+        };
+    }
+
+    /// The number of accounts cached, including expired ones not yet
+    /// removed.
+    pub fn len(&self) -> usize {
+        self.state().accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().expect("account cache")
+    }
+
+    /// The fresh accounts of `ids` in the cache, the ids to look up, and
+    /// the generation to insert their accounts at.
+    fn cached(&self, ids: &[u128], now: Instant) -> (HashMap<u128, Account>, Vec<u128>, u64) {
+        let mut state = self.state();
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+        for &id in ids {
+            match state.accounts.get(&id) {
+                Some(entry) if entry.expires > now => {
+                    found.insert(id, entry.account);
+                    state.touch(id);
+                }
+                Some(_) => {
+                    state.remove(id);
+                    missing.push(id);
+                }
+                None => missing.push(id),
+            }
+        }
+        (found, missing, state.generation)
+    }
+
+    /// Cache `accounts`, looked up at `generation`, unless the cache was
+    /// invalidated since.
+    fn insert(&self, accounts: &[Account], generation: u64, now: Instant) {
+        let mut state = self.state();
+        if state.generation != generation {
+            return;
+        }
+        for account in accounts {
+            state.remove(account.id);
+            let used = state.use_next;
+            state.use_next += 1;
+            state.used.insert(used, account.id);
+            state.accounts.insert(
+                account.id,
+                CacheEntry {
+                    account: *account,
+                    expires: now + self.ttl,
+                    used,
+                },
+            );
+            if state.accounts.len() > self.capacity {
+                let (_, &id) = state.used.iter().next().expect("least recently used");
+                state.remove(id);
+            }
+        }
+    }
+}
+
+impl CacheState {
+    fn remove(&mut self, id: u128) {
+        if let Some(entry) = self.accounts.remove(&id) {
+            self.used.remove(&entry.used);
+        }
+    }
+
+    fn touch(&mut self, id: u128) {
+        let used = self.use_next;
+        self.use_next += 1;
+        let entry = self.accounts.get_mut(&id).expect("cached");
+        self.used.remove(&entry.used);
+        entry.used = used;
+        self.used.insert(used, id);
+    }
+}
+
+impl fmt::Debug for AccountCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("AccountCache")
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    fn cluster() -> InMemoryClient {
+        let client = InMemoryClient::new();
+        let accounts: Vec<Account> = (1..=3)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+        client
+    }
+
+    fn ids(accounts: &[Account]) -> Vec<u128> {
+        accounts.iter().map(|account| account.id).collect()
+    }
+
+    #[test]
+    fn lookup() {
+        let client = cluster();
+        let cache = AccountCache::new(2, Duration::from_secs(60));
+        let found = block_on(cache.lookup_accounts(&client, &[2, 4, 1, 2])).unwrap();
+        assert_eq!(ids(&found), [2, 1, 2]);
+        assert_eq!(cache.len(), 2);
+
+        // Cached accounts are stale until invalidated.
+        let transfer = Transfer {
+            id: 1,
+            debit_account_id: 1,
+            credit_account_id: 3,
+            amount: 10,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        assert!(block_on(client.create_transfers(&[transfer]))
+            .unwrap()
+            .is_empty());
+        let found = block_on(cache.lookup_accounts(&client, &[1])).unwrap();
+        assert_eq!(found[0].debits_posted, 0);
+        cache.invalidate_transfers(&[transfer]);
+        let found = block_on(cache.lookup_accounts(&client, &[1])).unwrap();
+        assert_eq!(found[0].debits_posted, 10);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn eviction() {
+        let cache = AccountCache::new(2, Duration::from_secs(60));
+        let now = Instant::now();
+        let account = |id| Account {
+            id,
+            ..Default::default()
+        };
+        cache.insert(&[account(1), account(2)], 0, now);
+        // Account 2 is now the least recently used.
+        assert_eq!(cache.cached(&[1], now).1, []);
+        cache.insert(&[account(3)], 0, now);
+        assert_eq!(cache.cached(&[1, 2, 3], now).1, [2]);
+
+        let later = now + Duration::from_secs(60);
+        assert_eq!(cache.cached(&[1, 3], later).1, [1, 3]);
+        assert!(cache.is_empty());
+
+        // A lookup made before an invalidation isn't cached.
+        let (_, _, generation) = cache.cached(&[1], now);
+        cache.invalidate(1);
+        cache.insert(&[account(1)], generation, now);
+        assert!(cache.is_empty());
+    }
+}
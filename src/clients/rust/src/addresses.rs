@@ -0,0 +1,328 @@
+//! Parsing and validation of replica addresses.
+//!
+//! Clients are given the addresses of a cluster's replicas as a
+//! comma-separated string, such as `"10.0.0.1:3000,10.0.0.2:3000"`.
+//! [`parse`] splits and validates such a string, returning a structured
+//! [`ReplicaAddress`] for each replica.
+//!
+//! Each address is a host, a port, or a host and port separated by a colon.
+//! The host may be an IPv4 address, an IPv6 address in square brackets, or a
+//! DNS name. The default host is `127.0.0.1` and the default port is `3001`.
+//!
+//! ```
+//! use tigerbeetle::addresses::{self, Host, ReplicaAddress};
+//! use std::net::Ipv4Addr;
+//!
+//! let replicas = addresses::parse("3000,[::1]:3000,replica-2.tigerbeetle")?;
+//! assert_eq!(
+//!     replicas[0],
+//!     ReplicaAddress {
+//!         host: Host::Ip(Ipv4Addr::LOCALHOST.into()),
+//!         port: 3000,
+//!     }
+//! );
+//! assert_eq!(replicas[1].to_string(), "[::1]:3000");
+//! assert_eq!(replicas[2].host, Host::Dns("replica-2.tigerbeetle".to_owned()));
+//! assert_eq!(replicas[2].port, addresses::PORT_DEFAULT);
+//! # Ok::<(), addresses::AddressError>(())
+//! ```
+//!
+//! DNS names are resolved by the client each time it registers a session,
+//! since `tb_client` itself only accepts IP addresses. To follow replicas
+//! whose IP addresses change, see [`Client::refresh_addresses`].
+//!
+//! [`Client::refresh_addresses`]: crate::Client::refresh_addresses
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+
+/// The port of an address that only specifies a host.
+pub const PORT_DEFAULT: u16 = 3001;
+
+/// The address of a single replica.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ReplicaAddress {
+    pub host: Host,
+    pub port: u16,
+}
+
+/// The host of a [`ReplicaAddress`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Host {
+    Ip(IpAddr),
+    Dns(String),
+}
+
+impl ReplicaAddress {
+    /// Resolve the address to a socket address.
+    ///
+    /// IP addresses resolve to themselves. DNS names are resolved with the
+    /// system resolver, preferring IPv4 addresses.
+    pub fn resolve(&self) -> Result<SocketAddr, std::io::Error> {
+        match &self.host {
+            Host::Ip(ip) => Ok(SocketAddr::new(*ip, self.port)),
+            Host::Dns(name) => {
+                let resolved: Vec<SocketAddr> =
+                    (name.as_str(), self.port).to_socket_addrs()?.collect();
+                resolved
+                    .iter()
+                    .find(|address| address.is_ipv4())
+                    .or_else(|| resolved.first())
+                    .copied()
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("no addresses found for {name}"),
+                        )
+                    })
+            }
+        }
+    }
+}
+
+impl fmt::Display for ReplicaAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match &self.host {
+            Host::Ip(IpAddr::V6(ip)) => write!(f, "[{ip}]:{}", self.port),
+            Host::Ip(IpAddr::V4(ip)) => write!(f, "{ip}:{}", self.port),
+            Host::Dns(name) => write!(f, "{name}:{}", self.port),
+        }
+    }
+}
+
+impl std::str::FromStr for ReplicaAddress {
+    type Err = AddressError;
+
+    /// Parse a single address.
+    ///
+    /// Errors report the address at index 0.
+    fn from_str(address: &str) -> Result<ReplicaAddress, AddressError> {
+        parse_address(address, 0)
+    }
+}
+
+/// Parse a comma-separated list of replica addresses.
+///
+/// Whitespace around each address is ignored.
+///
+/// # Errors
+///
+/// Returns [`AddressError::Empty`] if there are no addresses, and otherwise
+/// an error identifying the first invalid address by its index.
+pub fn parse(addresses: &str) -> Result<Vec<ReplicaAddress>, AddressError> {
+    if addresses.trim().is_empty() {
+        return Err(AddressError::Empty);
+    }
+    addresses
+        .split(',')
+        .enumerate()
+        .map(|(index, address)| parse_address(address, index))
+        .collect()
+}
+
+/// Resolve addresses and format them for `tb_client`.
+pub(crate) fn resolve(addresses: &[ReplicaAddress]) -> Result<String, AddressError> {
+    let mut resolved = Vec::with_capacity(addresses.len());
+    for (index, address) in addresses.iter().enumerate() {
+        let address = address
+            .resolve()
+            .map_err(|_| AddressError::Unresolved { index })?;
+        resolved.push(address.to_string());
+    }
+    Ok(resolved.join(","))
+}
+
+fn parse_address(address: &str, index: usize) -> Result<ReplicaAddress, AddressError> {
+    let address = address.trim();
+    if address.is_empty() {
+        return Err(AddressError::Missing { index });
+    }
+
+    // Ports are plain decimal numbers, which `u16::from_str` would also
+    // accept with a leading `+`.
+    let port = |port: &str| match port.parse::<u16>() {
+        Ok(port_number) if port_number != 0 && !port.starts_with('+') => Ok(port_number),
+        _ => Err(AddressError::PortInvalid { index }),
+    };
+
+    if let Some(rest) = address.strip_prefix('[') {
+        let (ip, rest) = rest
+            .split_once(']')
+            .ok_or(AddressError::HostInvalid { index })?;
+        let ip: Ipv6Addr = ip
+            .parse()
+            .map_err(|_| AddressError::HostInvalid { index })?;
+        let port = match rest {
+            "" => PORT_DEFAULT,
+            _ => port(
+                rest.strip_prefix(':')
+                    .ok_or(AddressError::PortInvalid { index })?,
+            )?,
+        };
+        return Ok(ReplicaAddress {
+            host: Host::Ip(IpAddr::V6(ip)),
+            port,
+        });
+    }
+
+    if address.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Ok(ReplicaAddress {
+            host: Host::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            port: port(address)?,
+        });
+    }
+
+    if let Ok(ip) = address.parse::<Ipv6Addr>() {
+        return Ok(ReplicaAddress {
+            host: Host::Ip(IpAddr::V6(ip)),
+            port: PORT_DEFAULT,
+        });
+    }
+
+    let (host, port) = match address.split_once(':') {
+        Some((host, port_str)) => (host, port(port_str)?),
+        None => (address, PORT_DEFAULT),
+    };
+    let host = parse_host(host).ok_or(AddressError::HostInvalid { index })?;
+    Ok(ReplicaAddress { host, port })
+}
+
+fn parse_host(host: &str) -> Option<Host> {
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        return Some(Host::Ip(IpAddr::V4(ip)));
+    }
+
+    // A DNS name, per RFC 1123. Names whose labels are all numeric
+    // are malformed IPv4 addresses rather than names.
+    let name = host.strip_suffix('.').unwrap_or(host);
+    let labels_valid = !name.is_empty()
+        && name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        });
+    let numeric = name
+        .split('.')
+        .all(|label| label.bytes().all(|byte| byte.is_ascii_digit()));
+    if labels_valid && !numeric {
+        Some(Host::Dns(host.to_owned()))
+    } else {
+        None
+    }
+}
+
+/// Errors resulting from parsing replica addresses.
+///
+/// Addresses are identified by their index in the comma-separated list.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum AddressError {
+    /// No addresses were given.
+    Empty,
+    /// The address at `index` is empty, e.g. because of a trailing comma.
+    Missing { index: usize },
+    /// The host of the address at `index` is not a valid IPv4 address,
+    /// bracketed IPv6 address, or DNS name.
+    HostInvalid { index: usize },
+    /// The port of the address at `index` is not a number from 1 to 65535.
+    PortInvalid { index: usize },
+    /// The DNS name of the address at `index` could not be resolved.
+    Unresolved { index: usize },
+}
+
+impl std::error::Error for AddressError {}
+impl core::fmt::Display for AddressError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("no addresses"),
+            Self::Missing { index } => write!(f, "address {index} is empty"),
+            Self::HostInvalid { index } => write!(f, "address {index} has an invalid host"),
+            Self::PortInvalid { index } => write!(f, "address {index} has an invalid port"),
+            Self::Unresolved { index } => write!(f, "address {index} could not be resolved"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(ip: &str, port: u16) -> ReplicaAddress {
+        ReplicaAddress {
+            host: Host::Ip(ip.parse().unwrap()),
+            port,
+        }
+    }
+
+    #[test]
+    fn parse_valid() {
+        assert_eq!(
+            parse("3000, 10.0.0.1, 10.0.0.2:3002 ,[::1],[fe80::1]:3004,::2").unwrap(),
+            [
+                ip("127.0.0.1", 3000),
+                ip("10.0.0.1", PORT_DEFAULT),
+                ip("10.0.0.2", 3002),
+                ip("::1", PORT_DEFAULT),
+                ip("fe80::1", 3004),
+                ip("::2", PORT_DEFAULT),
+            ]
+        );
+        assert_eq!(
+            parse("replica-0.tb.svc.cluster.local:3000,localhost").unwrap(),
+            [
+                ReplicaAddress {
+                    host: Host::Dns("replica-0.tb.svc.cluster.local".to_owned()),
+                    port: 3000,
+                },
+                ReplicaAddress {
+                    host: Host::Dns("localhost".to_owned()),
+                    port: PORT_DEFAULT,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse(""), Err(AddressError::Empty));
+        assert_eq!(parse(" "), Err(AddressError::Empty));
+        assert_eq!(parse("3000,"), Err(AddressError::Missing { index: 1 }));
+        assert_eq!(parse("0"), Err(AddressError::PortInvalid { index: 0 }));
+        assert_eq!(parse("65536"), Err(AddressError::PortInvalid { index: 0 }));
+        assert_eq!(parse("1,a:+1"), Err(AddressError::PortInvalid { index: 1 }));
+        assert_eq!(parse("a:"), Err(AddressError::PortInvalid { index: 0 }));
+        assert_eq!(
+            parse("[::1]3000"),
+            Err(AddressError::PortInvalid { index: 0 })
+        );
+        assert_eq!(parse("[::1"), Err(AddressError::HostInvalid { index: 0 }));
+        assert_eq!(
+            parse("[1.2.3.4]"),
+            Err(AddressError::HostInvalid { index: 0 })
+        );
+        assert_eq!(
+            parse("256.0.0.1"),
+            Err(AddressError::HostInvalid { index: 0 })
+        );
+        assert_eq!(parse("-a.b"), Err(AddressError::HostInvalid { index: 0 }));
+        assert_eq!(parse("a..b"), Err(AddressError::HostInvalid { index: 0 }));
+        assert_eq!(
+            parse("a_b:3000"),
+            Err(AddressError::HostInvalid { index: 0 })
+        );
+        assert_eq!(parse(":3000"), Err(AddressError::HostInvalid { index: 0 }));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for address in ["127.0.0.1:3000", "[::1]:3001", "localhost:3002"] {
+            let parsed: ReplicaAddress = address.parse().unwrap();
+            assert_eq!(parsed.to_string(), address);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use super::*;
+
+/// An amount in a ledger's smallest unit, together with the ledger's scale.
+///
+/// TigerBeetle amounts are integers. A ledger for a currency with cents has
+/// a scale of 2, so `12.34` is stored as `1234`; a ledger of micro-units has
+/// a scale of 6. Submitting a decimal amount without scaling it is a common
+/// bug, which `Amount` prevents by only converting from decimal forms with an
+/// explicit scale, and failing rather than rounding.
+///
+/// # Example
+///
+/// ```
+/// use tigerbeetle as tb;
+///
+/// let amount = tb::Amount::parse("12.34", 2)?;
+/// assert_eq!(amount.units(), 1234);
+/// assert_eq!(amount.to_string(), "12.34");
+///
+/// assert_eq!(tb::Amount::parse("12.345", 2), Err(tb::AmountError::TooPrecise));
+///
+/// let transfer = tb::Transfer {
+///     amount: amount.units(),
+///     ..Default::default()
+/// };
+/// # Ok::<(), tb::AmountError>(())
+/// ```
+///
+/// With the `decimal` feature amounts can also be converted from
+/// [`rust_decimal::Decimal`](https://docs.rs/rust_decimal) with
+/// `Amount::from_decimal`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Amount {
+    units: u128,
+    scale: u8,
+}
+
+impl Amount {
+    /// The largest supported scale.
+    ///
+    /// `10^38` is the largest power of ten that fits in a `u128`.
+    pub const SCALE_MAX: u8 = 38;
+
+    /// Create an amount of `units` of the ledger's smallest unit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is greater than [`Amount::SCALE_MAX`].
+    pub fn new(units: u128, scale: u8) -> Amount {
+        assert!(scale <= Amount::SCALE_MAX);
+        Amount { units, scale }
+    }
+
+    /// Parse a decimal amount, such as `"12.34"`, for a ledger with `scale`.
+    ///
+    /// The amount is a non-negative number of whole units, optionally
+    /// followed by a `.` and fractional digits. Fractional digits beyond
+    /// `scale` are only accepted if they are zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AmountError::Invalid`] if the string is not a decimal number,
+    /// [`AmountError::Negative`] if it is negative,
+    /// [`AmountError::TooPrecise`] if it has more significant fractional
+    /// digits than `scale`, and [`AmountError::Overflow`] if the scaled amount
+    /// doesn't fit in a `u128`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is greater than [`Amount::SCALE_MAX`].
+    pub fn parse(amount: &str, scale: u8) -> Result<Amount, AmountError> {
+        assert!(scale <= Amount::SCALE_MAX);
+
+        let (integer, fraction) = match amount.split_once('.') {
+            Some((integer, fraction)) => (integer, fraction),
+            None => (amount, ""),
+        };
+        if integer.starts_with('-')
+            && integer.len() > 1
+            && integer[1..].bytes().all(|byte| byte.is_ascii_digit())
+        {
+            return Err(AmountError::Negative);
+        }
+        let digits = |digits: &str| digits.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.is_empty()
+            || !digits(integer)
+            || !digits(fraction)
+            || (amount.contains('.') && fraction.is_empty())
+        {
+            return Err(AmountError::Invalid);
+        }
+
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.len() > scale as usize {
+            return Err(AmountError::TooPrecise);
+        }
+
+        let mut units: u128 = 0;
+        let fraction_padded = fraction
+            .bytes()
+            .chain(std::iter::repeat(b'0'))
+            .take(scale as usize);
+        for byte in integer.bytes().chain(fraction_padded) {
+            units = units
+                .checked_mul(10)
+                .and_then(|units| units.checked_add(u128::from(byte - b'0')))
+                .ok_or(AmountError::Overflow)?;
+        }
+        Ok(Amount { units, scale })
+    }
+
+    /// Convert a [`Decimal`](rust_decimal::Decimal) for a ledger with `scale`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AmountError::Negative`] if the decimal is negative,
+    /// [`AmountError::TooPrecise`] if it has more significant fractional
+    /// digits than `scale`, and [`AmountError::Overflow`] if the scaled amount
+    /// doesn't fit in a `u128`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is greater than [`Amount::SCALE_MAX`].
+    #[cfg(feature = "decimal")]
+    pub fn from_decimal(amount: rust_decimal::Decimal, scale: u8) -> Result<Amount, AmountError> {
+        assert!(scale <= Amount::SCALE_MAX);
+
+        if amount.is_sign_negative() && !amount.is_zero() {
+            return Err(AmountError::Negative);
+        }
+        let amount = amount.normalize();
+        let amount_scale = amount.scale();
+        if amount_scale > u32::from(scale) {
+            return Err(AmountError::TooPrecise);
+        }
+        // Non-negative, so the mantissa fits.
+        let mantissa = amount.mantissa() as u128;
+        10u128
+            .checked_pow(u32::from(scale) - amount_scale)
+            .and_then(|factor| mantissa.checked_mul(factor))
+            .map(|units| Amount { units, scale })
+            .ok_or(AmountError::Overflow)
+    }
+
+    /// The amount in the ledger's smallest unit, as submitted in
+    /// [`Transfer::amount`].
+    pub fn units(self) -> u128 {
+        self.units
+    }
+
+    /// The number of fractional digits of the ledger's unit.
+    pub fn scale(self) -> u8 {
+        self.scale
+    }
+}
+
+impl From<Amount> for u128 {
+    fn from(amount: Amount) -> u128 {
+        amount.units
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", self.units, width = scale + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - scale);
+        if fraction.is_empty() {
+            f.write_str(integer)
+        } else {
+            write!(f, "{integer}.{fraction}")
+        }
+    }
+}
+
+/// Errors resulting from converting an [`Amount`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum AmountError {
+    /// The string is not a decimal number.
+    Invalid,
+    /// The amount is negative. TigerBeetle amounts are unsigned; the
+    /// direction of a transfer is given by its debit and credit accounts.
+    Negative,
+    /// The amount has more significant fractional digits than the scale,
+    /// and would have to be rounded.
+    TooPrecise,
+    /// The scaled amount doesn't fit in a `u128`.
+    Overflow,
+}
+
+impl std::error::Error for AmountError {}
+impl core::fmt::Display for AmountError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Invalid => "amount invalid",
+            Self::Negative => "amount negative",
+            Self::TooPrecise => "amount too precise for scale",
+            Self::Overflow => "amount overflows",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let units = |amount: &str, scale| Amount::parse(amount, scale).map(Amount::units);
+
+        assert_eq!(units("12.34", 2), Ok(1234));
+        assert_eq!(units("12.3", 2), Ok(1230));
+        assert_eq!(units("12", 2), Ok(1200));
+        assert_eq!(units("0.01", 2), Ok(1));
+        assert_eq!(units("12.340000", 2), Ok(1234));
+        assert_eq!(units("007", 0), Ok(7));
+        assert_eq!(units("1", 6), Ok(1_000_000));
+        assert_eq!(units(&u128::MAX.to_string(), 0), Ok(u128::MAX));
+
+        assert_eq!(units("12.345", 2), Err(AmountError::TooPrecise));
+        assert_eq!(units("0.5", 0), Err(AmountError::TooPrecise));
+        assert_eq!(units("-1", 2), Err(AmountError::Negative));
+        assert_eq!(units("-1.5", 2), Err(AmountError::Negative));
+        assert_eq!(units(&u128::MAX.to_string(), 1), Err(AmountError::Overflow));
+        for invalid in [
+            "", ".", "1.", ".5", "+1", "-", "1,000", "1e3", " 1", "1.2.3",
+        ] {
+            assert_eq!(units(invalid, 2), Err(AmountError::Invalid), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for (amount, scale) in [("12.34", 2), ("0.01", 2), ("0.00", 2), ("7", 0)] {
+            assert_eq!(Amount::parse(amount, scale).unwrap().to_string(), amount);
+        }
+        assert_eq!(Amount::new(1, Amount::SCALE_MAX).to_string().len(), 40);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn from_decimal() {
+        use rust_decimal::Decimal;
+
+        let units = |amount: &str, scale| {
+            Amount::from_decimal(amount.parse::<Decimal>().unwrap(), scale).map(Amount::units)
+        };
+        assert_eq!(units("12.34", 2), Ok(1234));
+        assert_eq!(units("12.3400", 2), Ok(1234));
+        assert_eq!(units("-0.00", 2), Ok(0));
+        assert_eq!(units("12.345", 2), Err(AmountError::TooPrecise));
+        assert_eq!(units("-1", 2), Err(AmountError::Negative));
+        assert_eq!(units("4", 38), Err(AmountError::Overflow));
+    }
+}
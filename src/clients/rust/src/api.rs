@@ -0,0 +1,175 @@
+use super::*;
+
+use std::pin::Pin;
+
+/// A boxed future returned by [`TigerBeetleClient`] methods.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The operations supported by a TigerBeetle client.
+///
+/// This trait is implemented by [`Client`] and [`ClientPool`], which make
+/// requests to a TigerBeetle cluster, and by [`InMemoryClient`], which
+/// emulates a cluster locally. Application code written against this trait
+/// can be unit-tested with an `InMemoryClient` and run in production with a
+/// `Client`. A [`Tenant`](tenant::Tenant) wraps another implementation,
+/// restricting it to one tenant's accounts and transfers.
+///
+/// The methods behave as the corresponding methods of [`Client`],
+/// which document their results in detail.
+///
+/// # Example
+///
+/// ```
+/// use tigerbeetle as tb;
+/// use tb::TigerBeetleClient;
+///
+/// async fn open_account(
+///     client: &dyn TigerBeetleClient,
+///     id: u128,
+/// ) -> Result<(), Box<dyn std::error::Error>> {
+///     let results = client
+///         .create_accounts(&[tb::Account {
+///             id,
+///             ledger: 1,
+///             code: 1,
+///             ..Default::default()
+///         }])
+///         .await?;
+///     assert!(results.is_empty());
+///     Ok(())
+/// }
+///
+/// let client = tb::InMemoryClient::new();
+/// futures::executor::block_on(open_account(&client, tb::id())).unwrap();
+/// ```
+pub trait TigerBeetleClient: Send + Sync {
+    /// Create one or more accounts.
+    ///
+    /// See [`Client::create_accounts`].
+    fn create_accounts<'a>(
+        &'a self,
+        events: &'a [Account],
+    ) -> BoxFuture<'a, Result<Vec<CreateAccountsResult>, PacketStatus>>;
+
+    /// Create one or more transfers.
+    ///
+    /// See [`Client::create_transfers`].
+    fn create_transfers<'a>(
+        &'a self,
+        events: &'a [Transfer],
+    ) -> BoxFuture<'a, Result<Vec<CreateTransfersResult>, PacketStatus>>;
+
+    /// Query individual accounts.
+    ///
+    /// See [`Client::lookup_accounts`].
+    fn lookup_accounts<'a>(
+        &'a self,
+        events: &'a [u128],
+    ) -> BoxFuture<'a, Result<Vec<Account>, PacketStatus>>;
+
+    /// Query individual transfers.
+    ///
+    /// See [`Client::lookup_transfers`].
+    fn lookup_transfers<'a>(
+        &'a self,
+        events: &'a [u128],
+    ) -> BoxFuture<'a, Result<Vec<Transfer>, PacketStatus>>;
+
+    /// Query multiple transfers for a single account.
+    ///
+    /// See [`Client::get_account_transfers`].
+    fn get_account_transfers(
+        &self,
+        event: AccountFilter,
+    ) -> BoxFuture<'_, Result<Vec<Transfer>, PacketStatus>>;
+
+    /// Query historical account balances for a single account.
+    ///
+    /// See [`Client::get_account_balances`].
+    fn get_account_balances(
+        &self,
+        event: AccountFilter,
+    ) -> BoxFuture<'_, Result<Vec<AccountBalance>, PacketStatus>>;
+
+    /// Query multiple accounts related by fields and timestamps.
+    ///
+    /// See [`Client::query_accounts`].
+    fn query_accounts(
+        &self,
+        event: QueryFilter,
+    ) -> BoxFuture<'_, Result<Vec<Account>, PacketStatus>>;
+
+    /// Query multiple transfers related by fields and timestamps.
+    ///
+    /// See [`Client::query_transfers`].
+    fn query_transfers(
+        &self,
+        event: QueryFilter,
+    ) -> BoxFuture<'_, Result<Vec<Transfer>, PacketStatus>>;
+}
+
+macro_rules! impl_tigerbeetle_client {
+    ($client:ty) => {
+        impl TigerBeetleClient for $client {
+            fn create_accounts<'a>(
+                &'a self,
+                events: &'a [Account],
+            ) -> BoxFuture<'a, Result<Vec<CreateAccountsResult>, PacketStatus>> {
+                Box::pin(<$client>::create_accounts(self, events))
+            }
+
+            fn create_transfers<'a>(
+                &'a self,
+                events: &'a [Transfer],
+            ) -> BoxFuture<'a, Result<Vec<CreateTransfersResult>, PacketStatus>> {
+                Box::pin(<$client>::create_transfers(self, events))
+            }
+
+            fn lookup_accounts<'a>(
+                &'a self,
+                events: &'a [u128],
+            ) -> BoxFuture<'a, Result<Vec<Account>, PacketStatus>> {
+                Box::pin(<$client>::lookup_accounts(self, events))
+            }
+
+            fn lookup_transfers<'a>(
+                &'a self,
+                events: &'a [u128],
+            ) -> BoxFuture<'a, Result<Vec<Transfer>, PacketStatus>> {
+                Box::pin(<$client>::lookup_transfers(self, events))
+            }
+
+            fn get_account_transfers(
+                &self,
+                event: AccountFilter,
+            ) -> BoxFuture<'_, Result<Vec<Transfer>, PacketStatus>> {
+                Box::pin(<$client>::get_account_transfers(self, event))
+            }
+
+            fn get_account_balances(
+                &self,
+                event: AccountFilter,
+            ) -> BoxFuture<'_, Result<Vec<AccountBalance>, PacketStatus>> {
+                Box::pin(<$client>::get_account_balances(self, event))
+            }
+
+            fn query_accounts(
+                &self,
+                event: QueryFilter,
+            ) -> BoxFuture<'_, Result<Vec<Account>, PacketStatus>> {
+                Box::pin(<$client>::query_accounts(self, event))
+            }
+
+            fn query_transfers(
+                &self,
+                event: QueryFilter,
+            ) -> BoxFuture<'_, Result<Vec<Transfer>, PacketStatus>> {
+                Box::pin(<$client>::query_transfers(self, event))
+            }
+        }
+    };
+}
+
+impl_tigerbeetle_client!(Client);
+impl_tigerbeetle_client!(ClientPool);
+impl_tigerbeetle_client!(InMemoryClient);
@@ -0,0 +1,269 @@
+//! Integrity checks of a ledger's accounts.
+//!
+//! Every transfer debits one account and credits another by the same
+//! amount, so across a ledger's accounts the debits and credits are equal.
+//! [`trial_balance`] sums them, and checks each account's balance limits:
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//! use tb::audit;
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), audit::AuditError> {
+//! let report = audit::trial_balance(client, 840).await?;
+//! assert!(report.is_balanced());
+//! for violation in &report.violations {
+//!     println!("account {}: {}", violation.account.id, violation.kind);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::paging;
+use crate::{Account, AccountFlags, PacketStatus, QueryFilter, TigerBeetleClient};
+
+use core::fmt;
+
+/// The totals of a ledger's accounts, and the accounts that violate their
+/// limits.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct TrialBalance {
+    pub ledger: u32,
+    /// The number of accounts.
+    pub accounts: usize,
+    pub debits_pending: u128,
+    pub debits_posted: u128,
+    pub credits_pending: u128,
+    pub credits_posted: u128,
+    /// The accounts whose balances exceed their limits, in the order they
+    /// were created.
+    pub violations: Vec<Violation>,
+}
+
+impl TrialBalance {
+    /// Whether the pending debits equal the pending credits, and the posted
+    /// debits the posted credits.
+    pub fn is_balanced(&self) -> bool {
+        self.debits_pending == self.credits_pending && self.debits_posted == self.credits_posted
+    }
+
+    /// Whether the ledger is balanced and no account violates its limits.
+    pub fn is_sound(&self) -> bool {
+        self.is_balanced() && self.violations.is_empty()
+    }
+}
+
+/// An account whose balances exceed a limit set by its flags.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Violation {
+    pub account: Account,
+    pub kind: ViolationKind,
+}
+
+/// The limit a [`Violation`] exceeds.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ViolationKind {
+    /// The account has `DebitsMustNotExceedCredits`, but its pending and
+    /// posted debits exceed its posted credits.
+    DebitsExceedCredits,
+    /// The account has `CreditsMustNotExceedDebits`, but its pending and
+    /// posted credits exceed its posted debits.
+    CreditsExceedDebits,
+}
+
+impl fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DebitsExceedCredits => f.write_str("debits exceed credits"),
+            Self::CreditsExceedDebits => f.write_str("credits exceed debits"),
+        }
+    }
+}
+
+/// Sum the balances of the accounts on `ledger`, and check each account's
+/// limits.
+///
+/// The accounts are queried in pages, so on a ledger with concurrent
+/// transfers they aren't a consistent snapshot: a transfer between
+/// accounts on different pages may be counted on one side only, and the
+/// ledger may appear unbalanced. Audit a ledger while no transfers are
+/// being created, or check it again.
+///
+/// # Errors
+///
+/// Returns [`AuditError::Packet`] if a query fails, and
+/// [`AuditError::Overflow`] if a total exceeds a `u128`.
+pub async fn trial_balance(
+    client: &dyn TigerBeetleClient,
+    ledger: u32,
+) -> Result<TrialBalance, AuditError> {
+    let filter = QueryFilter {
+        ledger,
+        ..Default::default()
+    };
+    let accounts = paging::all(filter, |filter| client.query_accounts(filter)).await?;
+    let mut report = TrialBalance {
+        ledger,
+        ..Default::default()
+    };
+    for account in &accounts {
+        report.add(account)?;
+    }
+    Ok(report)
+}
+
+impl TrialBalance {
+    fn add(&mut self, account: &Account) -> Result<(), AuditError> {
+        let add = |total: u128, amount: u128| total.checked_add(amount).ok_or(AuditError::Overflow);
+        self.accounts += 1;
+        self.debits_pending = add(self.debits_pending, account.debits_pending)?;
+        self.debits_posted = add(self.debits_posted, account.debits_posted)?;
+        self.credits_pending = add(self.credits_pending, account.credits_pending)?;
+        self.credits_posted = add(self.credits_posted, account.credits_posted)?;
+
+        // Pending and posted amounts can't overflow within an account.
+        let debits = account.debits_pending + account.debits_posted;
+        let credits = account.credits_pending + account.credits_posted;
+        let flags = account.flags;
+        if flags.contains(AccountFlags::DebitsMustNotExceedCredits)
+            && debits > account.credits_posted
+        {
+            self.violations.push(Violation {
+                account: *account,
+                kind: ViolationKind::DebitsExceedCredits,
+            });
+        }
+        if flags.contains(AccountFlags::CreditsMustNotExceedDebits)
+            && credits > account.debits_posted
+        {
+            self.violations.push(Violation {
+                account: *account,
+                kind: ViolationKind::CreditsExceedDebits,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Errors auditing a ledger.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum AuditError {
+    /// A query failed.
+    Packet(PacketStatus),
+    /// A total exceeds a `u128`.
+    Overflow,
+}
+
+impl From<PacketStatus> for AuditError {
+    fn from(status: PacketStatus) -> AuditError {
+        AuditError::Packet(status)
+    }
+}
+
+impl std::error::Error for AuditError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Packet(status) => Some(status),
+            Self::Overflow => None,
+        }
+    }
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Packet(status) => fmt::Display::fmt(status, f),
+            Self::Overflow => f.write_str("total exceeds a u128"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InMemoryClient, Transfer};
+
+    use futures::executor::block_on;
+
+    fn account(id: u128, ledger: u32, flags: AccountFlags) -> Account {
+        Account {
+            id,
+            ledger,
+            code: 1,
+            flags,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn trial_balance() {
+        let client = InMemoryClient::new();
+        let mut accounts: Vec<Account> = (1..=300)
+            .map(|id| account(id, 1, AccountFlags::empty()))
+            .collect();
+        accounts.push(account(1000, 2, AccountFlags::empty()));
+        for chunk in accounts.chunks(crate::BATCH_MAX) {
+            assert!(block_on(client.create_accounts(chunk)).unwrap().is_empty());
+        }
+        let transfers: Vec<Transfer> = (1..300)
+            .map(|id| Transfer {
+                id,
+                debit_account_id: id,
+                credit_account_id: id + 1,
+                amount: 10,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_transfers(&transfers))
+            .unwrap()
+            .is_empty());
+
+        let report = block_on(super::trial_balance(&client, 1)).unwrap();
+        assert_eq!(report.accounts, 300);
+        assert_eq!(report.debits_posted, 2990);
+        assert_eq!(report.credits_posted, 2990);
+        assert!(report.is_sound());
+
+        let report = block_on(super::trial_balance(&client, 3)).unwrap();
+        assert_eq!(
+            report,
+            TrialBalance {
+                ledger: 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn violations() {
+        let mut report = TrialBalance::default();
+        let mut debit = account(1, 1, AccountFlags::DebitsMustNotExceedCredits);
+        debit.debits_pending = 5;
+        debit.credits_posted = 5;
+        debit.credits_pending = 100;
+        report.add(&debit).unwrap();
+        assert!(report.violations.is_empty());
+
+        debit.debits_posted = 1;
+        let mut credit = account(2, 1, AccountFlags::CreditsMustNotExceedDebits);
+        credit.credits_posted = 1;
+        report.add(&debit).unwrap();
+        report.add(&credit).unwrap();
+        let kinds: Vec<_> = report.violations.iter().map(|v| v.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ViolationKind::DebitsExceedCredits,
+                ViolationKind::CreditsExceedDebits
+            ]
+        );
+        assert!(!report.is_balanced());
+
+        let mut large = account(3, 1, AccountFlags::empty());
+        large.credits_posted = u128::MAX;
+        assert_eq!(report.add(&large), Err(AuditError::Overflow));
+    }
+}
@@ -0,0 +1,916 @@
+//! Export of a ledger to an archive file, and its restoration.
+//!
+//! [`export`] writes every account on a ledger, and every transfer between
+//! them, to a file that can be kept for audits or disaster-recovery drills.
+//! [`verify`] checks that a file is an intact archive, and [`restore`]
+//! imports it into another cluster:
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//! use tb::backup;
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), backup::BackupError> {
+//! let manifest = backup::export(client, 840, "ledger-840.tbb").await?;
+//! println!("exported {} accounts, {} transfers", manifest.accounts, manifest.transfers);
+//!
+//! assert_eq!(backup::verify("ledger-840.tbb")?, manifest);
+//!
+//! # let other_client = client;
+//! backup::restore("ledger-840.tbb", other_client, |progress| {
+//!     println!("{} of {} transfers", progress.transfers, progress.manifest.transfers);
+//! })
+//! .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Format
+//!
+//! An archive is a 64-byte header followed by a compressed body. All
+//! integers are little-endian. The header is:
+//!
+//! | Offset | Size | Field                                  |
+//! |--------|------|----------------------------------------|
+//! | 0      | 8    | `TBBACKUP`                             |
+//! | 8      | 4    | Format version, 1                      |
+//! | 12     | 4    | Ledger                                 |
+//! | 16     | 8    | Number of accounts                     |
+//! | 24     | 8    | Number of transfers                    |
+//! | 32     | 8    | Length of the body                     |
+//! | 40     | 4    | CRC-32 (IEEE) of the body              |
+//! | 44     | 20   | Zero                                   |
+//!
+//! The body, once decompressed, is the accounts and then the transfers,
+//! each in the order they were created and in their 128-byte protocol
+//! layout. Since most of a record's bytes are usually zero, the body is
+//! compressed by encoding runs of zeros: each byte `n` is followed by
+//! `n + 1` literal bytes if it is less than 128, and otherwise stands for
+//! `n - 127` zero bytes.
+
+use crate::paging::{self, Timestamped};
+use crate::wire::{self, Wire};
+use crate::{Account, AccountFlags, CreateAccountResult, CreateTransferResult, PacketStatus};
+use crate::{QueryFilter, QueryFilterFlags, TigerBeetleClient, Transfer, TransferFlags};
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"TBBACKUP";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 64;
+const RECORD_SIZE: usize = 128;
+
+/// What an archive contains.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Manifest {
+    pub ledger: u32,
+    /// The number of accounts.
+    pub accounts: u64,
+    /// The number of transfers.
+    pub transfers: u64,
+}
+
+/// Write the accounts and transfers of `ledger` to an archive at `path`,
+/// replacing any file there.
+///
+/// The accounts are queried, and then the transfers, in pages, so they
+/// aren't a consistent snapshot of a ledger in use: a transfer created
+/// during the export may be included, but an account created during it
+/// isn't. Export a ledger while no accounts are being created on it.
+///
+/// # Errors
+///
+/// Returns [`BackupError::Packet`] if a query fails, and
+/// [`BackupError::Io`] if writing the file fails, in which case the file
+/// is incomplete and [`verify`] rejects it.
+pub async fn export(
+    client: &dyn TigerBeetleClient,
+    ledger: u32,
+    path: impl AsRef<Path>,
+) -> Result<Manifest, BackupError> {
+    let mut file = File::create(path)?;
+    file.write_all(&[0; HEADER_SIZE])?;
+    let mut body = Checksummed::new(BufWriter::new(&file));
+    let mut manifest = Manifest {
+        ledger,
+        ..Default::default()
+    };
+    let filter = QueryFilter {
+        ledger,
+        ..Default::default()
+    };
+
+    let mut pages = paging::Pages::new(filter);
+    while let Some(accounts) = pages.next(|filter| client.query_accounts(filter)).await? {
+        write_records(&mut body, &accounts)?;
+        manifest.accounts += accounts.len() as u64;
+    }
+    let mut pages = paging::Pages::new(filter);
+    while let Some(transfers) = pages.next(|filter| client.query_transfers(filter)).await? {
+        write_records(&mut body, &transfers)?;
+        manifest.transfers += transfers.len() as u64;
+    }
+
+    body.inner.flush()?;
+    let (len, checksum) = (body.len, body.crc);
+    drop(body);
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header(&manifest, len, checksum))?;
+    file.sync_all()?;
+    Ok(manifest)
+}
+
+/// Check that the file at `path` is an intact archive, and return its
+/// manifest.
+///
+/// The body must match the header's length and checksum, and hold the
+/// number of accounts and transfers it records, of its ledger and in the
+/// order they were created.
+pub fn verify(path: impl AsRef<Path>) -> Result<Manifest, BackupError> {
+    let mut archive = Archive::open(path.as_ref())?;
+    let manifest = archive.manifest;
+    let mut accounts = Vec::new();
+    while archive.read::<Account>(&mut accounts, crate::BATCH_MAX)? > 0 {}
+    let mut transfers = Vec::new();
+    while archive.read::<Transfer>(&mut transfers, crate::BATCH_MAX)? > 0 {}
+    archive.finish()?;
+    Ok(manifest)
+}
+
+/// The progress of a [`restore`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Progress {
+    /// The archive's manifest, with the total numbers of accounts and
+    /// transfers.
+    pub manifest: Manifest,
+    /// The number of accounts restored, including those restored before.
+    pub accounts: u64,
+    /// The number of transfers restored, including those restored before.
+    pub transfers: u64,
+}
+
+/// Import the archive at `path` into the cluster of `client`.
+///
+/// The archive is verified, then its accounts and transfers are imported
+/// in the order they were created, with their original timestamps, so
+/// that the transfers rebuild the accounts' balances. Events are imported
+/// without their `linked` flags, since they all succeeded, and accounts
+/// are created open, to be closed again by their closing transfers.
+/// `progress` is called after each request.
+///
+/// A restore that was interrupted resumes when run again: accounts and
+/// transfers no later than the ledger's latest in the cluster are skipped.
+/// The cluster must otherwise have no accounts or transfers later than the
+/// archive's earliest, since imported timestamps must increase.
+///
+/// Pending transfers are imported without their timeouts, since imported
+/// transfers can't expire. A pending transfer that expired before the
+/// export remains pending after the restore, with its amount reserved;
+/// void it to release it.
+///
+/// # Errors
+///
+/// Returns an error if the archive doesn't [`verify`], if a request fails,
+/// and [`BackupError::AccountFailed`] or [`BackupError::TransferFailed`]
+/// for the first event the cluster rejects, when the events before it have
+/// been imported.
+pub async fn restore(
+    path: impl AsRef<Path>,
+    client: &dyn TigerBeetleClient,
+    mut progress: impl FnMut(&Progress),
+) -> Result<Manifest, BackupError> {
+    let path = path.as_ref();
+    let manifest = verify(path)?;
+    let latest = QueryFilter {
+        ledger: manifest.ledger,
+        limit: 1,
+        flags: QueryFilterFlags::Reversed,
+        ..Default::default()
+    };
+    let accounts_latest = client
+        .query_accounts(latest)
+        .await?
+        .first()
+        .map_or(0, |a| a.timestamp);
+    let transfers_latest = client
+        .query_transfers(latest)
+        .await?
+        .first()
+        .map_or(0, |t| t.timestamp);
+
+    // Read the accounts and transfers side by side, to import them in the
+    // order they were created.
+    let mut accounts = Records::<Account>::new(Archive::open(path)?);
+    let mut transfers = Archive::open(path)?;
+    while transfers.read::<Account>(&mut Vec::new(), crate::BATCH_MAX)? > 0 {}
+    let mut transfers = Records::<Transfer>::new(transfers);
+
+    let mut restored = Progress {
+        manifest,
+        ..Default::default()
+    };
+    loop {
+        let account = accounts.peek()?;
+        let transfer = transfers.peek()?;
+        let accounts_first = match (account, transfer) {
+            (None, None) => break,
+            (Some(account), Some(transfer)) => account < transfer,
+            (account, _) => account.is_some(),
+        };
+        if accounts_first {
+            let batch = accounts.batch(transfer)?;
+            restored.accounts += batch.len() as u64;
+            let events: Vec<Account> = batch
+                .iter()
+                .filter(|account| account.timestamp > accounts_latest)
+                .map(restored_account)
+                .collect();
+            if !events.is_empty() {
+                for failed in client.create_accounts(&events).await? {
+                    if failed.result != CreateAccountResult::Exists {
+                        return Err(BackupError::AccountFailed {
+                            id: events[failed.index].id,
+                            result: failed.result,
+                        });
+                    }
+                }
+            }
+        } else {
+            let batch = transfers.batch(account)?;
+            restored.transfers += batch.len() as u64;
+            let events: Vec<Transfer> = batch
+                .iter()
+                .filter(|transfer| transfer.timestamp > transfers_latest)
+                .map(restored_transfer)
+                .collect();
+            if !events.is_empty() {
+                for failed in client.create_transfers(&events).await? {
+                    if failed.result != CreateTransferResult::Exists {
+                        return Err(BackupError::TransferFailed {
+                            id: events[failed.index].id,
+                            result: failed.result,
+                        });
+                    }
+                }
+            }
+        }
+        progress(&restored);
+    }
+    Ok(manifest)
+}
+
+fn restored_account(account: &Account) -> Account {
+    let mut flags = account.flags;
+    flags.remove(AccountFlags::Linked | AccountFlags::Closed);
+    flags.insert(AccountFlags::Imported);
+    Account {
+        debits_pending: 0,
+        debits_posted: 0,
+        credits_pending: 0,
+        credits_posted: 0,
+        flags,
+        ..*account
+    }
+}
+
+fn restored_transfer(transfer: &Transfer) -> Transfer {
+    let mut flags = transfer.flags;
+    flags.remove(TransferFlags::Linked);
+    flags.insert(TransferFlags::Imported);
+    Transfer {
+        timeout: 0,
+        flags,
+        ..*transfer
+    }
+}
+
+fn header(manifest: &Manifest, len: u64, checksum: u32) -> [u8; HEADER_SIZE] {
+    let mut header = [0; HEADER_SIZE];
+    header[0..8].copy_from_slice(MAGIC);
+    header[8..12].copy_from_slice(&VERSION.to_le_bytes());
+    header[12..16].copy_from_slice(&manifest.ledger.to_le_bytes());
+    header[16..24].copy_from_slice(&manifest.accounts.to_le_bytes());
+    header[24..32].copy_from_slice(&manifest.transfers.to_le_bytes());
+    header[32..40].copy_from_slice(&len.to_le_bytes());
+    header[40..44].copy_from_slice(&checksum.to_le_bytes());
+    header
+}
+
+fn write_records<T: Wire>(body: &mut impl Write, records: &[T]) -> io::Result<()> {
+    let mut compressed = Vec::new();
+    compress(&wire::encode(records), &mut compressed);
+    body.write_all(&compressed)
+}
+
+/// An account or transfer, as stored in an archive.
+trait Record: Wire + Timestamped {
+    /// The section of the archive's records of this type.
+    fn section(archive: &mut Archive) -> &mut Section;
+
+    fn ledger(&self) -> u32;
+}
+
+impl Record for Account {
+    fn section(archive: &mut Archive) -> &mut Section {
+        &mut archive.accounts
+    }
+
+    fn ledger(&self) -> u32 {
+        self.ledger
+    }
+}
+
+impl Record for Transfer {
+    fn section(archive: &mut Archive) -> &mut Section {
+        debug_assert_eq!(archive.accounts.remaining, 0, "accounts come first");
+        &mut archive.transfers
+    }
+
+    fn ledger(&self) -> u32 {
+        self.ledger
+    }
+}
+
+/// An archive being read.
+struct Archive {
+    manifest: Manifest,
+    body: Decompressor<Checksummed<io::Take<BufReader<File>>>>,
+    checksum: u32,
+    accounts: Section,
+    transfers: Section,
+}
+
+/// The accounts or transfers of an archive.
+struct Section {
+    /// The number of records left to read.
+    remaining: u64,
+    /// The timestamp of the last record read.
+    timestamp: u64,
+}
+
+impl Section {
+    fn new(records: u64) -> Section {
+        Section {
+            remaining: records,
+            timestamp: 0,
+        }
+    }
+}
+
+impl Archive {
+    fn open(path: &Path) -> Result<Archive, BackupError> {
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0; HEADER_SIZE];
+        file.read_exact(&mut header)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => BackupError::NotAnArchive,
+                _ => BackupError::Io(error),
+            })?;
+        let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+        if &header[0..8] != MAGIC || u32_at(8) != VERSION {
+            return Err(BackupError::NotAnArchive);
+        }
+        let manifest = Manifest {
+            ledger: u32_at(12),
+            accounts: u64_at(16),
+            transfers: u64_at(24),
+        };
+        let len = u64_at(32);
+        if file.get_ref().metadata()?.len() != HEADER_SIZE as u64 + len {
+            return Err(BackupError::Corrupt("length differs from the header"));
+        }
+        Ok(Archive {
+            manifest,
+            body: Decompressor::new(Checksummed::new(file.take(len))),
+            checksum: u32_at(40),
+            accounts: Section::new(manifest.accounts),
+            transfers: Section::new(manifest.transfers),
+        })
+    }
+
+    /// Read up to `limit` of the remaining records of type `T` into
+    /// `records`, returning how many were read.
+    ///
+    /// All accounts must be read before transfers.
+    fn read<T: Record>(
+        &mut self,
+        records: &mut Vec<T>,
+        limit: usize,
+    ) -> Result<usize, BackupError> {
+        records.clear();
+        let ledger = self.manifest.ledger;
+        let count = T::section(self).remaining.min(limit as u64) as usize;
+        if count == 0 {
+            return Ok(0);
+        }
+
+        let mut bytes = vec![0; count * RECORD_SIZE];
+        self.body
+            .read_exact(&mut bytes)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::UnexpectedEof => {
+                    BackupError::Corrupt("fewer records than the header")
+                }
+                io::ErrorKind::InvalidData => BackupError::Corrupt("invalid compression"),
+                _ => BackupError::Io(error),
+            })?;
+        records.extend(wire::decode::<T>(&bytes).expect("whole records"));
+
+        let section = T::section(self);
+        section.remaining -= count as u64;
+        for record in records.iter() {
+            if record.ledger() != ledger {
+                return Err(BackupError::Corrupt("record of another ledger"));
+            }
+            if record.timestamp() <= section.timestamp {
+                return Err(BackupError::Corrupt("records out of order"));
+            }
+            section.timestamp = record.timestamp();
+        }
+        Ok(count)
+    }
+
+    /// Check that all records were read, and the checksum.
+    fn finish(mut self) -> Result<(), BackupError> {
+        debug_assert_eq!((self.accounts.remaining, self.transfers.remaining), (0, 0));
+        let mut rest = Vec::new();
+        self.body
+            .read_to_end(&mut rest)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::InvalidData => BackupError::Corrupt("invalid compression"),
+                _ => BackupError::Io(error),
+            })?;
+        if !rest.is_empty() {
+            return Err(BackupError::Corrupt("more records than the header"));
+        }
+        if self.body.inner.crc != self.checksum {
+            return Err(BackupError::Corrupt("checksum mismatch"));
+        }
+        Ok(())
+    }
+}
+
+/// The records of one type of an archive, buffered to be merged with
+/// the other's.
+struct Records<T> {
+    archive: Archive,
+    buffer: VecDeque<T>,
+    page: Vec<T>,
+}
+
+impl<T: Record> Records<T> {
+    fn new(archive: Archive) -> Self {
+        Records {
+            archive,
+            buffer: VecDeque::new(),
+            page: Vec::new(),
+        }
+    }
+
+    /// The timestamp of the next record, or `None` after the last.
+    fn peek(&mut self) -> Result<Option<u64>, BackupError> {
+        if self.buffer.is_empty() {
+            self.archive.read(&mut self.page, crate::BATCH_MAX)?;
+            self.buffer.extend(self.page.drain(..));
+        }
+        Ok(self.buffer.front().map(Timestamped::timestamp))
+    }
+
+    /// Up to a batch of the next records, created before `before`.
+    fn batch(&mut self, before: Option<u64>) -> Result<Vec<T>, BackupError> {
+        let mut batch = Vec::new();
+        while batch.len() < crate::BATCH_MAX {
+            match self.peek()? {
+                Some(timestamp) if before.map_or(true, |before| timestamp < before) => {
+                    batch.push(self.buffer.pop_front().expect("peeked"));
+                }
+                _ => break,
+            }
+        }
+        Ok(batch)
+    }
+}
+
+/// Errors exporting, verifying or restoring an archive.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BackupError {
+    /// Reading or writing the file failed.
+    Io(io::Error),
+    /// A query failed.
+    Packet(PacketStatus),
+    /// The file isn't an archive, or is of another format version.
+    NotAnArchive,
+    /// The archive doesn't match its header.
+    Corrupt(&'static str),
+    /// The cluster rejected an account being restored.
+    AccountFailed {
+        id: u128,
+        result: CreateAccountResult,
+    },
+    /// The cluster rejected a transfer being restored.
+    TransferFailed {
+        id: u128,
+        result: CreateTransferResult,
+    },
+}
+
+impl From<io::Error> for BackupError {
+    fn from(error: io::Error) -> BackupError {
+        BackupError::Io(error)
+    }
+}
+
+impl From<PacketStatus> for BackupError {
+    fn from(status: PacketStatus) -> BackupError {
+        BackupError::Packet(status)
+    }
+}
+
+impl std::error::Error for BackupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Packet(status) => Some(status),
+            Self::NotAnArchive
+            | Self::Corrupt(_)
+            | Self::AccountFailed { .. }
+            | Self::TransferFailed { .. } => None,
+        }
+    }
+}
+
+impl core::fmt::Display for BackupError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => core::fmt::Display::fmt(error, f),
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+            Self::NotAnArchive => f.write_str("not an archive"),
+            Self::Corrupt(reason) => write!(f, "corrupt archive: {reason}"),
+            Self::AccountFailed { id, result } => write!(f, "account {id}: {result}"),
+            Self::TransferFailed { id, result } => write!(f, "transfer {id}: {result}"),
+        }
+    }
+}
+
+/// The longest run of literal or zero bytes a byte of the body encodes.
+const RUN_MAX: usize = 128;
+const ZEROS: u8 = 0x80;
+
+/// Append `bytes`, compressed, to `compressed`.
+///
+/// Compressed slices can be concatenated, and decompress to the
+/// concatenation of their bytes.
+fn compress(bytes: &[u8], compressed: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < bytes.len() {
+        let zeros = bytes[i..]
+            .iter()
+            .take(RUN_MAX)
+            .take_while(|&&byte| byte == 0)
+            .count();
+        if zeros >= 2 {
+            compressed.push(ZEROS | (zeros - 1) as u8);
+            i += zeros;
+            continue;
+        }
+        // Literals, up to the next run of zeros.
+        let start = i;
+        while i < bytes.len()
+            && i - start < RUN_MAX
+            && !(bytes[i] == 0 && bytes.get(i + 1) == Some(&0))
+        {
+            i += 1;
+        }
+        compressed.push((i - start - 1) as u8);
+        compressed.extend_from_slice(&bytes[start..i]);
+    }
+}
+
+/// Reads the bytes compressed by [`compress`] from a reader.
+struct Decompressor<R> {
+    inner: R,
+    zeros: usize,
+    literals: usize,
+}
+
+impl<R: Read> Decompressor<R> {
+    fn new(inner: R) -> Self {
+        Decompressor {
+            inner,
+            zeros: 0,
+            literals: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+        if self.zeros == 0 && self.literals == 0 {
+            let mut run = [0];
+            if self.inner.read(&mut run)? == 0 {
+                return Ok(0);
+            }
+            match run[0] {
+                run if run & ZEROS == 0 => self.literals = usize::from(run) + 1,
+                run => self.zeros = usize::from(run & !ZEROS) + 1,
+            }
+        }
+        if self.zeros > 0 {
+            let len = self.zeros.min(buffer.len());
+            buffer[..len].fill(0);
+            self.zeros -= len;
+            return Ok(len);
+        }
+        let len = self.literals.min(buffer.len());
+        let len = self.inner.read(&mut buffer[..len])?;
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "compressed run truncated",
+            ));
+        }
+        self.literals -= len;
+        Ok(len)
+    }
+}
+
+/// A reader or writer that computes the CRC-32 of the bytes through it.
+struct Checksummed<T> {
+    inner: T,
+    crc: u32,
+    len: u64,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            crc: 0,
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.crc = crc32(self.crc, bytes);
+        self.len += bytes.len() as u64;
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buffer)?;
+        self.update(&buffer[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buffer)?;
+        self.update(&buffer[..len]);
+        Ok(len)
+    }
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Continue the CRC-32 `crc` of some bytes with `bytes`.
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc = CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryClient;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn compression() {
+        let mut bytes = vec![0; 1000];
+        bytes[0] = 1;
+        bytes[2] = 2;
+        bytes[500..800].fill(3);
+        bytes[999] = 4;
+        for bytes in [&bytes[..], &[], &[0], &[5], &bytes[1..]] {
+            let mut compressed = Vec::new();
+            compress(bytes, &mut compressed);
+            compress(bytes, &mut compressed);
+            let mut decompressed = Vec::new();
+            Decompressor::new(&compressed[..])
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, [bytes, bytes].concat());
+        }
+
+        let mut compressed = Vec::new();
+        compress(&bytes, &mut compressed);
+        assert!(compressed.len() < 330);
+        let truncated = &compressed[..compressed.len() - 1];
+        let error = Decompressor::new(truncated)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn export_verify() {
+        let client = InMemoryClient::new();
+        let accounts: Vec<Account> = (1..=3)
+            .map(|id| Account {
+                id,
+                ledger: if id == 3 { 2 } else { 1 },
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+        let transfers: Vec<Transfer> = (1..=10)
+            .map(|id| Transfer {
+                id,
+                debit_account_id: 1,
+                credit_account_id: 2,
+                amount: id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_transfers(&transfers))
+            .unwrap()
+            .is_empty());
+
+        let path = std::env::temp_dir().join(format!("backup-{}.tbb", std::process::id()));
+        let manifest = block_on(export(&client, 1, &path)).unwrap();
+        assert_eq!(
+            manifest,
+            Manifest {
+                ledger: 1,
+                accounts: 2,
+                transfers: 10,
+            }
+        );
+        assert_eq!(verify(&path).unwrap(), manifest);
+
+        let mut archive = Archive::open(&path).unwrap();
+        let mut exported = Vec::new();
+        assert_eq!(archive.read::<Account>(&mut exported, 1).unwrap(), 1);
+        assert_eq!(exported[0].id, 1);
+        assert_eq!(archive.read::<Account>(&mut exported, 5).unwrap(), 1);
+        let mut exported = Vec::new();
+        assert_eq!(archive.read::<Transfer>(&mut exported, 20).unwrap(), 10);
+        let amounts: Vec<u128> = exported.iter().map(|transfer| transfer.amount).collect();
+        assert_eq!(amounts, (1..=10).collect::<Vec<_>>());
+
+        let mut file = std::fs::read(&path).unwrap();
+        let last = file.len() - 1;
+        file[last] ^= 1;
+        std::fs::write(&path, &file).unwrap();
+        assert!(matches!(
+            verify(&path),
+            Err(BackupError::Corrupt("checksum mismatch"))
+        ));
+        file.push(0);
+        std::fs::write(&path, &file).unwrap();
+        assert!(matches!(verify(&path), Err(BackupError::Corrupt(_))));
+        std::fs::write(&path, b"TBBACKUP").unwrap();
+        assert!(matches!(verify(&path), Err(BackupError::NotAnArchive)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn restore() {
+        let source = InMemoryClient::new();
+        let account = |id| Account {
+            id,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        let transfer = |id, credit_account_id, amount, flags| Transfer {
+            id,
+            debit_account_id: 1,
+            credit_account_id,
+            amount,
+            ledger: 1,
+            code: 1,
+            flags,
+            ..Default::default()
+        };
+        let create_accounts = |accounts: &[Account]| {
+            assert!(block_on(source.create_accounts(accounts))
+                .unwrap()
+                .is_empty());
+        };
+        let create_transfers = |transfers: &[Transfer]| {
+            assert!(block_on(source.create_transfers(transfers))
+                .unwrap()
+                .is_empty());
+        };
+        create_accounts(&[account(1), account(2)]);
+        create_transfers(&[
+            transfer(1, 2, 10, TransferFlags::Linked),
+            transfer(2, 2, 20, TransferFlags::empty()),
+            Transfer {
+                timeout: 60,
+                ..transfer(3, 2, 30, TransferFlags::Pending)
+            },
+        ]);
+        let base = std::env::temp_dir().join(format!("restore-{}", std::process::id()));
+        let (early, late) = (base.with_extension("1.tbb"), base.with_extension("2.tbb"));
+        block_on(export(&source, 1, &early)).unwrap();
+
+        // An account created after transfers is restored before transfers
+        // to it.
+        create_accounts(&[account(3)]);
+        create_transfers(&[
+            Transfer {
+                pending_id: 3,
+                ..transfer(4, 2, 0, TransferFlags::PostPendingTransfer)
+            },
+            transfer(5, 3, 50, TransferFlags::empty()),
+        ]);
+        let manifest = block_on(export(&source, 1, &late)).unwrap();
+
+        let target = InMemoryClient::new();
+        let mut updates = Vec::new();
+        block_on(super::restore(&early, &target, |progress| {
+            updates.push(*progress)
+        }))
+        .unwrap();
+        assert_eq!(updates.last().unwrap().transfers, 3);
+        // Resumed after the events restored from the earlier archive.
+        updates.clear();
+        assert_eq!(
+            block_on(super::restore(&late, &target, |progress| updates.push(*progress))).unwrap(),
+            manifest
+        );
+        let counts: Vec<_> = updates.iter().map(|p| (p.accounts, p.transfers)).collect();
+        assert_eq!(counts, [(2, 0), (2, 3), (3, 3), (3, 5)]);
+
+        let ids = [1, 2, 3];
+        assert_eq!(
+            block_on(target.lookup_accounts(&ids)).unwrap(),
+            block_on(source.lookup_accounts(&ids))
+                .unwrap()
+                .iter()
+                .map(|account| Account {
+                    flags: AccountFlags::Imported,
+                    ..*account
+                })
+                .collect::<Vec<_>>()
+        );
+        let restored = block_on(target.lookup_transfers(&[1, 2, 3, 4, 5])).unwrap();
+        assert_eq!(restored[2].timeout, 0);
+        assert!(restored
+            .iter()
+            .all(|transfer| !transfer.flags.contains(TransferFlags::Linked)));
+
+        std::fs::remove_file(&early).unwrap();
+        std::fs::remove_file(&late).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+//! A Prometheus exporter for TigerBeetle account balances.
+//!
+//! Serves the balances of the configured accounts as gauges in the
+//! Prometheus text format, looking the accounts up on each scrape:
+//!
+//! ```text
+//! tb-exporter --addresses 3000 --listen 127.0.0.1:9464 --account 1=operating --account 2
+//! ```
+//!
+//! Amounts are exported in the ledger's smallest unit.
+
+use tigerbeetle as tb;
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: tb-exporter [options] --account <id>[=<name>]...
+
+Options:
+  --cluster-id <id>      The cluster id [default: 0]
+  --addresses <list>     The replica addresses [default: 3000]
+  --listen <address>     The address to serve /metrics on [default: 127.0.0.1:9464]
+  --account <id>[=name]  An account to export, with an optional name label
+";
+
+#[derive(Debug, Eq, PartialEq)]
+struct Args {
+    cluster_id: u128,
+    addresses: String,
+    listen: String,
+    accounts: Vec<(u128, Option<String>)>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        cluster_id: 0,
+        addresses: "3000".to_owned(),
+        listen: "127.0.0.1:9464".to_owned(),
+        accounts: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--cluster-id" => {
+                parsed.cluster_id = value()?
+                    .parse()
+                    .map_err(|_| "invalid --cluster-id".to_owned())?
+            }
+            "--addresses" => parsed.addresses = value()?,
+            "--listen" => parsed.listen = value()?,
+            "--account" => {
+                let value = value()?;
+                let (id, name) = match value.split_once('=') {
+                    Some((id, name)) => (id, Some(name.to_owned())),
+                    None => (value.as_str(), None),
+                };
+                let id = id.parse().map_err(|_| format!("invalid account id {id}"))?;
+                parsed.accounts.push((id, name));
+            }
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    if parsed.accounts.is_empty() {
+        return Err("no accounts to export".to_owned());
+    }
+    Ok(parsed)
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let mut client = match tb::blocking::Client::new(args.cluster_id, &args.addresses) {
+        Ok(client) => client,
+        Err(error) => {
+            eprintln!("error: connecting to {}: {error}", args.addresses);
+            std::process::exit(1);
+        }
+    };
+    // A scrape that can't reach the cluster should fail, not hang.
+    client.set_retry_policy(
+        tb::RetryPolicy::new()
+            .max_attempts(3)
+            .max_delay(Duration::from_secs(1)),
+    );
+
+    let listener = match TcpListener::bind(&args.listen) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("error: listening on {}: {error}", args.listen);
+            std::process::exit(1);
+        }
+    };
+    eprintln!("serving http://{}/metrics", args.listen);
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| serve(stream, &client, &args.accounts));
+        if let Err(error) = result {
+            eprintln!("warning: {error}");
+        }
+    }
+}
+
+/// Serve one HTTP request.
+fn serve(
+    mut stream: TcpStream,
+    client: &tb::blocking::Client,
+    accounts: &[(u128, Option<String>)],
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let (status, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", scrape(client, accounts)),
+        _ => ("404 Not Found", "not found\n".to_owned()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn scrape(client: &tb::blocking::Client, accounts: &[(u128, Option<String>)]) -> String {
+    let ids: Vec<u128> = accounts.iter().map(|(id, _)| *id).collect();
+    let started = Instant::now();
+    let result = client.lookup_accounts(&ids);
+    if let Err(status) = &result {
+        eprintln!("warning: looking up accounts: {status}");
+    }
+    render(accounts, result.as_deref().ok(), started.elapsed())
+}
+
+/// A gauge's name, help and value.
+type Gauge = (&'static str, &'static str, fn(&tb::Account) -> u128);
+
+const GAUGES: [Gauge; 4] = [
+    (
+        "tigerbeetle_account_debits_pending",
+        "Amount of pending debits.",
+        |account| account.debits_pending,
+    ),
+    (
+        "tigerbeetle_account_debits_posted",
+        "Amount of posted debits.",
+        |account| account.debits_posted,
+    ),
+    (
+        "tigerbeetle_account_credits_pending",
+        "Amount of pending credits.",
+        |account| account.credits_pending,
+    ),
+    (
+        "tigerbeetle_account_credits_posted",
+        "Amount of posted credits.",
+        |account| account.credits_posted,
+    ),
+];
+
+/// Render the accounts found by a lookup, or `None` if it failed.
+fn render(
+    accounts: &[(u128, Option<String>)],
+    found: Option<&[tb::Account]>,
+    duration: Duration,
+) -> String {
+    let mut out = String::new();
+    let success = found.is_some();
+    let found = found.unwrap_or_default();
+    for (name, help, value) in GAUGES {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge");
+        for (id, label) in accounts {
+            let account = match found.iter().find(|account| account.id == *id) {
+                Some(account) => account,
+                None => continue,
+            };
+            let _ = write!(
+                out,
+                "{name}{{account=\"{id}\",ledger=\"{}\",code=\"{}\"",
+                account.ledger, account.code
+            );
+            if let Some(label) = label {
+                let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+                let _ = write!(out, ",name=\"{label}\"");
+            }
+            // Values are floats; Prometheus parses integers of any size.
+            let _ = writeln!(out, "}} {}", value(account));
+        }
+    }
+
+    if success {
+        let _ = writeln!(
+            out,
+            "# HELP tigerbeetle_exporter_accounts_missing Configured accounts that don't exist.\n\
+             # TYPE tigerbeetle_exporter_accounts_missing gauge\n\
+             tigerbeetle_exporter_accounts_missing {}",
+            accounts
+                .iter()
+                .filter(|(id, _)| !found.iter().any(|account| account.id == *id))
+                .count()
+        );
+    }
+    let _ = writeln!(
+        out,
+        "# HELP tigerbeetle_exporter_scrape_success Whether the accounts were looked up.\n\
+         # TYPE tigerbeetle_exporter_scrape_success gauge\n\
+         tigerbeetle_exporter_scrape_success {}\n\
+         # HELP tigerbeetle_exporter_scrape_duration_seconds Duration of the lookup.\n\
+         # TYPE tigerbeetle_exporter_scrape_duration_seconds gauge\n\
+         tigerbeetle_exporter_scrape_duration_seconds {}",
+        u8::from(success),
+        duration.as_secs_f64()
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            args(&[
+                "--addresses",
+                "3001",
+                "--account",
+                "1=cash \"usd\"",
+                "--account",
+                "2"
+            ]),
+            Ok(Args {
+                cluster_id: 0,
+                addresses: "3001".to_owned(),
+                listen: "127.0.0.1:9464".to_owned(),
+                accounts: vec![(1, Some("cash \"usd\"".to_owned())), (2, None)],
+            })
+        );
+        assert!(args(&[]).is_err());
+        assert!(args(&["--account", "x"]).is_err());
+        assert!(args(&["--account"]).is_err());
+    }
+
+    #[test]
+    fn render() {
+        let accounts = [(1, Some("cash \"usd\"".to_owned())), (3, None)];
+        let found = [tb::Account {
+            id: 1,
+            ledger: 840,
+            code: 10,
+            debits_posted: u128::MAX,
+            ..Default::default()
+        }];
+        let out = super::render(&accounts, Some(&found), Duration::from_millis(5));
+        assert!(out.contains(&format!(
+            "tigerbeetle_account_debits_posted{{account=\"1\",ledger=\"840\",code=\"10\",\
+             name=\"cash \\\"usd\\\"\"}} {}\n",
+            u128::MAX
+        )));
+        assert!(!out.contains("account=\"3\""));
+        assert!(out.contains("tigerbeetle_exporter_accounts_missing 1\n"));
+        assert!(out.contains("tigerbeetle_exporter_scrape_success 1\n"));
+
+        let out = super::render(&accounts, None, Duration::ZERO);
+        assert!(out.contains("tigerbeetle_exporter_scrape_success 0\n"));
+    }
+}
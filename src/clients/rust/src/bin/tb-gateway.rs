@@ -0,0 +1,879 @@
+//! An HTTP gateway to a TigerBeetle cluster.
+//!
+//! Exposes each operation as a JSON endpoint, for services that can't link
+//! a TigerBeetle client:
+//!
+//! ```text
+//! tb-gateway --addresses 3000 --listen 127.0.0.1:8080
+//! curl localhost:8080/transfers -H 'Idempotency-Key: order-42' \
+//!     -d '[{"debit_account_id": "1", "credit_account_id": "2", "amount": "10", "ledger": 1, "code": 1}]'
+//! ```
+//!
+//! Events and results are in the format of the `serde` feature, so `u128`
+//! fields are strings. Unknown fields are rejected, so that a misspelled
+//! field isn't silently zero. `GET /openapi.json` describes the endpoints.
+//!
+//! Accounts and transfers created without an id are given one with
+//! `tb::id()`, returned with the results. With an `Idempotency-Key` header,
+//! the ids are stored for the key, so retrying a request that may have
+//! succeeded reuses them and creates nothing new, and its results are
+//! `exists`. Keys must be unique across requests, e.g. UUIDs, and expire
+//! after `--idempotency-ttl`. They are kept in memory, and forgotten when
+//! the gateway exits, unless `--redis` stores them in a Redis server, which
+//! gateways behind a load balancer can share.
+
+use tb::idempotency::{self, IdempotencyError, IdempotencyStore, InMemoryStore, RedisStore};
+use tigerbeetle as tb;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "\
+Usage: tb-gateway [options]
+
+Options:
+  --cluster-id <id>   The cluster id [default: 0]
+  --addresses <list>  The replica addresses [default: 3000]
+  --listen <address>  The address to serve HTTP on [default: 127.0.0.1:8080]
+  --redis <address>   A Redis server to store idempotency keys in
+  --idempotency-ttl <seconds>
+                      How long idempotency keys are kept [default: 86400]
+";
+
+/// The largest request body accepted, enough for a batch of transfers.
+const BODY_MAX: usize = 16 << 20;
+
+/// The largest request line and headers accepted, in bytes.
+const HEAD_MAX: u64 = 64 << 10;
+
+/// The most headers accepted in a request.
+const HEADERS_MAX: usize = 100;
+
+/// The most connections served at once, each on its own thread. Further
+/// connections are answered with `503 Service Unavailable`.
+const CONNECTIONS_MAX: usize = 256;
+
+#[derive(Debug, Eq, PartialEq)]
+struct Args {
+    cluster_id: u128,
+    addresses: String,
+    listen: String,
+    redis: Option<String>,
+    idempotency_ttl: Duration,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args {
+        cluster_id: 0,
+        addresses: "3000".to_owned(),
+        listen: "127.0.0.1:8080".to_owned(),
+        redis: None,
+        idempotency_ttl: Duration::from_secs(24 * 60 * 60),
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--cluster-id" => {
+                parsed.cluster_id = value()?
+                    .parse()
+                    .map_err(|_| "invalid --cluster-id".to_owned())?
+            }
+            "--addresses" => parsed.addresses = value()?,
+            "--listen" => parsed.listen = value()?,
+            "--redis" => parsed.redis = Some(value()?),
+            "--idempotency-ttl" => {
+                parsed.idempotency_ttl = match value()?.parse() {
+                    Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+                    _ => return Err("invalid --idempotency-ttl".to_owned()),
+                }
+            }
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    Ok(parsed)
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let client = match tb::blocking::Client::new(args.cluster_id, &args.addresses) {
+        Ok(client) => client,
+        Err(error) => {
+            eprintln!("error: connecting to {}: {error}", args.addresses);
+            std::process::exit(1);
+        }
+    };
+
+    let listener = match TcpListener::bind(&args.listen) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("error: listening on {}: {error}", args.listen);
+            std::process::exit(1);
+        }
+    };
+    let store: Box<dyn IdempotencyStore> = match &args.redis {
+        Some(address) => Box::new(RedisStore::new(address, args.idempotency_ttl)),
+        None => Box::new(InMemoryStore::new(args.idempotency_ttl)),
+    };
+    let gateway = Arc::new(Gateway {
+        client,
+        store,
+        connections: AtomicUsize::new(0),
+    });
+    eprintln!("serving http://{}/", args.listen);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("warning: {error}");
+                continue;
+            }
+        };
+        let connection = match Connection::open(&gateway) {
+            Some(connection) => connection,
+            None => {
+                let (status, body) =
+                    error_response("503 Service Unavailable", "too many connections");
+                if let Err(error) = write_response(stream, status, body) {
+                    eprintln!("warning: {error}");
+                }
+                continue;
+            }
+        };
+        // Requests from concurrent connections are batched by the client.
+        thread::spawn(move || {
+            if let Err(error) = serve(stream, &connection.gateway) {
+                eprintln!("warning: {error}");
+            }
+        });
+    }
+}
+
+/// The client, and the store of the ids used for each idempotency key.
+struct Gateway {
+    client: tb::blocking::Client,
+    store: Box<dyn IdempotencyStore>,
+    /// The number of connections being served.
+    connections: AtomicUsize,
+}
+
+/// A connection being served, counted against [`CONNECTIONS_MAX`] until
+/// dropped.
+struct Connection {
+    gateway: Arc<Gateway>,
+}
+
+impl Connection {
+    fn open(gateway: &Arc<Gateway>) -> Option<Connection> {
+        let count = &gateway.connections;
+        count
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < CONNECTIONS_MAX).then(|| count + 1)
+            })
+            .ok()?;
+        Some(Connection {
+            gateway: Arc::clone(gateway),
+        })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.gateway.connections.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// An HTTP request.
+#[derive(Debug, Default, Eq, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    idempotency_key: Option<String>,
+    body: Vec<u8>,
+}
+
+/// An HTTP response: a status and a JSON body.
+type Response = (&'static str, Value);
+
+/// Serve one HTTP request.
+fn serve(stream: TcpStream, gateway: &Gateway) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let (status, body) = match read_request(BufReader::new(&stream)) {
+        Ok(request) => handle(gateway, &request),
+        Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+            error_response("400 Bad Request", error)
+        }
+        Err(error) => return Err(error),
+    };
+    write_response(stream, status, body)
+}
+
+fn write_response(mut stream: TcpStream, status: &str, body: Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn read_request(mut reader: impl BufRead) -> io::Result<Request> {
+    let invalid = |error: &str| io::Error::new(io::ErrorKind::InvalidData, error);
+
+    // Lines are read until a newline, so bound them all.
+    let mut head = (&mut reader).take(HEAD_MAX);
+    let mut read_line = |line: &mut String| -> io::Result<usize> {
+        line.clear();
+        let read = head.read_line(line)?;
+        if read > 0 && !line.ends_with('\n') {
+            return Err(invalid(if head.limit() == 0 {
+                "headers too large"
+            } else {
+                "incomplete request"
+            }));
+        }
+        Ok(read)
+    };
+
+    let mut line = String::new();
+    read_line(&mut line)?;
+    let mut request_line = line.split_whitespace();
+    let mut request = Request {
+        method: request_line.next().unwrap_or_default().to_owned(),
+        path: request_line.next().unwrap_or_default().to_owned(),
+        ..Default::default()
+    };
+    if request.path.is_empty() {
+        return Err(invalid("invalid request line"));
+    }
+
+    let mut content_length = 0;
+    let mut headers = 0;
+    while read_line(&mut line)? > 2 {
+        headers += 1;
+        if headers > HEADERS_MAX {
+            return Err(invalid("too many headers"));
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_ascii_lowercase(), value.trim()),
+            None => return Err(invalid("invalid header")),
+        };
+        match name.as_str() {
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| invalid("invalid content-length"))?
+            }
+            "idempotency-key" => request.idempotency_key = Some(value.to_owned()),
+            _ => {}
+        }
+    }
+    if content_length > BODY_MAX {
+        return Err(invalid("body too large"));
+    }
+    // Grow the body as it arrives, rather than trusting the declared length.
+    reader
+        .take(content_length as u64)
+        .read_to_end(&mut request.body)?;
+    if request.body.len() < content_length {
+        return Err(invalid("incomplete request"));
+    }
+    Ok(request)
+}
+
+fn handle(gateway: &Gateway, request: &Request) -> Response {
+    route(gateway, request).map_or_else(|response| response, |body| ("200 OK", body))
+}
+
+fn route(gateway: &Gateway, request: &Request) -> Result<Value, Response> {
+    let client = &gateway.client;
+    let path = request.path.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let body = &request.body;
+    let key = request.idempotency_key.as_deref();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["openapi.json"]) => Ok(openapi()),
+        ("POST", ["accounts"]) => create(
+            body,
+            key.map(|key| (&*gateway.store, format!("accounts:{key}"))),
+            |a: &mut tb::Account| &mut a.id,
+            |accounts| client.create_accounts(accounts),
+        ),
+        ("POST", ["transfers"]) => create(
+            body,
+            key.map(|key| (&*gateway.store, format!("transfers:{key}"))),
+            |t: &mut tb::Transfer| &mut t.id,
+            |transfers| client.create_transfers(transfers),
+        ),
+        ("GET", ["accounts", id]) => lookup(id, |ids| client.lookup_accounts(ids)),
+        ("GET", ["transfers", id]) => lookup(id, |ids| client.lookup_transfers(ids)),
+        ("POST", ["accounts", "lookup"]) => respond(client.lookup_accounts(&lookup_ids(body)?)),
+        ("POST", ["transfers", "lookup"]) => respond(client.lookup_transfers(&lookup_ids(body)?)),
+        ("POST", ["accounts", "query"]) => respond(client.query_accounts(filter(body)?)),
+        ("POST", ["transfers", "query"]) => respond(client.query_transfers(filter(body)?)),
+        ("POST", ["accounts", "transfers"]) => respond(client.get_account_transfers(filter(body)?)),
+        ("POST", ["accounts", "balances"]) => respond(client.get_account_balances(filter(body)?)),
+        _ if routes()
+            .iter()
+            .any(|route| route_matches(route.path, &segments)) =>
+        {
+            Err(error_response(
+                "405 Method Not Allowed",
+                "method not allowed",
+            ))
+        }
+        _ => Err(error_response("404 Not Found", "not found")),
+    }
+}
+
+fn route_matches(route: &str, segments: &[&str]) -> bool {
+    let route: Vec<&str> = route.trim_matches('/').split('/').collect();
+    route.len() == segments.len()
+        && route
+            .iter()
+            .zip(segments)
+            .all(|(route, segment)| route.starts_with('{') || route == segment)
+}
+
+fn error_response(status: &'static str, error: impl std::fmt::Display) -> Response {
+    (status, json!({ "error": error.to_string() }))
+}
+
+fn bad_request(error: impl std::fmt::Display) -> Response {
+    error_response("400 Bad Request", error)
+}
+
+/// The response to a request that failed in the client or cluster.
+fn unavailable(status: tb::PacketStatus) -> Response {
+    error_response("503 Service Unavailable", status)
+}
+
+fn respond<T: Serialize>(result: Result<Vec<T>, tb::PacketStatus>) -> Result<Value, Response> {
+    let values = result.map_err(unavailable)?;
+    serde_json::to_value(values).map_err(|error| error_response("500 Internal Server Error", error))
+}
+
+/// Create the events of a request, giving those without an id one.
+fn create<T: Serialize + DeserializeOwned + Default, R: Serialize>(
+    body: &[u8],
+    idempotency_key: Option<(&dyn IdempotencyStore, String)>,
+    id: fn(&mut T) -> &mut u128,
+    create: impl FnOnce(&[T]) -> Result<Vec<R>, tb::PacketStatus>,
+) -> Result<Value, Response> {
+    let mut events = events(body)?;
+    assign_ids(&mut events, idempotency_key, id).map_err(|error| match error {
+        IdempotencyError::KeyReused => error_response("422 Unprocessable Entity", error),
+        error => error_response("503 Service Unavailable", error),
+    })?;
+    let ids: Vec<String> = events
+        .iter_mut()
+        .map(|event| id(event).to_string())
+        .collect();
+    let results = create(&events).map_err(unavailable)?;
+    Ok(json!({ "ids": ids, "results": results }))
+}
+
+/// The events of a create request: a JSON array of one to `BATCH_MAX`.
+fn events<T: Serialize + DeserializeOwned + Default>(body: &[u8]) -> Result<Vec<T>, Response> {
+    let values = match serde_json::from_slice(body).map_err(bad_request)? {
+        Value::Array(values) => values,
+        _ => return Err(bad_request("expected an array of events")),
+    };
+    if values.is_empty() || values.len() > tb::BATCH_MAX {
+        return Err(bad_request(format!(
+            "expected 1 to {} events, not {}",
+            tb::BATCH_MAX,
+            values.len()
+        )));
+    }
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            object(value).map_err(|error| bad_request(format!("[{index}]: {error}")))
+        })
+        .collect()
+}
+
+/// The filter of a query request.
+fn filter<T: Serialize + DeserializeOwned + Default>(body: &[u8]) -> Result<T, Response> {
+    object(serde_json::from_slice(body).map_err(bad_request)?).map_err(bad_request)
+}
+
+/// Deserialize an object, rejecting fields `T` doesn't have.
+fn object<T: Serialize + DeserializeOwned + Default>(value: Value) -> Result<T, String> {
+    let fields = fields::<T>();
+    match &value {
+        Value::Object(object) => {
+            if let Some(field) = object.keys().find(|key| !fields.contains_key(*key)) {
+                return Err(format!("unknown field {field}"));
+            }
+        }
+        _ => return Err("expected an object".to_owned()),
+    }
+    serde_json::from_value(value).map_err(|error| error.to_string())
+}
+
+/// The fields of `T` as serialized, with their default values.
+fn fields<T: Serialize + Default>() -> Map<String, Value> {
+    match serde_json::to_value(T::default()) {
+        Ok(Value::Object(fields)) => fields,
+        _ => unreachable!("events serialize as objects"),
+    }
+}
+
+/// An id, as a decimal or hexadecimal string.
+#[derive(Deserialize)]
+struct Id(#[serde(with = "tb::serde_u128")] u128);
+
+fn lookup_ids(body: &[u8]) -> Result<Vec<u128>, Response> {
+    let ids: Vec<Id> = serde_json::from_slice(body).map_err(bad_request)?;
+    if ids.is_empty() || ids.len() > tb::BATCH_MAX {
+        return Err(bad_request(format!("expected 1 to {} ids", tb::BATCH_MAX)));
+    }
+    Ok(ids.into_iter().map(|Id(id)| id).collect())
+}
+
+/// Look up the account or transfer with the id in a path.
+fn lookup<T: Serialize>(
+    id: &str,
+    lookup: impl FnOnce(&[u128]) -> Result<Vec<T>, tb::PacketStatus>,
+) -> Result<Value, Response> {
+    let id = match id.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16),
+        None => id.parse(),
+    }
+    .map_err(|_| bad_request(format!("invalid id {id}")))?;
+    match lookup(&[id]).map_err(unavailable)?.pop() {
+        Some(found) => serde_json::to_value(found)
+            .map_err(|error| error_response("500 Internal Server Error", error)),
+        None => Err(error_response("404 Not Found", "not found")),
+    }
+}
+
+/// Give the events without an id one, or with an idempotency key, the ids
+/// stored for the key.
+fn assign_ids<T>(
+    events: &mut [T],
+    idempotency_key: Option<(&dyn IdempotencyStore, String)>,
+    id: fn(&mut T) -> &mut u128,
+) -> Result<(), IdempotencyError> {
+    match idempotency_key {
+        Some((store, key)) => idempotency::assign_ids(store, &key, events, id),
+        None => {
+            for event in events {
+                if *id(event) == 0 {
+                    *id(event) = tb::id();
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// A route, for the OpenAPI document.
+struct Route {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    request: Option<Value>,
+    response: Value,
+}
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{name}") })
+}
+
+fn array(name: &str) -> Value {
+    json!({ "type": "array", "items": schema(name) })
+}
+
+fn routes() -> Vec<Route> {
+    let route = |method, path, summary, request, response| Route {
+        method,
+        path,
+        summary,
+        request,
+        response,
+    };
+    vec![
+        route(
+            "get",
+            "/openapi.json",
+            "This document",
+            None,
+            json!({ "type": "object" }),
+        ),
+        route(
+            "post",
+            "/accounts",
+            "Create accounts",
+            Some(array("Account")),
+            json!({
+                "type": "object",
+                "properties": {
+                    "ids": array("Id"),
+                    "results": array("CreateAccountsResult"),
+                },
+            }),
+        ),
+        route(
+            "post",
+            "/transfers",
+            "Create transfers",
+            Some(array("Transfer")),
+            json!({
+                "type": "object",
+                "properties": {
+                    "ids": array("Id"),
+                    "results": array("CreateTransfersResult"),
+                },
+            }),
+        ),
+        route(
+            "get",
+            "/accounts/{id}",
+            "Look up an account",
+            None,
+            schema("Account"),
+        ),
+        route(
+            "get",
+            "/transfers/{id}",
+            "Look up a transfer",
+            None,
+            schema("Transfer"),
+        ),
+        route(
+            "post",
+            "/accounts/lookup",
+            "Look up accounts",
+            Some(array("Id")),
+            array("Account"),
+        ),
+        route(
+            "post",
+            "/transfers/lookup",
+            "Look up transfers",
+            Some(array("Id")),
+            array("Transfer"),
+        ),
+        route(
+            "post",
+            "/accounts/query",
+            "Query accounts",
+            Some(schema("QueryFilter")),
+            array("Account"),
+        ),
+        route(
+            "post",
+            "/transfers/query",
+            "Query transfers",
+            Some(schema("QueryFilter")),
+            array("Transfer"),
+        ),
+        route(
+            "post",
+            "/accounts/transfers",
+            "Get an account's transfers",
+            Some(schema("AccountFilter")),
+            array("Transfer"),
+        ),
+        route(
+            "post",
+            "/accounts/balances",
+            "Get an account's historical balances",
+            Some(schema("AccountFilter")),
+            array("AccountBalance"),
+        ),
+    ]
+}
+
+/// The schema of an object type, generated from its serialized fields.
+fn object_schema<T: Serialize + Default>(description: &str) -> Value {
+    let properties: Map<String, Value> = fields::<T>()
+        .into_iter()
+        .map(|(field, default)| {
+            let schema = match default {
+                _ if field == "flags" => json!({
+                    "type": "string",
+                    "description": "Flag names separated by |, e.g. \"Linked | Pending\"",
+                }),
+                Value::String(_) => schema("U128"),
+                _ => json!({ "type": "integer", "minimum": 0 }),
+            };
+            (field, schema)
+        })
+        .collect();
+    json!({ "type": "object", "description": description, "properties": properties })
+}
+
+fn result_schema(description: &str) -> Value {
+    json!({
+        "type": "object",
+        "description": description,
+        "properties": {
+            "index": { "type": "integer", "minimum": 0 },
+            "result": { "type": "string" },
+        },
+    })
+}
+
+/// The OpenAPI document describing the gateway.
+fn openapi() -> Value {
+    let mut paths = Map::new();
+    for route in routes() {
+        let mut operation = json!({
+            "summary": route.summary,
+            "responses": {
+                "200": {
+                    "description": "Success",
+                    "content": { "application/json": { "schema": route.response } },
+                },
+                "400": { "$ref": "#/components/responses/Error" },
+                "503": { "$ref": "#/components/responses/Error" },
+            },
+        });
+        if let Some(request) = route.request {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": request } },
+            });
+        }
+        if route.path.contains("{id}") {
+            operation["parameters"] = json!([{
+                "name": "id",
+                "in": "path",
+                "required": true,
+                "schema": schema("U128"),
+            }]);
+            operation["responses"]["404"] = json!({ "$ref": "#/components/responses/Error" });
+        }
+        if route.path == "/accounts" || route.path == "/transfers" {
+            operation["parameters"] = json!([{
+                "name": "Idempotency-Key",
+                "in": "header",
+                "description": "Give events without an id the ids first used with this key",
+                "schema": { "type": "string" },
+            }]);
+        }
+        let path = paths
+            .entry(route.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        path[route.method] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "TigerBeetle gateway",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "U128": {
+                    "type": "string",
+                    "description": "A 128-bit integer, in decimal or 0x-prefixed hexadecimal",
+                    "pattern": "^([0-9]+|0x[0-9a-fA-F]+)$",
+                },
+                "Id": schema("U128"),
+                "Account": object_schema::<tb::Account>("An account"),
+                "Transfer": object_schema::<tb::Transfer>("A transfer"),
+                "AccountBalance": object_schema::<tb::AccountBalance>("An account balance"),
+                "AccountFilter": object_schema::<tb::AccountFilter>("A filter of an account's transfers"),
+                "QueryFilter": object_schema::<tb::QueryFilter>("A filter of accounts or transfers"),
+                "CreateAccountsResult": result_schema("The result of a failed account, by index"),
+                "CreateTransfersResult": result_schema("The result of a failed transfer, by index"),
+            },
+            "responses": {
+                "Error": {
+                    "description": "An error",
+                    "content": {
+                        "application/json": {
+                            "schema": {
+                                "type": "object",
+                                "properties": { "error": { "type": "string" } },
+                            },
+                        },
+                    },
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            args(&["--addresses", "3001", "--listen", "0.0.0.0:80"]),
+            Ok(Args {
+                cluster_id: 0,
+                addresses: "3001".to_owned(),
+                listen: "0.0.0.0:80".to_owned(),
+                redis: None,
+                idempotency_ttl: Duration::from_secs(86400),
+            })
+        );
+        let parsed = args(&["--redis", "localhost:6379", "--idempotency-ttl", "60"]).unwrap();
+        assert_eq!(parsed.redis.as_deref(), Some("localhost:6379"));
+        assert_eq!(parsed.idempotency_ttl, Duration::from_secs(60));
+        assert!(args(&["--idempotency-ttl", "0"]).is_err());
+        assert!(args(&["--cluster-id", "x"]).is_err());
+        assert!(args(&["--listen"]).is_err());
+    }
+
+    #[test]
+    fn request() {
+        let request = read_request(
+            &b"POST /transfers HTTP/1.1\r\n\
+               Host: localhost\r\n\
+               Idempotency-Key: order-42\r\n\
+               content-length: 2\r\n\r\n[]"[..],
+        )
+        .unwrap();
+        assert_eq!(
+            request,
+            Request {
+                method: "POST".to_owned(),
+                path: "/transfers".to_owned(),
+                idempotency_key: Some("order-42".to_owned()),
+                body: b"[]".to_vec(),
+            }
+        );
+
+        let error = read_request(&b"\r\n"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error =
+            read_request(&b"POST / HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n"[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let long = format!(
+            "GET / HTTP/1.1\r\nX: {}\r\n\r\n",
+            "a".repeat(HEAD_MAX as usize)
+        );
+        let error = read_request(long.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "headers too large");
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: a\r\n".repeat(HEADERS_MAX + 1)
+        );
+        let error = read_request(many.as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "too many headers");
+        let error = read_request(&b"GET / HTTP/1.1\r\nX: a"[..]).unwrap_err();
+        assert_eq!(error.to_string(), "incomplete request");
+        let error =
+            read_request(&b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\n[]"[..]).unwrap_err();
+        assert_eq!(error.to_string(), "incomplete request");
+    }
+
+    #[test]
+    fn validation() {
+        let transfers: Vec<tb::Transfer> =
+            events(br#"[{"id": "0x10", "amount": "100", "ledger": 1}]"#).unwrap();
+        assert_eq!(transfers[0].id, 16);
+        assert_eq!(transfers[0].amount, 100);
+
+        let error = |body: &str| match events::<tb::Transfer>(body.as_bytes()) {
+            Err(("400 Bad Request", error)) => error["error"].as_str().unwrap().to_owned(),
+            result => panic!("{result:?}"),
+        };
+        assert_eq!(
+            error(r#"[{"ammount": "100"}]"#),
+            "[0]: unknown field ammount"
+        );
+        assert_eq!(error(r#"{"amount": "100"}"#), "expected an array of events");
+        assert_eq!(error("[]"), "expected 1 to 8189 events, not 0");
+        assert!(error(r#"[{}, {"amount": -1}]"#).starts_with("[1]: "));
+        assert!(error("[").starts_with("EOF"));
+
+        let filter: tb::QueryFilter = filter(br#"{"ledger": 1, "limit": 10}"#).unwrap();
+        assert_eq!((filter.ledger, filter.limit), (1, 10));
+        assert!(super::filter::<tb::QueryFilter>(br#"{"account_id": "1"}"#).is_err());
+        assert_eq!(lookup_ids(br#"["1", "0x2"]"#).unwrap(), vec![1, 2]);
+        assert!(lookup_ids(b"[]").is_err());
+    }
+
+    #[test]
+    fn idempotency() {
+        let store = InMemoryStore::new(Duration::from_secs(60));
+        let key = || {
+            Some((
+                &store as &dyn IdempotencyStore,
+                "transfers:order-42".to_owned(),
+            ))
+        };
+        let mut transfers = [
+            tb::Transfer::default(),
+            tb::Transfer {
+                id: 7,
+                ..Default::default()
+            },
+            tb::Transfer::default(),
+        ];
+        assign_ids(&mut transfers, key(), |t| &mut t.id).unwrap();
+        assert!(transfers[0].id != 0 && transfers[0].id != transfers[2].id);
+        assert_eq!(transfers[1].id, 7);
+
+        // A retry is given the same ids.
+        let mut retried = [tb::Transfer::default(); 3];
+        retried[1].id = 7;
+        assign_ids(&mut retried, key(), |t| &mut t.id).unwrap();
+        assert_eq!(retried, transfers);
+
+        let mut other = [tb::Transfer::default(); 2];
+        assert_eq!(
+            assign_ids(&mut other, key(), |t| &mut t.id),
+            Err(IdempotencyError::KeyReused)
+        );
+        assert_eq!(other, [tb::Transfer::default(); 2]);
+
+        let mut transfers = [tb::Transfer::default(); 2];
+        assign_ids(&mut transfers, None, |t| &mut t.id).unwrap();
+        assert!(transfers[0].id != 0 && transfers[0].id != transfers[1].id);
+    }
+
+    #[test]
+    fn routes() {
+        assert!(route_matches("/accounts/{id}", &["accounts", "1"]));
+        assert!(!route_matches("/accounts/{id}", &["accounts"]));
+        assert!(!route_matches("/accounts/query", &["accounts", "lookup"]));
+
+        let document = openapi();
+        assert_eq!(
+            document["paths"].as_object().unwrap().len(),
+            super::routes().len()
+        );
+        assert!(document["paths"]["/accounts/{id}"]["get"].is_object());
+        assert!(document["paths"]["/transfers"]["post"]["requestBody"].is_object());
+        let transfer = &document["components"]["schemas"]["Transfer"]["properties"];
+        assert_eq!(transfer["amount"]["$ref"], "#/components/schemas/U128");
+        assert_eq!(transfer["ledger"]["type"], "integer");
+        assert_eq!(transfer["flags"]["type"], "string");
+        assert!(transfer.get("reserved").is_none());
+    }
+}
@@ -0,0 +1,721 @@
+//! `tb`, a command line client for TigerBeetle.
+//!
+//! ```text
+//! tb create-account --id 1 --ledger 840 --code 10
+//! tb transfer --debit 1 --credit 2 --amount 1000 --ledger 840 --code 1
+//! tb lookup account 1 2
+//! tb balances 1 --limit 10 --json
+//! ```
+//!
+//! Events can also be given as JSON, or read from JSON Lines batch files, in
+//! the format of the `serde` feature. `tb repl` starts an interactive prompt.
+
+mod repl;
+
+use tb::ledger::{ChartOfAccounts, LedgerRegistry};
+use tigerbeetle as tb;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: tb [options] <command> [arguments]
+
+Commands:
+  create-account  Create accounts, from options, --json or --file
+  transfer        Create transfers, from options, --json or --file
+  lookup          lookup (account|transfer) <id>...
+  query           query (accounts|transfers) with filter options
+  balances        balances <account-id>, of an account with the history flag
+  repl            An interactive prompt; try help
+
+Options:
+  --cluster <id>       The cluster id [default: 0]
+  --addresses <list>   The replica addresses [default: $TB_ADDRESS or 3000]
+  --json               Print JSON Lines rather than tables
+  --ledgers <path>     A JSON ledger registry, to print amounts as decimals
+
+Event options:
+  --json <event>       A single account or transfer as JSON
+  --file <path>        A JSON Lines file of accounts or transfers, or - for stdin
+  --id <id>            [default: a new tb::id()]
+  --debit <id>, --credit <id>, --amount <amount>, --pending-id <id>, --timeout <seconds>
+  --ledger <ledger>, --code <code>, --flags <flag | flag>
+  --user-data-128 <u128>, --user-data-64 <u64>, --user-data-32 <u32>
+
+Filter options:
+  --ledger, --code, --user-data-128, --user-data-64, --user-data-32,
+  --timestamp-min <ns>, --timestamp-max <ns>, --limit <count> [default: 100], --reversed
+
+Repl options:
+  --chart <path>       A JSON chart of accounts, to name accounts
+  --scale <digits>     The scale of amounts of ledgers not in --ledgers [default: 0]
+
+Ids may be decimal, or hexadecimal with a 0x prefix.
+";
+
+/// Options that don't take a value.
+const SWITCHES: &[&str] = &["--help", "--reversed"];
+
+/// A parsed command line.
+#[derive(Debug, Default)]
+struct Args {
+    positional: Vec<String>,
+    options: BTreeMap<String, String>,
+    switches: BTreeSet<String>,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if SWITCHES.contains(&arg.as_str()) {
+                parsed.switches.insert(arg);
+            } else if arg == "--json" {
+                // A switch for output, or an option with an event.
+                match args.next() {
+                    Some(value) if value.starts_with('{') => {
+                        parsed.options.insert(arg, value);
+                    }
+                    value => {
+                        parsed.switches.insert(arg);
+                        parsed.positional.extend(value);
+                    }
+                }
+            } else if arg.starts_with("--") {
+                let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+                if parsed.options.insert(arg.clone(), value).is_some() {
+                    return Err(format!("{arg} given more than once"));
+                }
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn switch(&mut self, name: &str) -> bool {
+        self.switches.remove(name)
+    }
+
+    fn take<T: Value>(&mut self, name: &str) -> Result<Option<T>, String> {
+        self.options
+            .remove(name)
+            .map(|value| T::parse(&value).ok_or_else(|| format!("invalid {name} {value}")))
+            .transpose()
+    }
+
+    fn positional(&mut self, name: &str) -> Result<String, String> {
+        if self.positional.is_empty() {
+            return Err(format!("missing {name}"));
+        }
+        Ok(self.positional.remove(0))
+    }
+
+    /// Fail if any arguments were not used.
+    fn finish(self) -> Result<(), String> {
+        let unused = self
+            .options
+            .keys()
+            .chain(&self.switches)
+            .chain(&self.positional)
+            .next();
+        match unused {
+            Some(arg) => Err(format!("unexpected argument {arg}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A value of an option.
+trait Value: Sized {
+    fn parse(value: &str) -> Option<Self>;
+}
+
+impl Value for u128 {
+    fn parse(value: &str) -> Option<u128> {
+        match value.strip_prefix("0x") {
+            Some(hex) => u128::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    }
+}
+
+macro_rules! value_from_str {
+    ($($t:ty),*) => {
+        $(impl Value for $t {
+            fn parse(value: &str) -> Option<$t> {
+                value.parse().ok()
+            }
+        })*
+    };
+}
+value_from_str!(u64, u32, u16, u8, String);
+
+macro_rules! value_from_flags {
+    ($($t:ty),*) => {
+        $(impl Value for $t {
+            fn parse(value: &str) -> Option<$t> {
+                bitflags::parser::from_str(value).ok()
+            }
+        })*
+    };
+}
+value_from_flags!(tb::AccountFlags, tb::TransferFlags);
+
+fn main() -> ExitCode {
+    match run(std::env::args().skip(1)) {
+        Ok(code) => code,
+        Err(Error::Usage(error)) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+        Err(Error::Failed(error)) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+enum Error {
+    /// The arguments are invalid.
+    Usage(String),
+    Failed(String),
+}
+
+impl From<String> for Error {
+    fn from(error: String) -> Error {
+        Error::Usage(error)
+    }
+}
+
+fn failed(error: impl std::fmt::Display) -> Error {
+    Error::Failed(error.to_string())
+}
+
+fn run(args: impl IntoIterator<Item = String>) -> Result<ExitCode, Error> {
+    let mut args = Args::parse(args)?;
+    if args.switch("--help") {
+        print!("{USAGE}");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let cluster_id = args.take("--cluster")?.unwrap_or(0);
+    let addresses = match args.take::<String>("--addresses")? {
+        Some(addresses) => addresses,
+        None => std::env::var("TB_ADDRESS").unwrap_or_else(|_| "3000".to_owned()),
+    };
+    let output = Output {
+        json: args.switch("--json"),
+        ledgers: match args.take::<String>("--ledgers")? {
+            Some(path) => load(&path)?,
+            None => LedgerRegistry::new(),
+        },
+    };
+    let command = args.positional("command")?;
+
+    // Parse everything before connecting.
+    let request = Request::parse(&command, &mut args)?;
+    args.finish()?;
+
+    let client = tb::blocking::Client::new(cluster_id, &addresses).map_err(failed)?;
+    let result = request.execute(&client, &output);
+    client.close();
+    result
+}
+
+enum Request {
+    CreateAccounts(Vec<tb::Account>),
+    CreateTransfers(Vec<tb::Transfer>),
+    LookupAccounts(Vec<u128>),
+    LookupTransfers(Vec<u128>),
+    QueryAccounts(tb::QueryFilter),
+    QueryTransfers(tb::QueryFilter),
+    GetAccountBalances(tb::AccountFilter),
+    Repl { chart: ChartOfAccounts, scale: u8 },
+}
+
+impl Request {
+    fn parse(command: &str, args: &mut Args) -> Result<Request, Error> {
+        Ok(match command {
+            "create-account" => {
+                Request::CreateAccounts(events(args, parse_account, tb::io::import_accounts)?)
+            }
+            "transfer" => {
+                Request::CreateTransfers(events(args, parse_transfer, tb::io::import_transfers)?)
+            }
+            "lookup" => {
+                let kind = args.positional("account or transfer")?;
+                let ids = std::mem::take(&mut args.positional)
+                    .iter()
+                    .map(|id| Value::parse(id).ok_or_else(|| format!("invalid id {id}")))
+                    .collect::<Result<Vec<u128>, _>>()?;
+                if ids.is_empty() {
+                    return Err(Error::Usage("missing ids".to_owned()));
+                }
+                match kind.as_str() {
+                    "account" | "accounts" => Request::LookupAccounts(ids),
+                    "transfer" | "transfers" => Request::LookupTransfers(ids),
+                    _ => return Err(Error::Usage(format!("unknown lookup {kind}"))),
+                }
+            }
+            "query" => {
+                let kind = args.positional("accounts or transfers")?;
+                let filter = parse_query_filter(args)?;
+                match kind.as_str() {
+                    "accounts" => Request::QueryAccounts(filter),
+                    "transfers" => Request::QueryTransfers(filter),
+                    _ => return Err(Error::Usage(format!("unknown query {kind}"))),
+                }
+            }
+            "balances" => {
+                let account = args.positional("account id")?;
+                let mut filter = parse_account_filter(args)?;
+                filter.account_id =
+                    Value::parse(&account).ok_or_else(|| format!("invalid id {account}"))?;
+                Request::GetAccountBalances(filter)
+            }
+            "repl" => {
+                let chart = match args.take::<String>("--chart")? {
+                    Some(path) => load(&path)?,
+                    None => ChartOfAccounts::new(),
+                };
+                let scale = args.take("--scale")?.unwrap_or(0);
+                if scale > tb::Amount::SCALE_MAX {
+                    return Err(Error::Usage(format!("invalid --scale {scale}")));
+                }
+                Request::Repl { chart, scale }
+            }
+            _ => return Err(Error::Usage(format!("unknown command {command}"))),
+        })
+    }
+
+    fn execute(self, client: &tb::blocking::Client, output: &Output) -> Result<ExitCode, Error> {
+        match self {
+            Request::CreateAccounts(accounts) => {
+                let results = client.create_accounts(&accounts).map_err(failed)?;
+                output.results(accounts.len(), &results, |result| {
+                    (result.index, result.result.to_string())
+                })
+            }
+            Request::CreateTransfers(transfers) => {
+                let results = client.create_transfers(&transfers).map_err(failed)?;
+                output.results(transfers.len(), &results, |result| {
+                    (result.index, result.result.to_string())
+                })
+            }
+            Request::LookupAccounts(ids) => {
+                output.accounts(&client.lookup_accounts(&ids).map_err(failed)?)
+            }
+            Request::LookupTransfers(ids) => {
+                output.transfers(&client.lookup_transfers(&ids).map_err(failed)?)
+            }
+            Request::QueryAccounts(filter) => {
+                output.accounts(&client.query_accounts(filter).map_err(failed)?)
+            }
+            Request::QueryTransfers(filter) => {
+                output.transfers(&client.query_transfers(filter).map_err(failed)?)
+            }
+            Request::GetAccountBalances(filter) => {
+                // The account's ledger, for the scale of its amounts.
+                let ledger = if output.ledgers.is_empty() {
+                    0
+                } else {
+                    let accounts = client
+                        .lookup_accounts(&[filter.account_id])
+                        .map_err(failed)?;
+                    accounts.first().map_or(0, |account| account.ledger)
+                };
+                output.balances(
+                    ledger,
+                    &client.get_account_balances(filter).map_err(failed)?,
+                )
+            }
+            Request::Repl { chart, scale } => {
+                repl::run(client, &chart, scale, output, std::io::stdin().lock())
+            }
+        }
+    }
+}
+
+/// Load a JSON file, such as a chart of accounts.
+fn load<T: serde::de::DeserializeOwned>(path: &str) -> Result<T, Error> {
+    let file = File::open(path).map_err(|error| failed(format!("{path}: {error}")))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|error| failed(format!("{path}: {error}")))
+}
+
+/// A batch file, or stdin.
+type Input = Box<dyn BufRead>;
+
+/// The events of a create command, from `--file`, `--json`, or options.
+fn events<T: serde::de::DeserializeOwned>(
+    args: &mut Args,
+    parse: fn(&mut Args) -> Result<T, String>,
+    import: fn(Input) -> tb::io::JsonLines<Input, T>,
+) -> Result<Vec<T>, Error> {
+    if let Some(path) = args.take::<String>("--file")? {
+        let reader: Input = if path == "-" {
+            Box::new(std::io::stdin().lock())
+        } else {
+            Box::new(BufReader::new(
+                File::open(&path).map_err(|error| failed(format!("{path}: {error}")))?,
+            ))
+        };
+        return import(reader)
+            .collect::<Result<Vec<T>, _>>()
+            .map_err(|error| failed(format!("{path}: {error}")));
+    }
+    if let Some(json) = args.take::<String>("--json")? {
+        let event = serde_json::from_str(&json).map_err(|error| format!("--json: {error}"))?;
+        return Ok(vec![event]);
+    }
+    Ok(vec![parse(args)?])
+}
+
+fn parse_account(args: &mut Args) -> Result<tb::Account, String> {
+    Ok(tb::Account {
+        id: args.take("--id")?.unwrap_or_else(tb::id),
+        ledger: args.take("--ledger")?.ok_or("missing --ledger")?,
+        code: args.take("--code")?.ok_or("missing --code")?,
+        flags: args.take("--flags")?.unwrap_or_default(),
+        user_data_128: args.take("--user-data-128")?.unwrap_or(0),
+        user_data_64: args.take("--user-data-64")?.unwrap_or(0),
+        user_data_32: args.take("--user-data-32")?.unwrap_or(0),
+        ..Default::default()
+    })
+}
+
+fn parse_transfer(args: &mut Args) -> Result<tb::Transfer, String> {
+    Ok(tb::Transfer {
+        id: args.take("--id")?.unwrap_or_else(tb::id),
+        debit_account_id: args.take("--debit")?.unwrap_or(0),
+        credit_account_id: args.take("--credit")?.unwrap_or(0),
+        amount: args.take("--amount")?.unwrap_or(0),
+        pending_id: args.take("--pending-id")?.unwrap_or(0),
+        timeout: args.take("--timeout")?.unwrap_or(0),
+        ledger: args.take("--ledger")?.unwrap_or(0),
+        code: args.take("--code")?.unwrap_or(0),
+        flags: args.take("--flags")?.unwrap_or_default(),
+        user_data_128: args.take("--user-data-128")?.unwrap_or(0),
+        user_data_64: args.take("--user-data-64")?.unwrap_or(0),
+        user_data_32: args.take("--user-data-32")?.unwrap_or(0),
+        ..Default::default()
+    })
+}
+
+fn parse_query_filter(args: &mut Args) -> Result<tb::QueryFilter, String> {
+    Ok(tb::QueryFilter {
+        user_data_128: args.take("--user-data-128")?.unwrap_or(0),
+        user_data_64: args.take("--user-data-64")?.unwrap_or(0),
+        user_data_32: args.take("--user-data-32")?.unwrap_or(0),
+        ledger: args.take("--ledger")?.unwrap_or(0),
+        code: args.take("--code")?.unwrap_or(0),
+        timestamp_min: args.take("--timestamp-min")?.unwrap_or(0),
+        timestamp_max: args.take("--timestamp-max")?.unwrap_or(0),
+        limit: args.take("--limit")?.unwrap_or(100),
+        flags: if args.switch("--reversed") {
+            tb::QueryFilterFlags::Reversed
+        } else {
+            tb::QueryFilterFlags::empty()
+        },
+        ..Default::default()
+    })
+}
+
+fn parse_account_filter(args: &mut Args) -> Result<tb::AccountFilter, String> {
+    let mut flags = tb::AccountFilterFlags::Debits | tb::AccountFilterFlags::Credits;
+    if args.switch("--reversed") {
+        flags |= tb::AccountFilterFlags::Reversed;
+    }
+    Ok(tb::AccountFilter {
+        timestamp_min: args.take("--timestamp-min")?.unwrap_or(0),
+        timestamp_max: args.take("--timestamp-max")?.unwrap_or(0),
+        limit: args.take("--limit")?.unwrap_or(100),
+        flags,
+        ..Default::default()
+    })
+}
+
+struct Output {
+    json: bool,
+    /// Amounts of these ledgers are printed as decimals in tables, such as
+    /// `USD 12.34`.
+    ledgers: LedgerRegistry,
+}
+
+impl Output {
+    /// Print the events that failed; exits with failure if any did.
+    fn results<T: serde::Serialize>(
+        &self,
+        count: usize,
+        results: &[T],
+        row: fn(&T) -> (usize, String),
+    ) -> Result<ExitCode, Error> {
+        if self.json {
+            self.json_lines(results)?;
+        } else if results.is_empty() {
+            println!("ok: {count} created");
+        } else {
+            let rows = results
+                .iter()
+                .map(|result| {
+                    let (index, result) = row(result);
+                    vec![index.to_string(), result]
+                })
+                .collect();
+            print!("{}", table(&["index", "result"], rows));
+        }
+        Ok(if results.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    fn accounts(&self, accounts: &[tb::Account]) -> Result<ExitCode, Error> {
+        if self.json {
+            return self.json_lines(accounts);
+        }
+        let rows = accounts
+            .iter()
+            .map(|account| {
+                vec![
+                    account.id.to_string(),
+                    account.ledger.to_string(),
+                    account.code.to_string(),
+                    self.ledgers.format(account.ledger, account.debits_pending),
+                    self.ledgers.format(account.ledger, account.debits_posted),
+                    self.ledgers.format(account.ledger, account.credits_pending),
+                    self.ledgers.format(account.ledger, account.credits_posted),
+                    flags(account.flags),
+                ]
+            })
+            .collect();
+        print!(
+            "{}",
+            table(
+                &[
+                    "id",
+                    "ledger",
+                    "code",
+                    "debits_pending",
+                    "debits_posted",
+                    "credits_pending",
+                    "credits_posted",
+                    "flags",
+                ],
+                rows,
+            )
+        );
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn transfers(&self, transfers: &[tb::Transfer]) -> Result<ExitCode, Error> {
+        if self.json {
+            return self.json_lines(transfers);
+        }
+        let rows = transfers
+            .iter()
+            .map(|transfer| {
+                vec![
+                    transfer.id.to_string(),
+                    transfer.debit_account_id.to_string(),
+                    transfer.credit_account_id.to_string(),
+                    self.ledgers.format(transfer.ledger, transfer.amount),
+                    transfer.ledger.to_string(),
+                    transfer.code.to_string(),
+                    flags(transfer.flags),
+                    transfer.timestamp.to_string(),
+                ]
+            })
+            .collect();
+        print!(
+            "{}",
+            table(
+                &[
+                    "id",
+                    "debit_account_id",
+                    "credit_account_id",
+                    "amount",
+                    "ledger",
+                    "code",
+                    "flags",
+                    "timestamp",
+                ],
+                rows,
+            )
+        );
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn balances(&self, ledger: u32, balances: &[tb::AccountBalance]) -> Result<ExitCode, Error> {
+        if self.json {
+            return self.json_lines(balances);
+        }
+        let rows = balances
+            .iter()
+            .map(|balance| {
+                vec![
+                    balance.timestamp.to_string(),
+                    self.ledgers.format(ledger, balance.debits_pending),
+                    self.ledgers.format(ledger, balance.debits_posted),
+                    self.ledgers.format(ledger, balance.credits_pending),
+                    self.ledgers.format(ledger, balance.credits_posted),
+                ]
+            })
+            .collect();
+        print!(
+            "{}",
+            table(
+                &[
+                    "timestamp",
+                    "debits_pending",
+                    "debits_posted",
+                    "credits_pending",
+                    "credits_posted",
+                ],
+                rows,
+            )
+        );
+        Ok(ExitCode::SUCCESS)
+    }
+
+    fn json_lines<T: serde::Serialize>(&self, values: &[T]) -> Result<ExitCode, Error> {
+        let mut stdout = std::io::stdout().lock();
+        for value in values {
+            serde_json::to_writer(&mut stdout, value).map_err(failed)?;
+            writeln!(stdout).map_err(failed)?;
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn flags<F: bitflags::Flags>(flags: F) -> String
+where
+    F::Bits: bitflags::parser::WriteHex,
+{
+    let mut names = String::new();
+    // Writing to a String can't fail.
+    let _ = bitflags::parser::to_writer(&flags, &mut names);
+    names
+}
+
+/// Format rows as a table with aligned columns.
+fn table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    let headers = headers.iter().map(|header| header.to_string()).collect();
+    for row in std::iter::once(headers).chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Args {
+        Args::parse(args.split_whitespace().map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn transfer() {
+        let mut args =
+            args("--debit 1 --credit 0x2 --amount 100 --ledger 1 --code 7 --flags Pending --id 9");
+        let transfer = parse_transfer(&mut args).unwrap();
+        args.finish().unwrap();
+        assert_eq!(
+            transfer,
+            tb::Transfer {
+                id: 9,
+                debit_account_id: 1,
+                credit_account_id: 2,
+                amount: 100,
+                ledger: 1,
+                code: 7,
+                flags: tb::TransferFlags::Pending,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn request() {
+        let mut parsed = args("--json lookup account 1 0x10");
+        assert!(parsed.switch("--json"));
+        let command = parsed.positional("command").unwrap();
+        assert!(matches!(
+            Request::parse(&command, &mut parsed),
+            Ok(Request::LookupAccounts(ids)) if ids == [1, 16]
+        ));
+
+        let mut parsed = Args::parse(
+            [
+                "create-account",
+                "--json",
+                r#"{"id": "5", "ledger": 2, "code": 3}"#,
+            ]
+            .map(String::from),
+        )
+        .unwrap();
+        let command = parsed.positional("command").unwrap();
+        assert!(matches!(
+            Request::parse(&command, &mut parsed),
+            Ok(Request::CreateAccounts(accounts)) if accounts[0].id == 5 && accounts[0].ledger == 2
+        ));
+        parsed.finish().unwrap();
+
+        let mut parsed = args("create-account --ledger 1");
+        let command = parsed.positional("command").unwrap();
+        assert!(matches!(
+            Request::parse(&command, &mut parsed),
+            Err(Error::Usage(error)) if error == "missing --code"
+        ));
+
+        let mut parsed = args("query accounts --ledger 1 --reversed --bogus 1");
+        let command = parsed.positional("command").unwrap();
+        assert!(matches!(
+            Request::parse(&command, &mut parsed),
+            Ok(Request::QueryAccounts(filter))
+                if filter.ledger == 1 && filter.flags == tb::QueryFilterFlags::Reversed
+        ));
+        assert_eq!(
+            parsed.finish(),
+            Err("unexpected argument --bogus".to_owned())
+        );
+    }
+
+    #[test]
+    fn table() {
+        assert_eq!(
+            super::table(
+                &["id", "flags"],
+                vec![
+                    vec!["1".to_owned(), "Linked | Pending".to_owned()],
+                    vec!["100".to_owned(), "".to_owned()],
+                ],
+            ),
+            "id   flags\n1    Linked | Pending\n100\n"
+        );
+        assert_eq!(
+            flags(tb::TransferFlags::Linked | tb::TransferFlags::Pending),
+            "Linked | Pending"
+        );
+    }
+}
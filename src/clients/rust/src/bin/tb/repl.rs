@@ -0,0 +1,317 @@
+//! `tb repl`, an interactive prompt with a small query language.
+//!
+//! ```text
+//! tb> transfer 10.00 from cash to revenue on ledger 1 code 100
+//! tb> balance cash
+//! ```
+//!
+//! Accounts are named by their chart of accounts, or by id.
+
+use super::{failed, Error, Output};
+
+use tb::ledger::{ChartOfAccounts, LedgerRegistry};
+use tigerbeetle as tb;
+
+use std::io::{BufRead, Write};
+use std::process::ExitCode;
+
+const HELP: &str = "\
+Statements:
+  transfer <amount> from <account> to <account> [on ledger <ledger>] code <code>
+  balance <account>
+  lookup <account>
+  help
+  quit
+
+Accounts are names from the chart of accounts, or ids. Amounts are decimals
+with the scale of their ledger in --ledgers, or given by --scale.
+";
+
+#[derive(Debug, Eq, PartialEq)]
+enum Statement {
+    Transfer(TransferStatement),
+    Balance(String),
+    Help,
+    Quit,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct TransferStatement {
+    amount: String,
+    from: String,
+    to: String,
+    ledger: Option<u32>,
+    code: u16,
+}
+
+fn parse(line: &str) -> Result<Option<Statement>, String> {
+    let mut tokens = line.split_whitespace();
+    let keyword = match tokens.next() {
+        Some(keyword) => keyword,
+        None => return Ok(None),
+    };
+    let mut next = |expected: &str| {
+        tokens
+            .next()
+            .map(str::to_owned)
+            .ok_or_else(|| format!("expected {expected}"))
+    };
+
+    let statement = match keyword {
+        "transfer" => {
+            let amount = next("an amount")?;
+            expect(&next("from")?, "from")?;
+            let from = next("an account")?;
+            expect(&next("to")?, "to")?;
+            let to = next("an account")?;
+
+            let mut ledger = None;
+            let mut code = None;
+            while let Ok(token) = next("") {
+                match token.as_str() {
+                    "on" => {
+                        expect(&next("ledger")?, "ledger")?;
+                        ledger = Some(number(&next("a ledger")?)?);
+                    }
+                    "ledger" => ledger = Some(number(&next("a ledger")?)?),
+                    "code" => code = Some(number(&next("a code")?)?),
+                    _ => return Err(format!("unexpected {token}")),
+                }
+            }
+            Statement::Transfer(TransferStatement {
+                amount,
+                from,
+                to,
+                ledger,
+                code: code.ok_or("expected code")?,
+            })
+        }
+        "balance" | "lookup" => Statement::Balance(next("an account")?),
+        "help" => Statement::Help,
+        "quit" | "exit" => Statement::Quit,
+        _ => return Err(format!("unknown statement {keyword}; try help")),
+    };
+    match next("") {
+        Ok(token) => Err(format!("unexpected {token}")),
+        Err(_) => Ok(Some(statement)),
+    }
+}
+
+fn expect(token: &str, expected: &str) -> Result<(), String> {
+    if token == expected {
+        Ok(())
+    } else {
+        Err(format!("expected {expected}, found {token}"))
+    }
+}
+
+fn number<T: std::str::FromStr>(token: &str) -> Result<T, String> {
+    token.parse().map_err(|_| format!("invalid number {token}"))
+}
+
+/// The id and, if it is in the chart, the ledger of an account.
+fn resolve(chart: &ChartOfAccounts, account: &str) -> Result<(u128, Option<u32>), String> {
+    if let Some(definition) = chart.get(account) {
+        return Ok((definition.id, Some(definition.ledger)));
+    }
+    <u128 as super::Value>::parse(account)
+        .map(|id| (id, None))
+        .ok_or_else(|| format!("unknown account {account}"))
+}
+
+/// The transfer for a `transfer` statement.
+///
+/// The amount has the scale of the ledger in `ledgers`, or `scale` if it
+/// isn't registered.
+fn transfer(
+    chart: &ChartOfAccounts,
+    ledgers: &LedgerRegistry,
+    scale: u8,
+    statement: &TransferStatement,
+) -> Result<tb::Transfer, String> {
+    let (debit_account_id, debit_ledger) = resolve(chart, &statement.from)?;
+    let (credit_account_id, credit_ledger) = resolve(chart, &statement.to)?;
+
+    let mut candidates = [statement.ledger, debit_ledger, credit_ledger]
+        .into_iter()
+        .flatten();
+    let ledger = candidates
+        .next()
+        .ok_or("the ledger is unknown; add on ledger <ledger>")?;
+    if candidates.any(|other| other != ledger) {
+        return Err("the accounts are on different ledgers".to_owned());
+    }
+    let scale = ledgers.get(ledger).map_or(scale, |info| info.scale);
+    let amount = tb::Amount::parse(&statement.amount, scale)
+        .map_err(|error| format!("{error}: {}", statement.amount))?;
+
+    Ok(tb::Transfer {
+        id: tb::id(),
+        debit_account_id,
+        credit_account_id,
+        amount: amount.units(),
+        ledger,
+        code: statement.code,
+        ..Default::default()
+    })
+}
+
+/// Run statements from `input` until it ends or `quit`.
+pub(super) fn run(
+    client: &tb::blocking::Client,
+    chart: &ChartOfAccounts,
+    scale: u8,
+    output: &Output,
+    mut input: impl BufRead,
+) -> Result<ExitCode, Error> {
+    let mut line = String::new();
+    loop {
+        print!("tb> ");
+        std::io::stdout().flush().map_err(failed)?;
+        line.clear();
+        if input.read_line(&mut line).map_err(failed)? == 0 {
+            println!();
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let result = match parse(&line) {
+            Ok(None) => continue,
+            Ok(Some(Statement::Quit)) => return Ok(ExitCode::SUCCESS),
+            Ok(Some(Statement::Help)) => {
+                print!("{HELP}");
+                continue;
+            }
+            Ok(Some(Statement::Balance(account))) => {
+                execute_balance(client, chart, output, &account)
+            }
+            Ok(Some(Statement::Transfer(statement))) => {
+                execute_transfer(client, chart, scale, output, &statement)
+            }
+            Err(error) => Err(Error::Usage(error)),
+        };
+        match result {
+            Ok(_) => {}
+            Err(Error::Usage(error)) | Err(Error::Failed(error)) => eprintln!("error: {error}"),
+        }
+    }
+}
+
+fn execute_balance(
+    client: &tb::blocking::Client,
+    chart: &ChartOfAccounts,
+    output: &Output,
+    account: &str,
+) -> Result<ExitCode, Error> {
+    let (id, _) = resolve(chart, account)?;
+    let accounts = client.lookup_accounts(&[id]).map_err(failed)?;
+    if accounts.is_empty() {
+        return Err(failed(format!("account {account} not found")));
+    }
+    output.accounts(&accounts)
+}
+
+fn execute_transfer(
+    client: &tb::blocking::Client,
+    chart: &ChartOfAccounts,
+    scale: u8,
+    output: &Output,
+    statement: &TransferStatement,
+) -> Result<ExitCode, Error> {
+    let transfer = transfer(chart, &output.ledgers, scale, statement)?;
+    let results = client.create_transfers(&[transfer]).map_err(failed)?;
+    output.results(1, &results, |result| {
+        (result.index, result.result.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tb::ledger::{AccountDefinition, LedgerInfo};
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            super::parse("transfer 10.00 from cash to revenue on ledger 1 code 100"),
+            Ok(Some(Statement::Transfer(TransferStatement {
+                amount: "10.00".to_owned(),
+                from: "cash".to_owned(),
+                to: "revenue".to_owned(),
+                ledger: Some(1),
+                code: 100,
+            })))
+        );
+        assert_eq!(
+            super::parse("  balance   cash \n"),
+            Ok(Some(Statement::Balance("cash".to_owned())))
+        );
+        assert_eq!(super::parse("\n"), Ok(None));
+        assert_eq!(
+            super::parse("transfer 1 from a to b"),
+            Err("expected code".to_owned())
+        );
+        assert_eq!(
+            super::parse("transfer 1 from a into b code 1"),
+            Err("expected to, found into".to_owned())
+        );
+        assert_eq!(
+            super::parse("balance cash now"),
+            Err("unexpected now".to_owned())
+        );
+    }
+
+    #[test]
+    fn transfer() {
+        let mut chart = ChartOfAccounts::new();
+        for (name, id, ledger) in [("cash", 1, 840), ("revenue", 2, 840), ("euros", 3, 978)] {
+            chart.insert(
+                name,
+                AccountDefinition {
+                    id,
+                    ledger,
+                    code: 10,
+                    ..Default::default()
+                },
+            );
+        }
+        let mut ledgers = LedgerRegistry::new();
+        ledgers.insert(978, LedgerInfo::new("EUR", 3, "Euro"));
+        let transfer = |statement: &str| match super::parse(statement) {
+            Ok(Some(Statement::Transfer(statement))) => {
+                super::transfer(&chart, &ledgers, 2, &statement)
+            }
+            parsed => panic!("{parsed:?}"),
+        };
+
+        let created = transfer("transfer 10.50 from cash to 0x2 code 100").unwrap();
+        assert_eq!(
+            (
+                created.debit_account_id,
+                created.credit_account_id,
+                created.amount,
+                created.ledger,
+                created.code
+            ),
+            (1, 2, 1050, 840, 100)
+        );
+        assert_eq!(
+            transfer("transfer 1 from 1 to 2 on ledger 7 code 1")
+                .unwrap()
+                .ledger,
+            7
+        );
+
+        assert!(transfer("transfer 1 from 1 to 2 code 1").is_err());
+        assert!(transfer("transfer 1 from cash to euros code 1").is_err());
+        assert!(transfer("transfer 1 from cash to revenue on ledger 978 code 1").is_err());
+        assert!(transfer("transfer 1.001 from cash to revenue code 1").is_err());
+        assert_eq!(
+            transfer("transfer 1.001 from euros to 4 code 1")
+                .unwrap()
+                .amount,
+            1001
+        );
+        assert!(transfer("transfer 1 from cash to nowhere code 1").is_err());
+    }
+}
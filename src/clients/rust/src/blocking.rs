@@ -0,0 +1,449 @@
+//! A synchronous TigerBeetle client.
+//!
+//! [`blocking::Client`](Client) wraps the async [`tigerbeetle::Client`](crate::Client),
+//! blocking the calling thread until each request completes. No async runtime
+//! is needed: requests are executed by the client's own off-thread event loop,
+//! and the calling thread sleeps until it is woken with the response.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//!
+//! fn synchronous_function() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = tb::blocking::Client::new(0, "127.0.0.1:3000")?;
+//!
+//!     let accounts = [tb::Account {
+//!         id: tb::id(),
+//!         ledger: 1,
+//!         code: 1,
+//!         ..Default::default()
+//!     }];
+//!
+//!     let results = client.create_accounts(&accounts)?;
+//!
+//!     client.close();
+//!     Ok(())
+//! }
+//! ```
+//!
+//! A client configured with a [`ClientBuilder`](crate::ClientBuilder) is
+//! made blocking with [`Client::from`]. Methods not forwarded here can be
+//! called on the async client, from [`Client::as_async`], with any executor.
+
+use crate::{
+    addresses, Account, AccountBalance, AccountFilter, AccountSpec, ClientError, ClientEvent,
+    CreateAccountsError, CreateAccountsResult, CreateTransfersError, CreateTransfersResult,
+    EnsureAccountsSummary, EvictionReason, ImportError, InitStatus, PacketStatus, QueryFilter,
+    RateLimit, RetryPolicy, SubmitOptions, Transfer, VersionError, WouldBlock,
+};
+
+use futures_core::Stream;
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+/// A synchronous TigerBeetle client.
+///
+/// Each method blocks until its request completes. The methods otherwise
+/// behave as their async counterparts on [`tigerbeetle::Client`](crate::Client),
+/// which document their results in detail.
+///
+/// Like the async client, `Client` is `Send` and `Sync`, and requests made
+/// concurrently from multiple threads are batched together.
+pub struct Client {
+    client: crate::Client,
+}
+
+impl Client {
+    /// Create a new TigerBeetle client.
+    ///
+    /// See [`tigerbeetle::Client::new`](crate::Client::new).
+    pub fn new(cluster_id: u128, addresses: &str) -> Result<Client, InitStatus> {
+        crate::Client::new(cluster_id, addresses).map(Client::from)
+    }
+
+    /// See [`tigerbeetle::Client::set_batch_splitting`](crate::Client::set_batch_splitting).
+    pub fn set_batch_splitting(&mut self, enabled: bool) {
+        self.client.set_batch_splitting(enabled);
+    }
+
+    /// See [`tigerbeetle::Client::set_retry_policy`](crate::Client::set_retry_policy).
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.client.set_retry_policy(retry_policy);
+    }
+
+    /// See [`tigerbeetle::Client::set_rate_limit`](crate::Client::set_rate_limit).
+    pub fn set_rate_limit(&mut self, rate_limit: RateLimit) {
+        self.client.set_rate_limit(rate_limit);
+    }
+
+    /// See [`tigerbeetle::Client::set_validation`](crate::Client::set_validation).
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.client.set_validation(enabled);
+    }
+
+    /// See [`tigerbeetle::Client::set_duplicate_id_detection`](crate::Client::set_duplicate_id_detection).
+    pub fn set_duplicate_id_detection(&mut self, enabled: bool) {
+        self.client.set_duplicate_id_detection(enabled);
+    }
+
+    /// See [`tigerbeetle::Client::set_request_timeout`](crate::Client::set_request_timeout).
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.client.set_request_timeout(timeout);
+    }
+
+    /// See [`tigerbeetle::Client::set_webhooks`](crate::Client::set_webhooks).
+    #[cfg(feature = "webhooks")]
+    pub fn set_webhooks(&mut self, webhooks: crate::webhooks::Webhooks) {
+        self.client.set_webhooks(webhooks);
+    }
+
+    /// See [`tigerbeetle::Client::on_eviction`](crate::Client::on_eviction).
+    pub fn on_eviction(&mut self, on_eviction: impl Fn(EvictionReason) + Send + Sync + 'static) {
+        self.client.on_eviction(on_eviction);
+    }
+
+    /// See [`tigerbeetle::Client::on_event`](crate::Client::on_event).
+    pub fn on_event(&mut self, on_event: impl Fn(&ClientEvent) + Send + Sync + 'static) {
+        self.client.on_event(on_event);
+    }
+
+    /// See [`tigerbeetle::Client::update_addresses`](crate::Client::update_addresses).
+    pub fn update_addresses(
+        &self,
+        addresses: &[addresses::ReplicaAddress],
+    ) -> Result<(), InitStatus> {
+        self.client.update_addresses(addresses)
+    }
+
+    /// See [`tigerbeetle::Client::refresh_addresses`](crate::Client::refresh_addresses).
+    pub fn refresh_addresses(&self) -> Result<(), InitStatus> {
+        self.client.refresh_addresses()
+    }
+
+    /// Create one or more accounts.
+    ///
+    /// See [`tigerbeetle::Client::create_accounts`](crate::Client::create_accounts).
+    pub fn create_accounts(
+        &self,
+        events: &[Account],
+    ) -> Result<Vec<CreateAccountsResult>, PacketStatus> {
+        block_on(self.client.create_accounts(events))
+    }
+
+    /// Create one or more transfers.
+    ///
+    /// See [`tigerbeetle::Client::create_transfers`](crate::Client::create_transfers).
+    pub fn create_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<Vec<CreateTransfersResult>, PacketStatus> {
+        block_on(self.client.create_transfers(events))
+    }
+
+    /// Create one or more accounts, failing with [`WouldBlock`] instead of
+    /// blocking if the request would exceed the client's [`RateLimit`].
+    ///
+    /// See [`tigerbeetle::Client::try_create_accounts`](crate::Client::try_create_accounts).
+    pub fn try_create_accounts(
+        &self,
+        events: &[Account],
+    ) -> Result<Result<Vec<CreateAccountsResult>, PacketStatus>, WouldBlock> {
+        Ok(block_on(self.client.try_create_accounts(events)?))
+    }
+
+    /// Create one or more transfers, failing with [`WouldBlock`] instead of
+    /// blocking if the request would exceed the client's [`RateLimit`].
+    ///
+    /// See [`tigerbeetle::Client::try_create_transfers`](crate::Client::try_create_transfers).
+    pub fn try_create_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<Result<Vec<CreateTransfersResult>, PacketStatus>, WouldBlock> {
+        Ok(block_on(self.client.try_create_transfers(events)?))
+    }
+
+    /// See [`tigerbeetle::Client::create_accounts_with_deadline`](crate::Client::create_accounts_with_deadline).
+    pub fn create_accounts_with_deadline(
+        &self,
+        events: &[Account],
+        timeout: Duration,
+    ) -> Result<Vec<CreateAccountsResult>, ClientError> {
+        block_on(self.client.create_accounts_with_deadline(events, timeout))
+    }
+
+    /// See [`tigerbeetle::Client::create_transfers_with_deadline`](crate::Client::create_transfers_with_deadline).
+    pub fn create_transfers_with_deadline(
+        &self,
+        events: &[Transfer],
+        timeout: Duration,
+    ) -> Result<Vec<CreateTransfersResult>, ClientError> {
+        block_on(self.client.create_transfers_with_deadline(events, timeout))
+    }
+
+    /// See [`tigerbeetle::Client::create_accounts_with_options`](crate::Client::create_accounts_with_options).
+    pub fn create_accounts_with_options(
+        &self,
+        events: &[Account],
+        options: SubmitOptions,
+    ) -> Result<Vec<CreateAccountsResult>, PacketStatus> {
+        block_on(self.client.create_accounts_with_options(events, options))
+    }
+
+    /// See [`tigerbeetle::Client::create_transfers_with_options`](crate::Client::create_transfers_with_options).
+    pub fn create_transfers_with_options(
+        &self,
+        events: &[Transfer],
+        options: SubmitOptions,
+    ) -> Result<Vec<CreateTransfersResult>, PacketStatus> {
+        block_on(self.client.create_transfers_with_options(events, options))
+    }
+
+    /// Create accounts, failing if any of them could not be created.
+    ///
+    /// See [`tigerbeetle::Client::create_accounts_checked`](crate::Client::create_accounts_checked).
+    pub fn create_accounts_checked(&self, events: &[Account]) -> Result<(), CreateAccountsError> {
+        block_on(self.client.create_accounts_checked(events))
+    }
+
+    /// Create transfers, failing if any of them could not be created.
+    ///
+    /// See [`tigerbeetle::Client::create_transfers_checked`](crate::Client::create_transfers_checked).
+    pub fn create_transfers_checked(
+        &self,
+        events: &[Transfer],
+    ) -> Result<(), CreateTransfersError> {
+        block_on(self.client.create_transfers_checked(events))
+    }
+
+    /// Create accounts unless they already exist.
+    ///
+    /// See [`tigerbeetle::Client::ensure_accounts`](crate::Client::ensure_accounts).
+    pub fn ensure_accounts(
+        &self,
+        specs: &[AccountSpec],
+    ) -> Result<EnsureAccountsSummary, CreateAccountsError> {
+        block_on(self.client.ensure_accounts(specs))
+    }
+
+    /// Check transfers against the cluster's state without creating them.
+    ///
+    /// See [`tigerbeetle::Client::validate_transfers`](crate::Client::validate_transfers).
+    pub fn validate_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<Vec<CreateTransfersResult>, PacketStatus> {
+        block_on(self.client.validate_transfers(events))
+    }
+
+    /// See [`tigerbeetle::Client::import_accounts`](crate::Client::import_accounts).
+    pub fn import_accounts(
+        &self,
+        events: &[Account],
+    ) -> Result<Vec<CreateAccountsResult>, ImportError> {
+        block_on(self.client.import_accounts(events))
+    }
+
+    /// See [`tigerbeetle::Client::import_transfers`](crate::Client::import_transfers).
+    pub fn import_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<Vec<CreateTransfersResult>, ImportError> {
+        block_on(self.client.import_transfers(events))
+    }
+
+    /// Query individual accounts.
+    ///
+    /// See [`tigerbeetle::Client::lookup_accounts`](crate::Client::lookup_accounts).
+    pub fn lookup_accounts(&self, events: &[u128]) -> Result<Vec<Account>, PacketStatus> {
+        block_on(self.client.lookup_accounts(events))
+    }
+
+    /// Query individual transfers.
+    ///
+    /// See [`tigerbeetle::Client::lookup_transfers`](crate::Client::lookup_transfers).
+    pub fn lookup_transfers(&self, events: &[u128]) -> Result<Vec<Transfer>, PacketStatus> {
+        block_on(self.client.lookup_transfers(events))
+    }
+
+    /// Query multiple transfers for a single account.
+    ///
+    /// See [`tigerbeetle::Client::get_account_transfers`](crate::Client::get_account_transfers).
+    pub fn get_account_transfers(
+        &self,
+        event: AccountFilter,
+    ) -> Result<Vec<Transfer>, PacketStatus> {
+        block_on(self.client.get_account_transfers(event))
+    }
+
+    /// Query historical account balances for a single account.
+    ///
+    /// See [`tigerbeetle::Client::get_account_balances`](crate::Client::get_account_balances).
+    pub fn get_account_balances(
+        &self,
+        event: AccountFilter,
+    ) -> Result<Vec<AccountBalance>, PacketStatus> {
+        block_on(self.client.get_account_balances(event))
+    }
+
+    /// Query multiple accounts related by fields and timestamps.
+    ///
+    /// See [`tigerbeetle::Client::query_accounts`](crate::Client::query_accounts).
+    pub fn query_accounts(&self, event: QueryFilter) -> Result<Vec<Account>, PacketStatus> {
+        block_on(self.client.query_accounts(event))
+    }
+
+    /// Query multiple transfers related by fields and timestamps.
+    ///
+    /// See [`tigerbeetle::Client::query_transfers`](crate::Client::query_transfers).
+    pub fn query_transfers(&self, event: QueryFilter) -> Result<Vec<Transfer>, PacketStatus> {
+        block_on(self.client.query_transfers(event))
+    }
+
+    /// Query all transfers matching `filter`, blocking for each page.
+    ///
+    /// See [`tigerbeetle::Client::query_transfers_paged`](crate::Client::query_transfers_paged).
+    pub fn query_transfers_paged(&self, filter: QueryFilter) -> Iter<crate::PagedTransfers<'_>> {
+        Iter(self.client.query_transfers_paged(filter))
+    }
+
+    /// Watch the balances of accounts, blocking until each update. The
+    /// iterator never ends.
+    ///
+    /// See [`tigerbeetle::Client::watch_balances`](crate::Client::watch_balances).
+    pub fn watch_balances(
+        &self,
+        account_ids: &[u128],
+        interval: Duration,
+    ) -> Iter<crate::BalanceWatch<'_>> {
+        Iter(self.client.watch_balances(account_ids, interval))
+    }
+
+    /// Check connectivity to the cluster, returning the round-trip time.
+    ///
+    /// See [`tigerbeetle::Client::ping`](crate::Client::ping).
+    pub fn ping(&self) -> Result<Duration, PacketStatus> {
+        block_on(self.client.ping())
+    }
+
+    /// See [`tigerbeetle::Client::check_version`](crate::Client::check_version).
+    pub fn check_version(&self) -> Result<(), VersionError> {
+        block_on(self.client.check_version())
+    }
+
+    /// Close the client and block until all resources are released.
+    ///
+    /// See [`tigerbeetle::Client::close`](crate::Client::close).
+    pub fn close(self) {
+        block_on(self.client.close())
+    }
+
+    /// Block until in-flight requests complete, for up to `timeout`, then
+    /// close the client.
+    ///
+    /// See [`tigerbeetle::Client::drain_and_close`](crate::Client::drain_and_close).
+    pub fn drain_and_close(self, timeout: Duration) -> Result<(), ClientError> {
+        block_on(self.client.drain_and_close(timeout))
+    }
+
+    /// The async client, e.g. for methods not forwarded here.
+    pub fn as_async(&self) -> &crate::Client {
+        &self.client
+    }
+
+    /// Convert into the async client.
+    pub fn into_async(self) -> crate::Client {
+        self.client
+    }
+}
+
+/// An iterator over the items of one of the async client's streams,
+/// blocking until each is ready.
+pub struct Iter<S>(S);
+
+impl<S: Stream + Unpin> Iterator for Iter<S> {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        struct Next<'a, S>(&'a mut S);
+
+        impl<S: Stream + Unpin> Future for Next<'_, S> {
+            type Output = Option<S::Item>;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+                Pin::new(&mut *self.0).poll_next(cx)
+            }
+        }
+
+        block_on(Next(&mut self.0))
+    }
+}
+
+impl<S> fmt::Debug for Iter<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str("blocking::Iter")
+    }
+}
+
+impl From<crate::Client> for Client {
+    fn from(client: crate::Client) -> Client {
+        Client { client }
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str("blocking::Client")
+    }
+}
+
+/// Run a future to completion on the current thread.
+///
+/// Client futures are completed by the client's own event loop thread,
+/// so all that's needed here is to park until woken.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+        match future.as_mut().poll(&mut context) {
+            Poll::Ready(output) => return output,
+            // Spurious wakeups just cause another poll.
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures_channel::oneshot::channel;
+    use std::time::Duration;
+
+    #[test]
+    fn block_on_wakes_from_another_thread() {
+        let (tx, rx) = channel::<u32>();
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(42).unwrap();
+        });
+
+        assert_eq!(block_on(rx), Ok(42));
+        sender.join().unwrap();
+    }
+}
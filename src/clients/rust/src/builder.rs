@@ -0,0 +1,350 @@
+use super::*;
+
+/// A builder for configuring a [`Client`].
+///
+/// # Example
+///
+/// ```no_run
+/// use tigerbeetle as tb;
+///
+/// # fn example() -> Result<(), tb::ClientBuildError> {
+/// let client = tb::ClientBuilder::new()
+///     .cluster_id_hex("0x1f")
+///     .address("10.0.0.1:3000")
+///     .address("10.0.0.2:3000")
+///     .address("10.0.0.3:3000")
+///     .retry_policy(tb::RetryPolicy::new())
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Requests can be given a timeout with [`ClientBuilder::request_timeout`],
+/// or individual deadlines with [`with_deadline`], and the client's retries
+/// and errors can be logged with [`ClientBuilder::on_event`], or with the
+/// `tracing` feature.
+#[derive(Clone)]
+pub struct ClientBuilder {
+    cluster_id: Result<u128, ClientBuildError>,
+    addresses: Vec<String>,
+    split_batches: bool,
+    validate: bool,
+    detect_duplicate_ids: bool,
+    retry_policy: RetryPolicy,
+    rate_limit: RateLimit,
+    address_refresh_interval: Option<Duration>,
+    request_timeout: Option<Duration>,
+    on_event: Option<events::OnEvent>,
+    #[cfg(feature = "webhooks")]
+    webhooks: Option<webhooks::Webhooks>,
+}
+
+impl ClientBuilder {
+    /// Create a builder for a client of cluster 0, with no addresses.
+    pub fn new() -> ClientBuilder {
+        ClientBuilder {
+            cluster_id: Ok(0),
+            addresses: Vec::new(),
+            split_batches: true,
+            validate: true,
+            detect_duplicate_ids: false,
+            retry_policy: RetryPolicy::none(),
+            rate_limit: RateLimit::new(),
+            address_refresh_interval: None,
+            request_timeout: None,
+            on_event: None,
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
+        }
+    }
+
+    /// Set the cluster id.
+    pub fn cluster_id(mut self, cluster_id: u128) -> ClientBuilder {
+        self.cluster_id = Ok(cluster_id);
+        self
+    }
+
+    /// Set the cluster id from a hexadecimal string, with or without a
+    /// `0x` prefix.
+    ///
+    /// If the string is not valid, [`ClientBuilder::build`] fails with
+    /// [`ClientBuildError::ClusterIdInvalid`].
+    pub fn cluster_id_hex(mut self, cluster_id: &str) -> ClientBuilder {
+        let digits = cluster_id
+            .strip_prefix("0x")
+            .or_else(|| cluster_id.strip_prefix("0X"))
+            .unwrap_or(cluster_id);
+        self.cluster_id = if digits.is_empty() || digits.starts_with('+') {
+            Err(ClientBuildError::ClusterIdInvalid)
+        } else {
+            u128::from_str_radix(digits, 16).map_err(|_| ClientBuildError::ClusterIdInvalid)
+        };
+        self
+    }
+
+    /// Add the address of a replica.
+    ///
+    /// Replicas may be added in any order. See [`Client::new`] for the
+    /// address format.
+    pub fn address(mut self, address: &str) -> ClientBuilder {
+        self.addresses.push(address.to_owned());
+        self
+    }
+
+    /// Add the addresses of replicas from a comma-separated string.
+    ///
+    /// This is the format accepted by [`Client::new`].
+    pub fn addresses(mut self, addresses: &str) -> ClientBuilder {
+        self.addresses.extend(
+            addresses
+                .split(',')
+                .map(str::trim)
+                .filter(|address| !address.is_empty())
+                .map(str::to_owned),
+        );
+        self
+    }
+
+    /// See [`Client::set_batch_splitting`].
+    pub fn batch_splitting(mut self, enabled: bool) -> ClientBuilder {
+        self.split_batches = enabled;
+        self
+    }
+
+    /// See [`Client::set_validation`].
+    pub fn validation(mut self, enabled: bool) -> ClientBuilder {
+        self.validate = enabled;
+        self
+    }
+
+    /// See [`Client::set_duplicate_id_detection`].
+    pub fn duplicate_id_detection(mut self, enabled: bool) -> ClientBuilder {
+        self.detect_duplicate_ids = enabled;
+        self
+    }
+
+    /// See [`Client::set_retry_policy`].
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> ClientBuilder {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// See [`Client::set_rate_limit`].
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> ClientBuilder {
+        self.rate_limit = rate_limit;
+        self
+    }
+
+    /// See [`Client::set_request_timeout`].
+    pub fn request_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`Client::on_event`].
+    pub fn on_event(
+        mut self,
+        on_event: impl Fn(&ClientEvent) + Send + Sync + 'static,
+    ) -> ClientBuilder {
+        self.on_event = Some(Arc::new(on_event));
+        self
+    }
+
+    /// See [`Client::set_webhooks`].
+    #[cfg(feature = "webhooks")]
+    pub fn webhooks(mut self, webhooks: webhooks::Webhooks) -> ClientBuilder {
+        self.webhooks = Some(webhooks);
+        self
+    }
+
+    /// Resolve the replica addresses again every `interval`, reconnecting
+    /// if any of them changed.
+    ///
+    /// By default addresses are only resolved when a session is registered.
+    /// See [`Client::refresh_addresses`].
+    pub fn address_refresh_interval(mut self, interval: Duration) -> ClientBuilder {
+        self.address_refresh_interval = Some(interval);
+        self
+    }
+
+    /// Create the client.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ClientBuildError::ClusterIdInvalid`] if the cluster id could
+    /// not be parsed, [`ClientBuildError::AddressesMissing`] if no addresses
+    /// were given, [`ClientBuildError::Address`] if an address is invalid or
+    /// could not be resolved, and [`ClientBuildError::Init`] if the client
+    /// could not be initialized.
+    pub fn build(self) -> Result<Client, ClientBuildError> {
+        let cluster_id = self.cluster_id?;
+        if self.addresses.is_empty() {
+            return Err(ClientBuildError::AddressesMissing);
+        }
+        let addresses = addresses::parse(&self.addresses.join(","))?;
+        // Resolve up front to report which address failed.
+        addresses::resolve(&addresses)?;
+
+        let mut client = Client::with_addresses(cluster_id, addresses)?;
+        client.set_batch_splitting(self.split_batches);
+        client.set_validation(self.validate);
+        client.set_duplicate_id_detection(self.detect_duplicate_ids);
+        client.set_retry_policy(self.retry_policy);
+        client.set_rate_limit(self.rate_limit);
+        client.set_request_timeout(self.request_timeout);
+        client.on_event = self.on_event;
+        #[cfg(feature = "webhooks")]
+        if let Some(webhooks) = self.webhooks {
+            client.set_webhooks(webhooks);
+        }
+        if let Some(interval) = self.address_refresh_interval {
+            ClientCore::refresh_addresses_every(&client.core, interval);
+        }
+        Ok(client)
+    }
+
+    /// Create a [`ClientPool`] of `size` clients, each configured by this
+    /// builder.
+    ///
+    /// The clients share the rate limit, which limits the pool as a whole,
+    /// and the webhooks' background thread.
+    ///
+    /// # Errors
+    ///
+    /// As for [`ClientBuilder::build`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn build_pool(self, size: usize) -> Result<ClientPool, ClientBuildError> {
+        assert!(size > 0, "client pool size must be greater than zero");
+        let mut clients = vec![self.clone().build()?];
+        while clients.len() < size {
+            let mut client = ClientBuilder {
+                rate_limit: RateLimit::new(),
+                #[cfg(feature = "webhooks")]
+                webhooks: None,
+                ..self.clone()
+            }
+            .build()?;
+            client.limiter = clients[0].limiter.clone();
+            #[cfg(feature = "webhooks")]
+            {
+                client.webhooks = clients[0].webhooks.clone();
+            }
+            clients.push(client);
+        }
+        Ok(ClientPool::from_clients(clients))
+    }
+}
+
+impl core::fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut debug = f.debug_struct("ClientBuilder");
+        debug
+            .field("cluster_id", &self.cluster_id)
+            .field("addresses", &self.addresses)
+            .field("split_batches", &self.split_batches)
+            .field("validate", &self.validate)
+            .field("detect_duplicate_ids", &self.detect_duplicate_ids)
+            .field("retry_policy", &self.retry_policy)
+            .field("rate_limit", &self.rate_limit)
+            .field("address_refresh_interval", &self.address_refresh_interval)
+            .field("request_timeout", &self.request_timeout)
+            .field("on_event", &self.on_event.is_some());
+        #[cfg(feature = "webhooks")]
+        debug.field("webhooks", &self.webhooks);
+        debug.finish()
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+/// Errors resulting from building a [`Client`] with a [`ClientBuilder`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ClientBuildError {
+    /// The cluster id is not a valid hexadecimal `u128`.
+    ClusterIdInvalid,
+    /// No replica addresses were given.
+    AddressesMissing,
+    /// An address is invalid. See [`AddressError`].
+    ///
+    /// [`AddressError`]: addresses::AddressError
+    Address(addresses::AddressError),
+    /// The client could not be initialized. See [`InitStatus`].
+    Init(InitStatus),
+}
+
+impl From<InitStatus> for ClientBuildError {
+    fn from(status: InitStatus) -> ClientBuildError {
+        ClientBuildError::Init(status)
+    }
+}
+
+impl From<addresses::AddressError> for ClientBuildError {
+    fn from(error: addresses::AddressError) -> ClientBuildError {
+        ClientBuildError::Address(error)
+    }
+}
+
+impl std::error::Error for ClientBuildError {}
+impl core::fmt::Display for ClientBuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::ClusterIdInvalid => f.write_str("cluster id invalid"),
+            Self::AddressesMissing => f.write_str("addresses missing"),
+            Self::Address(error) => core::fmt::Display::fmt(error, f),
+            Self::Init(status) => core::fmt::Display::fmt(status, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cluster_id_hex() {
+        let cluster_id = |hex| ClientBuilder::new().cluster_id_hex(hex).cluster_id;
+
+        assert_eq!(cluster_id("0"), Ok(0));
+        assert_eq!(cluster_id("0x1F"), Ok(31));
+        assert_eq!(cluster_id("ff"), Ok(255));
+        assert_eq!(
+            cluster_id("ffffffffffffffffffffffffffffffff"),
+            Ok(u128::MAX)
+        );
+        assert_eq!(cluster_id(""), Err(ClientBuildError::ClusterIdInvalid));
+        assert_eq!(cluster_id("0x"), Err(ClientBuildError::ClusterIdInvalid));
+        assert_eq!(cluster_id("+1"), Err(ClientBuildError::ClusterIdInvalid));
+        assert_eq!(cluster_id("0xg"), Err(ClientBuildError::ClusterIdInvalid));
+        assert_eq!(
+            cluster_id("1ffffffffffffffffffffffffffffffff"),
+            Err(ClientBuildError::ClusterIdInvalid)
+        );
+    }
+
+    #[test]
+    fn addresses() {
+        let builder = ClientBuilder::new()
+            .address("3000")
+            .addresses("127.0.0.1:3001, 3002,");
+        assert_eq!(builder.addresses, ["3000", "127.0.0.1:3001", "3002"]);
+
+        let result = ClientBuilder::new().build();
+        assert_eq!(result.err(), Some(ClientBuildError::AddressesMissing));
+
+        let result = ClientBuilder::new().address("3000").address("x:0").build();
+        assert_eq!(
+            result.err(),
+            Some(ClientBuildError::Address(
+                addresses::AddressError::PortInvalid { index: 1 }
+            ))
+        );
+    }
+}
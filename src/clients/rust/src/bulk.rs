@@ -0,0 +1,854 @@
+//! Bulk import of transfers from CSV files.
+//!
+//! [`import_transfers`] reads a CSV file with a header row, maps its columns
+//! to transfer fields with a [`Mapping`], and creates the transfers in
+//! batches of at most [`BATCH_MAX`] events. Rows that can't be parsed, or
+//! that the cluster rejects, are written to a CSV error report, so that a
+//! migration from another system can be corrected and resumed.
+//! [`write_transfers`] exports transfers to CSV, with decimal amounts.
+//!
+//! Importing the same file again is safe: transfers that already exist are
+//! counted as existing, and not reported.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::{BufReader, BufWriter};
+//! use tigerbeetle as tb;
+//! use tb::bulk::{Field, Mapping};
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), Box<dyn std::error::Error>> {
+//! // txn_id,from_account,to_account,amount
+//! // 1001,1,2,12.50
+//! let mapping = Mapping::new(tb::Transfer {
+//!     ledger: 840,
+//!     code: 1,
+//!     ..Default::default()
+//! })
+//! .column("txn_id", Field::Id)
+//! .column("from_account", Field::DebitAccountId)
+//! .column("to_account", Field::CreditAccountId)
+//! .column("amount", Field::Amount)
+//! .amount_scale(2);
+//!
+//! let csv = BufReader::new(File::open("ledger.csv")?);
+//! let report = BufWriter::new(File::create("ledger-errors.csv")?);
+//! let summary = tb::bulk::import_transfers(client, &mapping, csv, report).await?;
+//! println!("{} created, {} failed", summary.created, summary.failed);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`BATCH_MAX`]: crate::BATCH_MAX
+
+use crate::ledger::LedgerRegistry;
+use crate::{
+    Amount, AmountError, CreateTransferResult, PacketStatus, TigerBeetleClient, Transfer,
+    TransferFlags, BATCH_MAX,
+};
+
+use std::io::{self, BufRead, Write};
+
+/// A transfer field that a CSV column can be mapped to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum Field {
+    Id,
+    DebitAccountId,
+    CreditAccountId,
+    /// An integer number of the ledger's smallest unit, or a decimal amount
+    /// if the mapping has an [amount scale](Mapping::amount_scale) or the
+    /// transfer's ledger is in its [ledgers](Mapping::ledgers).
+    Amount,
+    PendingId,
+    UserData128,
+    UserData64,
+    UserData32,
+    Timeout,
+    Ledger,
+    Code,
+    /// Flag names separated by `|`, such as `Linked | Pending`, added to
+    /// the default flags.
+    Flags,
+}
+
+/// How the columns of a CSV file map to transfer fields.
+///
+/// Columns are found by their name in the header row. Columns that aren't
+/// mapped are ignored, and fields that aren't mapped, or whose cell is
+/// empty, take their value from the default transfer.
+///
+/// `u128` fields may be decimal, or hexadecimal with a `0x` prefix.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Mapping {
+    columns: Vec<(String, Field)>,
+    defaults: Transfer,
+    amount_scale: Option<u8>,
+    ledgers: LedgerRegistry,
+}
+
+impl Mapping {
+    /// A mapping with no columns, for transfers based on `defaults`.
+    pub fn new(defaults: Transfer) -> Mapping {
+        Mapping {
+            columns: Vec::new(),
+            defaults,
+            amount_scale: None,
+            ledgers: LedgerRegistry::new(),
+        }
+    }
+
+    /// Map the column named `name` to `field`.
+    pub fn column(mut self, name: &str, field: Field) -> Mapping {
+        self.columns.push((name.to_owned(), field));
+        self
+    }
+
+    /// Parse amounts as decimals with `scale` fractional digits, with
+    /// [`Amount::parse`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `scale` is greater than [`Amount::SCALE_MAX`].
+    pub fn amount_scale(mut self, scale: u8) -> Mapping {
+        assert!(scale <= Amount::SCALE_MAX);
+        self.amount_scale = Some(scale);
+        self
+    }
+
+    /// Parse amounts as decimals with the scale of the transfer's ledger in
+    /// `ledgers`, as written by [`write_transfers`].
+    ///
+    /// Amounts of ledgers that aren't registered are parsed with the
+    /// [amount scale](Mapping::amount_scale), if any.
+    pub fn ledgers(mut self, ledgers: LedgerRegistry) -> Mapping {
+        self.ledgers = ledgers;
+        self
+    }
+
+    /// Find the mapped columns in the header row.
+    fn resolve(&self, header: &[String]) -> Result<Vec<(usize, &str, Field)>, BulkError> {
+        self.columns
+            .iter()
+            .map(|(name, field)| {
+                header
+                    .iter()
+                    .position(|column| column.trim() == name)
+                    .map(|index| (index, name.as_str(), *field))
+                    .ok_or_else(|| BulkError::ColumnMissing(name.clone()))
+            })
+            .collect()
+    }
+
+    fn transfer(
+        &self,
+        columns: &[(usize, &str, Field)],
+        record: &[String],
+    ) -> Result<Transfer, RowError> {
+        let mut transfer = self.defaults;
+        let mut amount = None;
+        for &(index, name, field) in columns {
+            let value = record.get(index).map(|value| value.trim()).unwrap_or("");
+            if value.is_empty() {
+                continue;
+            }
+            let invalid = || RowError::FieldInvalid {
+                column: name.to_owned(),
+            };
+            match field {
+                Field::Id => transfer.id = parse_u128(value).ok_or_else(invalid)?,
+                Field::DebitAccountId => {
+                    transfer.debit_account_id = parse_u128(value).ok_or_else(invalid)?
+                }
+                Field::CreditAccountId => {
+                    transfer.credit_account_id = parse_u128(value).ok_or_else(invalid)?
+                }
+                // Parsed once the ledger is known.
+                Field::Amount => amount = Some((name, value)),
+                Field::PendingId => transfer.pending_id = parse_u128(value).ok_or_else(invalid)?,
+                Field::UserData128 => {
+                    transfer.user_data_128 = parse_u128(value).ok_or_else(invalid)?
+                }
+                Field::UserData64 => {
+                    transfer.user_data_64 = value.parse().map_err(|_| invalid())?
+                }
+                Field::UserData32 => {
+                    transfer.user_data_32 = value.parse().map_err(|_| invalid())?
+                }
+                Field::Timeout => transfer.timeout = value.parse().map_err(|_| invalid())?,
+                Field::Ledger => transfer.ledger = value.parse().map_err(|_| invalid())?,
+                Field::Code => transfer.code = value.parse().map_err(|_| invalid())?,
+                Field::Flags => {
+                    transfer.flags |=
+                        bitflags::parser::from_str::<TransferFlags>(value).map_err(|_| invalid())?
+                }
+            }
+        }
+        if let Some((name, value)) = amount {
+            let scale = match self.ledgers.get(transfer.ledger) {
+                Some(info) => Some(info.scale),
+                None => self.amount_scale,
+            };
+            transfer.amount = match scale {
+                Some(scale) => Amount::parse(value, scale)
+                    .map(|amount| amount.units())
+                    .map_err(|error| RowError::AmountInvalid {
+                        column: name.to_owned(),
+                        error,
+                    })?,
+                None => parse_u128(value).ok_or_else(|| RowError::FieldInvalid {
+                    column: name.to_owned(),
+                })?,
+            }
+        }
+        Ok(transfer)
+    }
+}
+
+fn parse_u128(value: &str) -> Option<u128> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) if !hex.starts_with('+') => u128::from_str_radix(hex, 16).ok(),
+        None if !value.starts_with('+') => value.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The outcome of [`import_transfers`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ImportSummary {
+    /// The number of rows read, excluding the header.
+    pub rows: usize,
+    pub created: usize,
+    /// Transfers that already existed with the same fields.
+    pub existing: usize,
+    /// Rows written to the error report.
+    pub failed: usize,
+}
+
+/// Why a row was written to the error report.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum RowError {
+    /// A cell could not be parsed as its field.
+    FieldInvalid { column: String },
+    /// An amount could not be parsed with the mapping's scale.
+    AmountInvalid { column: String, error: AmountError },
+    /// Another row of the same linked chain could not be parsed, so the
+    /// chain was not submitted.
+    LinkedRowInvalid,
+    /// The cluster did not create the transfer.
+    Rejected(CreateTransferResult),
+}
+
+impl std::error::Error for RowError {}
+impl core::fmt::Display for RowError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::FieldInvalid { column } => write!(f, "invalid {column}"),
+            Self::AmountInvalid { column, error } => write!(f, "{column}: {error}"),
+            Self::LinkedRowInvalid => f.write_str("another row of the linked chain is invalid"),
+            Self::Rejected(result) => core::fmt::Display::fmt(result, f),
+        }
+    }
+}
+
+/// Errors that stop an import.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BulkError {
+    /// Reading the CSV file or writing the report failed.
+    Io(io::Error),
+    /// The CSV file has no header row.
+    HeaderMissing,
+    /// A mapped column is not in the header row.
+    ColumnMissing(String),
+    /// A request failed. Transfers of earlier batches may have been
+    /// created; importing the file again is safe.
+    Packet(PacketStatus),
+    /// A row could not be read by [`read_transfers`].
+    Row { row: usize, error: RowError },
+}
+
+impl From<io::Error> for BulkError {
+    fn from(error: io::Error) -> BulkError {
+        BulkError::Io(error)
+    }
+}
+
+impl From<PacketStatus> for BulkError {
+    fn from(status: PacketStatus) -> BulkError {
+        BulkError::Packet(status)
+    }
+}
+
+impl std::error::Error for BulkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Packet(status) => Some(status),
+            Self::Row { error, .. } => Some(error),
+            Self::HeaderMissing | Self::ColumnMissing(_) => None,
+        }
+    }
+}
+
+impl core::fmt::Display for BulkError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => core::fmt::Display::fmt(error, f),
+            Self::HeaderMissing => f.write_str("header row missing"),
+            Self::ColumnMissing(name) => write!(f, "column {name} missing"),
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+            Self::Row { row, error } => write!(f, "row {row}: {error}"),
+        }
+    }
+}
+
+/// Import transfers from the CSV file `csv`, writing rows that failed to
+/// `report`.
+///
+/// The report is a CSV file with the columns `row`, the row's number
+/// counting from 1 after the header, `id`, and `error`, in the order the
+/// errors are found. Requests through a [`Client`](crate::Client) are
+/// retried according to its [`RetryPolicy`](crate::RetryPolicy).
+///
+/// Linked chains, rows with the `Linked` flag followed by a row without it,
+/// are submitted in the same batch. If a row of a chain can't be parsed,
+/// none of the chain is submitted.
+///
+/// The files are read and written synchronously, between requests.
+///
+/// # Errors
+///
+/// Returns [`BulkError::HeaderMissing`] or [`BulkError::ColumnMissing`]
+/// before submitting anything if the header doesn't match `mapping`, and
+/// [`BulkError::Io`] or [`BulkError::Packet`] if reading, writing or a
+/// request fails part way through.
+pub async fn import_transfers<R: BufRead, W: Write>(
+    client: &dyn TigerBeetleClient,
+    mapping: &Mapping,
+    csv: R,
+    report: W,
+) -> Result<ImportSummary, BulkError> {
+    let mut records = CsvReader::new(csv);
+    let header = records.next().ok_or(BulkError::HeaderMissing)??;
+    let columns = mapping.resolve(&header)?;
+
+    let mut import = Import {
+        client,
+        report: CsvWriter::new(report),
+        summary: ImportSummary::default(),
+        batch: Vec::new(),
+        chain: Vec::new(),
+        chain_invalid: false,
+    };
+    import.report.write(&["row", "id", "error"])?;
+
+    for record in records {
+        let record = record?;
+        import.summary.rows += 1;
+        let row = import.summary.rows;
+        let linked = match mapping.transfer(&columns, &record) {
+            Ok(transfer) => {
+                import.chain.push(Row { row, transfer });
+                transfer.flags.contains(TransferFlags::Linked)
+            }
+            Err(error) => {
+                let cell = |wanted: Field| {
+                    columns
+                        .iter()
+                        .find(|(_, _, field)| *field == wanted)
+                        .and_then(|&(index, _, _)| record.get(index))
+                        .map_or("", |value| value.trim())
+                };
+                import.fail(row, cell(Field::Id), &error)?;
+                import.chain_invalid = true;
+                // Keep the chain open if the row looks linked, so that the
+                // rest of its chain is not submitted without it.
+                mapping.defaults.flags.contains(TransferFlags::Linked)
+                    || cell(Field::Flags).contains("Linked")
+            }
+        };
+        if !linked {
+            import.end_chain().await?;
+        }
+    }
+    // An unterminated chain is submitted as is, and fails in the cluster.
+    import.end_chain().await?;
+    import.submit().await?;
+    import.report.flush()?;
+
+    Ok(import.summary)
+}
+
+/// Read the transfers of the CSV file `csv`, without creating them, e.g.
+/// as a statement to [reconcile](crate::reconcile).
+///
+/// # Errors
+///
+/// Returns [`BulkError::HeaderMissing`] or [`BulkError::ColumnMissing`] if
+/// the header doesn't match `mapping`, [`BulkError::Row`] for the first row
+/// that can't be parsed, and [`BulkError::Io`] if reading fails.
+pub fn read_transfers<R: BufRead>(mapping: &Mapping, csv: R) -> Result<Vec<Transfer>, BulkError> {
+    let mut records = CsvReader::new(csv);
+    let header = records.next().ok_or(BulkError::HeaderMissing)??;
+    let columns = mapping.resolve(&header)?;
+    records
+        .enumerate()
+        .map(|(index, record)| {
+            mapping
+                .transfer(&columns, &record?)
+                .map_err(|error| BulkError::Row {
+                    row: index + 1,
+                    error,
+                })
+        })
+        .collect()
+}
+
+/// Write `transfers` to the CSV file `csv`, with a header row.
+///
+/// Amounts are decimals with the scale of their ledger in `ledgers`, and
+/// the `currency` column is the ledger's currency, or empty if the ledger
+/// isn't registered. Flags are written as their names, and ids as decimals,
+/// so the file can be read back with a [`Mapping`] whose columns are named
+/// after the fields, and whose [ledgers](Mapping::ledgers) are `ledgers`.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_transfers<W: Write>(
+    ledgers: &LedgerRegistry,
+    transfers: &[Transfer],
+    csv: W,
+) -> io::Result<()> {
+    let mut csv = CsvWriter::new(csv);
+    csv.write(&[
+        "id",
+        "debit_account_id",
+        "credit_account_id",
+        "amount",
+        "currency",
+        "pending_id",
+        "user_data_128",
+        "user_data_64",
+        "user_data_32",
+        "timeout",
+        "ledger",
+        "code",
+        "flags",
+        "timestamp",
+    ])?;
+    for transfer in transfers {
+        let currency = ledgers
+            .get(transfer.ledger)
+            .map_or("", |info| info.currency.as_str());
+        let mut flags = String::new();
+        // Writing to a String can't fail.
+        let _ = bitflags::parser::to_writer(&transfer.flags, &mut flags);
+        csv.write(&[
+            &transfer.id.to_string(),
+            &transfer.debit_account_id.to_string(),
+            &transfer.credit_account_id.to_string(),
+            &ledgers.amount(transfer.ledger, transfer.amount).to_string(),
+            currency,
+            &transfer.pending_id.to_string(),
+            &transfer.user_data_128.to_string(),
+            &transfer.user_data_64.to_string(),
+            &transfer.user_data_32.to_string(),
+            &transfer.timeout.to_string(),
+            &transfer.ledger.to_string(),
+            &transfer.code.to_string(),
+            &flags,
+            &transfer.timestamp.to_string(),
+        ])?;
+    }
+    csv.flush()
+}
+
+struct Row {
+    /// From 1, after the header.
+    row: usize,
+    transfer: Transfer,
+}
+
+struct Import<'a, W: Write> {
+    client: &'a dyn TigerBeetleClient,
+    report: CsvWriter<W>,
+    summary: ImportSummary,
+    /// Complete chains, ready to submit.
+    batch: Vec<Row>,
+    /// The rows of the current linked chain.
+    chain: Vec<Row>,
+    chain_invalid: bool,
+}
+
+impl<W: Write> Import<'_, W> {
+    fn fail(&mut self, row: usize, id: &str, error: &RowError) -> io::Result<()> {
+        self.summary.failed += 1;
+        self.report
+            .write(&[&row.to_string(), id, &error.to_string()])
+    }
+
+    /// Move the current chain to the batch, or report it if a row of it is
+    /// invalid. Submits the batch first if the chain doesn't fit.
+    async fn end_chain(&mut self) -> Result<(), BulkError> {
+        let chain = std::mem::take(&mut self.chain);
+        if std::mem::replace(&mut self.chain_invalid, false) {
+            for row in chain {
+                self.fail(
+                    row.row,
+                    &row.transfer.id.to_string(),
+                    &RowError::LinkedRowInvalid,
+                )?;
+            }
+            return Ok(());
+        }
+        if self.batch.len() + chain.len() > BATCH_MAX {
+            self.submit().await?;
+        }
+        self.batch.extend(chain);
+        Ok(())
+    }
+
+    async fn submit(&mut self) -> Result<(), BulkError> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.batch);
+        let transfers: Vec<Transfer> = batch.iter().map(|row| row.transfer).collect();
+        let results = self.client.create_transfers(&transfers).await?;
+
+        let mut created = batch.len();
+        for result in results {
+            let row = &batch[result.index];
+            created -= 1;
+            if result.result == CreateTransferResult::Exists {
+                self.summary.existing += 1;
+            } else {
+                self.fail(
+                    row.row,
+                    &row.transfer.id.to_string(),
+                    &RowError::Rejected(result.result),
+                )?;
+            }
+        }
+        self.summary.created += created;
+        Ok(())
+    }
+}
+
+/// A reader of RFC 4180 CSV records.
+///
+/// Fields may be quoted with `"`, in which case they may contain commas,
+/// newlines and `""` for a quote.
+struct CsvReader<R> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    fn new(reader: R) -> CsvReader<R> {
+        CsvReader {
+            reader,
+            line: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = io::Result<Vec<String>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<String>>> {
+        let mut record = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) if quoted => {
+                    // An unterminated quote ends at the end of the file.
+                    record.push(field);
+                    return Some(Ok(record));
+                }
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(error) => return Some(Err(error)),
+            }
+            if !quoted && self.line.trim().is_empty() {
+                continue;
+            }
+
+            let mut chars = self.line.chars().peekable();
+            while let Some(char) = chars.next() {
+                match (quoted, char) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => quoted = false,
+                    (true, _) => field.push(char),
+                    (false, '"') if field.is_empty() => quoted = true,
+                    (false, ',') => record.push(std::mem::take(&mut field)),
+                    (false, '\r' | '\n') => {}
+                    (false, _) => field.push(char),
+                }
+            }
+            if !quoted {
+                record.push(field);
+                return Some(Ok(record));
+            }
+        }
+    }
+}
+
+struct CsvWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> CsvWriter<W> {
+    fn new(writer: W) -> CsvWriter<W> {
+        CsvWriter { writer }
+    }
+
+    fn write(&mut self, record: &[&str]) -> io::Result<()> {
+        for (index, field) in record.iter().enumerate() {
+            if index > 0 {
+                self.writer.write_all(b",")?;
+            }
+            if field.contains(&[',', '"', '\n', '\r'][..]) {
+                write!(self.writer, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.writer.write_all(field.as_bytes())?;
+            }
+        }
+        self.writer.write_all(b"\n")
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ledger::LedgerInfo;
+    use crate::{Account, InMemoryClient};
+
+    use futures::executor::block_on;
+
+    fn records(csv: &str) -> Vec<Vec<String>> {
+        CsvReader::new(csv.as_bytes())
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn csv_reader() {
+        assert_eq!(
+            records("a,b,c\r\n1,\"two, \"\"2\"\"\",\n\n\"multi\nline\",x,y\n"),
+            [
+                vec!["a", "b", "c"],
+                vec!["1", "two, \"2\"", ""],
+                vec!["multi\nline", "x", "y"],
+            ]
+        );
+    }
+
+    #[test]
+    fn csv_writer() {
+        let mut writer = CsvWriter::new(Vec::new());
+        writer.write(&["1", "a, b", "say \"hi\""]).unwrap();
+        let csv = String::from_utf8(writer.writer).unwrap();
+        assert_eq!(csv, "1,\"a, b\",\"say \"\"hi\"\"\"\n");
+        assert_eq!(records(&csv), [vec!["1", "a, b", "say \"hi\""]]);
+    }
+
+    #[test]
+    fn mapping() {
+        let mapping = Mapping::new(Transfer {
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        })
+        .column("id", Field::Id)
+        .column("amount", Field::Amount)
+        .column("flags", Field::Flags)
+        .amount_scale(2);
+
+        let header = records("memo,amount,id,flags\n").remove(0);
+        let columns = mapping.resolve(&header).unwrap();
+
+        let transfer = mapping
+            .transfer(
+                &columns,
+                &["x", "12.5", "0x10", "Linked | Pending"].map(String::from),
+            )
+            .unwrap();
+        assert_eq!(transfer.id, 16);
+        assert_eq!(transfer.amount, 1250);
+        assert_eq!(transfer.ledger, 1);
+        assert_eq!(
+            transfer.flags,
+            TransferFlags::Linked | TransferFlags::Pending
+        );
+
+        assert_eq!(
+            mapping.transfer(&columns, &["x", "1.234", "1", ""].map(String::from)),
+            Err(RowError::AmountInvalid {
+                column: "amount".to_owned(),
+                error: AmountError::TooPrecise,
+            })
+        );
+        assert_eq!(
+            mapping.transfer(&columns, &["x", "1", "one", ""].map(String::from)),
+            Err(RowError::FieldInvalid {
+                column: "id".to_owned(),
+            })
+        );
+
+        assert!(matches!(
+            mapping.resolve(&records("id,amount\n").remove(0)),
+            Err(BulkError::ColumnMissing(name)) if name == "flags"
+        ));
+
+        let transfers = read_transfers(&mapping, &b"id,amount,flags\n1,1.00,\n2,2,\n"[..]).unwrap();
+        assert_eq!(
+            transfers.iter().map(|t| t.amount).collect::<Vec<_>>(),
+            [100, 200]
+        );
+        assert!(matches!(
+            read_transfers(&mapping, &b"id,amount,flags\n1,1,\n2,x,\n"[..]),
+            Err(BulkError::Row { row: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn import_transfers() {
+        let client = InMemoryClient::new();
+        let accounts = [1, 2].map(|id| Account {
+            id,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        });
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+
+        let mapping = Mapping::new(Transfer {
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        })
+        .column("id", Field::Id)
+        .column("from", Field::DebitAccountId)
+        .column("to", Field::CreditAccountId)
+        .column("amount", Field::Amount)
+        .column("flags", Field::Flags);
+        let csv = "id,from,to,amount,flags\n\
+                   1,1,2,10,\n\
+                   2,1,3,10,\n\
+                   3,1,2,10,Linked\n\
+                   4,1,2,x,\n\
+                   5,1,2,10,\n";
+
+        let mut report = Vec::new();
+        let summary = block_on(super::import_transfers(
+            &client,
+            &mapping,
+            csv.as_bytes(),
+            &mut report,
+        ))
+        .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                rows: 5,
+                created: 2,
+                existing: 0,
+                failed: 3,
+            }
+        );
+        assert_eq!(
+            records(std::str::from_utf8(&report).unwrap()),
+            [
+                vec!["row", "id", "error"],
+                vec!["4", "4", "invalid amount"],
+                vec!["3", "3", "another row of the linked chain is invalid"],
+                vec![
+                    "2",
+                    "2",
+                    &CreateTransferResult::CreditAccountNotFound.to_string()
+                ],
+            ]
+        );
+
+        let mut report = Vec::new();
+        let summary = block_on(super::import_transfers(
+            &client,
+            &mapping,
+            &b"id,from,to,amount,flags\n1,1,2,10,\n6,1,2,10,\n"[..],
+            &mut report,
+        ))
+        .unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                rows: 2,
+                created: 1,
+                existing: 1,
+                failed: 0,
+            }
+        );
+        assert_eq!(records(std::str::from_utf8(&report).unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn write_transfers() {
+        let mut ledgers = LedgerRegistry::new();
+        ledgers.insert(840, LedgerInfo::new("USD", 2, "US Dollar"));
+        let transfers = [
+            Transfer {
+                id: 1,
+                debit_account_id: 2,
+                credit_account_id: 3,
+                amount: 1234,
+                ledger: 840,
+                code: 1,
+                flags: TransferFlags::Linked | TransferFlags::Pending,
+                ..Default::default()
+            },
+            Transfer {
+                id: 4,
+                amount: 56,
+                ledger: 1,
+                code: 2,
+                ..Default::default()
+            },
+        ];
+        let mut csv = Vec::new();
+        super::write_transfers(&ledgers, &transfers, &mut csv).unwrap();
+        let rows = records(std::str::from_utf8(&csv).unwrap());
+        assert_eq!(rows[1][3..5], ["12.34", "USD"]);
+        assert_eq!(rows[1][12], "Linked | Pending");
+        assert_eq!(rows[2][3..5], ["56", ""]);
+
+        let mapping = Mapping::new(Transfer::default())
+            .column("id", Field::Id)
+            .column("debit_account_id", Field::DebitAccountId)
+            .column("credit_account_id", Field::CreditAccountId)
+            .column("amount", Field::Amount)
+            .column("ledger", Field::Ledger)
+            .column("code", Field::Code)
+            .column("flags", Field::Flags)
+            .ledgers(ledgers);
+        assert_eq!(read_transfers(&mapping, &csv[..]).unwrap(), transfers);
+    }
+}
@@ -0,0 +1,284 @@
+use super::*;
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// Wait for a request to complete, failing with [`ClientError::Timeout`]
+/// if it does not complete within `timeout`.
+///
+/// `request` may be the future returned by any [`Client`] method.
+///
+/// When the deadline passes the request's future is dropped, releasing the
+/// client's interest in the response. As with any dropped request future,
+/// the request may still be processed by the cluster: resubmitting the same
+/// events is safe, and will report events that were already applied as
+/// `Exists`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use tigerbeetle as tb;
+///
+/// # async fn example(client: &tb::Client) -> Result<(), tb::ClientError> {
+/// let request = client.lookup_accounts(&[1]);
+/// let accounts = tb::with_deadline(request, Duration::from_secs(1)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn with_deadline<T>(
+    request: impl Future<Output = Result<T, PacketStatus>>,
+    timeout: Duration,
+) -> impl Future<Output = Result<T, ClientError>> {
+    Race {
+        request: Box::pin(request),
+        stop: Box::pin(timer::sleep(timeout)),
+        error: ClientError::Timeout,
+    }
+}
+
+/// Fail `request` with [`PacketStatus::Timeout`] if `deadline` completes
+/// first. See [`Client::set_request_timeout`].
+pub(crate) fn with_request_timeout<T>(
+    request: impl Future<Output = Result<T, PacketStatus>>,
+    deadline: timer::Sleep,
+) -> impl Future<Output = Result<T, PacketStatus>> {
+    Race {
+        request: Box::pin(request),
+        stop: Box::pin(deadline),
+        error: PacketStatus::Timeout,
+    }
+}
+
+/// Wait for a request to complete, failing with [`ClientError::Cancelled`]
+/// if `token` is cancelled first.
+///
+/// As with [`with_deadline`], cancelling a request releases the client's
+/// interest in the response, but does not prevent the cluster from
+/// processing it.
+pub fn with_cancellation<T>(
+    request: impl Future<Output = Result<T, PacketStatus>>,
+    token: &CancellationToken,
+) -> impl Future<Output = Result<T, ClientError>> {
+    Race {
+        request: Box::pin(request),
+        stop: Box::pin(token.cancelled()),
+        error: ClientError::Cancelled,
+    }
+}
+
+/// A token for cancelling requests made with [`with_cancellation`].
+///
+/// Clones of a token share its state: cancelling any clone
+/// cancels every request waiting on the token.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationInner>,
+}
+
+#[derive(Default)]
+struct CancellationInner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Wakers>,
+}
+
+/// The wakers of pending [`Cancelled`] futures, keyed so that each future
+/// replaces its own waker when re-polled and removes it when dropped.
+#[derive(Default)]
+struct Wakers {
+    next_key: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancel all requests waiting on this token.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::Release);
+        let wakers = mem::take(
+            &mut self
+                .inner
+                .wakers
+                .lock()
+                .expect("cancellation wakers")
+                .wakers,
+        );
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    /// Returns a future that completes once the token is cancelled.
+    pub fn cancelled(&self) -> impl Future<Output = ()> {
+        Cancelled {
+            inner: Arc::clone(&self.inner),
+            key: None,
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("CancellationToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+struct Cancelled {
+    inner: Arc<CancellationInner>,
+    /// The key of this future's waker in `inner.wakers`, once polled.
+    key: Option<u64>,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.inner.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+
+        let this = &mut *self;
+        let mut wakers = this.inner.wakers.lock().expect("cancellation wakers");
+        // Check again under the lock, in case `cancel` took the wakers
+        // between the check above and here.
+        if this.inner.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        let key = *this.key.get_or_insert_with(|| {
+            wakers.next_key += 1;
+            wakers.next_key
+        });
+        match wakers.wakers.get_mut(&key) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => *waker = cx.waker().clone(),
+            None => {
+                wakers.wakers.insert(key, cx.waker().clone());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            if let Ok(mut wakers) = self.inner.wakers.lock() {
+                wakers.wakers.remove(&key);
+            }
+        }
+    }
+}
+
+/// Resolves to the request's result, or to `error` if `stop` completes first.
+struct Race<Request, Stop, Error> {
+    request: Pin<Box<Request>>,
+    stop: Pin<Box<Stop>>,
+    error: Error,
+}
+
+impl<T, Request, Stop, Error> Future for Race<Request, Stop, Error>
+where
+    Request: Future<Output = Result<T, PacketStatus>>,
+    Stop: Future<Output = ()>,
+    Error: From<PacketStatus> + Copy + Unpin,
+{
+    type Output = Result<T, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(result) = self.request.as_mut().poll(cx) {
+            return Poll::Ready(result.map_err(Error::from));
+        }
+        if let Poll::Ready(()) = self.stop.as_mut().poll(cx) {
+            return Poll::Ready(Err(self.error));
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn deadline_expires() {
+        let (_tx, rx) = channel::<()>();
+        let request = async {
+            let _ = rx.await;
+            Ok(())
+        };
+
+        let result = block_on(with_deadline(request, Duration::from_millis(10)));
+        assert_eq!(result, Err(ClientError::Timeout));
+    }
+
+    #[test]
+    fn deadline_not_reached() {
+        let request = async { Err::<(), _>(PacketStatus::ClientShutdown) };
+
+        let result = block_on(with_deadline(request, Duration::from_secs(60)));
+        assert_eq!(
+            result,
+            Err(ClientError::Packet(PacketStatus::ClientShutdown))
+        );
+    }
+
+    #[test]
+    fn cancellation_from_another_thread() {
+        let token = CancellationToken::new();
+        let (_tx, rx) = channel::<()>();
+        let request = async {
+            let _ = rx.await;
+            Ok(())
+        };
+
+        let request = with_cancellation(request, &token);
+        let canceller = std::thread::spawn({
+            let token = token.clone();
+            move || {
+                std::thread::sleep(Duration::from_millis(10));
+                token.cancel();
+            }
+        });
+
+        assert_eq!(block_on(request), Err(ClientError::Cancelled));
+        assert!(token.is_cancelled());
+        canceller.join().unwrap();
+    }
+
+    #[test]
+    fn dropped_futures_release_their_wakers() {
+        let token = CancellationToken::new();
+        let waiting = || token.inner.wakers.lock().unwrap().wakers.len();
+        for _ in 0..100 {
+            let (tx, rx) = channel::<()>();
+            let request = async {
+                let _ = rx.await;
+                Ok(())
+            };
+            let mut request = Box::pin(with_cancellation(request, &token));
+            block_on(async {
+                assert!(futures::poll!(request.as_mut()).is_pending());
+                assert!(futures::poll!(request.as_mut()).is_pending());
+            });
+            assert_eq!(waiting(), 1);
+            tx.send(()).unwrap();
+            assert_eq!(block_on(request), Ok(()));
+            assert_eq!(waiting(), 0);
+        }
+    }
+}
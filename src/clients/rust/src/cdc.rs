@@ -0,0 +1,519 @@
+//! Decoding of change data capture (CDC) events.
+//!
+//! `tigerbeetle amqp` publishes an event to an AMQP exchange for each
+//! transfer the cluster commits, and for each pending transfer that expires,
+//! with both accounts' balances after it. This module decodes their JSON
+//! message bodies, to be used with any AMQP client:
+//!
+//! ```
+//! use tigerbeetle::cdc::{ChangeEvent, ChangeEventType, Checkpoint};
+//!
+//! # let body = br#"{"timestamp":7,"type":"single_phase","ledger":1,
+//! #     "transfer":{"id":9,"amount":10,"pending_id":0,"user_data_128":0,"user_data_64":0,
+//! #         "user_data_32":0,"timeout":0,"code":1,"flags":0,"timestamp":7},
+//! #     "debit_account":{"id":1,"debits_pending":0,"debits_posted":10,"credits_pending":0,
+//! #         "credits_posted":0,"user_data_128":0,"user_data_64":0,"user_data_32":0,"code":1,
+//! #         "flags":0,"timestamp":1},
+//! #     "credit_account":{"id":2,"debits_pending":0,"debits_posted":0,"credits_pending":0,
+//! #         "credits_posted":10,"user_data_128":0,"user_data_64":0,"user_data_32":0,"code":1,
+//! #         "flags":0,"timestamp":2}}"#;
+//! let mut checkpoint = Checkpoint::new(0);
+//!
+//! // For each message, in the order delivered:
+//! let event = ChangeEvent::from_json(body).unwrap();
+//! if checkpoint.is_new(&event) {
+//!     if event.event_type == ChangeEventType::SinglePhase {
+//!         println!("{} moved {}", event.transfer.id, event.transfer.amount);
+//!     }
+//!     checkpoint.processed(&event);
+//! }
+//! // Then acknowledge the message.
+//! ```
+//!
+//! # Delivery
+//!
+//! Events are published in order, and at least once: after a restart, the
+//! publisher may publish events again, and a consumer that fails before
+//! acknowledging a message receives it again. Events have unique, increasing
+//! timestamps, so a consumer that processes events in order can skip those
+//! it has already processed by keeping a [`Checkpoint`]. To process each
+//! event exactly once, store the checkpoint's timestamp atomically with the
+//! event's effects, e.g. in the same database transaction.
+//!
+//! # Consuming
+//!
+//! A [`Consumer`] does this for messages received through a [`Transport`],
+//! implemented over the application's AMQP client. It decodes each message,
+//! skips and acknowledges events already processed, and acknowledges the
+//! rest once the application has processed them:
+//!
+//! ```no_run
+//! use tigerbeetle::cdc::{Checkpoint, Consumer, ConsumerError, Transport};
+//!
+//! # async fn example(transport: impl Transport, stored: u64) -> Result<(), ConsumerError> {
+//! let mut consumer = Consumer::new(transport, Checkpoint::new(stored));
+//! consumer
+//!     .run(|event| {
+//!         // Apply the event, and store `event.timestamp` with its effects.
+//!         println!("{} moved {}", event.transfer.id, event.transfer.amount);
+//!         Ok::<(), ConsumerError>(())
+//!     })
+//!     .await
+//! # }
+//! ```
+//!
+//! Requires the `cdc` feature.
+
+use crate::{Account, AccountFlags, BoxFuture, Transfer, TransferFlags};
+
+use serde::de::{Deserializer, Error as _};
+
+use std::fmt;
+
+/// What a [`ChangeEvent`] records.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ChangeEventType {
+    /// A transfer posted its amount.
+    SinglePhase,
+    /// A pending transfer reserved its amount.
+    TwoPhasePending,
+    /// A pending transfer was posted.
+    TwoPhasePosted,
+    /// A pending transfer was voided.
+    TwoPhaseVoided,
+    /// A pending transfer timed out.
+    TwoPhaseExpired,
+}
+
+/// A change to the balances of two accounts.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ChangeEvent {
+    /// When the change was committed, unique across events.
+    ///
+    /// This is the transfer's timestamp, except for expiries, which happen
+    /// after the pending transfer was created.
+    pub timestamp: u64,
+    pub event_type: ChangeEventType,
+    /// The ledger of the transfer and accounts.
+    pub ledger: u32,
+    /// The transfer: the new transfer, or for an expiry, the pending one.
+    pub transfer: Transfer,
+    /// The debit account, with its balances after the change.
+    pub debit_account: Account,
+    /// The credit account, with its balances after the change.
+    pub credit_account: Account,
+}
+
+impl ChangeEvent {
+    /// Decode an event from the JSON body of a message.
+    pub fn from_json(body: &[u8]) -> Result<ChangeEvent, CdcError> {
+        let message: Message =
+            serde_json::from_slice(body).map_err(|error| CdcError(error.to_string()))?;
+        let account = |account: MessageAccount| Account {
+            id: account.id,
+            debits_pending: account.debits_pending,
+            debits_posted: account.debits_posted,
+            credits_pending: account.credits_pending,
+            credits_posted: account.credits_posted,
+            user_data_128: account.user_data_128,
+            user_data_64: account.user_data_64,
+            user_data_32: account.user_data_32,
+            ledger: message.ledger,
+            code: account.code,
+            flags: AccountFlags::from_bits_retain(account.flags),
+            timestamp: account.timestamp,
+            ..Default::default()
+        };
+        Ok(ChangeEvent {
+            timestamp: message.timestamp,
+            event_type: message.event_type,
+            ledger: message.ledger,
+            transfer: Transfer {
+                id: message.transfer.id,
+                debit_account_id: message.debit_account.id,
+                credit_account_id: message.credit_account.id,
+                amount: message.transfer.amount,
+                pending_id: message.transfer.pending_id,
+                user_data_128: message.transfer.user_data_128,
+                user_data_64: message.transfer.user_data_64,
+                user_data_32: message.transfer.user_data_32,
+                timeout: message.transfer.timeout,
+                ledger: message.ledger,
+                code: message.transfer.code,
+                flags: TransferFlags::from_bits_retain(message.transfer.flags),
+                timestamp: message.transfer.timestamp,
+            },
+            debit_account: account(message.debit_account),
+            credit_account: account(message.credit_account),
+        })
+    }
+}
+
+/// The last event processed by a consumer, to skip events delivered again.
+///
+/// See [Delivery](self#delivery).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Checkpoint {
+    timestamp: u64,
+}
+
+impl Checkpoint {
+    /// A checkpoint after the event with `timestamp`, or before all events
+    /// if it is zero.
+    pub fn new(timestamp: u64) -> Checkpoint {
+        Checkpoint { timestamp }
+    }
+
+    /// The timestamp of the last event processed, to be stored.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Whether `event` comes after the last event processed.
+    pub fn is_new(&self, event: &ChangeEvent) -> bool {
+        event.timestamp > self.timestamp
+    }
+
+    /// Record that `event` was processed.
+    pub fn processed(&mut self, event: &ChangeEvent) {
+        self.timestamp = self.timestamp.max(event.timestamp);
+    }
+}
+
+/// A message received from the CDC exchange's queue.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Delivery {
+    /// The delivery tag, to acknowledge or reject the message.
+    pub tag: u64,
+    /// The JSON body of the message.
+    pub body: Vec<u8>,
+}
+
+/// A connection to a queue bound to the CDC exchange, e.g. an AMQP channel
+/// consuming from the queue, implemented by the application over its AMQP
+/// client.
+///
+/// Consumers should use manual acknowledgement, and a prefetch count of one
+/// or more, so that unacknowledged messages are redelivered.
+pub trait Transport: Send {
+    /// The next message, in the order published, or `None` once the queue
+    /// or connection is closed.
+    fn receive(&mut self) -> BoxFuture<'_, Result<Option<Delivery>, ConsumerError>>;
+
+    /// Acknowledge the message with `tag`, e.g. with `basic.ack`.
+    fn ack(&mut self, tag: u64) -> BoxFuture<'_, Result<(), ConsumerError>>;
+
+    /// Reject the message with `tag`, e.g. with `basic.nack`, returning it to
+    /// the queue if `requeue`, or else discarding it or sending it to a
+    /// dead-letter exchange.
+    fn reject(&mut self, tag: u64, requeue: bool) -> BoxFuture<'_, Result<(), ConsumerError>>;
+}
+
+/// A [`ChangeEvent`] received by a [`Consumer`], to be passed back to
+/// [`Consumer::processed`] once processed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Received {
+    pub event: ChangeEvent,
+    tag: u64,
+}
+
+/// Receives change events through a [`Transport`], at least once each, and
+/// skips those a [`Checkpoint`] has already processed.
+///
+/// See [Consuming](self#consuming).
+pub struct Consumer<T: Transport> {
+    transport: T,
+    checkpoint: Checkpoint,
+}
+
+impl<T: Transport> Consumer<T> {
+    /// A consumer of the events after `checkpoint`.
+    pub fn new(transport: T, checkpoint: Checkpoint) -> Consumer<T> {
+        Consumer {
+            transport,
+            checkpoint,
+        }
+    }
+
+    /// The checkpoint after the last event processed.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint
+    }
+
+    /// The next event not yet processed, or `None` once the transport is
+    /// closed.
+    ///
+    /// Messages of events already processed are acknowledged and skipped.
+    /// A message that can't be decoded is rejected without being requeued,
+    /// and returned as [`ConsumerError::Decode`]; later messages can still
+    /// be received.
+    pub async fn next(&mut self) -> Result<Option<Received>, ConsumerError> {
+        loop {
+            let delivery = match self.transport.receive().await? {
+                Some(delivery) => delivery,
+                None => return Ok(None),
+            };
+            let event = match ChangeEvent::from_json(&delivery.body) {
+                Ok(event) => event,
+                Err(error) => {
+                    self.transport.reject(delivery.tag, false).await?;
+                    return Err(ConsumerError::Decode(error));
+                }
+            };
+            if self.checkpoint.is_new(&event) {
+                return Ok(Some(Received {
+                    event,
+                    tag: delivery.tag,
+                }));
+            }
+            self.transport.ack(delivery.tag).await?;
+        }
+    }
+
+    /// Record that `received` was processed, and acknowledge its message.
+    pub async fn processed(&mut self, received: Received) -> Result<(), ConsumerError> {
+        self.checkpoint.processed(&received.event);
+        self.transport.ack(received.tag).await
+    }
+
+    /// Return `received` to the queue unprocessed, to be received again.
+    pub async fn requeue(&mut self, received: Received) -> Result<(), ConsumerError> {
+        self.transport.reject(received.tag, true).await
+    }
+
+    /// Process events with `handler` until the transport is closed.
+    ///
+    /// Stops at the first error: if `handler` fails, its event is requeued.
+    pub async fn run<E: From<ConsumerError>>(
+        &mut self,
+        mut handler: impl FnMut(&ChangeEvent) -> Result<(), E>,
+    ) -> Result<(), E> {
+        while let Some(received) = self.next().await? {
+            match handler(&received.event) {
+                Ok(()) => self.processed(received).await?,
+                Err(error) => {
+                    self.requeue(received).await?;
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The transport, e.g. to close it.
+    pub fn into_transport(self) -> T {
+        self.transport
+    }
+}
+
+/// Errors consuming change events with a [`Consumer`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConsumerError {
+    /// A message could not be decoded, and was rejected.
+    Decode(CdcError),
+    /// The transport failed, e.g. because the connection was lost.
+    Transport(String),
+}
+
+impl std::error::Error for ConsumerError {}
+impl fmt::Display for ConsumerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Decode(error) => fmt::Display::fmt(error, f),
+            Self::Transport(error) => write!(f, "cdc transport failed: {error}"),
+        }
+    }
+}
+
+/// An error decoding a [`ChangeEvent`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CdcError(String);
+
+impl std::error::Error for CdcError {}
+impl fmt::Display for CdcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid change event: {}", self.0)
+    }
+}
+
+/// The message body, as published.
+///
+/// Integers too large for a double are published as strings.
+#[derive(serde::Deserialize)]
+struct Message {
+    #[serde(deserialize_with = "u64_from_any")]
+    timestamp: u64,
+    #[serde(rename = "type")]
+    event_type: ChangeEventType,
+    ledger: u32,
+    transfer: MessageTransfer,
+    debit_account: MessageAccount,
+    credit_account: MessageAccount,
+}
+
+#[derive(serde::Deserialize)]
+struct MessageTransfer {
+    #[serde(with = "crate::serde_u128")]
+    id: u128,
+    #[serde(with = "crate::serde_u128")]
+    amount: u128,
+    #[serde(with = "crate::serde_u128")]
+    pending_id: u128,
+    #[serde(with = "crate::serde_u128")]
+    user_data_128: u128,
+    #[serde(deserialize_with = "u64_from_any")]
+    user_data_64: u64,
+    user_data_32: u32,
+    timeout: u32,
+    code: u16,
+    flags: u16,
+    #[serde(deserialize_with = "u64_from_any")]
+    timestamp: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct MessageAccount {
+    #[serde(with = "crate::serde_u128")]
+    id: u128,
+    #[serde(with = "crate::serde_u128")]
+    debits_pending: u128,
+    #[serde(with = "crate::serde_u128")]
+    debits_posted: u128,
+    #[serde(with = "crate::serde_u128")]
+    credits_pending: u128,
+    #[serde(with = "crate::serde_u128")]
+    credits_posted: u128,
+    #[serde(with = "crate::serde_u128")]
+    user_data_128: u128,
+    #[serde(deserialize_with = "u64_from_any")]
+    user_data_64: u64,
+    user_data_32: u32,
+    code: u16,
+    flags: u16,
+    #[serde(deserialize_with = "u64_from_any")]
+    timestamp: u64,
+}
+
+fn u64_from_any<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = crate::serde_u128::deserialize(deserializer)?;
+    u64::try_from(value).map_err(|_| D::Error::custom(format!("{value} out of range for u64")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The largest event, as published.
+    const WORST_CASE: &str = r#"{"timestamp":"18446744073709551615","type":"two_phase_pending","ledger":4294967295,"transfer":{"id":"340282366920938463463374607431768211455","amount":"340282366920938463463374607431768211455","pending_id":"340282366920938463463374607431768211455","user_data_128":"340282366920938463463374607431768211455","user_data_64":"18446744073709551615","user_data_32":4294967295,"timeout":4294967295,"code":65535,"flags":65535,"timestamp":"18446744073709551615"},"debit_account":{"id":"340282366920938463463374607431768211455","debits_pending":"340282366920938463463374607431768211455","debits_posted":"340282366920938463463374607431768211455","credits_pending":"340282366920938463463374607431768211455","credits_posted":"340282366920938463463374607431768211455","user_data_128":"340282366920938463463374607431768211455","user_data_64":"18446744073709551615","user_data_32":4294967295,"code":65535,"flags":65535,"timestamp":"18446744073709551615"},"credit_account":{"id":"340282366920938463463374607431768211455","debits_pending":"340282366920938463463374607431768211455","debits_posted":"340282366920938463463374607431768211455","credits_pending":"340282366920938463463374607431768211455","credits_posted":"340282366920938463463374607431768211455","user_data_128":"340282366920938463463374607431768211455","user_data_64":"18446744073709551615","user_data_32":4294967295,"code":65535,"flags":65535,"timestamp":"18446744073709551615"}}"#;
+
+    #[test]
+    fn decode() {
+        let event = ChangeEvent::from_json(WORST_CASE.as_bytes()).unwrap();
+        assert_eq!(event.timestamp, u64::MAX);
+        assert_eq!(event.event_type, ChangeEventType::TwoPhasePending);
+        assert_eq!(event.transfer.amount, u128::MAX);
+        assert_eq!(event.transfer.ledger, u32::MAX);
+        assert_eq!(event.transfer.flags.bits(), u16::MAX);
+        assert_eq!(event.credit_account.credits_posted, u128::MAX);
+        assert_eq!(event.credit_account.ledger, u32::MAX);
+
+        let small = WORST_CASE
+            .replace("\"18446744073709551615\"", "5")
+            .replace("two_phase_pending", "two_phase_expired");
+        let event = ChangeEvent::from_json(small.as_bytes()).unwrap();
+        assert_eq!(event.timestamp, 5);
+        assert_eq!(event.transfer.user_data_64, 5);
+        assert_eq!(event.event_type, ChangeEventType::TwoPhaseExpired);
+
+        let error = ChangeEvent::from_json(br#"{"timestamp":1}"#).unwrap_err();
+        assert!(error.to_string().starts_with("invalid change event: "));
+        let invalid = WORST_CASE.replace("\"18446744073709551615\"", "\"18446744073709551616\"");
+        assert!(ChangeEvent::from_json(invalid.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn checkpoint() {
+        let mut event = ChangeEvent::from_json(WORST_CASE.as_bytes()).unwrap();
+        let mut checkpoint = Checkpoint::new(10);
+        event.timestamp = 10;
+        assert!(!checkpoint.is_new(&event));
+        event.timestamp = 11;
+        assert!(checkpoint.is_new(&event));
+        checkpoint.processed(&event);
+        assert_eq!(checkpoint.timestamp(), 11);
+        assert!(!checkpoint.is_new(&event));
+    }
+
+    /// A queue of messages, recording acknowledgements and rejections.
+    #[derive(Default)]
+    struct Queue {
+        messages: std::collections::VecDeque<Delivery>,
+        acked: Vec<u64>,
+        rejected: Vec<(u64, bool)>,
+    }
+
+    impl Transport for Queue {
+        fn receive(&mut self) -> BoxFuture<'_, Result<Option<Delivery>, ConsumerError>> {
+            Box::pin(async move { Ok(self.messages.pop_front()) })
+        }
+
+        fn ack(&mut self, tag: u64) -> BoxFuture<'_, Result<(), ConsumerError>> {
+            self.acked.push(tag);
+            Box::pin(async { Ok(()) })
+        }
+
+        fn reject(&mut self, tag: u64, requeue: bool) -> BoxFuture<'_, Result<(), ConsumerError>> {
+            self.rejected.push((tag, requeue));
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn consumer() {
+        use futures::executor::block_on;
+
+        let message = |tag, timestamp: u64| Delivery {
+            tag,
+            body: WORST_CASE
+                .replace("\"18446744073709551615\"", &timestamp.to_string())
+                .into_bytes(),
+        };
+        let mut queue = Queue::default();
+        queue.messages.extend([
+            message(1, 5),
+            message(2, 6),
+            Delivery {
+                tag: 3,
+                body: b"{}".to_vec(),
+            },
+            // Redelivered after the publisher restarted.
+            message(4, 6),
+            message(5, 7),
+        ]);
+        let mut consumer = Consumer::new(queue, Checkpoint::new(5));
+
+        let mut seen = Vec::new();
+        let result = block_on(consumer.run(|event| {
+            seen.push(event.timestamp);
+            Ok::<(), ConsumerError>(())
+        }));
+        assert!(matches!(result, Err(ConsumerError::Decode(_))));
+        assert_eq!(seen, [6]);
+        let result = block_on(consumer.run(|event| match event.timestamp {
+            7 => Err(ConsumerError::Transport("handler failed".to_owned())),
+            _ => Ok(()),
+        }));
+        assert_eq!(
+            result,
+            Err(ConsumerError::Transport("handler failed".to_owned()))
+        );
+        assert_eq!(consumer.checkpoint().timestamp(), 6);
+
+        let queue = consumer.into_transport();
+        assert_eq!(queue.acked, [1, 2, 4]);
+        assert_eq!(queue.rejected, [(3, false), (5, true)]);
+    }
+}
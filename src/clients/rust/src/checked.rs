@@ -0,0 +1,301 @@
+use super::*;
+
+impl Client {
+    /// Create one or more accounts, returning the failed events on error.
+    ///
+    /// This is [`Client::create_accounts`], with each failed result paired
+    /// with the account it refers to, so the caller doesn't have to index back
+    /// into `events`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CreateAccountsError::Failed`] if any account was not
+    /// created, and [`CreateAccountsError::Packet`] if the request failed.
+    ///
+    /// Unless validation is disabled with [`Client::set_validation`], the
+    /// accounts are first checked for errors that don't depend on the cluster's
+    /// state, such as zero ids. If any are found, none of the accounts are
+    /// submitted, and [`CreateAccountsError::Invalid`] is returned without a
+    /// round trip.
+    ///
+    /// With [`Client::set_duplicate_id_detection`] enabled, accounts with the
+    /// id of an earlier account in `events` are likewise reported by
+    /// [`CreateAccountsError::DuplicateIds`].
+    pub fn create_accounts_checked(
+        &self,
+        events: &[Account],
+    ) -> impl Future<Output = Result<(), CreateAccountsError>> {
+        let events = events.to_vec();
+        let request = self
+            .check_accounts(&events)
+            .map(|()| self.create_accounts(&events));
+
+        async move {
+            let failed: Vec<_> = request?
+                .await?
+                .into_iter()
+                .map(|result| FailedAccount {
+                    index: result.index,
+                    account: events[result.index],
+                    result: result.result,
+                })
+                .collect();
+
+            if failed.is_empty() {
+                Ok(())
+            } else {
+                Err(CreateAccountsError::Failed(failed))
+            }
+        }
+    }
+
+    /// Create one or more transfers, returning the failed events on error.
+    ///
+    /// This is [`Client::create_transfers`], with each failed result paired
+    /// with the transfer it refers to, so the caller doesn't have to index
+    /// back into `events`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CreateTransfersError::Failed`] if any transfer was not
+    /// created, and [`CreateTransfersError::Packet`] if the request failed.
+    ///
+    /// Unless validation is disabled with [`Client::set_validation`], the
+    /// transfers are first checked for errors that don't depend on the cluster's
+    /// state, such as zero ids. If any are found, none of the transfers are
+    /// submitted, and [`CreateTransfersError::Invalid`] is returned without a
+    /// round trip.
+    ///
+    /// With [`Client::set_duplicate_id_detection`] enabled, transfers with the
+    /// id of an earlier transfer in `events` are likewise reported by
+    /// [`CreateTransfersError::DuplicateIds`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    ///
+    /// # async fn example(client: &tb::Client, transfers: &[tb::Transfer]) -> Result<(), tb::PacketStatus> {
+    /// match client.create_transfers_checked(transfers).await {
+    ///     Ok(()) => {}
+    ///     Err(tb::CreateTransfersError::Failed(failed)) => {
+    ///         for failure in failed {
+    ///             println!("transfer {} failed: {}", failure.transfer.id, failure.result);
+    ///         }
+    ///     }
+    ///     Err(tb::CreateTransfersError::Packet(status)) => return Err(status),
+    ///     Err(error) => panic!("{error}"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_transfers_checked(
+        &self,
+        events: &[Transfer],
+    ) -> impl Future<Output = Result<(), CreateTransfersError>> {
+        let events = events.to_vec();
+        let request = self
+            .check_transfers(&events)
+            .map(|()| self.create_transfers(&events));
+
+        async move {
+            let failed: Vec<_> = request?
+                .await?
+                .into_iter()
+                .map(|result| FailedTransfer {
+                    index: result.index,
+                    transfer: events[result.index],
+                    result: result.result,
+                })
+                .collect();
+
+            if failed.is_empty() {
+                Ok(())
+            } else {
+                Err(CreateTransfersError::Failed(failed))
+            }
+        }
+    }
+
+    fn check_accounts(&self, events: &[Account]) -> Result<(), CreateAccountsError> {
+        if self.validate {
+            let invalid = validate::invalid_accounts(events);
+            if !invalid.is_empty() {
+                return Err(CreateAccountsError::Invalid(invalid));
+            }
+        }
+        if self.detect_duplicate_ids {
+            let duplicates = validate::duplicate_ids(events.iter().map(|account| account.id));
+            if !duplicates.is_empty() {
+                return Err(CreateAccountsError::DuplicateIds(duplicates));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_transfers(&self, events: &[Transfer]) -> Result<(), CreateTransfersError> {
+        if self.validate {
+            let invalid = validate::invalid_transfers(events);
+            if !invalid.is_empty() {
+                return Err(CreateTransfersError::Invalid(invalid));
+            }
+        }
+        if self.detect_duplicate_ids {
+            let duplicates = validate::duplicate_ids(events.iter().map(|transfer| transfer.id));
+            if !duplicates.is_empty() {
+                return Err(CreateTransfersError::DuplicateIds(duplicates));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An account that was not created, and why.
+///
+/// See [`Client::create_accounts_checked`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FailedAccount {
+    /// The index of the account in the submitted events.
+    pub index: usize,
+    /// The submitted account.
+    pub account: Account,
+    /// The result of the event.
+    pub result: CreateAccountResult,
+}
+
+/// A transfer that was not created, and why.
+///
+/// See [`Client::create_transfers_checked`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FailedTransfer {
+    /// The index of the transfer in the submitted events.
+    pub index: usize,
+    /// The submitted transfer.
+    pub transfer: Transfer,
+    /// The result of the event.
+    pub result: CreateTransferResult,
+}
+
+/// An event with the same id as an earlier event in its batch.
+///
+/// The cluster would report the later event as `Exists`, or as existing with
+/// different fields, rather than creating it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct DuplicateId {
+    /// The index of the event in the submitted events.
+    pub index: usize,
+    /// The index of the first event with the same id.
+    pub first_index: usize,
+    /// The duplicated id.
+    pub id: u128,
+}
+
+/// Errors returned by [`Client::create_accounts_checked`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum CreateAccountsError {
+    /// The request failed. See [`PacketStatus`].
+    Packet(PacketStatus),
+    /// Some accounts were not created.
+    Failed(Vec<FailedAccount>),
+    /// Some accounts are invalid, so none were submitted.
+    Invalid(Vec<FailedAccount>),
+    /// Some accounts have the id of an earlier account in the batch, so none
+    /// were submitted.
+    DuplicateIds(Vec<DuplicateId>),
+}
+
+impl From<PacketStatus> for CreateAccountsError {
+    fn from(status: PacketStatus) -> CreateAccountsError {
+        CreateAccountsError::Packet(status)
+    }
+}
+
+impl std::error::Error for CreateAccountsError {}
+impl core::fmt::Display for CreateAccountsError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+            Self::Failed(failed) => {
+                write!(f, "{} accounts failed", failed.len())?;
+                if let Some(first) = failed.first() {
+                    write!(f, ", first at index {}: {}", first.index, first.result)?;
+                }
+                Ok(())
+            }
+            Self::Invalid(invalid) => {
+                write!(f, "{} accounts are invalid", invalid.len())?;
+                if let Some(first) = invalid.first() {
+                    write!(f, ", first at index {}: {}", first.index, first.result)?;
+                }
+                Ok(())
+            }
+            Self::DuplicateIds(duplicates) => {
+                write!(f, "{} accounts have duplicate ids", duplicates.len())?;
+                if let Some(first) = duplicates.first() {
+                    write!(
+                        f,
+                        ", first at index {}, a duplicate of index {}",
+                        first.index, first.first_index
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Errors returned by [`Client::create_transfers_checked`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum CreateTransfersError {
+    /// The request failed. See [`PacketStatus`].
+    Packet(PacketStatus),
+    /// Some transfers were not created.
+    Failed(Vec<FailedTransfer>),
+    /// Some transfers are invalid, so none were submitted.
+    Invalid(Vec<FailedTransfer>),
+    /// Some transfers have the id of an earlier transfer in the batch, so none
+    /// were submitted.
+    DuplicateIds(Vec<DuplicateId>),
+}
+
+impl From<PacketStatus> for CreateTransfersError {
+    fn from(status: PacketStatus) -> CreateTransfersError {
+        CreateTransfersError::Packet(status)
+    }
+}
+
+impl std::error::Error for CreateTransfersError {}
+impl core::fmt::Display for CreateTransfersError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+            Self::Failed(failed) => {
+                write!(f, "{} transfers failed", failed.len())?;
+                if let Some(first) = failed.first() {
+                    write!(f, ", first at index {}: {}", first.index, first.result)?;
+                }
+                Ok(())
+            }
+            Self::Invalid(invalid) => {
+                write!(f, "{} transfers are invalid", invalid.len())?;
+                if let Some(first) = invalid.first() {
+                    write!(f, ", first at index {}: {}", first.index, first.result)?;
+                }
+                Ok(())
+            }
+            Self::DuplicateIds(duplicates) => {
+                write!(f, "{} transfers have duplicate ids", duplicates.len())?;
+                if let Some(first) = duplicates.first() {
+                    write!(
+                        f,
+                        ", first at index {}, a duplicate of index {}",
+                        first.index, first.first_index
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,12 +1,20 @@
 pub use super::*;
 
-#[rustfmt::skip]
 impl From<u32> for CreateAccountResult {
     fn from(other: u32) -> CreateAccountResult {
+        CreateAccountResult::from_code(other)
+            .unwrap_or_else(|| panic!("Unknown CreateAccountResult: {other}"))
+    }
+}
+
+impl CreateAccountResult {
+    /// The result for a code of the protocol, or `None` if it is unknown.
+    #[rustfmt::skip]
+    pub(crate) fn from_code(code: u32) -> Option<CreateAccountResult> {
         use tbc::*;
         use CreateAccountResult::*;
 
-        match other {
+        Some(match code {
             TB_CREATE_ACCOUNT_RESULT_TB_CREATE_ACCOUNT_OK => Ok,
             TB_CREATE_ACCOUNT_RESULT_TB_CREATE_ACCOUNT_LINKED_EVENT_FAILED => LinkedEventFailed,
             TB_CREATE_ACCOUNT_RESULT_TB_CREATE_ACCOUNT_LINKED_EVENT_CHAIN_OPEN => LinkedEventChainOpen,
@@ -34,8 +42,8 @@ impl From<u32> for CreateAccountResult {
             TB_CREATE_ACCOUNT_RESULT_TB_CREATE_ACCOUNT_LEDGER_MUST_NOT_BE_ZERO => LedgerMustNotBeZero,
             TB_CREATE_ACCOUNT_RESULT_TB_CREATE_ACCOUNT_CODE_MUST_NOT_BE_ZERO => CodeMustNotBeZero,
             TB_CREATE_ACCOUNT_RESULT_TB_CREATE_ACCOUNT_IMPORTED_EVENT_TIMESTAMP_MUST_NOT_REGRESS => ImportedEventTimestampMustNotRegress,
-            v => panic!("Unknown CreateAccountResult: {v}"),
-        }
+            _ => return None,
+        })
     }
 }
 
@@ -77,13 +85,21 @@ impl From<CreateAccountResult> for u32 {
     }
 }
 
-#[rustfmt::skip]
 impl From<u32> for CreateTransferResult {
     fn from(other: u32) -> CreateTransferResult {
+        CreateTransferResult::from_code(other)
+            .unwrap_or_else(|| panic!("Unknown CreateTransferResult: {other}"))
+    }
+}
+
+impl CreateTransferResult {
+    /// The result for a code of the protocol, or `None` if it is unknown.
+    #[rustfmt::skip]
+    pub(crate) fn from_code(code: u32) -> Option<CreateTransferResult> {
         use tbc::*;
         use CreateTransferResult::*;
 
-        match other {
+        Some(match code {
             TB_CREATE_TRANSFER_RESULT_TB_CREATE_TRANSFER_OK => Ok,
             TB_CREATE_TRANSFER_RESULT_TB_CREATE_TRANSFER_LINKED_EVENT_FAILED => LinkedEventFailed,
             TB_CREATE_TRANSFER_RESULT_TB_CREATE_TRANSFER_LINKED_EVENT_CHAIN_OPEN => LinkedEventChainOpen,
@@ -152,8 +168,8 @@ impl From<u32> for CreateTransferResult {
             TB_CREATE_TRANSFER_RESULT_TB_CREATE_TRANSFER_OVERFLOWS_TIMEOUT => OverflowsTimeout,
             TB_CREATE_TRANSFER_RESULT_TB_CREATE_TRANSFER_EXCEEDS_CREDITS => ExceedsCredits,
             TB_CREATE_TRANSFER_RESULT_TB_CREATE_TRANSFER_EXCEEDS_DEBITS => ExceedsDebits,
-            v => panic!("Unknown CreateTransferResult: {v}"),
-        }
+            _ => return None,
+        })
     }
 }
 
@@ -236,6 +252,7 @@ impl From<CreateTransferResult> for u32 {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<i32> for InitStatus {
     fn from(other: i32) -> InitStatus {
         use tbc::*;
@@ -254,6 +271,7 @@ impl From<i32> for InitStatus {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<InitStatus> for i32 {
     fn from(other: InitStatus) -> i32 {
         use tbc::*;
@@ -270,6 +288,7 @@ impl From<InitStatus> for i32 {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<u8> for PacketStatus {
     fn from(other: u8) -> PacketStatus {
         use tbc::*;
@@ -289,12 +308,19 @@ impl From<u8> for PacketStatus {
     }
 }
 
-impl From<PacketStatus> for u8 {
-    fn from(other: PacketStatus) -> u8 {
+#[cfg(feature = "std")]
+impl TryFrom<PacketStatus> for u8 {
+    type Error = PacketStatus;
+
+    /// The `tb_client` status code of a status.
+    ///
+    /// Fails with the status itself for [`PacketStatus::Timeout`], which is
+    /// the client's own and has no code.
+    fn try_from(other: PacketStatus) -> Result<u8, PacketStatus> {
         use tbc::*;
         use PacketStatus::*;
 
-        match other {
+        Ok(match other {
             TooMuchData => TB_PACKET_STATUS_TB_PACKET_TOO_MUCH_DATA,
             ClientEvicted => TB_PACKET_STATUS_TB_PACKET_CLIENT_EVICTED,
             ClientReleaseTooLow => TB_PACKET_STATUS_TB_PACKET_CLIENT_RELEASE_TOO_LOW,
@@ -302,6 +328,7 @@ impl From<PacketStatus> for u8 {
             ClientShutdown => TB_PACKET_STATUS_TB_PACKET_CLIENT_SHUTDOWN,
             InvalidOperation => TB_PACKET_STATUS_TB_PACKET_INVALID_OPERATION,
             InvalidDataSize => TB_PACKET_STATUS_TB_PACKET_INVALID_DATA_SIZE,
-        }
+            Timeout => return Err(other),
+        })
     }
 }
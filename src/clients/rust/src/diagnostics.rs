@@ -0,0 +1,27 @@
+//! Internal diagnostics.
+//!
+//! With the `tracing` feature these macros forward to the [`tracing`]
+//! crate's macros of the same name. Without it they expand to nothing,
+//! so their arguments are not evaluated.
+//!
+//! [`tracing`]: https://docs.rs/tracing
+
+#[cfg(feature = "tracing")]
+macro_rules! debug {
+    ($($arg:tt)*) => { tracing::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {};
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! warn {
+    ($($arg:tt)*) => { tracing::warn!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! warn {
+    ($($arg:tt)*) => {};
+}
@@ -0,0 +1,107 @@
+use super::*;
+
+/// The definition of an account for [`Client::ensure_accounts`].
+///
+/// These are the fields of an [`Account`] that are set when it is created.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct AccountSpec {
+    pub id: u128,
+    pub user_data_128: u128,
+    pub user_data_64: u64,
+    pub user_data_32: u32,
+    pub ledger: u32,
+    pub code: u16,
+    pub flags: AccountFlags,
+}
+
+impl From<AccountSpec> for Account {
+    fn from(spec: AccountSpec) -> Account {
+        Account {
+            id: spec.id,
+            user_data_128: spec.user_data_128,
+            user_data_64: spec.user_data_64,
+            user_data_32: spec.user_data_32,
+            ledger: spec.ledger,
+            code: spec.code,
+            flags: spec.flags,
+            ..Default::default()
+        }
+    }
+}
+
+/// The accounts created by [`Client::ensure_accounts`], and those that
+/// already existed, by id, in the order they were given.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct EnsureAccountsSummary {
+    pub created: Vec<u128>,
+    pub existing: Vec<u128>,
+}
+
+impl Client {
+    /// Create accounts that don't already exist.
+    ///
+    /// Accounts that already exist with the same fields are not an error, so
+    /// this is safe to call each time a service starts. Linked accounts are
+    /// created together or not at all, so a linked chain with an existing
+    /// account fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CreateAccountsError::Failed`] if an account could not be
+    /// created, including if it exists with different fields, and
+    /// [`CreateAccountsError::Packet`] if the request failed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    ///
+    /// # async fn example(client: &tb::Client) -> Result<(), tb::CreateAccountsError> {
+    /// let operating = tb::AccountSpec {
+    ///     id: 1,
+    ///     ledger: 840,
+    ///     code: 10,
+    ///     ..Default::default()
+    /// };
+    /// let summary = client.ensure_accounts(&[operating]).await?;
+    /// println!("created {} accounts", summary.created.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn ensure_accounts(
+        &self,
+        specs: &[AccountSpec],
+    ) -> impl Future<Output = Result<EnsureAccountsSummary, CreateAccountsError>> {
+        let events: Vec<Account> = specs.iter().copied().map(Account::from).collect();
+        let request = self.create_accounts(&events);
+
+        async move {
+            let mut existing = vec![false; events.len()];
+            let mut failed = Vec::new();
+            for result in request.await? {
+                if result.result == CreateAccountResult::Exists {
+                    existing[result.index] = true;
+                } else {
+                    failed.push(FailedAccount {
+                        index: result.index,
+                        account: events[result.index],
+                        result: result.result,
+                    });
+                }
+            }
+            if !failed.is_empty() {
+                return Err(CreateAccountsError::Failed(failed));
+            }
+
+            let mut summary = EnsureAccountsSummary::default();
+            for (account, existing) in events.iter().zip(existing) {
+                if existing {
+                    summary.existing.push(account.id);
+                } else {
+                    summary.created.push(account.id);
+                }
+            }
+            Ok(summary)
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use super::*;
+
+use std::sync::Arc;
+
+/// Something that happened to a client's requests, passed to
+/// [`Client::on_event`] callbacks, e.g. for logging.
+///
+/// These are the events the `tracing` feature logs at the `WARN` level, and
+/// the requests it logs as failed at `DEBUG`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ClientEvent {
+    /// A request failed, and is about to be retried. See [`RetryPolicy`].
+    Retry(Retry),
+    /// The cluster evicted the client's session, reported once per session.
+    Evicted(EvictionReason),
+    /// A request failed, including with [`PacketStatus::Timeout`], and won't
+    /// be retried.
+    Failed {
+        /// The name of the operation, e.g. `"create_transfers"`.
+        operation: &'static str,
+        status: PacketStatus,
+    },
+}
+
+impl core::fmt::Display for ClientEvent {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Retry(retry) => write!(
+                f,
+                "retrying {} (attempt {}) in {:?} after {}",
+                retry.operation, retry.attempt, retry.delay, retry.status
+            ),
+            Self::Evicted(reason) => write!(f, "session evicted: {reason}"),
+            Self::Failed { operation, status } => write!(f, "{operation} failed: {status}"),
+        }
+    }
+}
+
+pub(crate) type OnEvent = Arc<dyn Fn(&ClientEvent) + Send + Sync>;
+
+pub(crate) fn notify(on_event: &Option<OnEvent>, event: ClientEvent) {
+    if let Some(on_event) = on_event {
+        on_event(&event);
+    }
+}
+
+impl Client {
+    /// Set a callback invoked with each [`ClientEvent`], e.g. to log retries
+    /// and failures without the `tracing` feature.
+    ///
+    /// The callback applies to requests made after it is set, and is invoked
+    /// from whichever thread polls the request's future.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = tb::Client::new(0, "127.0.0.1:3000")?;
+    /// client.on_event(|event| eprintln!("tigerbeetle: {event}"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_event(&mut self, on_event: impl Fn(&ClientEvent) + Send + Sync + 'static) {
+        self.on_event = Some(Arc::new(on_event));
+    }
+}
@@ -0,0 +1,94 @@
+use super::*;
+
+use std::sync::Arc;
+
+/// Why the cluster evicted a client's session.
+///
+/// Passed to [`Client::on_eviction`] callbacks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum EvictionReason {
+    /// The session expired, usually because more clients are connected than
+    /// the cluster supports, and the least recently active were evicted.
+    ///
+    /// The client registers a new session.
+    SessionExpired,
+    /// The cluster requires a newer client release.
+    ///
+    /// Requests fail until the client is upgraded.
+    ClientReleaseTooLow,
+    /// The cluster requires an older client release, or must be upgraded.
+    ///
+    /// Requests fail until the cluster is upgraded.
+    ClientReleaseTooHigh,
+}
+
+impl EvictionReason {
+    /// The reason for an eviction, if `status` reports one.
+    pub(crate) fn from_status(status: PacketStatus) -> Option<EvictionReason> {
+        match status {
+            PacketStatus::ClientEvicted => Some(EvictionReason::SessionExpired),
+            PacketStatus::ClientReleaseTooLow => Some(EvictionReason::ClientReleaseTooLow),
+            PacketStatus::ClientReleaseTooHigh => Some(EvictionReason::ClientReleaseTooHigh),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for EvictionReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::SessionExpired => f.write_str("session expired"),
+            Self::ClientReleaseTooLow => f.write_str("client release too low"),
+            Self::ClientReleaseTooHigh => f.write_str("client release too high"),
+        }
+    }
+}
+
+pub(crate) type OnEviction = Arc<dyn Fn(EvictionReason) + Send + Sync>;
+
+impl Client {
+    /// Set a callback invoked when the cluster evicts one of the client's
+    /// sessions, e.g. to alert operators.
+    ///
+    /// The callback is invoked once per evicted session, however many
+    /// requests fail with it, from whichever thread polls the first failed
+    /// request's future. To consume evictions as a stream, send them to a
+    /// channel.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    ///
+    /// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = tb::Client::new(0, "127.0.0.1:3000")?;
+    /// client.on_eviction(|reason| match reason {
+    ///     tb::EvictionReason::SessionExpired => eprintln!("session expired"),
+    ///     reason => eprintln!("incompatible release: {reason}"),
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn on_eviction(&mut self, on_eviction: impl Fn(EvictionReason) + Send + Sync + 'static) {
+        self.core.set_on_eviction(Arc::new(on_eviction));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_status() {
+        assert_eq!(
+            EvictionReason::from_status(PacketStatus::ClientEvicted),
+            Some(EvictionReason::SessionExpired)
+        );
+        assert_eq!(
+            EvictionReason::from_status(PacketStatus::ClientReleaseTooHigh),
+            Some(EvictionReason::ClientReleaseTooHigh)
+        );
+        assert_eq!(EvictionReason::from_status(PacketStatus::TooMuchData), None);
+    }
+}
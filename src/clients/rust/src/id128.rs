@@ -0,0 +1,309 @@
+//! Conversion of `u128` ids to and from hexadecimal, UUID and ULID strings.
+//!
+//! A UUID or ULID is a 128-bit number, and converts to the `u128` with the
+//! same value, i.e. the first character of its string is the most
+//! significant. So ids generated by [`id`](crate::id), whose most
+//! significant bits are a millisecond timestamp, are valid ULIDs with the
+//! same timestamp, and UUIDv7s sort as TigerBeetle ids do.
+//!
+//! ```
+//! use tigerbeetle::id128;
+//!
+//! let id = id128::from_uuid("0190163d-8694-739b-aea5-966c26f8ad91").unwrap();
+//! assert_eq!(id, 0x0190163d_8694_739b_aea5_966c26f8ad91);
+//! assert_eq!(id128::uuid(id).to_string(), "0190163d-8694-739b-aea5-966c26f8ad91");
+//! assert_eq!(id128::ulid(id).to_string(), "01J0B3V1MMEEDTX9CPDGKFHBCH");
+//! assert_eq!(id128::from_ulid("01J0B3V1MMEEDTX9CPDGKFHBCH"), Ok(id));
+//! ```
+//!
+//! Formatting doesn't allocate, so these are available without the standard
+//! library, apart from generating random UUIDs.
+
+use core::fmt;
+
+/// Format `id` as 32 lowercase hexadecimal digits.
+pub fn hex(id: u128) -> Hex {
+    Hex(id)
+}
+
+/// Format `id` as a hyphenated lowercase UUID.
+pub fn uuid(id: u128) -> Uuid {
+    Uuid(id)
+}
+
+/// Format `id` as a 26-character ULID.
+pub fn ulid(id: u128) -> Ulid {
+    Ulid(id)
+}
+
+/// An id formatted as hexadecimal. See [`hex`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Hex(pub u128);
+
+/// An id formatted as a UUID. See [`uuid`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Uuid(pub u128);
+
+/// An id formatted as a ULID. See [`ulid`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Ulid(pub u128);
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            id >> 96,
+            (id >> 80) & 0xffff,
+            (id >> 64) & 0xffff,
+            (id >> 48) & 0xffff,
+            id & 0xffff_ffff_ffff,
+        )
+    }
+}
+
+/// Crockford's base 32 alphabet, used by ULIDs.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+impl fmt::Display for Ulid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // 26 digits of 5 bits, the first of which holds the top 3 bits.
+        let mut digits = [0; 26];
+        for (index, digit) in digits.iter_mut().enumerate() {
+            let shift = 5 * (25 - index);
+            *digit = CROCKFORD[((self.0 >> shift) & 0x1f) as usize];
+        }
+        f.write_str(core::str::from_utf8(&digits).expect("ascii"))
+    }
+}
+
+/// Parse 32 hexadecimal digits, with or without a `0x` prefix.
+///
+/// # Errors
+///
+/// Returns [`Id128Error::Length`] if there aren't 32 digits, and
+/// [`Id128Error::Character`] if any isn't hexadecimal.
+pub fn from_hex(hex: &str) -> Result<u128, Id128Error> {
+    let digits = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    if digits.len() != 32 {
+        return Err(Id128Error::Length);
+    }
+    parse_hex_digits(digits.bytes())
+}
+
+/// Parse a UUID, hyphenated or as 32 hexadecimal digits.
+///
+/// Any version is accepted, and letters may be of either case.
+///
+/// # Errors
+///
+/// Returns [`Id128Error::Length`] if the UUID has the wrong length, and
+/// [`Id128Error::Character`] if it has a non-hexadecimal digit or a
+/// misplaced hyphen.
+pub fn from_uuid(uuid: &str) -> Result<u128, Id128Error> {
+    match uuid.len() {
+        32 => parse_hex_digits(uuid.bytes()),
+        36 => {
+            let bytes = uuid.as_bytes();
+            if [8, 13, 18, 23].iter().any(|&index| bytes[index] != b'-') {
+                return Err(Id128Error::Character);
+            }
+            parse_hex_digits(bytes.iter().copied().filter(|&byte| byte != b'-'))
+        }
+        _ => Err(Id128Error::Length),
+    }
+}
+
+/// Parse a ULID.
+///
+/// Letters may be of either case, and as Crockford's base 32 specifies,
+/// `I` and `L` are read as `1`, and `O` as `0`.
+///
+/// # Errors
+///
+/// Returns [`Id128Error::Length`] if the ULID isn't 26 characters,
+/// [`Id128Error::Character`] if any isn't a base 32 digit, and
+/// [`Id128Error::Overflow`] if it is greater than `7ZZZZZZZZZZZZZZZZZZZZZZZZZ`.
+pub fn from_ulid(ulid: &str) -> Result<u128, Id128Error> {
+    if ulid.len() != 26 {
+        return Err(Id128Error::Length);
+    }
+    if ulid.as_bytes()[0] > b'7' {
+        return Err(Id128Error::Overflow);
+    }
+    ulid.bytes().try_fold(0, |id, byte| {
+        let digit = match byte.to_ascii_uppercase() {
+            b'O' => 0,
+            b'I' | b'L' => 1,
+            byte => CROCKFORD
+                .iter()
+                .position(|&digit| digit == byte)
+                .ok_or(Id128Error::Character)?,
+        };
+        Ok(id << 5 | digit as u128)
+    })
+}
+
+fn parse_hex_digits(digits: impl Iterator<Item = u8>) -> Result<u128, Id128Error> {
+    let mut id = 0;
+    let mut count = 0;
+    for digit in digits {
+        let value = (digit as char).to_digit(16).ok_or(Id128Error::Character)?;
+        id = id << 4 | value as u128;
+        count += 1;
+    }
+    if count == 32 {
+        Ok(id)
+    } else {
+        Err(Id128Error::Length)
+    }
+}
+
+/// Generate a random (version 4) UUID.
+#[cfg(feature = "std")]
+pub fn uuid_v4() -> u128 {
+    let random = u128::from(crate::time_based_id::random_u64()) << 64
+        | u128::from(crate::time_based_id::random_u64());
+    with_version(random, 4)
+}
+
+/// Generate a time-ordered (version 7) UUID.
+///
+/// Its most significant 48 bits are the milliseconds since the Unix epoch,
+/// as with [`id`](crate::id), but unlike those ids, UUIDs generated in the
+/// same millisecond are in random order.
+#[cfg(feature = "std")]
+pub fn uuid_v7() -> u128 {
+    let ms_since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or(0);
+    let random = u128::from(crate::time_based_id::random_u64()) << 64
+        | u128::from(crate::time_based_id::random_u64());
+    with_version(
+        (ms_since_epoch & 0xffff_ffff_ffff) << 80 | random & ((1 << 80) - 1),
+        7,
+    )
+}
+
+/// Set the version and variant bits of a UUID.
+#[cfg(feature = "std")]
+fn with_version(id: u128, version: u128) -> u128 {
+    const VERSION: u128 = 0xf << 76;
+    const VARIANT: u128 = 0b11 << 62;
+    (id & !VERSION & !VARIANT) | version << 76 | 0b10 << 62
+}
+
+/// Derive the id of event `index` of `seed`, such as a transfer of a
+/// period's close, so that retries use the same ids.
+///
+/// Distinct indexes of a seed have distinct ids, and ids of distinct seeds
+/// are unrelated, but aren't guaranteed not to collide.
+#[cfg(feature = "std")]
+pub(crate) fn derive(seed: u128, index: u128) -> u128 {
+    // A bijection, so that distinct indexes have distinct ids.
+    fn mix(mut x: u128) -> u128 {
+        x ^= x >> 64;
+        x = x.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835);
+        x ^= x >> 64;
+        x = x.wrapping_mul(0xd6e8_feb8_6659_fd93_2545_f491_4f6c_dd1d);
+        x ^ x >> 64
+    }
+    // Zero and `u128::MAX` aren't valid ids.
+    mix(mix(seed) ^ index).clamp(1, u128::MAX - 1)
+}
+
+/// Errors parsing an id.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum Id128Error {
+    /// The string has the wrong number of characters.
+    Length,
+    /// The string has an invalid character.
+    Character,
+    /// The ULID is greater than the maximum `u128`.
+    Overflow,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Id128Error {}
+impl fmt::Display for Id128Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Length => f.write_str("wrong length"),
+            Self::Character => f.write_str("invalid character"),
+            Self::Overflow => f.write_str("ulid out of range"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for id in [0, 1, 0x0123_4567_89ab_cdef, u128::MAX, crate::id()] {
+            assert_eq!(from_hex(&hex(id).to_string()), Ok(id));
+            assert_eq!(from_uuid(&uuid(id).to_string()), Ok(id));
+            assert_eq!(from_uuid(&hex(id).to_string()), Ok(id));
+            assert_eq!(from_ulid(&ulid(id).to_string()), Ok(id));
+        }
+        assert_eq!(ulid(u128::MAX).to_string(), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+        assert_eq!(uuid(1).to_string(), "00000000-0000-0000-0000-000000000001");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(from_hex("0x1"), Err(Id128Error::Length));
+        assert_eq!(from_hex(&"g".repeat(32)), Err(Id128Error::Character));
+        assert_eq!(
+            from_uuid("0190163d-8694-739b-aea5+966c26f8ad91"),
+            Err(Id128Error::Character)
+        );
+        assert_eq!(from_uuid("0190163d"), Err(Id128Error::Length));
+        assert_eq!(
+            from_ulid("8ZZZZZZZZZZZZZZZZZZZZZZZZZ"),
+            Err(Id128Error::Overflow)
+        );
+        assert_eq!(
+            from_ulid("01J0B3V1MMEEDTX9CPDGKFHBCU"),
+            Err(Id128Error::Character)
+        );
+        assert_eq!(
+            from_ulid("01j0b3v1mmeedtx9cpdgkfhbch"),
+            from_ulid("01J0B3V1MMEEDTX9CPDGKFHBCH")
+        );
+    }
+
+    #[test]
+    fn versions() {
+        let v4 = uuid_v4();
+        assert_eq!(uuid(v4).to_string().as_bytes()[14], b'4');
+        assert_eq!(v4 >> 62 & 0b11, 0b10);
+
+        let v7 = uuid_v7();
+        assert_eq!(uuid(v7).to_string().as_bytes()[14], b'7');
+        assert_eq!(v7 >> 62 & 0b11, 0b10);
+        // The same timestamp as a TigerBeetle id.
+        assert!((crate::id() >> 80).abs_diff(v7 >> 80) < 1_000);
+    }
+
+    #[test]
+    fn derive() {
+        assert_eq!(super::derive(1, 2), super::derive(1, 2));
+        assert_ne!(super::derive(1, 2), super::derive(1, 3));
+        assert_ne!(super::derive(1, 2), super::derive(2, 2));
+        assert_ne!(super::derive(0, 0), 0);
+    }
+}
@@ -0,0 +1,475 @@
+//! Idempotency keys, for creating accounts and transfers exactly once.
+//!
+//! A client that retries a request, e.g. an HTTP request that timed out,
+//! can't tell whether its transfers were created. If the retry reuses the
+//! transfers' ids, the cluster creates them at most once, reporting the
+//! retried transfers as `Exists`. An [`IdempotencyStore`] maps the
+//! application's request keys, such as `Idempotency-Key` headers, to the ids
+//! first used for them, so a retry handled by any process gets the same ids:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use tigerbeetle as tb;
+//! use tb::idempotency::{self, InMemoryStore};
+//!
+//! # async fn example(client: &tb::Client, key: &str, mut transfers: Vec<tb::Transfer>) -> Result<(), Box<dyn std::error::Error>> {
+//! let store = InMemoryStore::new(Duration::from_secs(24 * 60 * 60));
+//! idempotency::assign_ids(&store, key, &mut transfers, |transfer| &mut transfer.id)?;
+//! for result in client.create_transfers(&transfers).await? {
+//!     if result.result != tb::CreateTransferResult::Exists {
+//!         println!("transfer {} failed: {}", transfers[result.index].id, result.result);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The [`InMemoryStore`] serves a single process, and forgets its keys when
+//! the process exits. With the `redis` feature, the `RedisStore` shares keys
+//! between processes through a Redis server.
+//!
+//! Keys expire after a time to live, which should be longer than the
+//! clients retry for. Once a key expires, a retry with it creates new
+//! transfers.
+
+use core::fmt;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A store of the ids used for each idempotency key.
+pub trait IdempotencyStore: Send + Sync {
+    /// Associate `ids` with `key`, unless it already has ids, and return the
+    /// ids of `key`.
+    ///
+    /// This must be atomic: of concurrent calls with the same key, all
+    /// return the ids of the first.
+    fn ids(&self, key: &str, ids: &[u128]) -> Result<Vec<u128>, IdempotencyError>;
+}
+
+/// Give `events`, e.g. transfers or accounts, the ids first used with
+/// `key`, where `id` is an event's id field.
+///
+/// The first time a key is used, events without an id are given one
+/// with [`id`](crate::id()), and the ids are stored. Later, each event is
+/// given the stored id at its index.
+///
+/// # Errors
+///
+/// Returns [`IdempotencyError::KeyReused`] if `key` was used for a
+/// different number of events, or for an event with a different id,
+/// and [`IdempotencyError::Store`] if the store failed. The events are
+/// unchanged on error.
+pub fn assign_ids<T>(
+    store: &dyn IdempotencyStore,
+    key: &str,
+    events: &mut [T],
+    id: fn(&mut T) -> &mut u128,
+) -> Result<(), IdempotencyError> {
+    let ids: Vec<u128> = events
+        .iter_mut()
+        .map(|event| match *id(event) {
+            0 => crate::id(),
+            id => id,
+        })
+        .collect();
+    let stored = store.ids(key, &ids)?;
+    let reused = stored.len() != events.len()
+        || events
+            .iter_mut()
+            .zip(&stored)
+            .any(|(event, &stored)| *id(event) != 0 && *id(event) != stored);
+    if reused {
+        return Err(IdempotencyError::KeyReused);
+    }
+    for (event, stored) in events.iter_mut().zip(stored) {
+        *id(event) = stored;
+    }
+    Ok(())
+}
+
+/// An [`IdempotencyStore`] in memory, for a single process.
+pub struct InMemoryStore {
+    ttl: Duration,
+    state: Mutex<StoreState>,
+}
+
+#[derive(Default)]
+struct StoreState {
+    keys: HashMap<String, Vec<u128>>,
+    /// The keys in the order they were stored, so they expire in order.
+    expiries: VecDeque<(Instant, String)>,
+}
+
+impl InMemoryStore {
+    /// A store whose keys expire `ttl` after they are first used.
+    pub fn new(ttl: Duration) -> InMemoryStore {
+        InMemoryStore {
+            ttl,
+            state: Mutex::default(),
+        }
+    }
+
+    /// The number of keys stored, including those expired since the store
+    /// was last used.
+    pub fn len(&self) -> usize {
+        self.state.lock().expect("idempotency store").keys.len()
+    }
+
+    /// Whether no keys are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl IdempotencyStore for InMemoryStore {
+    fn ids(&self, key: &str, ids: &[u128]) -> Result<Vec<u128>, IdempotencyError> {
+        let now = Instant::now();
+        let mut state = self.state.lock().expect("idempotency store");
+        while let Some((expires, _)) = state.expiries.front() {
+            if *expires > now {
+                break;
+            }
+            let (_, expired) = state.expiries.pop_front().expect("front");
+            state.keys.remove(&expired);
+        }
+
+        if let Some(stored) = state.keys.get(key) {
+            return Ok(stored.clone());
+        }
+        state.keys.insert(key.to_owned(), ids.to_vec());
+        state.expiries.push_back((now + self.ttl, key.to_owned()));
+        Ok(ids.to_vec())
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis::RedisStore;
+
+#[cfg(feature = "redis")]
+mod redis {
+    use super::{IdempotencyError, IdempotencyStore};
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// An [`IdempotencyStore`] in a Redis server, shared by processes.
+    ///
+    /// Keys are stored with `SET ... NX`, so concurrent requests with the
+    /// same key agree on its ids, as the ids' decimal strings separated by
+    /// commas. Calls block the thread on a single connection to the server,
+    /// which is made when first needed and again after an error.
+    pub struct RedisStore {
+        address: String,
+        prefix: String,
+        ttl: Duration,
+        timeout: Duration,
+        connection: Mutex<Option<BufReader<TcpStream>>>,
+    }
+
+    impl RedisStore {
+        /// A store in the Redis server at `address`, a host and port, whose
+        /// keys expire `ttl` after they are first used.
+        ///
+        /// Keys are prefixed with `tigerbeetle:idempotency:`, and requests
+        /// time out after 5 seconds.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `ttl` is less than a second, Redis's resolution.
+        pub fn new(address: &str, ttl: Duration) -> RedisStore {
+            assert!(ttl.as_secs() > 0, "ttl must be at least a second");
+            RedisStore {
+                address: address.to_owned(),
+                prefix: "tigerbeetle:idempotency:".to_owned(),
+                ttl,
+                timeout: Duration::from_secs(5),
+                connection: Mutex::new(None),
+            }
+        }
+
+        /// Prefix keys with `prefix`, e.g. to share a server between
+        /// applications.
+        pub fn prefix(mut self, prefix: &str) -> RedisStore {
+            self.prefix = prefix.to_owned();
+            self
+        }
+
+        /// The time to connect, or to send a command and receive its reply.
+        pub fn timeout(mut self, timeout: Duration) -> RedisStore {
+            self.timeout = timeout;
+            self
+        }
+
+        /// Send a command, returning its reply, or `None` if it is nil.
+        fn command(&self, args: &[&[u8]]) -> Result<Option<Vec<u8>>, String> {
+            let mut connection = self.connection.lock().expect("redis connection");
+            if connection.is_none() {
+                *connection = Some(self.connect()?);
+            }
+            let stream = connection.as_mut().expect("connected");
+            let reply = request(stream, args);
+            if reply.is_err() {
+                // The connection may be mid-reply, so don't reuse it.
+                *connection = None;
+            }
+            reply
+        }
+
+        fn connect(&self) -> Result<BufReader<TcpStream>, String> {
+            let mut last_error = "no address".to_owned();
+            let addresses = self
+                .address
+                .to_socket_addrs()
+                .map_err(|error| error.to_string())?;
+            for address in addresses {
+                match TcpStream::connect_timeout(&address, self.timeout) {
+                    Ok(stream) => {
+                        let error = |error: std::io::Error| error.to_string();
+                        stream.set_read_timeout(Some(self.timeout)).map_err(error)?;
+                        stream
+                            .set_write_timeout(Some(self.timeout))
+                            .map_err(error)?;
+                        return Ok(BufReader::new(stream));
+                    }
+                    Err(error) => last_error = error.to_string(),
+                }
+            }
+            Err(last_error)
+        }
+    }
+
+    impl IdempotencyStore for RedisStore {
+        fn ids(&self, key: &str, ids: &[u128]) -> Result<Vec<u128>, IdempotencyError> {
+            let key = format!("{}{key}", self.prefix);
+            let value = ids
+                .iter()
+                .map(u128::to_string)
+                .collect::<Vec<_>>()
+                .join(",");
+            let ttl = self.ttl.as_secs().to_string();
+            // Retry if the key expires between `SET` and `GET`.
+            for _ in 0..3 {
+                let set = self
+                    .command(&[
+                        b"SET",
+                        key.as_bytes(),
+                        value.as_bytes(),
+                        b"NX",
+                        b"EX",
+                        ttl.as_bytes(),
+                    ])
+                    .map_err(IdempotencyError::Store)?;
+                if set.is_some() {
+                    return Ok(ids.to_vec());
+                }
+                let stored = self
+                    .command(&[b"GET", key.as_bytes()])
+                    .map_err(IdempotencyError::Store)?;
+                if let Some(stored) = stored {
+                    return parse(&stored)
+                        .ok_or_else(|| IdempotencyError::Store(format!("invalid ids at {key}")));
+                }
+            }
+            Err(IdempotencyError::Store(format!("{key} keeps expiring")))
+        }
+    }
+
+    /// Send a command in the Redis protocol, and read its reply.
+    fn request(
+        stream: &mut BufReader<TcpStream>,
+        args: &[&[u8]],
+    ) -> Result<Option<Vec<u8>>, String> {
+        let mut command = format!("*{}\r\n", args.len()).into_bytes();
+        for arg in args {
+            command.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            command.extend_from_slice(arg);
+            command.extend_from_slice(b"\r\n");
+        }
+        let error = |error: std::io::Error| error.to_string();
+        stream.get_mut().write_all(&command).map_err(error)?;
+
+        let mut line = String::new();
+        stream.read_line(&mut line).map_err(error)?;
+        let line = line
+            .strip_suffix("\r\n")
+            .ok_or_else(|| "connection closed".to_owned())?;
+        let reply = line.get(1..).unwrap_or_default();
+        match (line.chars().next(), reply) {
+            (Some('+' | ':'), reply) => Ok(Some(reply.as_bytes().to_vec())),
+            (Some('-'), error) => Err(error.to_owned()),
+            (Some('$'), "-1") => Ok(None),
+            (Some('$'), length) => {
+                let length: usize = length
+                    .parse()
+                    .map_err(|_| format!("invalid reply {line}"))?;
+                let mut reply = vec![0; length + 2];
+                stream.read_exact(&mut reply).map_err(error)?;
+                reply.truncate(length);
+                Ok(Some(reply))
+            }
+            _ => Err(format!("unexpected reply {line}")),
+        }
+    }
+
+    fn parse(value: &[u8]) -> Option<Vec<u128>> {
+        let value = std::str::from_utf8(value).ok()?;
+        if value.is_empty() {
+            return Some(Vec::new());
+        }
+        value.split(',').map(|id| id.parse().ok()).collect()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        use std::collections::HashMap;
+        use std::net::TcpListener;
+
+        /// A Redis server supporting only `SET ... NX` and `GET`, without
+        /// expiry, for two connections in turn.
+        fn serve(listener: TcpListener) {
+            let mut keys: HashMap<Vec<u8>, Vec<u8>> = HashMap::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                serve_connection(BufReader::new(stream), &mut keys);
+            }
+        }
+
+        fn serve_connection(
+            mut reader: BufReader<TcpStream>,
+            keys: &mut HashMap<Vec<u8>, Vec<u8>>,
+        ) {
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    return;
+                }
+                let count: usize = line.trim()[1..].parse().unwrap();
+                let mut args = Vec::new();
+                for _ in 0..count {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let length: usize = line.trim()[1..].parse().unwrap();
+                    let mut arg = vec![0; length + 2];
+                    reader.read_exact(&mut arg).unwrap();
+                    arg.truncate(length);
+                    args.push(arg);
+                }
+                let reply = match args[0].as_slice() {
+                    b"SET" if keys.contains_key(&args[1]) => b"$-1\r\n".to_vec(),
+                    b"SET" => {
+                        assert_eq!(args[3..5], [b"NX".to_vec(), b"EX".to_vec()]);
+                        keys.insert(args[1].clone(), args[2].clone());
+                        b"+OK\r\n".to_vec()
+                    }
+                    b"GET" => {
+                        let value = &keys[&args[1]];
+                        let mut reply = format!("${}\r\n", value.len()).into_bytes();
+                        reply.extend_from_slice(value);
+                        reply.extend_from_slice(b"\r\n");
+                        reply
+                    }
+                    _ => b"-ERR unknown command\r\n".to_vec(),
+                };
+                reader.get_mut().write_all(&reply).unwrap();
+            }
+        }
+
+        #[test]
+        fn redis_store() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let server = std::thread::spawn(move || serve(listener));
+
+            let store = RedisStore::new(&address, Duration::from_secs(60)).prefix("test:");
+            assert_eq!(store.ids("a", &[1, u128::MAX]), Ok(vec![1, u128::MAX]));
+            assert_eq!(store.ids("a", &[3]), Ok(vec![1, u128::MAX]));
+            assert_eq!(store.ids("b", &[]), Ok(vec![]));
+            assert_eq!(store.ids("b", &[4]), Ok(vec![]));
+            assert_eq!(
+                store.command(&[b"PING"]),
+                Err("ERR unknown command".to_owned())
+            );
+            // Reconnects after an error.
+            assert_eq!(store.ids("a", &[5]), Ok(vec![1, u128::MAX]));
+
+            drop(store);
+            server.join().unwrap();
+        }
+    }
+}
+
+/// Errors assigning ids with an [`IdempotencyStore`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum IdempotencyError {
+    /// The key was used for a different request.
+    KeyReused,
+    /// The store failed, e.g. because its server could not be reached.
+    Store(String),
+}
+
+impl std::error::Error for IdempotencyError {}
+impl fmt::Display for IdempotencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::KeyReused => f.write_str("idempotency key reused for a different request"),
+            Self::Store(error) => write!(f, "idempotency store failed: {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Transfer;
+
+    fn transfers(ids: &[u128]) -> Vec<Transfer> {
+        ids.iter()
+            .map(|&id| Transfer {
+                id,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assign_ids() {
+        let store = InMemoryStore::new(Duration::from_secs(60));
+        let mut first = transfers(&[0, 7, 0]);
+        super::assign_ids(&store, "order-42", &mut first, |t| &mut t.id).unwrap();
+        assert_eq!(first[1].id, 7);
+        assert!(first.iter().all(|transfer| transfer.id != 0));
+
+        let mut retry = transfers(&[0, 0, 0]);
+        super::assign_ids(&store, "order-42", &mut retry, |t| &mut t.id).unwrap();
+        assert_eq!(retry, first);
+
+        let mut other = transfers(&[0, 0, 0]);
+        super::assign_ids(&store, "order-43", &mut other, |t| &mut t.id).unwrap();
+        assert_ne!(other[0].id, first[0].id);
+        assert_eq!(store.len(), 2);
+
+        for ids in [&[0, 0][..], &[0, 8, 0]] {
+            let mut reused = transfers(ids);
+            assert_eq!(
+                super::assign_ids(&store, "order-42", &mut reused, |t| &mut t.id),
+                Err(IdempotencyError::KeyReused)
+            );
+            assert_eq!(reused, transfers(ids));
+        }
+    }
+
+    #[test]
+    fn expiry() {
+        let store = InMemoryStore::new(Duration::ZERO);
+        assert_eq!(store.ids("a", &[1]), Ok(vec![1]));
+        assert_eq!(store.ids("a", &[2]), Ok(vec![2]));
+        assert_eq!(store.len(), 1);
+    }
+}
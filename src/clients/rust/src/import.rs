@@ -0,0 +1,180 @@
+use super::*;
+
+impl Client {
+    /// Import historical accounts, with user-specified timestamps.
+    ///
+    /// This sets the [`AccountFlags::Imported`] flag on every event, then
+    /// creates the accounts as [`Client::create_accounts`] does. Each event's
+    /// `timestamp` must be set, and timestamps must be strictly increasing.
+    ///
+    /// TigerBeetle further requires that imported timestamps are in the past,
+    /// and later than any existing account's timestamp; events that violate
+    /// this are reported as failed results, as with any other event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ImportError::TimestampMustNotBeZero`] or
+    /// [`ImportError::TimestampMustIncrease`], without submitting a request,
+    /// if the timestamps are invalid, and [`ImportError::Packet`] if the
+    /// request fails.
+    ///
+    /// # Protocol reference
+    ///
+    /// [`Account.flags.imported`](https://docs.tigerbeetle.com/reference/account/#flagsimported).
+    pub fn import_accounts(
+        &self,
+        events: &[Account],
+    ) -> impl Future<Output = Result<Vec<CreateAccountsResult>, ImportError>> {
+        let request = imported(events).map(|events| self.create_accounts(&events));
+
+        async { Ok(request?.await?) }
+    }
+
+    /// Import historical transfers, with user-specified timestamps.
+    ///
+    /// This sets the [`TransferFlags::Imported`] flag on every event, then
+    /// creates the transfers as [`Client::create_transfers`] does. Each
+    /// event's `timestamp` must be set, and timestamps must be strictly
+    /// increasing.
+    ///
+    /// TigerBeetle further requires that imported timestamps are in the past,
+    /// and later than any existing transfer's timestamp, and that the
+    /// debit and credit accounts were created before the transfer; events
+    /// that violate this are reported as failed results.
+    ///
+    /// # Errors
+    ///
+    /// As for [`Client::import_accounts`].
+    ///
+    /// # Protocol reference
+    ///
+    /// [`Transfer.flags.imported`](https://docs.tigerbeetle.com/reference/transfer/#flagsimported).
+    pub fn import_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> impl Future<Output = Result<Vec<CreateTransfersResult>, ImportError>> {
+        let request = imported(events).map(|events| self.create_transfers(&events));
+
+        async { Ok(request?.await?) }
+    }
+}
+
+/// Errors resulting from importing events.
+///
+/// See [`Client::import_accounts`] and [`Client::import_transfers`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ImportError {
+    /// The event at `index` has no timestamp.
+    TimestampMustNotBeZero { index: usize },
+    /// The event at `index` has a timestamp that is not later than the
+    /// timestamp of the previous event.
+    TimestampMustIncrease { index: usize },
+    /// The request failed. See [`PacketStatus`].
+    Packet(PacketStatus),
+}
+
+impl From<PacketStatus> for ImportError {
+    fn from(status: PacketStatus) -> ImportError {
+        ImportError::Packet(status)
+    }
+}
+
+impl std::error::Error for ImportError {}
+impl core::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::TimestampMustNotBeZero { index } => {
+                write!(f, "imported event {index} has no timestamp")
+            }
+            Self::TimestampMustIncrease { index } => {
+                write!(f, "imported event {index} timestamp must increase")
+            }
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+        }
+    }
+}
+
+trait Importable: Copy {
+    fn timestamp(&self) -> u64;
+    fn set_imported(&mut self);
+}
+
+impl Importable for Account {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    fn set_imported(&mut self) {
+        self.flags.insert(AccountFlags::Imported);
+    }
+}
+
+impl Importable for Transfer {
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    fn set_imported(&mut self) {
+        self.flags.insert(TransferFlags::Imported);
+    }
+}
+
+/// Validate the timestamps of `events` and set their `imported` flags.
+fn imported<Event: Importable>(events: &[Event]) -> Result<Vec<Event>, ImportError> {
+    let mut timestamp_previous = 0;
+    for (index, event) in events.iter().enumerate() {
+        if event.timestamp() == 0 {
+            return Err(ImportError::TimestampMustNotBeZero { index });
+        }
+        if event.timestamp() <= timestamp_previous {
+            return Err(ImportError::TimestampMustIncrease { index });
+        }
+        timestamp_previous = event.timestamp();
+    }
+
+    Ok(events
+        .iter()
+        .map(|event| {
+            let mut event = *event;
+            event.set_imported();
+            event
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(timestamp: u64) -> Account {
+        Account {
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn imported_sets_flags() {
+        let accounts = imported(&[account(1), account(2)]).unwrap();
+        assert!(accounts
+            .iter()
+            .all(|account| account.flags.contains(AccountFlags::Imported)));
+    }
+
+    #[test]
+    fn imported_validates_timestamps() {
+        assert_eq!(
+            imported(&[account(1), account(0)]),
+            Err(ImportError::TimestampMustNotBeZero { index: 1 })
+        );
+        assert_eq!(
+            imported(&[account(1), account(3), account(3)]),
+            Err(ImportError::TimestampMustIncrease { index: 2 })
+        );
+        assert_eq!(
+            imported(&[account(2), account(1)]),
+            Err(ImportError::TimestampMustIncrease { index: 1 })
+        );
+    }
+}
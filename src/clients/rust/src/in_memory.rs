@@ -0,0 +1,1649 @@
+use super::*;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::ready;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// An in-memory emulation of a TigerBeetle cluster, for unit tests.
+///
+/// `InMemoryClient` has the same methods as [`Client`], and implements
+/// [`TigerBeetleClient`], but executes requests locally instead of sending
+/// them to a cluster. It follows the semantics of TigerBeetle's state machine,
+/// including:
+///
+/// - validation of events, with the same result codes as the cluster;
+/// - idempotency: resubmitted events are reported as `Exists` or
+///   `ExistsWith...`, and transfers that failed with a transient error
+///   can't be retried with the same id;
+/// - linked chains, which succeed or fail as a unit;
+/// - balance limits, balancing transfers and closing transfers;
+/// - two-phase transfers, including expiry of pending transfers;
+/// - imported events;
+/// - account balance history, and the query operations.
+///
+/// Requests complete immediately, and never fail with a [`PacketStatus`]
+/// except [`PacketStatus::TooMuchData`].
+///
+/// Timestamps are assigned from the system clock, which can be advanced
+/// with [`InMemoryClient::advance_time`] to expire pending transfers
+/// without waiting.
+///
+/// This is not a complete or exact model of TigerBeetle, and does not
+/// replace testing against a real cluster, which also exercises
+/// networking, batching and failure handling.
+///
+/// # Example
+///
+/// ```
+/// use futures::executor::block_on;
+/// use tigerbeetle as tb;
+///
+/// let client = tb::InMemoryClient::new();
+///
+/// let results = block_on(client.create_accounts(&[tb::Account {
+///     id: 1,
+///     ledger: 1,
+///     code: 1,
+///     ..Default::default()
+/// }]))?;
+/// assert!(results.is_empty());
+///
+/// let results = block_on(client.create_transfers(&[tb::Transfer {
+///     id: 1,
+///     debit_account_id: 1,
+///     credit_account_id: 2,
+///     amount: 10,
+///     ledger: 1,
+///     code: 1,
+///     ..Default::default()
+/// }]))?;
+/// assert_eq!(results[0].result, tb::CreateTransferResult::CreditAccountNotFound);
+/// # Ok::<(), tb::PacketStatus>(())
+/// ```
+pub struct InMemoryClient {
+    state: Mutex<State>,
+}
+
+impl InMemoryClient {
+    pub fn new() -> InMemoryClient {
+        InMemoryClient {
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// A cluster holding `accounts` and `transfers` as they were looked up
+    /// from another, to execute requests against without changing it.
+    ///
+    /// Whether a pending transfer was since posted or voided isn't known, so
+    /// pending transfers are treated as pending, and aren't expired until a
+    /// transfer tries to post or void them.
+    pub(crate) fn snapshot(accounts: &[Account], transfers: &[Transfer]) -> InMemoryClient {
+        let mut state = State {
+            snapshot: true,
+            ..State::default()
+        };
+        for account in accounts {
+            state.insert_account(*account);
+        }
+        for transfer in transfers {
+            state.insert_transfer(*transfer);
+            if transfer.flags.contains(TransferFlags::Pending) {
+                state
+                    .transfers_pending
+                    .insert(transfer.timestamp, PendingStatus::Pending);
+            }
+        }
+        InMemoryClient {
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Advance the client's clock by `duration`.
+    ///
+    /// Pending transfers whose timeouts elapse expire
+    /// before the next request is executed.
+    pub fn advance_time(&self, duration: Duration) {
+        let mut state = self.state();
+        state.time_offset += duration;
+    }
+
+    /// Create one or more accounts.
+    ///
+    /// See [`Client::create_accounts`].
+    pub fn create_accounts(
+        &self,
+        events: &[Account],
+    ) -> impl Future<Output = Result<Vec<CreateAccountsResult>, PacketStatus>> {
+        let results = self.execute_create(events, |state, timestamp, account| {
+            state.create_account(timestamp, account)
+        });
+
+        ready(results.map(|results| {
+            results
+                .into_iter()
+                .map(|(index, result)| CreateAccountsResult { index, result })
+                .collect()
+        }))
+    }
+
+    /// Create one or more transfers.
+    ///
+    /// See [`Client::create_transfers`].
+    pub fn create_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>> {
+        let results = self.execute_create(events, |state, timestamp, transfer| {
+            state.create_transfer(timestamp, transfer)
+        });
+
+        ready(results.map(|results| {
+            results
+                .into_iter()
+                .map(|(index, result)| CreateTransfersResult { index, result })
+                .collect()
+        }))
+    }
+
+    /// Query individual accounts.
+    ///
+    /// See [`Client::lookup_accounts`].
+    pub fn lookup_accounts(
+        &self,
+        events: &[u128],
+    ) -> impl Future<Output = Result<Vec<Account>, PacketStatus>> {
+        let state = self.state_current();
+        let accounts = events
+            .iter()
+            .filter_map(|id| state.get_account(*id))
+            .collect();
+
+        ready(Ok(accounts))
+    }
+
+    /// Query individual transfers.
+    ///
+    /// See [`Client::lookup_transfers`].
+    pub fn lookup_transfers(
+        &self,
+        events: &[u128],
+    ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
+        let state = self.state_current();
+        let transfers = events
+            .iter()
+            .filter_map(|id| state.get_transfer(*id))
+            .collect();
+
+        ready(Ok(transfers))
+    }
+
+    /// Query multiple transfers for a single account.
+    ///
+    /// See [`Client::get_account_transfers`].
+    pub fn get_account_transfers(
+        &self,
+        event: AccountFilter,
+    ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
+        let state = self.state_current();
+        let transfers = if account_filter_valid(&event) {
+            let transfers = state
+                .transfers
+                .range(timestamp_range(event.timestamp_min, event.timestamp_max))
+                .map(|(_, transfer)| transfer)
+                .filter(|transfer| account_filter_matches(&event, transfer));
+            take_limit(
+                transfers,
+                event.limit,
+                event.flags.contains(AccountFilterFlags::Reversed),
+            )
+            .into_iter()
+            .copied()
+            .collect()
+        } else {
+            Vec::new()
+        };
+
+        ready(Ok(transfers))
+    }
+
+    /// Query historical account balances for a single account.
+    ///
+    /// See [`Client::get_account_balances`].
+    pub fn get_account_balances(
+        &self,
+        event: AccountFilter,
+    ) -> impl Future<Output = Result<Vec<AccountBalance>, PacketStatus>> {
+        let state = self.state_current();
+        let history = state.balances.get(&event.account_id);
+        let balances = match history {
+            Some(history) if account_filter_valid(&event) => {
+                let range = timestamp_range(event.timestamp_min, event.timestamp_max);
+                let balances = history
+                    .iter()
+                    .filter(|(balance, _)| range.contains(&balance.timestamp))
+                    .filter(|(_, transfer)| account_filter_matches(&event, transfer))
+                    .map(|(balance, _)| balance);
+                take_limit(
+                    balances,
+                    event.limit,
+                    event.flags.contains(AccountFilterFlags::Reversed),
+                )
+                .into_iter()
+                .copied()
+                .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        ready(Ok(balances))
+    }
+
+    /// Query multiple accounts related by fields and timestamps.
+    ///
+    /// See [`Client::query_accounts`].
+    pub fn query_accounts(
+        &self,
+        event: QueryFilter,
+    ) -> impl Future<Output = Result<Vec<Account>, PacketStatus>> {
+        let state = self.state_current();
+        let accounts = if query_filter_valid(&event) {
+            let accounts = state
+                .accounts
+                .range(timestamp_range(event.timestamp_min, event.timestamp_max))
+                .map(|(_, account)| account)
+                .filter(|account| {
+                    query_filter_matches(
+                        &event,
+                        (
+                            account.user_data_128,
+                            account.user_data_64,
+                            account.user_data_32,
+                        ),
+                        account.ledger,
+                        account.code,
+                    )
+                });
+            take_limit(
+                accounts,
+                event.limit,
+                event.flags.contains(QueryFilterFlags::Reversed),
+            )
+            .into_iter()
+            .copied()
+            .collect()
+        } else {
+            Vec::new()
+        };
+
+        ready(Ok(accounts))
+    }
+
+    /// Query multiple transfers related by fields and timestamps.
+    ///
+    /// See [`Client::query_transfers`].
+    pub fn query_transfers(
+        &self,
+        event: QueryFilter,
+    ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
+        let state = self.state_current();
+        let transfers = if query_filter_valid(&event) {
+            let transfers = state
+                .transfers
+                .range(timestamp_range(event.timestamp_min, event.timestamp_max))
+                .map(|(_, transfer)| transfer)
+                .filter(|transfer| {
+                    query_filter_matches(
+                        &event,
+                        (
+                            transfer.user_data_128,
+                            transfer.user_data_64,
+                            transfer.user_data_32,
+                        ),
+                        transfer.ledger,
+                        transfer.code,
+                    )
+                });
+            take_limit(
+                transfers,
+                event.limit,
+                event.flags.contains(QueryFilterFlags::Reversed),
+            )
+            .into_iter()
+            .copied()
+            .collect()
+        } else {
+            Vec::new()
+        };
+
+        ready(Ok(transfers))
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("in-memory client state")
+    }
+
+    /// The state, after expiring pending transfers.
+    fn state_current(&self) -> std::sync::MutexGuard<'_, State> {
+        let mut state = self.state();
+        state.expire_pending_transfers();
+        state
+    }
+
+    /// Execute a batch of `create_*` events, following the cluster's
+    /// handling of linked chains and imported events.
+    fn execute_create<Event: CreateEvent, Result: Copy + Eq + CreateResult>(
+        &self,
+        events: &[Event],
+        create: impl Fn(&mut State, u64, &Event) -> Result,
+    ) -> std::result::Result<Vec<(usize, Result)>, PacketStatus> {
+        if events.len() > BATCH_MAX {
+            // The client would split the events into multiple requests,
+            // which is equivalent to executing them as one batch.
+            linked_chain::split_at_chain_boundaries(events, BATCH_MAX)
+                .ok_or(PacketStatus::TooMuchData)?;
+        }
+
+        let mut state = self.state_current();
+        let timestamp = state.prepare(events.len());
+
+        let mut results = Vec::new();
+        let mut chain: Option<usize> = None;
+        let mut chain_broken = false;
+
+        // The first event determines whether the batch imports events.
+        let batch_imported = events.first().map_or(false, |event| event.is_imported());
+        for (index, event) in events.iter().enumerate() {
+            if event.is_linked() && chain.is_none() {
+                chain = Some(index);
+                state.undo = Some(Vec::new());
+            }
+
+            let result = if event.is_linked() && index == events.len() - 1 {
+                Result::LINKED_EVENT_CHAIN_OPEN
+            } else if chain_broken {
+                Result::LINKED_EVENT_FAILED
+            } else {
+                match event_timestamp(event, batch_imported, timestamp, index, events.len()) {
+                    Ok(timestamp_event) => create(&mut state, timestamp_event, event),
+                    Err(result) => result,
+                }
+            };
+
+            if result != Result::OK {
+                if let Some(chain_index) = chain {
+                    if !chain_broken {
+                        chain_broken = true;
+                        state.roll_back();
+                        results
+                            .extend((chain_index..index).map(|i| (i, Result::LINKED_EVENT_FAILED)));
+                    }
+                }
+                results.push((index, result));
+
+                if result.transient() {
+                    state.transfers_failed.insert(event.id());
+                }
+            }
+
+            if chain.is_some() && (!event.is_linked() || result == Result::LINKED_EVENT_CHAIN_OPEN)
+            {
+                chain = None;
+                chain_broken = false;
+                state.undo = None;
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+impl Default for InMemoryClient {
+    fn default() -> InMemoryClient {
+        InMemoryClient::new()
+    }
+}
+
+impl fmt::Debug for InMemoryClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let state = self.state();
+        f.debug_struct("InMemoryClient")
+            .field("accounts", &state.accounts.len())
+            .field("transfers", &state.transfers.len())
+            .finish()
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum PendingStatus {
+    Pending,
+    Posted,
+    Voided,
+    Expired,
+}
+
+#[derive(Clone, Default)]
+struct State {
+    /// Accounts and transfers are keyed by timestamp, and indexed by id.
+    accounts: BTreeMap<u64, Account>,
+    account_timestamps: HashMap<u128, u64>,
+    transfers: BTreeMap<u64, Transfer>,
+    transfer_timestamps: HashMap<u128, u64>,
+    /// Ids of transfers that failed with a transient error.
+    transfers_failed: HashSet<u128>,
+    /// The status of each pending transfer, keyed by its timestamp.
+    transfers_pending: HashMap<u64, PendingStatus>,
+    /// The balance history of accounts with [`AccountFlags::History`],
+    /// with the transfer that produced each balance.
+    balances: HashMap<u128, Vec<(AccountBalance, Transfer)>>,
+    prepare_timestamp: u64,
+    time_offset: Duration,
+    /// Whether the state is a [snapshot](InMemoryClient::snapshot), whose
+    /// balances may already reflect expiries.
+    snapshot: bool,
+    /// The changes made by the linked chain being executed, if any, to roll
+    /// back if it fails.
+    undo: Option<Vec<Undo>>,
+}
+
+/// A change to the [`State`], recorded to be rolled back.
+#[derive(Clone)]
+enum Undo {
+    AccountCreated(Account),
+    AccountUpdated(Account),
+    TransferCreated(Transfer),
+    PendingStatus(u64, Option<PendingStatus>),
+    BalanceRecorded(u128),
+}
+
+impl State {
+    /// Reserve `count` timestamps, returning the last.
+    fn prepare(&mut self, count: usize) -> u64 {
+        let realtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system time")
+            + self.time_offset;
+        let realtime = u64::try_from(realtime.as_nanos()).expect("timestamp");
+
+        self.prepare_timestamp = realtime.max(self.prepare_timestamp + count as u64);
+        self.prepare_timestamp
+    }
+
+    fn get_account(&self, id: u128) -> Option<Account> {
+        let timestamp = self.account_timestamps.get(&id)?;
+        Some(self.accounts[timestamp])
+    }
+
+    fn get_transfer(&self, id: u128) -> Option<Transfer> {
+        let timestamp = self.transfer_timestamps.get(&id)?;
+        Some(self.transfers[timestamp])
+    }
+
+    /// Record `change` to be rolled back, if executing a linked chain.
+    fn record(&mut self, change: Undo) {
+        if let Some(undo) = &mut self.undo {
+            undo.push(change);
+        }
+    }
+
+    /// Undo the changes made by the linked chain being executed.
+    fn roll_back(&mut self) {
+        let undo = self.undo.take().expect("linked chain");
+        for change in undo.into_iter().rev() {
+            match change {
+                Undo::AccountCreated(account) => {
+                    self.account_timestamps.remove(&account.id);
+                    self.accounts.remove(&account.timestamp);
+                }
+                Undo::AccountUpdated(account) => {
+                    self.accounts.insert(account.timestamp, account);
+                }
+                Undo::TransferCreated(transfer) => {
+                    self.transfer_timestamps.remove(&transfer.id);
+                    self.transfers.remove(&transfer.timestamp);
+                }
+                Undo::PendingStatus(timestamp, Some(status)) => {
+                    self.transfers_pending.insert(timestamp, status);
+                }
+                Undo::PendingStatus(timestamp, None) => {
+                    self.transfers_pending.remove(&timestamp);
+                }
+                Undo::BalanceRecorded(id) => {
+                    let balances = self.balances.get_mut(&id).expect("balances");
+                    balances.pop();
+                    if balances.is_empty() {
+                        self.balances.remove(&id);
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert_account(&mut self, account: Account) {
+        self.account_timestamps
+            .insert(account.id, account.timestamp);
+        self.accounts.insert(account.timestamp, account);
+        self.record(Undo::AccountCreated(account));
+    }
+
+    fn update_account(&mut self, account: Account) {
+        let previous = self.accounts.insert(account.timestamp, account);
+        self.record(Undo::AccountUpdated(previous.expect("account")));
+    }
+
+    fn insert_transfer(&mut self, transfer: Transfer) {
+        self.transfer_timestamps
+            .insert(transfer.id, transfer.timestamp);
+        self.transfers.insert(transfer.timestamp, transfer);
+        self.record(Undo::TransferCreated(transfer));
+    }
+
+    fn set_pending_status(&mut self, timestamp: u64, status: PendingStatus) {
+        let previous = self.transfers_pending.insert(timestamp, status);
+        self.record(Undo::PendingStatus(timestamp, previous));
+    }
+
+    /// Record the balances of history accounts after an event.
+    fn record_balances(
+        &mut self,
+        timestamp: u64,
+        dr_account: &Account,
+        cr_account: &Account,
+        transfer: &Transfer,
+    ) {
+        for account in [dr_account, cr_account] {
+            if account.flags.contains(AccountFlags::History) {
+                let balance = AccountBalance {
+                    debits_pending: account.debits_pending,
+                    debits_posted: account.debits_posted,
+                    credits_pending: account.credits_pending,
+                    credits_posted: account.credits_posted,
+                    timestamp,
+                    reserved: Default::default(),
+                };
+                self.balances
+                    .entry(account.id)
+                    .or_default()
+                    .push((balance, *transfer));
+                self.record(Undo::BalanceRecorded(account.id));
+            }
+        }
+    }
+
+    /// The timestamp of the most recently created account or transfer.
+    fn timestamp_max(&self) -> u64 {
+        let account_max = self.accounts.keys().next_back().copied().unwrap_or(0);
+        let transfer_max = self.transfers.keys().next_back().copied().unwrap_or(0);
+        account_max.max(transfer_max)
+    }
+
+    fn create_account(&mut self, timestamp: u64, a: &Account) -> CreateAccountResult {
+        if a.reserved != Reserved::default() {
+            return CreateAccountResult::ReservedField;
+        }
+        if !(a.flags - AccountFlags::all()).is_empty() {
+            return CreateAccountResult::ReservedFlag;
+        }
+
+        if a.id == 0 {
+            return CreateAccountResult::IdMustNotBeZero;
+        }
+        if a.id == u128::MAX {
+            return CreateAccountResult::IdMustNotBeIntMax;
+        }
+
+        if let Some(e) = self.get_account(a.id) {
+            return create_account_exists(a, &e);
+        }
+
+        if a.flags.contains(
+            AccountFlags::DebitsMustNotExceedCredits | AccountFlags::CreditsMustNotExceedDebits,
+        ) {
+            return CreateAccountResult::FlagsAreMutuallyExclusive;
+        }
+
+        if a.debits_pending != 0 {
+            return CreateAccountResult::DebitsPendingMustBeZero;
+        }
+        if a.debits_posted != 0 {
+            return CreateAccountResult::DebitsPostedMustBeZero;
+        }
+        if a.credits_pending != 0 {
+            return CreateAccountResult::CreditsPendingMustBeZero;
+        }
+        if a.credits_posted != 0 {
+            return CreateAccountResult::CreditsPostedMustBeZero;
+        }
+        if a.ledger == 0 {
+            return CreateAccountResult::LedgerMustNotBeZero;
+        }
+        if a.code == 0 {
+            return CreateAccountResult::CodeMustNotBeZero;
+        }
+
+        if a.flags.contains(AccountFlags::Imported) && timestamp <= self.timestamp_max() {
+            return CreateAccountResult::ImportedEventTimestampMustNotRegress;
+        }
+
+        self.insert_account(Account { timestamp, ..*a });
+        CreateAccountResult::Ok
+    }
+
+    fn create_transfer(&mut self, timestamp: u64, t: &Transfer) -> CreateTransferResult {
+        if !(t.flags - TransferFlags::all()).is_empty() {
+            return CreateTransferResult::ReservedFlag;
+        }
+
+        if t.id == 0 {
+            return CreateTransferResult::IdMustNotBeZero;
+        }
+        if t.id == u128::MAX {
+            return CreateTransferResult::IdMustNotBeIntMax;
+        }
+
+        if let Some(e) = self.get_transfer(t.id) {
+            return self.create_transfer_exists(t, &e);
+        }
+        if self.transfers_failed.contains(&t.id) {
+            return CreateTransferResult::IdAlreadyFailed;
+        }
+
+        if t.flags
+            .intersects(TransferFlags::PostPendingTransfer | TransferFlags::VoidPendingTransfer)
+        {
+            return self.post_or_void_pending_transfer(timestamp, t);
+        }
+
+        if t.debit_account_id == 0 {
+            return CreateTransferResult::DebitAccountIdMustNotBeZero;
+        }
+        if t.debit_account_id == u128::MAX {
+            return CreateTransferResult::DebitAccountIdMustNotBeIntMax;
+        }
+        if t.credit_account_id == 0 {
+            return CreateTransferResult::CreditAccountIdMustNotBeZero;
+        }
+        if t.credit_account_id == u128::MAX {
+            return CreateTransferResult::CreditAccountIdMustNotBeIntMax;
+        }
+        if t.credit_account_id == t.debit_account_id {
+            return CreateTransferResult::AccountsMustBeDifferent;
+        }
+
+        if t.pending_id != 0 {
+            return CreateTransferResult::PendingIdMustBeZero;
+        }
+        if !t.flags.contains(TransferFlags::Pending) {
+            if t.timeout != 0 {
+                return CreateTransferResult::TimeoutReservedForPendingTransfer;
+            }
+            if t.flags
+                .intersects(TransferFlags::ClosingDebit | TransferFlags::ClosingCredit)
+            {
+                return CreateTransferResult::ClosingTransferMustBePending;
+            }
+        }
+
+        if t.ledger == 0 {
+            return CreateTransferResult::LedgerMustNotBeZero;
+        }
+        if t.code == 0 {
+            return CreateTransferResult::CodeMustNotBeZero;
+        }
+
+        let dr_account = match self.get_account(t.debit_account_id) {
+            Some(account) => account,
+            None => return CreateTransferResult::DebitAccountNotFound,
+        };
+        let cr_account = match self.get_account(t.credit_account_id) {
+            Some(account) => account,
+            None => return CreateTransferResult::CreditAccountNotFound,
+        };
+
+        if dr_account.ledger != cr_account.ledger {
+            return CreateTransferResult::AccountsMustHaveTheSameLedger;
+        }
+        if t.ledger != dr_account.ledger {
+            return CreateTransferResult::TransferMustHaveTheSameLedgerAsAccounts;
+        }
+
+        if t.flags.contains(TransferFlags::Imported) {
+            if timestamp <= self.timestamp_max() {
+                return CreateTransferResult::ImportedEventTimestampMustNotRegress;
+            }
+            if timestamp <= dr_account.timestamp {
+                return CreateTransferResult::ImportedEventTimestampMustPostdateDebitAccount;
+            }
+            if timestamp <= cr_account.timestamp {
+                return CreateTransferResult::ImportedEventTimestampMustPostdateCreditAccount;
+            }
+            if t.timeout != 0 {
+                return CreateTransferResult::ImportedEventTimeoutMustBeZero;
+            }
+        }
+
+        if dr_account.flags.contains(AccountFlags::Closed) {
+            return CreateTransferResult::DebitAccountAlreadyClosed;
+        }
+        if cr_account.flags.contains(AccountFlags::Closed) {
+            return CreateTransferResult::CreditAccountAlreadyClosed;
+        }
+
+        let mut amount = t.amount;
+        if t.flags.contains(TransferFlags::BalancingDebit) {
+            let dr_balance = dr_account.debits_posted + dr_account.debits_pending;
+            amount = amount.min(dr_account.credits_posted.saturating_sub(dr_balance));
+        }
+        if t.flags.contains(TransferFlags::BalancingCredit) {
+            let cr_balance = cr_account.credits_posted + cr_account.credits_pending;
+            amount = amount.min(cr_account.debits_posted.saturating_sub(cr_balance));
+        }
+
+        let pending = t.flags.contains(TransferFlags::Pending);
+        if pending {
+            if amount.checked_add(dr_account.debits_pending).is_none() {
+                return CreateTransferResult::OverflowsDebitsPending;
+            }
+            if amount.checked_add(cr_account.credits_pending).is_none() {
+                return CreateTransferResult::OverflowsCreditsPending;
+            }
+        }
+        if amount.checked_add(dr_account.debits_posted).is_none() {
+            return CreateTransferResult::OverflowsDebitsPosted;
+        }
+        if amount.checked_add(cr_account.credits_posted).is_none() {
+            return CreateTransferResult::OverflowsCreditsPosted;
+        }
+        if amount
+            .checked_add(dr_account.debits_pending + dr_account.debits_posted)
+            .is_none()
+        {
+            return CreateTransferResult::OverflowsDebits;
+        }
+        if amount
+            .checked_add(cr_account.credits_pending + cr_account.credits_posted)
+            .is_none()
+        {
+            return CreateTransferResult::OverflowsCredits;
+        }
+        if timestamp
+            .checked_add(timeout_ns(t))
+            .map_or(true, |expires_at| !timestamp_valid(expires_at))
+        {
+            return CreateTransferResult::OverflowsTimeout;
+        }
+
+        if dr_account
+            .flags
+            .contains(AccountFlags::DebitsMustNotExceedCredits)
+            && dr_account.debits_pending + dr_account.debits_posted + amount
+                > dr_account.credits_posted
+        {
+            return CreateTransferResult::ExceedsCredits;
+        }
+        if cr_account
+            .flags
+            .contains(AccountFlags::CreditsMustNotExceedDebits)
+            && cr_account.credits_pending + cr_account.credits_posted + amount
+                > cr_account.debits_posted
+        {
+            return CreateTransferResult::ExceedsDebits;
+        }
+
+        let transfer = Transfer {
+            amount,
+            timestamp,
+            ..*t
+        };
+        self.insert_transfer(transfer);
+
+        let mut dr_account_new = dr_account;
+        let mut cr_account_new = cr_account;
+        if pending {
+            dr_account_new.debits_pending += amount;
+            cr_account_new.credits_pending += amount;
+            self.set_pending_status(timestamp, PendingStatus::Pending);
+        } else {
+            dr_account_new.debits_posted += amount;
+            cr_account_new.credits_posted += amount;
+        }
+        if t.flags.contains(TransferFlags::ClosingDebit) {
+            dr_account_new.flags |= AccountFlags::Closed;
+        }
+        if t.flags.contains(TransferFlags::ClosingCredit) {
+            cr_account_new.flags |= AccountFlags::Closed;
+        }
+        self.update_account(dr_account_new);
+        self.update_account(cr_account_new);
+        self.record_balances(timestamp, &dr_account_new, &cr_account_new, &transfer);
+
+        CreateTransferResult::Ok
+    }
+
+    fn create_transfer_exists(&self, t: &Transfer, e: &Transfer) -> CreateTransferResult {
+        // The flags change the behavior of the remaining comparisons,
+        // so compare the flags first.
+        if t.flags != e.flags {
+            return CreateTransferResult::ExistsWithDifferentFlags;
+        }
+        if t.pending_id != e.pending_id {
+            return CreateTransferResult::ExistsWithDifferentPendingId;
+        }
+        if t.timeout != e.timeout {
+            return CreateTransferResult::ExistsWithDifferentTimeout;
+        }
+
+        if t.flags
+            .intersects(TransferFlags::PostPendingTransfer | TransferFlags::VoidPendingTransfer)
+        {
+            let p = self.get_transfer(t.pending_id).expect("pending transfer");
+            return post_or_void_pending_transfer_exists(t, e, &p);
+        }
+
+        if t.debit_account_id != e.debit_account_id {
+            return CreateTransferResult::ExistsWithDifferentDebitAccountId;
+        }
+        if t.credit_account_id != e.credit_account_id {
+            return CreateTransferResult::ExistsWithDifferentCreditAccountId;
+        }
+        // The amount of a balancing transfer is an upper limit,
+        // so resubmitting it may request more than was moved.
+        if t.flags
+            .intersects(TransferFlags::BalancingDebit | TransferFlags::BalancingCredit)
+        {
+            if t.amount < e.amount {
+                return CreateTransferResult::ExistsWithDifferentAmount;
+            }
+        } else if t.amount != e.amount {
+            return CreateTransferResult::ExistsWithDifferentAmount;
+        }
+        if t.user_data_128 != e.user_data_128 {
+            return CreateTransferResult::ExistsWithDifferentUserData128;
+        }
+        if t.user_data_64 != e.user_data_64 {
+            return CreateTransferResult::ExistsWithDifferentUserData64;
+        }
+        if t.user_data_32 != e.user_data_32 {
+            return CreateTransferResult::ExistsWithDifferentUserData32;
+        }
+        if t.ledger != e.ledger {
+            return CreateTransferResult::ExistsWithDifferentLedger;
+        }
+        if t.code != e.code {
+            return CreateTransferResult::ExistsWithDifferentCode;
+        }
+        CreateTransferResult::Exists
+    }
+
+    fn post_or_void_pending_transfer(
+        &mut self,
+        timestamp: u64,
+        t: &Transfer,
+    ) -> CreateTransferResult {
+        let post = t.flags.contains(TransferFlags::PostPendingTransfer);
+        let void = t.flags.contains(TransferFlags::VoidPendingTransfer);
+
+        if (post && void)
+            || t.flags.intersects(
+                TransferFlags::Pending
+                    | TransferFlags::BalancingDebit
+                    | TransferFlags::BalancingCredit
+                    | TransferFlags::ClosingDebit
+                    | TransferFlags::ClosingCredit,
+            )
+        {
+            return CreateTransferResult::FlagsAreMutuallyExclusive;
+        }
+
+        if t.pending_id == 0 {
+            return CreateTransferResult::PendingIdMustNotBeZero;
+        }
+        if t.pending_id == u128::MAX {
+            return CreateTransferResult::PendingIdMustNotBeIntMax;
+        }
+        if t.pending_id == t.id {
+            return CreateTransferResult::PendingIdMustBeDifferent;
+        }
+        if t.timeout != 0 {
+            return CreateTransferResult::TimeoutReservedForPendingTransfer;
+        }
+
+        let p = match self.get_transfer(t.pending_id) {
+            Some(p) => p,
+            None => return CreateTransferResult::PendingTransferNotFound,
+        };
+        if !p.flags.contains(TransferFlags::Pending) {
+            return CreateTransferResult::PendingTransferNotPending;
+        }
+
+        let dr_account = self.get_account(p.debit_account_id).expect("debit account");
+        let cr_account = self
+            .get_account(p.credit_account_id)
+            .expect("credit account");
+
+        if t.debit_account_id > 0 && t.debit_account_id != p.debit_account_id {
+            return CreateTransferResult::PendingTransferHasDifferentDebitAccountId;
+        }
+        if t.credit_account_id > 0 && t.credit_account_id != p.credit_account_id {
+            return CreateTransferResult::PendingTransferHasDifferentCreditAccountId;
+        }
+        // The user_data fields are allowed to differ from the pending transfer.
+        if t.ledger > 0 && t.ledger != p.ledger {
+            return CreateTransferResult::PendingTransferHasDifferentLedger;
+        }
+        if t.code > 0 && t.code != p.code {
+            return CreateTransferResult::PendingTransferHasDifferentCode;
+        }
+
+        let amount = if void {
+            if t.amount == 0 {
+                p.amount
+            } else {
+                t.amount
+            }
+        } else if t.amount == AMOUNT_MAX {
+            p.amount
+        } else {
+            t.amount
+        };
+        if amount > p.amount {
+            return CreateTransferResult::ExceedsPendingTransferAmount;
+        }
+        if void && amount < p.amount {
+            return CreateTransferResult::PendingTransferHasDifferentAmount;
+        }
+
+        match self.transfers_pending[&p.timestamp] {
+            PendingStatus::Pending => {}
+            PendingStatus::Posted => return CreateTransferResult::PendingTransferAlreadyPosted,
+            PendingStatus::Voided => return CreateTransferResult::PendingTransferAlreadyVoided,
+            PendingStatus::Expired => return CreateTransferResult::PendingTransferExpired,
+        }
+        if p.timeout > 0 && p.timestamp + timeout_ns(&p) <= timestamp {
+            return CreateTransferResult::PendingTransferExpired;
+        }
+
+        if t.flags.contains(TransferFlags::Imported) && timestamp <= self.timestamp_max() {
+            return CreateTransferResult::ImportedEventTimestampMustNotRegress;
+        }
+
+        // The only movement allowed in a closed account is voiding a pending transfer.
+        if dr_account.flags.contains(AccountFlags::Closed) && !void {
+            return CreateTransferResult::DebitAccountAlreadyClosed;
+        }
+        if cr_account.flags.contains(AccountFlags::Closed) && !void {
+            return CreateTransferResult::CreditAccountAlreadyClosed;
+        }
+
+        let transfer = Transfer {
+            id: t.id,
+            debit_account_id: p.debit_account_id,
+            credit_account_id: p.credit_account_id,
+            amount,
+            pending_id: t.pending_id,
+            user_data_128: if t.user_data_128 > 0 {
+                t.user_data_128
+            } else {
+                p.user_data_128
+            },
+            user_data_64: if t.user_data_64 > 0 {
+                t.user_data_64
+            } else {
+                p.user_data_64
+            },
+            user_data_32: if t.user_data_32 > 0 {
+                t.user_data_32
+            } else {
+                p.user_data_32
+            },
+            timeout: 0,
+            ledger: p.ledger,
+            code: p.code,
+            flags: t.flags,
+            timestamp,
+        };
+        self.insert_transfer(transfer);
+
+        let status = if post {
+            PendingStatus::Posted
+        } else {
+            PendingStatus::Voided
+        };
+        self.set_pending_status(p.timestamp, status);
+
+        let mut dr_account_new = dr_account;
+        let mut cr_account_new = cr_account;
+        dr_account_new.debits_pending -= p.amount;
+        cr_account_new.credits_pending -= p.amount;
+        if post {
+            dr_account_new.debits_posted += amount;
+            cr_account_new.credits_posted += amount;
+        }
+        if void {
+            // Voiding a closing transfer reopens the account.
+            if p.flags.contains(TransferFlags::ClosingDebit) {
+                dr_account_new.flags -= AccountFlags::Closed;
+            }
+            if p.flags.contains(TransferFlags::ClosingCredit) {
+                cr_account_new.flags -= AccountFlags::Closed;
+            }
+        }
+        self.update_account(dr_account_new);
+        self.update_account(cr_account_new);
+        self.record_balances(timestamp, &dr_account_new, &cr_account_new, &transfer);
+
+        CreateTransferResult::Ok
+    }
+
+    /// Expire pending transfers whose timeout has elapsed.
+    fn expire_pending_transfers(&mut self) {
+        if self.snapshot {
+            return;
+        }
+        let now = self.prepare(0);
+
+        let mut expired: Vec<Transfer> = self
+            .transfers_pending
+            .iter()
+            .filter(|(_, status)| **status == PendingStatus::Pending)
+            .map(|(timestamp, _)| self.transfers[timestamp])
+            .filter(|p| p.timeout > 0 && p.timestamp + timeout_ns(p) <= now)
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        expired.sort_by_key(|p| (p.timestamp + timeout_ns(p), p.timestamp));
+
+        let timestamp = self.prepare(expired.len());
+        for (index, p) in expired.iter().enumerate() {
+            let timestamp_event = timestamp - expired.len() as u64 + index as u64 + 1;
+
+            let mut dr_account = self.get_account(p.debit_account_id).expect("debit account");
+            let mut cr_account = self
+                .get_account(p.credit_account_id)
+                .expect("credit account");
+            dr_account.debits_pending -= p.amount;
+            cr_account.credits_pending -= p.amount;
+            if p.flags.contains(TransferFlags::ClosingDebit) {
+                dr_account.flags -= AccountFlags::Closed;
+            }
+            if p.flags.contains(TransferFlags::ClosingCredit) {
+                cr_account.flags -= AccountFlags::Closed;
+            }
+            self.update_account(dr_account);
+            self.update_account(cr_account);
+            self.record_balances(timestamp_event, &dr_account, &cr_account, p);
+
+            self.set_pending_status(p.timestamp, PendingStatus::Expired);
+        }
+    }
+}
+
+/// The timestamp of an event in a batch committed at `timestamp`.
+fn event_timestamp<Result: CreateResult>(
+    event: &impl CreateEvent,
+    batch_imported: bool,
+    timestamp: u64,
+    index: usize,
+    count: usize,
+) -> std::result::Result<u64, Result> {
+    if batch_imported != event.is_imported() {
+        return Err(if event.is_imported() {
+            Result::IMPORTED_EVENT_NOT_EXPECTED
+        } else {
+            Result::IMPORTED_EVENT_EXPECTED
+        });
+    }
+
+    if event.is_imported() {
+        if !timestamp_valid(event.timestamp()) {
+            return Err(Result::IMPORTED_EVENT_TIMESTAMP_OUT_OF_RANGE);
+        }
+        if event.timestamp() >= timestamp {
+            return Err(Result::IMPORTED_EVENT_TIMESTAMP_MUST_NOT_ADVANCE);
+        }
+        Ok(event.timestamp())
+    } else {
+        if event.timestamp() != 0 {
+            return Err(Result::TIMESTAMP_MUST_BE_ZERO);
+        }
+        Ok(timestamp - count as u64 + index as u64 + 1)
+    }
+}
+
+fn create_account_exists(a: &Account, e: &Account) -> CreateAccountResult {
+    if a.flags != e.flags {
+        return CreateAccountResult::ExistsWithDifferentFlags;
+    }
+    if a.user_data_128 != e.user_data_128 {
+        return CreateAccountResult::ExistsWithDifferentUserData128;
+    }
+    if a.user_data_64 != e.user_data_64 {
+        return CreateAccountResult::ExistsWithDifferentUserData64;
+    }
+    if a.user_data_32 != e.user_data_32 {
+        return CreateAccountResult::ExistsWithDifferentUserData32;
+    }
+    if a.ledger != e.ledger {
+        return CreateAccountResult::ExistsWithDifferentLedger;
+    }
+    if a.code != e.code {
+        return CreateAccountResult::ExistsWithDifferentCode;
+    }
+    CreateAccountResult::Exists
+}
+
+fn post_or_void_pending_transfer_exists(
+    t: &Transfer,
+    e: &Transfer,
+    p: &Transfer,
+) -> CreateTransferResult {
+    if t.debit_account_id != 0 && t.debit_account_id != e.debit_account_id {
+        return CreateTransferResult::ExistsWithDifferentDebitAccountId;
+    }
+    if t.credit_account_id != 0 && t.credit_account_id != e.credit_account_id {
+        return CreateTransferResult::ExistsWithDifferentCreditAccountId;
+    }
+
+    let amount_default = if t.flags.contains(TransferFlags::VoidPendingTransfer) {
+        0
+    } else {
+        AMOUNT_MAX
+    };
+    let amount_expected = if t.amount == amount_default {
+        p.amount
+    } else {
+        t.amount
+    };
+    if e.amount != amount_expected {
+        return CreateTransferResult::ExistsWithDifferentAmount;
+    }
+
+    // Zero user data fields are inherited from the pending transfer.
+    let inherit = |t: u128, p: u128| if t == 0 { p } else { t };
+    if e.user_data_128 != inherit(t.user_data_128, p.user_data_128) {
+        return CreateTransferResult::ExistsWithDifferentUserData128;
+    }
+    if u128::from(e.user_data_64) != inherit(t.user_data_64.into(), p.user_data_64.into()) {
+        return CreateTransferResult::ExistsWithDifferentUserData64;
+    }
+    if u128::from(e.user_data_32) != inherit(t.user_data_32.into(), p.user_data_32.into()) {
+        return CreateTransferResult::ExistsWithDifferentUserData32;
+    }
+
+    if t.ledger != 0 && t.ledger != e.ledger {
+        return CreateTransferResult::ExistsWithDifferentLedger;
+    }
+    if t.code != 0 && t.code != e.code {
+        return CreateTransferResult::ExistsWithDifferentCode;
+    }
+    CreateTransferResult::Exists
+}
+
+/// The result codes shared by `create_accounts` and `create_transfers`.
+trait CreateResult {
+    const OK: Self;
+    const LINKED_EVENT_FAILED: Self;
+    const LINKED_EVENT_CHAIN_OPEN: Self;
+    const IMPORTED_EVENT_EXPECTED: Self;
+    const IMPORTED_EVENT_NOT_EXPECTED: Self;
+    const TIMESTAMP_MUST_BE_ZERO: Self;
+    const IMPORTED_EVENT_TIMESTAMP_OUT_OF_RANGE: Self;
+    const IMPORTED_EVENT_TIMESTAMP_MUST_NOT_ADVANCE: Self;
+
+    /// Whether the event's id can't be reused after this result.
+    fn transient(self) -> bool;
+}
+
+macro_rules! impl_create_result {
+    ($result:ident, $transient:expr) => {
+        impl CreateResult for $result {
+            const OK: Self = $result::Ok;
+            const LINKED_EVENT_FAILED: Self = $result::LinkedEventFailed;
+            const LINKED_EVENT_CHAIN_OPEN: Self = $result::LinkedEventChainOpen;
+            const IMPORTED_EVENT_EXPECTED: Self = $result::ImportedEventExpected;
+            const IMPORTED_EVENT_NOT_EXPECTED: Self = $result::ImportedEventNotExpected;
+            const TIMESTAMP_MUST_BE_ZERO: Self = $result::TimestampMustBeZero;
+            const IMPORTED_EVENT_TIMESTAMP_OUT_OF_RANGE: Self =
+                $result::ImportedEventTimestampOutOfRange;
+            const IMPORTED_EVENT_TIMESTAMP_MUST_NOT_ADVANCE: Self =
+                $result::ImportedEventTimestampMustNotAdvance;
+
+            fn transient(self) -> bool {
+                let transient: fn($result) -> bool = $transient;
+                transient(self)
+            }
+        }
+    };
+}
+
+// Account results don't depend on the state of other objects,
+// so are never transient.
+impl_create_result!(CreateAccountResult, |_| false);
+impl_create_result!(CreateTransferResult, |result| {
+    matches!(
+        result,
+        CreateTransferResult::DebitAccountNotFound
+            | CreateTransferResult::CreditAccountNotFound
+            | CreateTransferResult::PendingTransferNotFound
+            | CreateTransferResult::ExceedsCredits
+            | CreateTransferResult::ExceedsDebits
+            | CreateTransferResult::DebitAccountAlreadyClosed
+            | CreateTransferResult::CreditAccountAlreadyClosed
+    )
+});
+
+/// The fields of `create_*` events needed to execute a batch.
+trait CreateEvent: Linkable {
+    fn id(&self) -> u128;
+    fn timestamp(&self) -> u64;
+    fn is_imported(&self) -> bool;
+}
+
+impl CreateEvent for Account {
+    fn id(&self) -> u128 {
+        self.id
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    fn is_imported(&self) -> bool {
+        self.flags.contains(AccountFlags::Imported)
+    }
+}
+
+impl CreateEvent for Transfer {
+    fn id(&self) -> u128 {
+        self.id
+    }
+
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    fn is_imported(&self) -> bool {
+        self.flags.contains(TransferFlags::Imported)
+    }
+}
+
+fn timeout_ns(transfer: &Transfer) -> u64 {
+    u64::from(transfer.timeout) * 1_000_000_000
+}
+
+const TIMESTAMP_MAX: u64 = (1 << 63) - 1;
+
+fn timestamp_valid(timestamp: u64) -> bool {
+    (1..=TIMESTAMP_MAX).contains(&timestamp)
+}
+
+fn timestamp_range(min: u64, max: u64) -> std::ops::RangeInclusive<u64> {
+    let min = if min == 0 { 1 } else { min };
+    let max = if max == 0 { TIMESTAMP_MAX } else { max };
+    min..=max
+}
+
+fn timestamps_valid(min: u64, max: u64) -> bool {
+    (min == 0 || timestamp_valid(min))
+        && (max == 0 || timestamp_valid(max))
+        && (max == 0 || min <= max)
+}
+
+fn account_filter_valid(filter: &AccountFilter) -> bool {
+    filter.account_id != 0
+        && filter.account_id != u128::MAX
+        && timestamps_valid(filter.timestamp_min, filter.timestamp_max)
+        && filter.limit != 0
+        && filter
+            .flags
+            .intersects(AccountFilterFlags::Debits | AccountFilterFlags::Credits)
+        && (filter.flags - AccountFilterFlags::all()).is_empty()
+        && filter.reserved == Reserved::default()
+}
+
+fn account_filter_matches(filter: &AccountFilter, transfer: &Transfer) -> bool {
+    let debits = filter.flags.contains(AccountFilterFlags::Debits)
+        && transfer.debit_account_id == filter.account_id;
+    let credits = filter.flags.contains(AccountFilterFlags::Credits)
+        && transfer.credit_account_id == filter.account_id;
+
+    (debits || credits)
+        && (filter.user_data_128 == 0 || filter.user_data_128 == transfer.user_data_128)
+        && (filter.user_data_64 == 0 || filter.user_data_64 == transfer.user_data_64)
+        && (filter.user_data_32 == 0 || filter.user_data_32 == transfer.user_data_32)
+        && (filter.code == 0 || filter.code == transfer.code)
+}
+
+fn query_filter_valid(filter: &QueryFilter) -> bool {
+    timestamps_valid(filter.timestamp_min, filter.timestamp_max)
+        && filter.limit != 0
+        && (filter.flags - QueryFilterFlags::all()).is_empty()
+        && filter.reserved == Reserved::default()
+}
+
+fn query_filter_matches(
+    filter: &QueryFilter,
+    (user_data_128, user_data_64, user_data_32): (u128, u64, u32),
+    ledger: u32,
+    code: u16,
+) -> bool {
+    (filter.user_data_128 == 0 || filter.user_data_128 == user_data_128)
+        && (filter.user_data_64 == 0 || filter.user_data_64 == user_data_64)
+        && (filter.user_data_32 == 0 || filter.user_data_32 == user_data_32)
+        && (filter.ledger == 0 || filter.ledger == ledger)
+        && (filter.code == 0 || filter.code == code)
+}
+
+/// Take up to `limit` items, from the end if `reversed`.
+///
+/// The limit is capped to the number of results that fit in a reply.
+fn take_limit<'a, T: 'a>(
+    items: impl DoubleEndedIterator<Item = &'a T>,
+    limit: u32,
+    reversed: bool,
+) -> Vec<&'a T> {
+    let limit = usize::try_from(limit).unwrap_or(usize::MAX).min(BATCH_MAX);
+    if reversed {
+        items.rev().take(limit).collect()
+    } else {
+        items.take(limit).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    fn account(id: u128, flags: AccountFlags) -> Account {
+        Account {
+            id,
+            ledger: 1,
+            code: 1,
+            flags,
+            ..Default::default()
+        }
+    }
+
+    fn transfer(
+        id: u128,
+        debit_account_id: u128,
+        credit_account_id: u128,
+        amount: u128,
+    ) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id,
+            credit_account_id,
+            amount,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        }
+    }
+
+    fn create_accounts(
+        client: &InMemoryClient,
+        accounts: &[Account],
+    ) -> Vec<(usize, CreateAccountResult)> {
+        block_on(client.create_accounts(accounts))
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.index, r.result))
+            .collect()
+    }
+
+    fn create_transfers(
+        client: &InMemoryClient,
+        transfers: &[Transfer],
+    ) -> Vec<(usize, CreateTransferResult)> {
+        block_on(client.create_transfers(transfers))
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.index, r.result))
+            .collect()
+    }
+
+    fn balances(client: &InMemoryClient, id: u128) -> (u128, u128, u128, u128) {
+        let account = block_on(client.lookup_accounts(&[id])).unwrap()[0];
+        (
+            account.debits_pending,
+            account.debits_posted,
+            account.credits_pending,
+            account.credits_posted,
+        )
+    }
+
+    #[test]
+    fn accounts_are_idempotent() {
+        let client = InMemoryClient::new();
+        assert_eq!(
+            create_accounts(&client, &[account(1, AccountFlags::None)]),
+            []
+        );
+        assert_eq!(
+            create_accounts(
+                &client,
+                &[
+                    account(1, AccountFlags::None),
+                    account(1, AccountFlags::History),
+                    account(0, AccountFlags::None),
+                ]
+            ),
+            [
+                (0, CreateAccountResult::Exists),
+                (1, CreateAccountResult::ExistsWithDifferentFlags),
+                (2, CreateAccountResult::IdMustNotBeZero),
+            ]
+        );
+    }
+
+    #[test]
+    fn transfers_enforce_balance_limits() {
+        let client = InMemoryClient::new();
+        create_accounts(
+            &client,
+            &[
+                account(1, AccountFlags::DebitsMustNotExceedCredits),
+                account(2, AccountFlags::None),
+            ],
+        );
+
+        assert_eq!(
+            create_transfers(&client, &[transfer(1, 1, 2, 10)]),
+            [(0, CreateTransferResult::ExceedsCredits)]
+        );
+        // Transient failures can't be retried with the same id.
+        assert_eq!(create_transfers(&client, &[transfer(2, 2, 1, 10)]), []);
+        assert_eq!(
+            create_transfers(&client, &[transfer(1, 1, 2, 10)]),
+            [(0, CreateTransferResult::IdAlreadyFailed)]
+        );
+        assert_eq!(create_transfers(&client, &[transfer(3, 1, 2, 10)]), []);
+        assert_eq!(balances(&client, 1), (0, 10, 0, 10));
+    }
+
+    #[test]
+    fn linked_chains_roll_back() {
+        let client = InMemoryClient::new();
+        create_accounts(
+            &client,
+            &[
+                account(1, AccountFlags::History),
+                account(2, AccountFlags::None),
+            ],
+        );
+
+        let mut first = transfer(1, 1, 2, 10);
+        first.flags = TransferFlags::Linked;
+        assert_eq!(
+            create_transfers(
+                &client,
+                &[first, transfer(2, 1, 3, 10), transfer(3, 1, 2, 5)]
+            ),
+            [
+                (0, CreateTransferResult::LinkedEventFailed),
+                (1, CreateTransferResult::CreditAccountNotFound),
+            ]
+        );
+        assert_eq!(balances(&client, 1), (0, 5, 0, 0));
+        assert!(block_on(client.lookup_transfers(&[1])).unwrap().is_empty());
+
+        // Posting a pending transfer is rolled back along with its history.
+        let mut pending = transfer(4, 1, 2, 10);
+        pending.flags = TransferFlags::Pending;
+        assert_eq!(create_transfers(&client, &[pending]), []);
+        let mut post = Transfer::post_pending_transfer(5, 4, 10);
+        post.flags |= TransferFlags::Linked;
+        assert_eq!(
+            create_transfers(&client, &[post, transfer(6, 1, 3, 1)]),
+            [
+                (0, CreateTransferResult::LinkedEventFailed),
+                (1, CreateTransferResult::CreditAccountNotFound),
+            ]
+        );
+        assert_eq!(balances(&client, 1), (10, 5, 0, 0));
+        assert_eq!(
+            create_transfers(&client, &[Transfer::post_pending_transfer(7, 4, 10)]),
+            []
+        );
+        assert_eq!(balances(&client, 1), (0, 15, 0, 0));
+        let history = block_on(client.get_account_balances(AccountFilter {
+            account_id: 1,
+            limit: 10,
+            flags: AccountFilterFlags::Debits | AccountFilterFlags::Credits,
+            ..Default::default()
+        }))
+        .unwrap();
+        let debits_posted: Vec<u128> = history.iter().map(|b| b.debits_posted).collect();
+        assert_eq!(debits_posted, [5, 5, 15]);
+
+        let mut linked = account(3, AccountFlags::None);
+        linked.flags = AccountFlags::Linked;
+        assert_eq!(
+            create_accounts(&client, &[linked, account(0, AccountFlags::None)]),
+            [
+                (0, CreateAccountResult::LinkedEventFailed),
+                (1, CreateAccountResult::IdMustNotBeZero),
+            ]
+        );
+        assert!(block_on(client.lookup_accounts(&[3])).unwrap().is_empty());
+        assert_eq!(
+            create_accounts(&client, &[account(3, AccountFlags::None)]),
+            []
+        );
+    }
+
+    #[test]
+    fn pending_transfers_post_void_and_expire() {
+        let client = InMemoryClient::new();
+        create_accounts(
+            &client,
+            &[
+                account(1, AccountFlags::History),
+                account(2, AccountFlags::None),
+            ],
+        );
+
+        let mut pending = transfer(1, 1, 2, 10);
+        pending.flags = TransferFlags::Pending;
+        let mut expiring = transfer(2, 1, 2, 20);
+        expiring.flags = TransferFlags::Pending;
+        expiring.timeout = 60;
+        assert_eq!(create_transfers(&client, &[pending, expiring]), []);
+        assert_eq!(balances(&client, 1), (30, 0, 0, 0));
+
+        assert_eq!(
+            create_transfers(&client, &[Transfer::post_pending_transfer(3, 1, 4)]),
+            []
+        );
+        assert_eq!(balances(&client, 1), (20, 4, 0, 0));
+        assert_eq!(
+            create_transfers(&client, &[Transfer::void_pending_transfer(4, 1)]),
+            [(0, CreateTransferResult::PendingTransferAlreadyPosted)]
+        );
+
+        client.advance_time(Duration::from_secs(61));
+        assert_eq!(balances(&client, 1), (0, 4, 0, 0));
+        assert_eq!(
+            create_transfers(&client, &[Transfer::void_pending_transfer(5, 2)]),
+            [(0, CreateTransferResult::PendingTransferExpired)]
+        );
+
+        let history = block_on(client.get_account_balances(AccountFilter {
+            account_id: 1,
+            limit: 10,
+            flags: AccountFilterFlags::Debits | AccountFilterFlags::Credits,
+            ..Default::default()
+        }))
+        .unwrap();
+        let debits_pending: Vec<u128> = history.iter().map(|b| b.debits_pending).collect();
+        assert_eq!(debits_pending, [10, 30, 20, 0]);
+    }
+
+    #[test]
+    fn closing_transfers() {
+        let client = InMemoryClient::new();
+        create_accounts(
+            &client,
+            &[
+                account(1, AccountFlags::None),
+                account(2, AccountFlags::None),
+            ],
+        );
+
+        let mut closing = transfer(1, 1, 2, 0);
+        closing.flags = TransferFlags::Pending | TransferFlags::ClosingDebit;
+        assert_eq!(create_transfers(&client, &[closing]), []);
+        assert_eq!(
+            create_transfers(&client, &[transfer(2, 1, 2, 1)]),
+            [(0, CreateTransferResult::DebitAccountAlreadyClosed)]
+        );
+
+        // Voiding the closing transfer reopens the account.
+        assert_eq!(
+            create_transfers(&client, &[Transfer::void_pending_transfer(3, 1)]),
+            []
+        );
+        assert_eq!(create_transfers(&client, &[transfer(4, 1, 2, 1)]), []);
+    }
+
+    #[test]
+    fn queries_filter_and_limit() {
+        let client = InMemoryClient::new();
+        let mut accounts: Vec<Account> =
+            (1..=5).map(|id| account(id, AccountFlags::None)).collect();
+        accounts[1].user_data_32 = 7;
+        accounts[3].user_data_32 = 7;
+        create_accounts(&client, &accounts);
+
+        let found = block_on(client.query_accounts(QueryFilter {
+            user_data_32: 7,
+            limit: 10,
+            flags: QueryFilterFlags::Reversed,
+            ..Default::default()
+        }))
+        .unwrap();
+        let ids: Vec<u128> = found.iter().map(|a| a.id).collect();
+        assert_eq!(ids, [4, 2]);
+
+        let transfers: Vec<Transfer> = (1..=4).map(|id| transfer(id, 1, id + 1, 1)).collect();
+        create_transfers(&client, &transfers);
+
+        let found = block_on(client.get_account_transfers(AccountFilter {
+            account_id: 1,
+            limit: 2,
+            flags: AccountFilterFlags::Debits,
+            ..Default::default()
+        }))
+        .unwrap();
+        let ids: Vec<u128> = found.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2]);
+
+        // Invalid filters return no results.
+        let found = block_on(client.query_transfers(QueryFilter::default())).unwrap();
+        assert!(found.is_empty());
+    }
+}
@@ -0,0 +1,230 @@
+//! Import and export of accounts and transfers as JSON Lines.
+//!
+//! Each line is one account or transfer, serialized as with the `serde`
+//! feature, so `u128` fields are decimal strings. Files are read and written
+//! a line at a time, so they can be larger than memory.
+//!
+//! Requires the `jsonl` feature.
+//!
+//! # Example
+//!
+//! Seed a ledger from a file, in batches of at most [`BATCH_MAX`] transfers:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufReader;
+//! use tigerbeetle as tb;
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), Box<dyn std::error::Error>> {
+//! let file = BufReader::new(File::open("transfers.jsonl")?);
+//! let mut batch = Vec::with_capacity(tb::BATCH_MAX);
+//! for transfer in tb::io::import_transfers(file) {
+//!     batch.push(transfer?);
+//!     if batch.len() == tb::BATCH_MAX {
+//!         client.create_transfers(&batch).await?;
+//!         batch.clear();
+//!     }
+//! }
+//! if !batch.is_empty() {
+//!     client.create_transfers(&batch).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`BATCH_MAX`]: crate::BATCH_MAX
+
+use crate::{Account, Transfer};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use std::io::{BufRead, Write};
+use std::marker::PhantomData;
+
+/// Write accounts to `writer`, one per line.
+///
+/// Returns the number of accounts written. Wrap unbuffered writers, such as
+/// files, in a [`BufWriter`](std::io::BufWriter).
+pub fn export_accounts<W: Write>(
+    writer: W,
+    accounts: impl IntoIterator<Item = Account>,
+) -> Result<usize, JsonLinesError> {
+    export(writer, accounts)
+}
+
+/// Write transfers to `writer`, one per line.
+///
+/// Returns the number of transfers written. Wrap unbuffered writers, such as
+/// files, in a [`BufWriter`](std::io::BufWriter).
+pub fn export_transfers<W: Write>(
+    writer: W,
+    transfers: impl IntoIterator<Item = Transfer>,
+) -> Result<usize, JsonLinesError> {
+    export(writer, transfers)
+}
+
+/// Read accounts from `reader`, one per line.
+///
+/// Blank lines are skipped. Iteration continues after an invalid line.
+pub fn import_accounts<R: BufRead>(reader: R) -> JsonLines<R, Account> {
+    JsonLines::new(reader)
+}
+
+/// Read transfers from `reader`, one per line.
+///
+/// Blank lines are skipped. Iteration continues after an invalid line.
+pub fn import_transfers<R: BufRead>(reader: R) -> JsonLines<R, Transfer> {
+    JsonLines::new(reader)
+}
+
+fn export<W: Write, T: Serialize>(
+    mut writer: W,
+    values: impl IntoIterator<Item = T>,
+) -> Result<usize, JsonLinesError> {
+    let mut count = 0;
+    for value in values {
+        count += 1;
+        serde_json::to_writer(&mut writer, &value)
+            .map_err(|error| JsonLinesError::Json { line: count, error })?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// An iterator over the values of a JSON Lines reader.
+///
+/// Returned by [`import_accounts`] and [`import_transfers`].
+#[derive(Debug)]
+pub struct JsonLines<R, T> {
+    reader: R,
+    line: String,
+    /// The number of the last line read, from 1.
+    line_number: usize,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T: DeserializeOwned> JsonLines<R, T> {
+    fn new(reader: R) -> JsonLines<R, T> {
+        JsonLines {
+            reader,
+            line: String::new(),
+            line_number: 0,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for JsonLines<R, T> {
+    type Item = Result<T, JsonLinesError>;
+
+    fn next(&mut self) -> Option<Result<T, JsonLinesError>> {
+        loop {
+            self.line.clear();
+            match self.reader.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_number += 1,
+                Err(error) => return Some(Err(error.into())),
+            }
+            if self.line.trim().is_empty() {
+                continue;
+            }
+            return Some(
+                serde_json::from_str(&self.line).map_err(|error| JsonLinesError::Json {
+                    line: self.line_number,
+                    error,
+                }),
+            );
+        }
+    }
+}
+
+/// Errors reading or writing JSON Lines.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum JsonLinesError {
+    /// Reading or writing failed.
+    Io(std::io::Error),
+    /// A line is not a valid account or transfer, or a value could not be
+    /// serialized. Lines are numbered from 1.
+    Json {
+        line: usize,
+        error: serde_json::Error,
+    },
+}
+
+impl From<std::io::Error> for JsonLinesError {
+    fn from(error: std::io::Error) -> JsonLinesError {
+        JsonLinesError::Io(error)
+    }
+}
+
+impl std::error::Error for JsonLinesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Json { error, .. } => Some(error),
+        }
+    }
+}
+
+impl core::fmt::Display for JsonLinesError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => core::fmt::Display::fmt(error, f),
+            Self::Json { line, error } => write!(f, "line {line}: {error}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::AccountFlags;
+
+    #[test]
+    fn round_trip() {
+        let accounts = [
+            Account {
+                id: u128::MAX,
+                ledger: 1,
+                code: 10,
+                flags: AccountFlags::History,
+                ..Default::default()
+            },
+            Account {
+                id: 2,
+                ledger: 1,
+                code: 10,
+                ..Default::default()
+            },
+        ];
+
+        let mut file = Vec::new();
+        assert_eq!(export_accounts(&mut file, accounts).unwrap(), 2);
+        let file = String::from_utf8(file).unwrap();
+        assert_eq!(file.lines().count(), 2);
+        assert!(file.starts_with(&format!(r#"{{"id":"{}","#, u128::MAX)));
+
+        let imported: Vec<Account> = import_accounts(file.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(imported, accounts);
+    }
+
+    #[test]
+    fn invalid_line() {
+        let file = "{\"id\": \"1\", \"amount\": \"5\"}\n\n{\"id\": -1}\n{\"id\": \"3\"}\n";
+        let imported: Vec<_> = import_transfers(file.as_bytes()).collect();
+
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[0].as_ref().unwrap().amount, 5);
+        assert!(matches!(
+            imported[1],
+            Err(JsonLinesError::Json { line: 3, .. })
+        ));
+        assert_eq!(imported[2].as_ref().unwrap().id, 3);
+    }
+}
@@ -0,0 +1,830 @@
+//! Double-entry journal entries.
+//!
+//! A [`JournalEntry`] is a set of debit and credit legs against named
+//! accounts, which must balance within each ledger. It is converted to
+//! transfers between pairs of accounts, linked so that the cluster applies
+//! them atomically.
+//!
+//! ```
+//! use tigerbeetle as tb;
+//! use tb::ledger::{Accounts, JournalEntry};
+//!
+//! let mut accounts = Accounts::new();
+//! accounts.insert("cash", 1, 840);
+//! accounts.insert("revenue", 2, 840);
+//! accounts.insert("sales_tax", 3, 840);
+//!
+//! // A sale of 100.00 with 8.00 of tax, in cents.
+//! let entry = JournalEntry::new(tb::id(), 10)
+//!     .debit("cash", 10_800)
+//!     .credit("revenue", 10_000)
+//!     .credit("sales_tax", 800);
+//! let transfers = entry.to_transfers(&accounts)?;
+//!
+//! assert_eq!(transfers.len(), 2);
+//! assert_eq!(transfers[0].amount, 10_000);
+//! assert_eq!(transfers[1].amount, 800);
+//! assert!(transfers[0].flags.contains(tb::TransferFlags::Linked));
+//! assert!(!transfers[1].flags.contains(tb::TransferFlags::Linked));
+//! # Ok::<(), tb::ledger::JournalError>(())
+//! ```
+//!
+//! Submit the transfers with [`Client::create_transfers`]. If any of them
+//! fails, none are created.
+//!
+//! [`Client::create_transfers`]: crate::Client::create_transfers
+
+use crate::{
+    id, Account, AccountFlags, AccountSpec, Amount, AmountError, Client, CreateAccountsError,
+    EnsureAccountsSummary, Transfer, TransferFlags,
+};
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+
+/// Accounts by name.
+#[derive(Clone, Debug, Default)]
+pub struct Accounts {
+    accounts: HashMap<String, AccountRef>,
+}
+
+/// An account's id and ledger.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AccountRef {
+    pub id: u128,
+    pub ledger: u32,
+}
+
+impl Accounts {
+    pub fn new() -> Accounts {
+        Accounts::default()
+    }
+
+    /// Name the account `id`, on `ledger`.
+    ///
+    /// Replaces any account previously given the same name.
+    pub fn insert(&mut self, name: &str, id: u128, ledger: u32) {
+        self.accounts
+            .insert(name.to_owned(), AccountRef { id, ledger });
+    }
+
+    pub fn get(&self, name: &str) -> Option<AccountRef> {
+        self.accounts.get(name).copied()
+    }
+}
+
+/// A registry of accounts by symbolic name, such as `"assets:cash"`.
+///
+/// A chart of accounts defines each account's id, ledger, code and flags, so
+/// services can refer to accounts by name, and create them on startup with
+/// [`Client::create_chart_of_accounts`].
+///
+/// With the `serde` feature a chart can be loaded from, or saved to, any
+/// serde format. Ids are serialized as decimal strings, since neither TOML
+/// nor JavaScript can represent 128-bit integers, and flags as their names:
+///
+/// ```toml
+/// [accounts."assets:cash"]
+/// id = "1001"
+/// ledger = 840
+/// code = 10
+/// flags = "History"
+///
+/// [accounts."income:sales"]
+/// id = "0x3e9"
+/// ledger = 840
+/// code = 20
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChartOfAccounts {
+    pub accounts: BTreeMap<String, AccountDefinition>,
+}
+
+/// The definition of an account in a [`ChartOfAccounts`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccountDefinition {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub id: u128,
+    pub ledger: u32,
+    pub code: u16,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub flags: AccountFlags,
+}
+
+impl ChartOfAccounts {
+    pub fn new() -> ChartOfAccounts {
+        ChartOfAccounts::default()
+    }
+
+    /// Define the account `name`.
+    ///
+    /// Replaces any account previously defined with the same name.
+    pub fn insert(&mut self, name: &str, definition: AccountDefinition) {
+        self.accounts.insert(name.to_owned(), definition);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AccountDefinition> {
+        self.accounts.get(name)
+    }
+
+    /// The [`Account`] to create for `name`.
+    pub fn account(&self, name: &str) -> Option<Account> {
+        self.get(name).map(AccountDefinition::to_account)
+    }
+
+    /// The names of the accounts, for use in a [`JournalEntry`].
+    pub fn to_accounts(&self) -> Accounts {
+        let mut accounts = Accounts::new();
+        for (name, definition) in &self.accounts {
+            accounts.insert(name, definition.id, definition.ledger);
+        }
+        accounts
+    }
+}
+
+impl AccountDefinition {
+    /// The [`Account`] to create for this definition.
+    pub fn to_account(&self) -> Account {
+        Account {
+            id: self.id,
+            ledger: self.ledger,
+            code: self.code,
+            flags: self.flags,
+            ..Default::default()
+        }
+    }
+}
+
+impl Client {
+    /// Create the accounts of a chart of accounts that don't already exist.
+    ///
+    /// Accounts are created in order of their names. This is
+    /// [`Client::ensure_accounts`], so it is safe to call each time a service
+    /// starts.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CreateAccountsError::Failed`] if an account could not be
+    /// created, including if it exists with a different definition, and
+    /// [`CreateAccountsError::Packet`] if the request failed.
+    pub fn create_chart_of_accounts(
+        &self,
+        chart: &ChartOfAccounts,
+    ) -> impl Future<Output = Result<EnsureAccountsSummary, CreateAccountsError>> {
+        let specs: Vec<AccountSpec> = chart
+            .accounts
+            .values()
+            .map(|definition| AccountSpec {
+                id: definition.id,
+                ledger: definition.ledger,
+                code: definition.code,
+                flags: definition.flags,
+                ..Default::default()
+            })
+            .collect();
+        self.ensure_accounts(&specs)
+    }
+}
+
+/// The currency, scale and name of each ledger.
+///
+/// Amounts are stored as integers of a ledger's smallest unit. A registry
+/// records each ledger's scale, so that amounts can be formatted and parsed
+/// as decimals, such as `USD 12.34` for `1234` on ledger 840.
+///
+/// With the `serde` feature a registry can be loaded from any serde format:
+///
+/// ```toml
+/// [ledgers.840]
+/// currency = "USD"
+/// scale = 2
+/// name = "US Dollar"
+///
+/// [ledgers.978]
+/// currency = "EUR"
+/// scale = 2
+/// ```
+///
+/// # Example
+///
+/// ```
+/// use tigerbeetle as tb;
+/// use tb::ledger::{LedgerInfo, LedgerRegistry};
+///
+/// let mut ledgers = LedgerRegistry::new();
+/// ledgers.insert(840, LedgerInfo::new("USD", 2, "US Dollar"));
+///
+/// assert_eq!(ledgers.format(840, 1234), "USD 12.34");
+/// assert_eq!(ledgers.parse(840, "12.34")?.units(), 1234);
+/// // Amounts of ledgers that aren't registered are integers.
+/// assert_eq!(ledgers.format(1, 1234), "1234");
+/// # Ok::<(), tb::AmountError>(())
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LedgerRegistry {
+    #[cfg_attr(feature = "serde", serde(with = "ledgers"))]
+    pub ledgers: BTreeMap<u32, LedgerInfo>,
+}
+
+/// A ledger of a [`LedgerRegistry`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LedgerInfo {
+    /// The ISO 4217 code of the ledger's currency, such as `USD`, or the
+    /// symbol of another unit.
+    pub currency: String,
+    /// The number of fractional digits of the ledger's unit, at most
+    /// [`Amount::SCALE_MAX`].
+    #[cfg_attr(feature = "serde", serde(deserialize_with = "deserialize_scale"))]
+    pub scale: u8,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub name: String,
+}
+
+impl LedgerRegistry {
+    pub fn new() -> LedgerRegistry {
+        LedgerRegistry::default()
+    }
+
+    /// Register `ledger`, replacing any previous registration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ledger's scale is greater than [`Amount::SCALE_MAX`].
+    pub fn insert(&mut self, ledger: u32, info: LedgerInfo) {
+        assert!(info.scale <= Amount::SCALE_MAX);
+        self.ledgers.insert(ledger, info);
+    }
+
+    pub fn get(&self, ledger: u32) -> Option<&LedgerInfo> {
+        self.ledgers.get(&ledger)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ledgers.is_empty()
+    }
+
+    /// The scale of `ledger`, or 0 if it isn't registered.
+    pub fn scale(&self, ledger: u32) -> u8 {
+        self.get(ledger).map_or(0, |info| info.scale)
+    }
+
+    /// An amount of `units` on `ledger`, with the ledger's scale.
+    pub fn amount(&self, ledger: u32, units: u128) -> Amount {
+        Amount::new(units, self.scale(ledger))
+    }
+
+    /// Format an amount of `units` on `ledger`, such as `USD 12.34`.
+    ///
+    /// Amounts of ledgers that aren't registered are formatted as integers.
+    pub fn format(&self, ledger: u32, units: u128) -> String {
+        match self.get(ledger) {
+            Some(info) => info.format(units),
+            None => units.to_string(),
+        }
+    }
+
+    /// Parse a decimal amount on `ledger` with [`Amount::parse`], with the
+    /// ledger's scale.
+    ///
+    /// # Errors
+    ///
+    /// As [`Amount::parse`]. Amounts of ledgers that aren't registered must
+    /// be integers.
+    pub fn parse(&self, ledger: u32, amount: &str) -> Result<Amount, AmountError> {
+        Amount::parse(amount, self.scale(ledger))
+    }
+}
+
+impl LedgerInfo {
+    /// # Panics
+    ///
+    /// Panics if `scale` is greater than [`Amount::SCALE_MAX`].
+    pub fn new(currency: &str, scale: u8, name: &str) -> LedgerInfo {
+        assert!(scale <= Amount::SCALE_MAX);
+        LedgerInfo {
+            currency: currency.to_owned(),
+            scale,
+            name: name.to_owned(),
+        }
+    }
+
+    /// Format an amount of `units`, such as `USD 12.34`.
+    pub fn format(&self, units: u128) -> String {
+        let amount = Amount::new(units, self.scale);
+        if self.currency.is_empty() {
+            amount.to_string()
+        } else {
+            format!("{} {amount}", self.currency)
+        }
+    }
+}
+
+/// Ledgers keyed by number, serialized with string keys, since TOML keys are
+/// strings.
+#[cfg(feature = "serde")]
+mod ledgers {
+    use super::*;
+
+    use serde::de::{Deserializer, Error, Visitor};
+    use serde::ser::Serializer;
+    use serde::Deserialize;
+
+    pub fn serialize<S: Serializer>(
+        ledgers: &BTreeMap<u32, LedgerInfo>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            ledgers
+                .iter()
+                .map(|(ledger, info)| (ledger.to_string(), info)),
+        )
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<u32, LedgerInfo>, D::Error> {
+        let ledgers = BTreeMap::<Ledger, LedgerInfo>::deserialize(deserializer)?;
+        Ok(ledgers
+            .into_iter()
+            .map(|(Ledger(ledger), info)| (ledger, info))
+            .collect())
+    }
+
+    /// A ledger number, as an integer or a string.
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    struct Ledger(u32);
+
+    impl<'de> Deserialize<'de> for Ledger {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Ledger, D::Error> {
+            struct LedgerVisitor;
+
+            impl Visitor<'_> for LedgerVisitor {
+                type Value = Ledger;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a ledger number")
+                }
+
+                fn visit_u64<E: Error>(self, value: u64) -> Result<Ledger, E> {
+                    u32::try_from(value)
+                        .map(Ledger)
+                        .map_err(|_| E::custom("ledger out of range"))
+                }
+
+                fn visit_str<E: Error>(self, value: &str) -> Result<Ledger, E> {
+                    value
+                        .parse()
+                        .map(Ledger)
+                        .map_err(|_| E::custom(format!("invalid ledger {value}")))
+                }
+            }
+
+            deserializer.deserialize_any(LedgerVisitor)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn deserialize_scale<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    use serde::de::{Deserialize, Error, Unexpected};
+
+    let scale = u8::deserialize(deserializer)?;
+    if scale > Amount::SCALE_MAX {
+        return Err(D::Error::invalid_value(
+            Unexpected::Unsigned(u64::from(scale)),
+            &"a scale of at most 38",
+        ));
+    }
+    Ok(scale)
+}
+
+/// A debit or credit of a named account.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Leg {
+    pub account: String,
+    pub side: Side,
+    pub amount: u128,
+}
+
+/// The side of a [`Leg`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Side {
+    Debit,
+    Credit,
+}
+
+/// A balanced set of debits and credits.
+///
+/// See the [module documentation](self).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JournalEntry {
+    id: u128,
+    code: u16,
+    legs: Vec<Leg>,
+}
+
+impl JournalEntry {
+    /// Create an empty entry.
+    ///
+    /// Each of the entry's transfers has `code`, and has `id` as its
+    /// `user_data_128`, so the transfers of an entry can be found with
+    /// [`Client::query_transfers`](crate::Client::query_transfers).
+    pub fn new(id: u128, code: u16) -> JournalEntry {
+        JournalEntry {
+            id,
+            code,
+            legs: Vec::new(),
+        }
+    }
+
+    /// Debit `amount` from the account named `account`.
+    ///
+    /// Amounts are in the ledger's smallest unit; see [`Amount`].
+    pub fn debit(mut self, account: &str, amount: u128) -> JournalEntry {
+        self.legs.push(Leg {
+            account: account.to_owned(),
+            side: Side::Debit,
+            amount,
+        });
+        self
+    }
+
+    /// Credit `amount` to the account named `account`.
+    ///
+    /// Amounts are in the ledger's smallest unit; see [`Amount`].
+    pub fn credit(mut self, account: &str, amount: u128) -> JournalEntry {
+        self.legs.push(Leg {
+            account: account.to_owned(),
+            side: Side::Credit,
+            amount,
+        });
+        self
+    }
+
+    pub fn id(&self) -> u128 {
+        self.id
+    }
+
+    pub fn legs(&self) -> &[Leg] {
+        &self.legs
+    }
+
+    /// Convert the entry to linked transfers.
+    ///
+    /// Within each ledger, debits are paired with credits in the order the
+    /// legs were added, with a transfer for each overlapping amount, so an
+    /// entry with one debit and several credits becomes one transfer per
+    /// credit. Transfers are ordered by ledger, in the order each ledger first
+    /// appears in the legs, and are given new ids with [`id`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`JournalError`] if the entry has no legs, a leg's account
+    /// is not in `accounts` or its amount is zero, an account is both
+    /// debited and credited, or the legs of a ledger don't balance.
+    pub fn to_transfers(&self, accounts: &Accounts) -> Result<Vec<Transfer>, JournalError> {
+        if self.legs.is_empty() {
+            return Err(JournalError::Empty);
+        }
+
+        // The legs of each ledger, in order of first appearance.
+        let mut ledgers: Vec<LedgerLegs> = Vec::new();
+        for (index, leg) in self.legs.iter().enumerate() {
+            let account = accounts
+                .get(&leg.account)
+                .ok_or(JournalError::AccountUnknown { leg: index })?;
+            if leg.amount == 0 {
+                return Err(JournalError::AmountZero { leg: index });
+            }
+            let on_other_side = self.legs[..index]
+                .iter()
+                .any(|other| other.account == leg.account && other.side != leg.side);
+            if on_other_side {
+                return Err(JournalError::AccountDebitedAndCredited { leg: index });
+            }
+
+            let position = match ledgers
+                .iter()
+                .position(|legs| legs.ledger == account.ledger)
+            {
+                Some(position) => position,
+                None => {
+                    ledgers.push(LedgerLegs {
+                        ledger: account.ledger,
+                        debits: Vec::new(),
+                        credits: Vec::new(),
+                    });
+                    ledgers.len() - 1
+                }
+            };
+            let legs = &mut ledgers[position];
+            match leg.side {
+                Side::Debit => legs.debits.push((account.id, leg.amount)),
+                Side::Credit => legs.credits.push((account.id, leg.amount)),
+            }
+        }
+
+        for legs in &ledgers {
+            let total = |legs: &[(u128, u128)]| {
+                legs.iter()
+                    .try_fold(0u128, |total, (_, amount)| total.checked_add(*amount))
+            };
+            match (total(&legs.debits), total(&legs.credits)) {
+                (Some(debits), Some(credits)) if debits == credits => {}
+                _ => {
+                    return Err(JournalError::Unbalanced {
+                        ledger: legs.ledger,
+                    })
+                }
+            }
+        }
+
+        let mut transfers = Vec::new();
+        for LedgerLegs {
+            ledger,
+            mut debits,
+            mut credits,
+        } in ledgers
+        {
+            let (mut d, mut c) = (0, 0);
+            while d < debits.len() && c < credits.len() {
+                let amount = debits[d].1.min(credits[c].1);
+                transfers.push(Transfer {
+                    id: id(),
+                    debit_account_id: debits[d].0,
+                    credit_account_id: credits[c].0,
+                    amount,
+                    user_data_128: self.id,
+                    ledger,
+                    code: self.code,
+                    flags: TransferFlags::Linked,
+                    ..Default::default()
+                });
+                debits[d].1 -= amount;
+                credits[c].1 -= amount;
+                if debits[d].1 == 0 {
+                    d += 1;
+                }
+                if credits[c].1 == 0 {
+                    c += 1;
+                }
+            }
+        }
+        if let Some(last) = transfers.last_mut() {
+            last.flags.remove(TransferFlags::Linked);
+        }
+        Ok(transfers)
+    }
+}
+
+/// The debits and credits of one ledger, as account ids and amounts.
+struct LedgerLegs {
+    ledger: u32,
+    debits: Vec<(u128, u128)>,
+    credits: Vec<(u128, u128)>,
+}
+
+/// Errors resulting from converting a [`JournalEntry`] to transfers.
+///
+/// Legs are identified by the order in which they were added.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum JournalError {
+    /// The entry has no legs.
+    Empty,
+    /// The account of leg `leg` is not named in the [`Accounts`].
+    AccountUnknown { leg: usize },
+    /// The amount of leg `leg` is zero.
+    AmountZero { leg: usize },
+    /// The account of leg `leg` was already used on the other side.
+    AccountDebitedAndCredited { leg: usize },
+    /// The debits and credits of `ledger` are not equal.
+    Unbalanced { ledger: u32 },
+}
+
+impl std::error::Error for JournalError {}
+impl core::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("journal entry has no legs"),
+            Self::AccountUnknown { leg } => write!(f, "leg {leg} has an unknown account"),
+            Self::AmountZero { leg } => write!(f, "leg {leg} has a zero amount"),
+            Self::AccountDebitedAndCredited { leg } => {
+                write!(f, "leg {leg} is on both sides of an account")
+            }
+            Self::Unbalanced { ledger } => write!(f, "ledger {ledger} is unbalanced"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Accounts {
+        let mut accounts = Accounts::new();
+        accounts.insert("a", 1, 1);
+        accounts.insert("b", 2, 1);
+        accounts.insert("c", 3, 1);
+        accounts.insert("x", 4, 2);
+        accounts.insert("y", 5, 2);
+        accounts
+    }
+
+    fn pairs(transfers: &[Transfer]) -> Vec<(u128, u128, u128, u32, bool)> {
+        transfers
+            .iter()
+            .map(|t| {
+                (
+                    t.debit_account_id,
+                    t.credit_account_id,
+                    t.amount,
+                    t.ledger,
+                    t.flags.contains(TransferFlags::Linked),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn to_transfers() {
+        let entry = JournalEntry::new(7, 10)
+            .debit("a", 30)
+            .debit("b", 70)
+            .credit("x", 5)
+            .credit("c", 60)
+            .debit("y", 5)
+            .credit("c", 40);
+        let transfers = entry.to_transfers(&accounts()).unwrap();
+
+        assert_eq!(
+            pairs(&transfers),
+            [
+                (1, 3, 30, 1, true),
+                (2, 3, 30, 1, true),
+                (2, 3, 40, 1, true),
+                (5, 4, 5, 2, false),
+            ]
+        );
+        assert!(transfers
+            .iter()
+            .all(|t| t.user_data_128 == 7 && t.code == 10));
+    }
+
+    #[test]
+    fn to_transfers_invalid() {
+        let accounts = accounts();
+        let error = |entry: JournalEntry| entry.to_transfers(&accounts).unwrap_err();
+
+        assert_eq!(error(JournalEntry::new(1, 1)), JournalError::Empty);
+        assert_eq!(
+            error(JournalEntry::new(1, 1).debit("a", 1).credit("z", 1)),
+            JournalError::AccountUnknown { leg: 1 }
+        );
+        assert_eq!(
+            error(JournalEntry::new(1, 1).debit("a", 0)),
+            JournalError::AmountZero { leg: 0 }
+        );
+        assert_eq!(
+            error(JournalEntry::new(1, 1).debit("a", 1).credit("a", 1)),
+            JournalError::AccountDebitedAndCredited { leg: 1 }
+        );
+        assert_eq!(
+            error(JournalEntry::new(1, 1).debit("a", 2).credit("b", 1)),
+            JournalError::Unbalanced { ledger: 1 }
+        );
+        assert_eq!(
+            error(JournalEntry::new(1, 1).debit("a", 1).credit("x", 1)),
+            JournalError::Unbalanced { ledger: 1 }
+        );
+        assert_eq!(
+            error(
+                JournalEntry::new(1, 1)
+                    .debit("a", u128::MAX)
+                    .debit("b", 1)
+                    .credit("c", 1)
+            ),
+            JournalError::Unbalanced { ledger: 1 }
+        );
+    }
+
+    #[test]
+    fn chart_of_accounts() {
+        let mut chart = ChartOfAccounts::new();
+        chart.insert(
+            "assets:cash",
+            AccountDefinition {
+                id: 1,
+                ledger: 840,
+                code: 10,
+                flags: AccountFlags::History,
+            },
+        );
+
+        let account = chart.account("assets:cash").unwrap();
+        assert_eq!((account.id, account.ledger, account.code), (1, 840, 10));
+        assert_eq!(account.flags, AccountFlags::History);
+        assert_eq!(chart.account("assets:bank"), None);
+        assert_eq!(
+            chart.to_accounts().get("assets:cash"),
+            Some(AccountRef { id: 1, ledger: 840 })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn chart_of_accounts_serde() {
+        let chart: ChartOfAccounts = toml::from_str(
+            r#"
+            [accounts."assets:cash"]
+            id = "340282366920938463463374607431768211455"
+            ledger = 840
+            code = 10
+            flags = "History | Linked"
+
+            [accounts."income:sales"]
+            id = "0x3e9"
+            ledger = 840
+            code = 20
+            "#,
+        )
+        .unwrap();
+        assert_eq!(chart.get("assets:cash").unwrap().id, u128::MAX);
+        assert_eq!(
+            chart.get("assets:cash").unwrap().flags,
+            AccountFlags::History | AccountFlags::Linked
+        );
+        assert_eq!(chart.get("income:sales").unwrap().id, 1001);
+        assert_eq!(chart.get("income:sales").unwrap().flags, AccountFlags::None);
+
+        let round_trip: ChartOfAccounts =
+            toml::from_str(&toml::to_string(&chart).unwrap()).unwrap();
+        assert_eq!(round_trip, chart);
+
+        let json = serde_json::to_string(&chart).unwrap();
+        assert!(json.contains(r#""id":"1001""#));
+        assert_eq!(
+            serde_json::from_str::<ChartOfAccounts>(&json).unwrap(),
+            chart
+        );
+        let chart: ChartOfAccounts =
+            serde_json::from_str(r#"{"accounts": {"a": {"id": 7, "ledger": 1, "code": 1}}}"#)
+                .unwrap();
+        assert_eq!(chart.get("a").unwrap().id, 7);
+    }
+
+    #[test]
+    fn ledger_registry() {
+        let mut ledgers = LedgerRegistry::new();
+        ledgers.insert(840, LedgerInfo::new("USD", 2, "US Dollar"));
+        ledgers.insert(1, LedgerInfo::new("", 3, "Points"));
+
+        assert_eq!(ledgers.format(840, 1234), "USD 12.34");
+        assert_eq!(ledgers.format(840, 5), "USD 0.05");
+        assert_eq!(ledgers.format(1, 1234), "1.234");
+        assert_eq!(ledgers.format(2, 1234), "1234");
+        assert_eq!(ledgers.parse(840, "12.34").unwrap().units(), 1234);
+        assert_eq!(ledgers.parse(2, "12.34"), Err(AmountError::TooPrecise));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn ledger_registry_serde() {
+        let ledgers: LedgerRegistry = toml::from_str(
+            r#"
+            [ledgers.840]
+            currency = "USD"
+            scale = 2
+            name = "US Dollar"
+
+            [ledgers.978]
+            currency = "EUR"
+            scale = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            ledgers.get(840),
+            Some(&LedgerInfo::new("USD", 2, "US Dollar"))
+        );
+        assert_eq!(ledgers.get(978).unwrap().name, "");
+
+        let round_trip: LedgerRegistry =
+            toml::from_str(&toml::to_string(&ledgers).unwrap()).unwrap();
+        assert_eq!(round_trip, ledgers);
+
+        let json = serde_json::to_string(&ledgers).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LedgerRegistry>(&json).unwrap(),
+            ledgers
+        );
+        assert!(serde_json::from_str::<LedgerRegistry>(
+            r#"{"ledgers": {"1": {"currency": "X", "scale": 39}}}"#
+        )
+        .is_err());
+    }
+}
@@ -95,8 +95,14 @@
 //! limitations.
 //!
 //! In TigerBeetle's standard build-time configuration **the maximum number of
-//! events per batch is 8189**. If the events in a request exceed this number
-//! its future will return [`PacketStatus::TooMuchData`].
+//! events per batch is 8189** ([`BATCH_MAX`]). If the events in a request
+//! exceed this number its future will return [`PacketStatus::TooMuchData`],
+//! except for [`create_accounts`] and [`create_transfers`], which by default
+//! split oversized requests into multiple batches. See
+//! [`Client::set_batch_splitting`].
+//!
+//! [`create_accounts`]: `Client::create_accounts`
+//! [`create_transfers`]: `Client::create_transfers`
 //!
 //!
 //! # Range query limits
@@ -204,7 +210,9 @@
 //! Responses to requests are returned as [`Future`]s. It is not strictly
 //! necessary for applications to `await` these futures &mdash; requests are
 //! enqueued as soon as the request method is called and will be executed even
-//! if the future is dropped.
+//! if the future is dropped. The exception is a request deferred by the
+//! client's [`RateLimit`], which is enqueued by its future once it is within
+//! the limits, so it is cancelled if the future is dropped first.
 //!
 //! It is possible to drop a `Client` while request futures are still
 //! outstanding. In this case any pending requests will be completed with
@@ -218,9 +226,31 @@
 //! For orderly shutdown, it is recommended to await all
 //! request futures prior to destroying the client,
 //! and to destroy the client by calling `close` and awaiting
-//! its return value.
+//! its return value. Alternatively, [`drain_and_close`] waits for
+//! requests in flight to complete, with a deadline, before closing.
 //!
 //! [`close`]: Client::close
+//! [`drain_and_close`]: Client::drain_and_close
+//!
+//! Request futures don't time out on their own, unless the client has a
+//! [request timeout](Client::set_request_timeout). To wait for a response for
+//! a limited time, wrap a request future with [`with_deadline`], or with
+//! [`with_cancellation`] to abandon it on demand using a [`CancellationToken`].
+//!
+//!
+//! # Failover and session eviction
+//!
+//! The client finds the primary replica itself, and resends requests when a
+//! connection is reset or the primary fails over, so these are invisible to
+//! applications apart from latency.
+//!
+//! A cluster may also evict a client's session, e.g. when more clients are
+//! connected than it supports. The client then registers a new session, and
+//! resubmits queries, which have no effect, to it. Writes fail with
+//! [`PacketStatus::ClientEvicted`], since they may or may not have been
+//! executed; they are safe to resubmit, and a [`RetryPolicy`] does so
+//! automatically. Set [`Client::on_eviction`] to be told of evictions, and
+//! their [`EvictionReason`].
 //!
 //!
 //! # Concurrency and multithreading
@@ -259,9 +289,12 @@
 //!
 //! # Use in non-async codebases
 //!
-//! The TigerBeetle client is async-only, but if you're working in a synchronous
-//! codebase, you can use [`futures::executor::block_on`] to run async operations
-//! to completion.
+//! For synchronous codebases this crate provides [`blocking::Client`], which
+//! mirrors the async API but blocks the calling thread until each request
+//! completes. It does not require an async runtime.
+//!
+//! Alternately, you can use [`futures::executor::block_on`] to run async
+//! operations to completion.
 //!
 //! [`futures::executor::block_on`]: https://docs.rs/futures/latest/futures/executor/fn.block_on.html
 //!
@@ -291,6 +324,59 @@
 //! completes, so this approach works best for simple use cases or when you need
 //! to integrate TigerBeetle into an existing synchronous application.
 //!
+//! [`blocking::Client`]: crate::blocking::Client
+//!
+//!
+//! # Testing applications without a cluster
+//!
+//! The [`TigerBeetleClient`] trait abstracts over the client's operations.
+//! It is implemented by [`Client`] and by [`InMemoryClient`], which emulates
+//! a TigerBeetle cluster in-process, so that application logic written
+//! against the trait can be unit-tested without running TigerBeetle.
+//!
+//!
+//! # Diagnostics
+//!
+//! With the `tracing` feature enabled the client emits events through the
+//! [`tracing`](https://docs.rs/tracing) crate: requests and their failures
+//! at the `DEBUG` level, and retries, session evictions and failed address
+//! refreshes at `WARN`.
+//! Without the feature the client logs nothing, but [`Client::on_event`]
+//! reports the same retries, evictions and failures to a callback.
+//!
+//! With the `metrics` feature enabled the client records per-operation
+//! request and event counters, error counters by status and result code, and
+//! request latency histograms through the [`metrics`](https://docs.rs/metrics)
+//! facade, so they can be exported to Prometheus or any other backend with a
+//! `metrics` recorder:
+//!
+//! - `tigerbeetle_client_requests_total`
+//! - `tigerbeetle_client_events_total`
+//! - `tigerbeetle_client_errors_total`, labeled with the packet `status`
+//! - `tigerbeetle_client_event_results_total`, labeled with the `create_*` `result`
+//! - `tigerbeetle_client_request_duration_seconds`
+//!
+//! All are labeled with the `operation`, e.g. `create_transfers`.
+//!
+//! With the `otel` feature enabled each operation creates an
+//! [OpenTelemetry](https://docs.rs/opentelemetry) client span named after the
+//! operation, e.g. `tb.create_transfers`, through the global tracer provider.
+//! The span is a child of the OpenTelemetry context that is current when the
+//! operation is called, so a trace can follow a request from a web handler
+//! down to TigerBeetle. Spans carry the batch size (`tb.batch_size`), the
+//! ledger when all events share one (`tb.ledger`), the distinct result codes
+//! of failed `create_*` events (`tb.result_codes`), and the packet status of
+//! a failed request (`tb.packet_status`).
+//!
+//!
+//! With the `serde` feature the protocol types and `create_*` results
+//! implement `Serialize` and `Deserialize`. Their `u128` fields are
+//! serialized as decimal strings; see the `serde_u128` module. The `jsonl`
+//! feature adds the `io` module, to import and export them as JSON Lines.
+//! The `webhooks` feature adds the `webhooks` module, to post the results of
+//! created transfers to HTTP endpoints, and the `redis` feature adds a
+//! store of idempotency keys in Redis to the `idempotency` module.
+//!
 //!
 //! # Rust structure binary representation and the TigerBeetle protocol
 //!
@@ -310,19 +396,50 @@
 //! Note that status enums are not ABI-compatible with the protocol's status codes
 //! and must be converted with [`TryFrom`].
 //!
+//! The protocol is little-endian, and these types have the native layout, so
+//! the client supports only little-endian targets.
+//!
+//!
+//! # Without the standard library
+//!
+//! The client requires the `std` feature, which is enabled by default. With
+//! default features disabled the crate is `no_std`, links no native library,
+//! and contains only the protocol types above, their flags, the
+//! `create_*` result enums and [`TransferBuilder`], so that other crates,
+//! such as embedded gateways, can share their binary layout:
+//!
+//! ```toml
+//! [dependencies]
+//! tigerbeetle = { version = "...", default-features = false }
+//! ```
+//!
 //!
 //! # References
 //!
 //! [The TigerBeetle Reference](https://docs.tigerbeetle.com/reference/).
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
 use bitflags::bitflags;
+#[cfg(feature = "std")]
 use futures_channel::oneshot::{channel, Receiver};
 
+#[cfg(feature = "std")]
 use std::convert::Infallible;
+#[cfg(feature = "std")]
 use std::future::Future;
-use std::os::raw::{c_char, c_void};
+#[cfg(feature = "std")]
+use std::os::raw::c_void;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "std")]
 use std::{fmt, mem, ptr};
 
+#[cfg(feature = "std")]
+use session::{ClientCore, Connector};
+
 // The generated bindings.
 // These are not part of the public API but are re-exported hidden
 // so that the vortex driver can parse the TB protocol directly.
@@ -336,62 +453,383 @@ pub mod tb_client;
 
 use tb_client as tbc;
 
+#[cfg(feature = "std")]
+#[macro_use]
+mod diagnostics;
+
+#[cfg(feature = "std")]
+mod account_cache;
+#[cfg(feature = "std")]
+pub mod addresses;
+#[cfg(feature = "std")]
+mod amount;
+#[cfg(feature = "std")]
+mod api;
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod backup;
+#[cfg(feature = "std")]
+pub mod blocking;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+pub mod bulk;
+#[cfg(feature = "std")]
+mod cancellation;
+#[cfg(feature = "cdc")]
+pub mod cdc;
+#[cfg(feature = "std")]
+mod checked;
 mod conversions;
+#[cfg(feature = "std")]
+mod ensure;
+#[cfg(feature = "std")]
+mod events;
+#[cfg(feature = "std")]
+mod eviction;
+pub mod id128;
+#[cfg(feature = "std")]
+pub mod idempotency;
+#[cfg(feature = "std")]
+mod import;
+#[cfg(feature = "std")]
+mod in_memory;
+#[cfg(feature = "jsonl")]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod ledger;
+#[cfg(feature = "std")]
+mod linked_chain;
+#[cfg(feature = "std")]
+mod multi_cluster;
+#[cfg(feature = "std")]
+mod paging;
+#[cfg(feature = "std")]
+mod pending;
+#[cfg(feature = "std")]
+pub mod periods;
+#[cfg(feature = "std")]
+mod ping;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "std")]
+mod rate_limit;
+#[cfg(feature = "std")]
+pub mod recipes;
+#[cfg(feature = "std")]
+pub mod reconcile;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+pub mod scheduler;
+#[cfg(feature = "serde")]
+pub mod serde_u128;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "std")]
+mod submit_options;
+#[cfg(feature = "std")]
+mod telemetry;
+#[cfg(feature = "std")]
+pub mod tenant;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "std")]
 mod time_based_id;
-
+#[cfg(feature = "std")]
+mod timer;
+pub mod transfer_builder;
+mod types;
+pub mod user_data;
+#[cfg(feature = "std")]
+mod validate;
+#[cfg(feature = "std")]
+mod version;
+#[cfg(feature = "std")]
+mod watch;
+#[cfg(feature = "webhooks")]
+pub mod webhooks;
+#[cfg(feature = "std")]
+mod wire;
+
+#[cfg(feature = "std")]
+pub use account_cache::AccountCache;
+#[cfg(feature = "std")]
+pub use amount::{Amount, AmountError};
+#[cfg(feature = "std")]
+pub use api::{BoxFuture, TigerBeetleClient};
+#[cfg(feature = "std")]
+pub use builder::{ClientBuildError, ClientBuilder};
+#[cfg(feature = "std")]
+pub use cancellation::{with_cancellation, with_deadline, CancellationToken};
+#[cfg(feature = "std")]
+pub use checked::{
+    CreateAccountsError, CreateTransfersError, DuplicateId, FailedAccount, FailedTransfer,
+};
+#[cfg(feature = "std")]
+pub use ensure::{AccountSpec, EnsureAccountsSummary};
+#[cfg(feature = "std")]
+pub use events::ClientEvent;
+#[cfg(feature = "std")]
+pub use eviction::EvictionReason;
+#[cfg(feature = "std")]
+pub use import::ImportError;
+#[cfg(feature = "std")]
+pub use in_memory::InMemoryClient;
+#[cfg(feature = "std")]
+pub use linked_chain::{Linkable, LinkedChain, LinkedChainError, LinkedChainFailure};
+#[cfg(feature = "std")]
+pub use multi_cluster::{MultiClusterClient, MultiClusterError};
+#[cfg(feature = "std")]
+pub use paging::PagedTransfers;
+#[cfg(feature = "std")]
+pub use pending::PendingTransfers;
+#[cfg(feature = "std")]
+pub use pool::ClientPool;
+#[cfg(feature = "std")]
+pub use rate_limit::{RateLimit, WouldBlock};
+#[cfg(feature = "std")]
+pub use retry::{Retry, RetryPolicy};
+#[cfg(feature = "std")]
+pub use submit_options::SubmitOptions;
+#[cfg(feature = "std")]
 pub use time_based_id::id;
+pub use transfer_builder::TransferBuilder;
+pub use types::{
+    Account, AccountBalance, AccountFilter, AccountFilterFlags, AccountFlags, CreateAccountResult,
+    CreateAccountsResult, CreateTransferResult, CreateTransfersResult, QueryFilter,
+    QueryFilterFlags, Reserved, Transfer, TransferFlags, AMOUNT_MAX,
+};
+#[cfg(feature = "std")]
+pub use version::{VersionError, CLIENT_RELEASE};
+#[cfg(feature = "std")]
+pub use watch::{BalanceUpdate, BalanceWatch};
+
+/// The maximum number of events in a single request.
+///
+/// This is the limit in TigerBeetle's standard build-time configuration.
+/// Requests with more events fail with [`PacketStatus::TooMuchData`].
+pub const BATCH_MAX: usize = 8189;
 
 /// The tb_client completion context is unused by the Rust bindings.
 /// This is just a magic number to jump out of logs.
+#[cfg(feature = "std")]
 const COMPLETION_CONTEXT: usize = 0xAB;
 
 /// The TigerBeetle client.
+#[cfg(feature = "std")]
 pub struct Client {
-    client: *mut tbc::tb_client_t,
+    core: Arc<ClientCore>,
+    split_batches: bool,
+    validate: bool,
+    detect_duplicate_ids: bool,
+    retry_policy: RetryPolicy,
+    limiter: Option<Arc<rate_limit::Limiter>>,
+    request_timeout: Option<Duration>,
+    on_event: Option<events::OnEvent>,
+    #[cfg(feature = "webhooks")]
+    webhooks: Option<Arc<webhooks::Emitter>>,
 }
 
-unsafe impl Send for Client {}
-unsafe impl Sync for Client {}
-
+#[cfg(feature = "std")]
 impl Client {
     /// Create a new TigerBeetle client.
     ///
     /// # Addresses
     ///
     /// The `addresses` argument is a comma-separated string of addresses, where
-    /// each may be either an IP4 address, a port number, or the pair of IP4
-    /// address and port number separated by a colon. Examples include
-    /// `127.0.0.1`, `3001`, `127.0.0.1:3001` and
+    /// each may be either a host, a port number, or the pair of host and port
+    /// number separated by a colon. Hosts are IPv4 addresses, IPv6 addresses in
+    /// square brackets, or DNS names. Examples include `127.0.0.1`, `3001`,
+    /// `127.0.0.1:3001`, `[::1]:3001`, `replica-0.tigerbeetle:3001` and
     /// `127.0.0.1,3002,127.0.0.1:3003`. The default IP address is `127.0.0.1`
     /// and default port is `3001`.
     ///
-    /// This is the same address format supported by the TigerBeetle CLI.
+    /// This is the address format supported by the TigerBeetle CLI, extended
+    /// with DNS names, which are resolved by the client. Invalid addresses,
+    /// and DNS names that can't be resolved, fail with
+    /// [`InitStatus::AddressInvalid`]; see the [`addresses`] module to
+    /// validate addresses with more precise errors.
     ///
     /// # References
     ///
     /// [Client Sessions](https://docs.tigerbeetle.com/reference/sessions/).
     pub fn new(cluster_id: u128, addresses: &str) -> Result<Client, InitStatus> {
-        assert_abi_compatibility();
+        let addresses = addresses::parse(addresses).map_err(|_| InitStatus::AddressInvalid)?;
+        Client::with_addresses(cluster_id, addresses)
+    }
 
-        unsafe {
-            let tb_client = Box::new(tbc::tb_client_t {
-                opaque: Default::default(),
-            });
-            let tb_client = Box::into_raw(tb_client);
-            let status = tbc::tb_client_init(
-                tb_client,
-                &cluster_id.to_le_bytes(),
-                addresses.as_ptr() as *const c_char,
-                addresses.len() as u32,
-                COMPLETION_CONTEXT,
-                Some(on_completion),
-            );
-            if status == tbc::TB_INIT_STATUS_TB_INIT_SUCCESS {
-                Ok(Client { client: tb_client })
-            } else {
-                Err(status.into())
-            }
+    fn with_addresses(
+        cluster_id: u128,
+        addresses: Vec<addresses::ReplicaAddress>,
+    ) -> Result<Client, InitStatus> {
+        let core = ClientCore::new(Connector::Native {
+            cluster_id,
+            addresses,
+        })?;
+
+        Ok(Client::with_core(core))
+    }
+
+    fn with_core(core: ClientCore) -> Client {
+        Client {
+            core: Arc::new(core),
+            split_batches: true,
+            validate: true,
+            detect_duplicate_ids: false,
+            retry_policy: RetryPolicy::none(),
+            limiter: None,
+            request_timeout: None,
+            on_event: None,
+            #[cfg(feature = "webhooks")]
+            webhooks: None,
+        }
+    }
+
+    /// Create a builder for configuring a client.
+    ///
+    /// See [`ClientBuilder`].
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Enable or disable splitting of oversized `create_*` requests.
+    ///
+    /// When enabled, which is the default, [`create_accounts`] and
+    /// [`create_transfers`] requests with more than [`BATCH_MAX`] events are
+    /// split into multiple requests, and the results are combined with indexes
+    /// that refer to the caller's original slice. Linked chains are never
+    /// split across requests.
+    ///
+    /// The requests are submitted together, and the cluster may process them
+    /// in any order, e.g. if one is retried after a later one succeeded. An
+    /// event that depends on an earlier one, such as the transfer posting a
+    /// pending transfer, must be in the same linked chain, or in a later
+    /// call once the earlier one completes.
+    ///
+    /// When disabled, oversized requests fail with [`PacketStatus::TooMuchData`].
+    ///
+    /// [`create_accounts`]: Client::create_accounts
+    /// [`create_transfers`]: Client::create_transfers
+    pub fn set_batch_splitting(&mut self, enabled: bool) {
+        self.split_batches = enabled;
+    }
+
+    /// Enable or disable validation of events before they are submitted by
+    /// [`create_accounts_checked`] and [`create_transfers_checked`].
+    ///
+    /// When enabled, which is the default, events are checked for errors
+    /// that don't depend on the cluster's state, such as zero ids or
+    /// identical debit and credit accounts, and a batch with invalid events
+    /// fails without being submitted. Disable it to have the cluster report
+    /// every result, e.g. to compare against it.
+    ///
+    /// [`create_accounts_checked`]: Client::create_accounts_checked
+    /// [`create_transfers_checked`]: Client::create_transfers_checked
+    pub fn set_validation(&mut self, enabled: bool) {
+        self.validate = enabled;
+    }
+
+    /// Enable or disable detection of duplicate ids within a batch by
+    /// [`create_accounts_checked`] and [`create_transfers_checked`].
+    ///
+    /// The cluster reports an event with the id of an earlier event in the
+    /// same batch as `Exists`, or as existing with different fields, which
+    /// can hide a bug in how ids are generated. When enabled, such a batch
+    /// fails without being submitted. Disabled by default, since detection
+    /// hashes every id.
+    ///
+    /// [`create_accounts_checked`]: Client::create_accounts_checked
+    /// [`create_transfers_checked`]: Client::create_transfers_checked
+    pub fn set_duplicate_id_detection(&mut self, enabled: bool) {
+        self.detect_duplicate_ids = enabled;
+    }
+
+    /// Set the policy for retrying failed requests.
+    ///
+    /// The policy applies to requests made after it is set.
+    /// By default requests are not retried.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Set limits on the rate of requests. See [`RateLimit`].
+    ///
+    /// The limits apply to requests made after they are set, and requests
+    /// made before don't count towards them. By default there are no limits.
+    pub fn set_rate_limit(&mut self, rate_limit: RateLimit) {
+        self.limiter = rate_limit::Limiter::new(rate_limit);
+    }
+
+    /// Fail requests with [`PacketStatus::Timeout`] if they don't complete
+    /// within `timeout`, or with `None`, wait for them indefinitely.
+    ///
+    /// The timeout applies to requests made after it is set, and covers the
+    /// whole request: waiting for the rate limit, and any retries. As with
+    /// [`with_deadline`], a request that times out may still be processed by
+    /// the cluster. By default requests don't time out.
+    pub fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+    }
+
+    /// Post the results of created transfers to webhook endpoints. See the
+    /// [`webhooks`] module.
+    ///
+    /// Notifications are posted for requests made after this is set, by a
+    /// background thread that stops once the client is dropped and the
+    /// notifications queued before have been posted. By default there are
+    /// no webhooks.
+    #[cfg(feature = "webhooks")]
+    pub fn set_webhooks(&mut self, webhooks: webhooks::Webhooks) {
+        self.webhooks = if webhooks.endpoints.is_empty() {
+            None
+        } else {
+            Some(webhooks::Emitter::start(webhooks))
+        };
+    }
+
+    /// Replace the addresses of the cluster's replicas.
+    ///
+    /// A new session is registered with the new addresses, and used for
+    /// requests made after this returns. Requests already in flight, including
+    /// their retries, complete on the old session, which is closed once they
+    /// have all completed.
+    ///
+    /// Does nothing if the client is closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InitStatus::AddressInvalid`] if `addresses` is empty or an
+    /// address could not be resolved, and otherwise the status of the failed
+    /// session registration. On error the current session is kept.
+    pub fn update_addresses(
+        &self,
+        addresses: &[addresses::ReplicaAddress],
+    ) -> Result<(), InitStatus> {
+        if addresses.is_empty() {
+            return Err(InitStatus::AddressInvalid);
         }
+        self.core.update_addresses(addresses.to_vec())
+    }
+
+    /// Resolve the DNS names of the replica addresses again, reconnecting if
+    /// any of them changed.
+    ///
+    /// The client resolves DNS names when it registers a session, which is
+    /// not enough when replicas are rescheduled to new IP addresses, e.g. in
+    /// Kubernetes. Call this periodically, or set
+    /// [`ClientBuilder::address_refresh_interval`] to have the client do it.
+    /// Reconnecting has the same effect as [`Client::update_addresses`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InitStatus::AddressInvalid`] if an address could not be
+    /// resolved. On error the current session is kept.
+    pub fn refresh_addresses(&self) -> Result<(), InitStatus> {
+        self.core.refresh_addresses()
     }
 
     /// Create one or more accounts.
@@ -401,7 +839,8 @@ impl Client {
     /// [protocol reference](#protocol-reference).
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned [`Future`] will not cancel the request.
+    /// dropping the returned [`Future`] will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Interpreting the return value
     ///
@@ -488,10 +927,16 @@ impl Client {
     ///
     /// # Maximum batch size
     ///
-    /// If the length of the `events` argument exceeds the maximum batch size
-    /// the future will return [`Err`] of [`PacketStatus::TooMuchData`]. In
-    /// TigerBeetle's standard build-time configuration the maximum batch size
-    /// is 8189.
+    /// If the length of the `events` argument exceeds [`BATCH_MAX`] the events
+    /// are split into multiple requests, without splitting linked chains, and
+    /// the results are combined. The requests may be processed in any order,
+    /// and a returned [`Err`] does not guarantee that none of the events were
+    /// processed, though resubmitting the same events is safe. See
+    /// [`Client::set_batch_splitting`].
+    ///
+    /// If a single linked chain exceeds the maximum batch size, or batch
+    /// splitting is disabled, the future will return [`Err`] of
+    /// [`PacketStatus::TooMuchData`].
     ///
     /// # Protocol reference
     ///
@@ -500,27 +945,34 @@ impl Client {
         &self,
         events: &[Account],
     ) -> impl Future<Output = Result<Vec<CreateAccountsResult>, PacketStatus>> {
-        let (packet, rx) =
-            create_packet::<Account>(tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS, events);
-
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
-
-        async {
-            let msg = rx.await.expect("channel");
+        let mut span = telemetry::Span::start("create_accounts", events.len());
+        span.ledger(events.iter().map(|event| event.ledger));
+        let requests =
+            self.submit_split::<Account>(tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS, events);
 
-            let responses: &[tbc::tb_create_accounts_result_t] = handle_message(&msg)?;
+        telemetry::instrument(span, create_accounts_results(requests))
+    }
 
-            Ok(responses
-                .iter()
-                .map(|result| CreateAccountsResult {
-                    index: usize::try_from(result.index).expect("usize"),
-                    result: CreateAccountResult::from(result.result),
-                })
-                .collect())
-        }
+    /// Create one or more accounts, failing with [`WouldBlock`] instead of
+    /// waiting if the request would exceed the client's [`RateLimit`].
+    ///
+    /// If the request is split into several, all of them must be within the
+    /// limits. Without a rate limit this is the same as
+    /// [`Client::create_accounts`].
+    pub fn try_create_accounts(
+        &self,
+        events: &[Account],
+    ) -> Result<impl Future<Output = Result<Vec<CreateAccountsResult>, PacketStatus>>, WouldBlock>
+    {
+        let requests = self
+            .try_submit_split::<Account>(tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS, events)?;
+        let mut span = telemetry::Span::start("create_accounts", events.len());
+        span.ledger(events.iter().map(|event| event.ledger));
+
+        Ok(telemetry::instrument(
+            span,
+            create_accounts_results(requests),
+        ))
     }
 
     /// Create one or more transfers.
@@ -530,7 +982,8 @@ impl Client {
     /// [protocol reference](#protocol-reference).
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned [`Future`] will not cancel the request.
+    /// dropping the returned [`Future`] will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Interpreting the return value
     ///
@@ -620,10 +1073,16 @@ impl Client {
     ///
     /// # Maximum batch size
     ///
-    /// If the length of the `events` argument exceeds the maximum batch size
-    /// the future will return [`Err`] of [`PacketStatus::TooMuchData`]. In
-    /// TigerBeetle's standard build-time configuration the maximum batch size
-    /// is 8189.
+    /// If the length of the `events` argument exceeds [`BATCH_MAX`] the events
+    /// are split into multiple requests, without splitting linked chains, and
+    /// the results are combined. The requests may be processed in any order,
+    /// and a returned [`Err`] does not guarantee that none of the events were
+    /// processed, though resubmitting the same events is safe. See
+    /// [`Client::set_batch_splitting`].
+    ///
+    /// If a single linked chain exceeds the maximum batch size, or batch
+    /// splitting is disabled, the future will return [`Err`] of
+    /// [`PacketStatus::TooMuchData`].
     ///
     /// # Protocol reference
     ///
@@ -632,33 +1091,70 @@ impl Client {
         &self,
         events: &[Transfer],
     ) -> impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>> {
-        let (packet, rx) =
-            create_packet::<Transfer>(tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS, events);
-
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
+        let mut span = telemetry::Span::start("create_transfers", events.len());
+        span.ledger(events.iter().map(|event| event.ledger));
+        let requests =
+            self.submit_split::<Transfer>(tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS, events);
+
+        let results = telemetry::instrument(span, create_transfers_results(requests));
+        #[cfg(feature = "webhooks")]
+        let results = webhooks::notify(self.webhooks.clone(), events, results);
+        results
+    }
 
-        async {
-            let msg = rx.await.expect("channel");
+    /// Create one or more transfers, failing with [`WouldBlock`] instead of
+    /// waiting if the request would exceed the client's [`RateLimit`].
+    ///
+    /// If the request is split into several, all of them must be within the
+    /// limits. Without a rate limit this is the same as
+    /// [`Client::create_transfers`].
+    pub fn try_create_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>>, WouldBlock>
+    {
+        let requests = self.try_submit_split::<Transfer>(
+            tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS,
+            events,
+        )?;
+        let mut span = telemetry::Span::start("create_transfers", events.len());
+        span.ledger(events.iter().map(|event| event.ledger));
+
+        let results = telemetry::instrument(span, create_transfers_results(requests));
+        #[cfg(feature = "webhooks")]
+        let results = webhooks::notify(self.webhooks.clone(), events, results);
+        Ok(results)
+    }
 
-            let responses: &[tbc::tb_create_transfers_result_t] = handle_message(&msg)?;
+    /// Create one or more accounts, failing with [`ClientError::Timeout`]
+    /// if the request does not complete within `timeout`.
+    ///
+    /// See [`Client::create_accounts`] and [`with_deadline`].
+    pub fn create_accounts_with_deadline(
+        &self,
+        events: &[Account],
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Vec<CreateAccountsResult>, ClientError>> {
+        with_deadline(self.create_accounts(events), timeout)
+    }
 
-            Ok(responses
-                .iter()
-                .map(|result| CreateTransfersResult {
-                    index: usize::try_from(result.index).expect("usize"),
-                    result: CreateTransferResult::from(result.result),
-                })
-                .collect())
-        }
+    /// Create one or more transfers, failing with [`ClientError::Timeout`]
+    /// if the request does not complete within `timeout`.
+    ///
+    /// See [`Client::create_transfers`] and [`with_deadline`].
+    pub fn create_transfers_with_deadline(
+        &self,
+        events: &[Transfer],
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Vec<CreateTransfersResult>, ClientError>> {
+        with_deadline(self.create_transfers(events), timeout)
     }
 
     /// Query individual accounts.
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned future will not cancel the request.
+    /// dropping the returned future will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Interpreting the return value
     ///
@@ -740,25 +1236,20 @@ impl Client {
         &self,
         events: &[u128],
     ) -> impl Future<Output = Result<Vec<Account>, PacketStatus>> {
-        let (packet, rx) =
-            create_packet::<u128>(tbc::TB_OPERATION_TB_OPERATION_LOOKUP_ACCOUNTS, events);
-
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
+        let span = telemetry::Span::start("lookup_accounts", events.len());
+        let request = self.submit::<u128>(tbc::TB_OPERATION_TB_OPERATION_LOOKUP_ACCOUNTS, events);
 
-        async {
-            let msg = rx.await.expect("channel");
-            let responses: &[Account] = handle_message(&msg)?;
-            Ok(Vec::from(responses))
-        }
+        telemetry::instrument(span, async {
+            let msg = request.await?;
+            handle_message(&msg)
+        })
     }
 
     /// Query individual transfers.
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned future will not cancel the request.
+    /// dropping the returned future will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Maximum batch size
     ///
@@ -829,25 +1320,20 @@ impl Client {
         &self,
         events: &[u128],
     ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
-        let (packet, rx) =
-            create_packet::<u128>(tbc::TB_OPERATION_TB_OPERATION_LOOKUP_TRANSFERS, events);
+        let span = telemetry::Span::start("lookup_transfers", events.len());
+        let request = self.submit::<u128>(tbc::TB_OPERATION_TB_OPERATION_LOOKUP_TRANSFERS, events);
 
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
-
-        async {
-            let msg = rx.await.expect("channel");
-            let responses: &[Transfer] = handle_message(&msg)?;
-            Ok(Vec::from(responses))
-        }
+        telemetry::instrument(span, async {
+            let msg = request.await?;
+            handle_message(&msg)
+        })
     }
 
     /// Query multiple transfers for a single account.
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned future will not cancel the request.
+    /// dropping the returned future will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Errors
     ///
@@ -860,28 +1346,23 @@ impl Client {
         &self,
         event: AccountFilter,
     ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
-        let (packet, rx) = create_packet::<AccountFilter>(
+        let span = telemetry::Span::start("get_account_transfers", 1);
+        let request = self.submit::<AccountFilter>(
             tbc::TB_OPERATION_TB_OPERATION_GET_ACCOUNT_TRANSFERS,
             &[event],
         );
 
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
-
-        async {
-            let msg = rx.await.expect("channel");
-            let result: &[Transfer] = handle_message(&msg)?;
-
-            Ok(result.to_vec())
-        }
+        telemetry::instrument(span, async {
+            let msg = request.await?;
+            handle_message(&msg)
+        })
     }
 
     /// Query historical account balances for a single account.
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned future will not cancel the request.
+    /// dropping the returned future will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Errors
     ///
@@ -894,28 +1375,23 @@ impl Client {
         &self,
         event: AccountFilter,
     ) -> impl Future<Output = Result<Vec<AccountBalance>, PacketStatus>> {
-        let (packet, rx) = create_packet::<AccountFilter>(
+        let span = telemetry::Span::start("get_account_balances", 1);
+        let request = self.submit::<AccountFilter>(
             tbc::TB_OPERATION_TB_OPERATION_GET_ACCOUNT_BALANCES,
             &[event],
         );
 
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
-
-        async {
-            let msg = rx.await.expect("channel");
-            let result: &[AccountBalance] = handle_message(&msg)?;
-
-            Ok(result.to_vec())
-        }
+        telemetry::instrument(span, async {
+            let msg = request.await?;
+            handle_message(&msg)
+        })
     }
 
     /// Query multiple accounts related by fields and timestamps.
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned future will not cancel the request.
+    /// dropping the returned future will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Errors
     ///
@@ -928,26 +1404,22 @@ impl Client {
         &self,
         event: QueryFilter,
     ) -> impl Future<Output = Result<Vec<Account>, PacketStatus>> {
-        let (packet, rx) =
-            create_packet::<QueryFilter>(tbc::TB_OPERATION_TB_OPERATION_QUERY_ACCOUNTS, &[event]);
-
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
-
-        async {
-            let msg = rx.await.expect("channel");
-            let result: &[Account] = handle_message(&msg)?;
-
-            Ok(result.to_vec())
-        }
+        let mut span = telemetry::Span::start("query_accounts", 1);
+        span.ledger(Some(event.ledger).filter(|&ledger| ledger != 0));
+        let request =
+            self.submit::<QueryFilter>(tbc::TB_OPERATION_TB_OPERATION_QUERY_ACCOUNTS, &[event]);
+
+        telemetry::instrument(span, async {
+            let msg = request.await?;
+            handle_message(&msg)
+        })
     }
 
     /// Query multiple transfers related by fields and timestamps.
     ///
     /// The request is queued for submission prior to return of this function;
-    /// dropping the returned future will not cancel the request.
+    /// dropping the returned future will not cancel the request, unless it
+    /// is waiting for the client's [`RateLimit`].
     ///
     /// # Errors
     ///
@@ -960,20 +1432,15 @@ impl Client {
         &self,
         event: QueryFilter,
     ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
-        let (packet, rx) =
-            create_packet::<QueryFilter>(tbc::TB_OPERATION_TB_OPERATION_QUERY_TRANSFERS, &[event]);
-
-        unsafe {
-            let status = tbc::tb_client_submit(self.client, Box::into_raw(packet));
-            assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-        }
-
-        async {
-            let msg = rx.await.expect("channel");
-            let result: &[Transfer] = handle_message(&msg)?;
-
-            Ok(result.to_vec())
-        }
+        let mut span = telemetry::Span::start("query_transfers", 1);
+        span.ledger(Some(event.ledger).filter(|&ledger| ledger != 0));
+        let request =
+            self.submit::<QueryFilter>(tbc::TB_OPERATION_TB_OPERATION_QUERY_TRANSFERS, &[event]);
+
+        telemetry::instrument(span, async {
+            let msg = request.await?;
+            handle_message(&msg)
+        })
     }
 
     /// Close the client and asynchronously wait for completion.
@@ -984,637 +1451,441 @@ impl Client {
     ///
     /// Calling `close` will cancel any pending requests. This is only possible
     /// if the futures for those requests were dropped without awaiting them.
-    pub fn close(mut self) -> impl Future<Output = ()> {
-        struct SendClient(*mut tbc::tb_client_t);
-        unsafe impl Send for SendClient {}
-
-        let client = std::mem::replace(&mut self.client, std::ptr::null_mut());
-        let client = SendClient(client);
-
-        let (tx, rx) = channel::<Infallible>();
-
-        std::thread::spawn(move || {
-            let client = client;
-            unsafe {
-                // This is a blocking function so we're calling it offthread.
-                let status = tbc::tb_client_deinit(client.0);
-                assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
-                std::mem::drop(Box::from_raw(client.0));
-            }
-            drop(tx);
-        });
+    /// To let them complete first, use [`Client::drain_and_close`].
+    pub fn close(self) -> impl Future<Output = ()> {
+        let close = self.core.close();
 
         async {
-            // wait for the channel to close
-            let _ = rx.await;
-        }
-    }
-}
-
-impl Drop for Client {
-    fn drop(&mut self) {
-        if !self.client.is_null() {
-            let close_future = Client {
-                client: self.client,
+            if let Some(close) = close {
+                close.await;
             }
-            .close();
-            // NB: Rust 1.68 clippy - specifically - want's an explicit drop for this future.
-            drop(close_future);
         }
     }
-}
-
-impl fmt::Debug for Client {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        f.write_str("Client")
-    }
-}
-
-/// Make basic assertions about the ABI of our types.
-///
-/// We don't actually use some of the C types at all,
-/// instead casting directly to hand-written Rust types.
-///
-/// These assertions give us some confidence those types
-/// might be possibly correct.
-fn assert_abi_compatibility() {
-    assert_eq!(
-        std::mem::size_of::<Account>(),
-        std::mem::size_of::<tbc::tb_account_t>()
-    );
-    assert_eq!(
-        std::mem::align_of::<Account>(),
-        std::mem::align_of::<tbc::tb_account_t>()
-    );
-    assert_eq!(
-        std::mem::size_of::<AccountFlags>(),
-        std::mem::size_of::<tbc::TB_ACCOUNT_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::align_of::<AccountFlags>(),
-        std::mem::align_of::<tbc::TB_ACCOUNT_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::size_of::<Transfer>(),
-        std::mem::size_of::<tbc::tb_transfer_t>()
-    );
-    assert_eq!(
-        std::mem::align_of::<Transfer>(),
-        std::mem::align_of::<tbc::tb_transfer_t>()
-    );
-    assert_eq!(
-        std::mem::size_of::<TransferFlags>(),
-        std::mem::size_of::<tbc::TB_TRANSFER_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::align_of::<TransferFlags>(),
-        std::mem::align_of::<tbc::TB_TRANSFER_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::size_of::<AccountFilter>(),
-        std::mem::size_of::<tbc::tb_account_filter_t>()
-    );
-    assert_eq!(
-        std::mem::align_of::<AccountFilter>(),
-        std::mem::align_of::<tbc::tb_account_filter_t>()
-    );
-    assert_eq!(
-        std::mem::size_of::<AccountFilterFlags>(),
-        std::mem::size_of::<tbc::TB_ACCOUNT_FILTER_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::align_of::<AccountFilterFlags>(),
-        std::mem::align_of::<tbc::TB_ACCOUNT_FILTER_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::size_of::<AccountBalance>(),
-        std::mem::size_of::<tbc::tb_account_balance_t>()
-    );
-    assert_eq!(
-        std::mem::align_of::<AccountBalance>(),
-        std::mem::align_of::<tbc::tb_account_balance_t>()
-    );
-    assert_eq!(
-        std::mem::size_of::<QueryFilter>(),
-        std::mem::size_of::<tbc::tb_query_filter_t>()
-    );
-    assert_eq!(
-        std::mem::align_of::<QueryFilter>(),
-        std::mem::align_of::<tbc::tb_query_filter_t>()
-    );
-    assert_eq!(
-        std::mem::size_of::<QueryFilterFlags>(),
-        std::mem::size_of::<tbc::TB_QUERY_FILTER_FLAGS>()
-    );
-    assert_eq!(
-        std::mem::align_of::<QueryFilterFlags>(),
-        std::mem::align_of::<tbc::TB_QUERY_FILTER_FLAGS>()
-    );
-}
 
-/// A TigerBeetle account.
-///
-/// # Protocol reference
-///
-/// [`Account`](https://docs.tigerbeetle.com/reference/account/).
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Account {
-    pub id: u128,
-    pub debits_pending: u128,
-    pub debits_posted: u128,
-    pub credits_pending: u128,
-    pub credits_posted: u128,
-    pub user_data_128: u128,
-    pub user_data_64: u64,
-    pub user_data_32: u32,
-    pub reserved: Reserved<4>,
-    pub ledger: u32,
-    pub code: u16,
-    pub flags: AccountFlags,
-    pub timestamp: u64,
-}
-
-bitflags! {
-    /// Bitflags for the `flags` field of [`Account`].
+    /// Wait for in-flight requests to complete, for up to `timeout`, then
+    /// close the client.
     ///
-    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    /// This takes the client, so no new requests can be made, but requests
+    /// whose futures were dropped without awaiting them still complete,
+    /// except those that were deferred by the client's [`RateLimit`], which
+    /// are cancelled.
+    /// Retries of requests whose futures are still pending are not waited
+    /// for, and fail with [`PacketStatus::ClientShutdown`].
     ///
-    /// # Protocol reference
+    /// # Errors
     ///
-    /// [`Account.flags`](https://docs.tigerbeetle.com/reference/account/#flags).
-    #[repr(transparent)]
-    #[derive(Copy, Clone, Debug, Default)]
-    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
-    pub struct AccountFlags: u16 {
-        const None = 0;
-        const Linked = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_LINKED;
-        const DebitsMustNotExceedCredits = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_DEBITS_MUST_NOT_EXCEED_CREDITS;
-        const CreditsMustNotExceedDebits = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_CREDITS_MUST_NOT_EXCEED_DEBITS;
-        const History = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_HISTORY;
-        const Imported = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_IMPORTED;
-        const Closed = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_CLOSED;
-    }
-}
-
-/// A transfer between accounts.
-///
-/// # Protocol reference
-///
-/// [`Transfer`](https://docs.tigerbeetle.com/reference/transfer).
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Transfer {
-    pub id: u128,
-    pub debit_account_id: u128,
-    pub credit_account_id: u128,
-    pub amount: u128,
-    pub pending_id: u128,
-    pub user_data_128: u128,
-    pub user_data_64: u64,
-    pub user_data_32: u32,
-    pub timeout: u32,
-    pub ledger: u32,
-    pub code: u16,
-    pub flags: TransferFlags,
-    pub timestamp: u64,
-}
-
-bitflags! {
-    /// Bitflags for the `flags` field of [`Transfer`].
+    /// Returns [`ClientError::Timeout`] if requests were still in flight after
+    /// `timeout`, in which case they were cancelled as by [`Client::close`].
+    /// The client is closed either way.
     ///
-    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    /// # Example
     ///
-    /// # Protocol reference
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tigerbeetle as tb;
     ///
-    /// [`Transfer.flags`](https://docs.tigerbeetle.com/reference/transfer/#flags).
-    #[repr(transparent)]
-    #[derive(Copy, Clone, Debug, Default)]
-    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
-    pub struct TransferFlags: u16 {
-        const Linked = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_LINKED;
-        const Pending = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_PENDING;
-        const PostPendingTransfer = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_POST_PENDING_TRANSFER;
-        const VoidPendingTransfer = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_VOID_PENDING_TRANSFER;
-        const BalancingDebit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_BALANCING_DEBIT;
-        const BalancingCredit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_BALANCING_CREDIT;
-        const ClosingDebit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_CLOSING_DEBIT;
-        const ClosingCredit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_CLOSING_CREDIT;
-        const Imported = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_IMPORTED;
-    }
-}
+    /// # async fn example(client: tb::Client, transfers: &[tb::Transfer]) {
+    /// // Fire and forget, with no rate limit to defer the request.
+    /// drop(client.create_transfers(transfers));
+    ///
+    /// if client.drain_and_close(Duration::from_secs(5)).await.is_err() {
+    ///     eprintln!("transfers may not have been created");
+    /// }
+    /// # }
+    /// ```
+    pub fn drain_and_close(
+        self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), ClientError>> {
+        let idle = self.core.idle();
+        let drained = with_deadline(
+            async {
+                idle.await;
+                Ok(())
+            },
+            timeout,
+        );
 
-/// Filter for querying transfers and historical balances.
-///
-/// # Protocol reference
-///
-/// [`AccountFilter`](https://docs.tigerbeetle.com/reference/account-filter).
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct AccountFilter {
-    pub account_id: u128,
-    pub user_data_128: u128,
-    pub user_data_64: u64,
-    pub user_data_32: u32,
-    pub code: u16,
-    pub reserved: Reserved<58>,
-    pub timestamp_min: u64,
-    pub timestamp_max: u64,
-    pub limit: u32,
-    pub flags: AccountFilterFlags,
-}
+        async move {
+            let result = drained.await;
+            self.close().await;
+            result
+        }
+    }
 
-bitflags! {
-    /// Bitflags for the `flags` field of [`AccountFilter`].
-    ///
-    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    /// Submit a request, retrying according to the retry policy.
     ///
-    /// # Protocol reference
-    ///
-    /// [`AccountFilter.flags`](https://docs.tigerbeetle.com/reference/account-filter/#flags).
-    #[repr(transparent)]
-    #[derive(Copy, Clone, Debug, Default)]
-    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
-    pub struct AccountFilterFlags: u32 {
-        const Debits = tbc::TB_ACCOUNT_FILTER_FLAGS_TB_ACCOUNT_FILTER_DEBITS;
-        const Credits = tbc::TB_ACCOUNT_FILTER_FLAGS_TB_ACCOUNT_FILTER_CREDITS;
-        const Reversed = tbc::TB_ACCOUNT_FILTER_FLAGS_TB_ACCOUNT_FILTER_REVERSED;
+    /// The request is queued before this function returns, unless it exceeds
+    /// the rate limit, in which case it is queued by the returned future once
+    /// admitted. The returned future resolves to the completion of the first
+    /// successful attempt.
+    fn submit<Event: wire::Wire + Send + 'static>(
+        &self,
+        op: u8, // TB_OPERATION
+        events: &[Event],
+    ) -> impl Future<Output = Result<CompletionMessage<Event>, PacketStatus>> {
+        self.submit_with_permit(op, events, None)
     }
-}
 
-/// An account balance at a point in time.
-///
-/// # Protocol reference
-///
-/// [`AccountBalance`](https://docs.tigerbeetle.com/reference/account-balance/).
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct AccountBalance {
-    pub debits_pending: u128,
-    pub debits_posted: u128,
-    pub credits_pending: u128,
-    pub credits_posted: u128,
-    pub timestamp: u64,
-    pub reserved: Reserved<56>,
-}
+    /// Submit a request that may already have been admitted by the rate
+    /// limiter. See [`Client::submit`].
+    fn submit_with_permit<Event: wire::Wire + Send + 'static>(
+        &self,
+        op: u8, // TB_OPERATION
+        events: &[Event],
+        permit: Option<rate_limit::Permit>,
+    ) -> impl Future<Output = Result<CompletionMessage<Event>, PacketStatus>> {
+        let core = Arc::clone(&self.core);
+        let retry_policy = self.retry_policy.clone();
+        let on_event = self.on_event.clone();
+        // Started now, so that the timeout includes waiting to be polled.
+        let deadline = self.request_timeout.map(timer::sleep);
+
+        debug!(
+            operation = operation_name(op),
+            events = events.len(),
+            "submitting request"
+        );
+        telemetry::record_request(operation_name(op), events.len());
+        let started = Instant::now();
+
+        // Requests that would exceed the rate limit wait for admission, with
+        // a copy of their events, before being submitted.
+        let (permit, deferred) = match (permit, &self.limiter) {
+            (Some(permit), _) => (Some(permit), None),
+            (None, None) => (None, None),
+            (None, Some(limiter)) => match limiter.try_acquire(&[events.len()]) {
+                Ok(mut permits) => (permits.pop(), None),
+                Err(WouldBlock) => (None, Some((Arc::clone(limiter), events.to_vec()))),
+            },
+        };
+
+        let mut session = core.session();
+        let rx = if deferred.is_none() {
+            let (packet, rx) = create_packet::<Event>(op, events);
+            session.submit(packet);
+            Some(rx)
+        } else {
+            None
+        };
+
+        let notify = on_event.clone();
+        let request = async move {
+            // Held until the request, including its retries, completes.
+            let mut _permit = permit;
+            let mut rx = match (rx, deferred) {
+                (Some(rx), _) => rx,
+                (None, Some((limiter, events))) => {
+                    _permit = Some(limiter.acquire(events.len()).await);
+                    session = core.session();
+                    let (packet, rx) = create_packet::<Event>(op, &events);
+                    session.submit(packet);
+                    rx
+                }
+                (None, None) => unreachable!(),
+            };
+            let mut attempt = 1;
+            let mut resubmitted = false;
+            loop {
+                let msg = rx.await.expect("channel");
+
+                let status = msg.packet.0.status;
+                if status == tbc::TB_PACKET_STATUS_TB_PACKET_OK {
+                    telemetry::record_completion(operation_name(op), started, None);
+                    return Ok(msg);
+                }
+
+                let status = PacketStatus::from(status);
+                if let Some(reason) = EvictionReason::from_status(status) {
+                    if core.evicted(&session, reason) {
+                        events::notify(&on_event, ClientEvent::Evicted(reason));
+                    }
+                }
+                if status == PacketStatus::ClientEvicted {
+                    // An evicted session can't be used again, whether or not
+                    // this request is retried.
+                    core.replace_session(&session);
+                }
+                match retry_policy.next_retry(operation_name(op), attempt, status) {
+                    Some(retry) => {
+                        warn!(
+                            operation = retry.operation,
+                            attempt = retry.attempt,
+                            status = %retry.status,
+                            delay_ms = retry.delay.as_millis() as u64,
+                            "retrying request"
+                        );
+                        retry_policy.notify(&retry);
+                        events::notify(&on_event, ClientEvent::Retry(retry));
+                        timer::sleep(retry.delay).await;
+                    }
+                    // Queries have no effect, so are resubmitted to the new
+                    // session once even if the policy doesn't retry them.
+                    None if status == PacketStatus::ClientEvicted
+                        && is_query(op)
+                        && !resubmitted =>
+                    {
+                        debug!(
+                            operation = operation_name(op),
+                            "resubmitting query after eviction"
+                        );
+                        resubmitted = true;
+                    }
+                    None => {
+                        debug!(
+                            operation = operation_name(op),
+                            attempt,
+                            status = %status,
+                            "request failed"
+                        );
+                        telemetry::record_completion(operation_name(op), started, Some(status));
+                        events::notify(
+                            &on_event,
+                            ClientEvent::Failed {
+                                operation: operation_name(op),
+                                status,
+                            },
+                        );
+                        return Err(status);
+                    }
+                }
+                session = core.session();
+
+                let (packet, rx_next) = create_packet::<Event>(op, &msg.events);
+                session.submit(packet);
+                rx = rx_next;
+                attempt += 1;
+            }
+        };
 
-/// Parameters for querying accounts and transfers.
-///
-/// # Protocol reference
-///
-/// [`QueryFilter`](https://docs.tigerbeetle.com/reference/query-filter/).
-#[repr(C)]
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct QueryFilter {
-    pub user_data_128: u128,
-    pub user_data_64: u64,
-    pub user_data_32: u32,
-    pub ledger: u32,
-    pub code: u16,
-    pub reserved: Reserved<6>,
-    pub timestamp_min: u64,
-    pub timestamp_max: u64,
-    pub limit: u32,
-    pub flags: QueryFilterFlags,
-}
+        async move {
+            let deadline = match deadline {
+                Some(deadline) => deadline,
+                None => return request.await,
+            };
+            let result = cancellation::with_request_timeout(request, deadline).await;
+            if let Err(PacketStatus::Timeout) = result {
+                debug!(operation = operation_name(op), "request timed out");
+                telemetry::record_completion(
+                    operation_name(op),
+                    started,
+                    Some(PacketStatus::Timeout),
+                );
+                events::notify(
+                    &notify,
+                    ClientEvent::Failed {
+                        operation: operation_name(op),
+                        status: PacketStatus::Timeout,
+                    },
+                );
+            }
+            result
+        }
+    }
 
-bitflags! {
-    /// Bitflags for the `flags` field of [`QueryFilter`].
-    ///
-    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
-    ///
-    /// # Protocol reference
+    /// Submit `create_*` events, split into requests of at most [`BATCH_MAX`]
+    /// events if batch splitting is enabled.
     ///
-    /// [`QueryFilter.flags`](https://docs.tigerbeetle.com/reference/query-filter/#flags).
-    #[repr(transparent)]
-    #[derive(Copy, Clone, Debug, Default)]
-    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
-    pub struct QueryFilterFlags: u32 {
-        const Reversed = tbc::TB_QUERY_FILTER_FLAGS_TB_QUERY_FILTER_REVERSED;
+    /// Returns the future for each request, paired with the offset of its
+    /// first event in `events`.
+    #[allow(clippy::type_complexity)]
+    fn submit_split<Event: Linkable + wire::Wire + Send + 'static>(
+        &self,
+        op: u8, // TB_OPERATION
+        events: &[Event],
+    ) -> Result<
+        Vec<(
+            usize,
+            impl Future<Output = Result<CompletionMessage<Event>, PacketStatus>>,
+        )>,
+        PacketStatus,
+    > {
+        let chunks = self.split(events)?;
+        let permits = chunks.iter().map(|_| None).collect();
+        Ok(self.submit_chunks(op, chunks, permits))
     }
-}
 
-/// The result of a single [`create_accounts`] event.
-///
-/// For the meaning of individual enum variants see the linked protocol reference.
-///
-/// See also [`CreateAccountsResult`] (note the plural), the type directly
-/// returned by `create_accunts`, and which contains an additional index for
-/// relating results with input events.
-///
-/// [`create_accounts`]: `Client::create_accounts`
-///
-/// # Protocol reference
-///
-/// [`CreateAccountResult`](https://docs.tigerbeetle.com/reference/requests/create_accounts/#result).
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-#[non_exhaustive]
-pub enum CreateAccountResult {
-    Ok,
-    LinkedEventFailed,
-    LinkedEventChainOpen,
-    ImportedEventExpected,
-    ImportedEventNotExpected,
-    TimestampMustBeZero,
-    ImportedEventTimestampOutOfRange,
-    ImportedEventTimestampMustNotAdvance,
-    ReservedField,
-    ReservedFlag,
-    IdMustNotBeZero,
-    IdMustNotBeIntMax,
-    ExistsWithDifferentFlags,
-    ExistsWithDifferentUserData128,
-    ExistsWithDifferentUserData64,
-    ExistsWithDifferentUserData32,
-    ExistsWithDifferentLedger,
-    ExistsWithDifferentCode,
-    Exists,
-    FlagsAreMutuallyExclusive,
-    DebitsPendingMustBeZero,
-    DebitsPostedMustBeZero,
-    CreditsPendingMustBeZero,
-    CreditsPostedMustBeZero,
-    LedgerMustNotBeZero,
-    CodeMustNotBeZero,
-    ImportedEventTimestampMustNotRegress,
-}
+    /// Like [`Client::submit_split`], but fails with [`WouldBlock`] unless
+    /// all of the requests are admitted by the rate limiter at once.
+    #[allow(clippy::type_complexity)]
+    fn try_submit_split<Event: Linkable + wire::Wire + Send + 'static>(
+        &self,
+        op: u8, // TB_OPERATION
+        events: &[Event],
+    ) -> Result<
+        Result<
+            Vec<(
+                usize,
+                impl Future<Output = Result<CompletionMessage<Event>, PacketStatus>>,
+            )>,
+            PacketStatus,
+        >,
+        WouldBlock,
+    > {
+        let chunks = match self.split(events) {
+            Ok(chunks) => chunks,
+            Err(status) => return Ok(Err(status)),
+        };
+        let permits = match &self.limiter {
+            Some(limiter) => {
+                let events: Vec<usize> = chunks.iter().map(|chunk| chunk.len()).collect();
+                limiter
+                    .try_acquire(&events)?
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+            None => chunks.iter().map(|_| None).collect(),
+        };
+        Ok(Ok(self.submit_chunks(op, chunks, permits)))
+    }
 
-/// The result of a single [`create_accounts`] event, with index.
-///
-/// [`create_accounts`]: `Client::create_accounts`
-///
-/// # Protocol reference
-///
-/// [`CreateAccountResult`](https://docs.tigerbeetle.com/reference/requests/create_accounts/#result).
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct CreateAccountsResult {
-    pub index: usize,
-    pub result: CreateAccountResult,
+    fn split<'a, Event: Linkable>(
+        &self,
+        events: &'a [Event],
+    ) -> Result<Vec<&'a [Event]>, PacketStatus> {
+        if self.split_batches {
+            linked_chain::split_at_chain_boundaries(events, BATCH_MAX)
+                .ok_or(PacketStatus::TooMuchData)
+        } else {
+            Ok(vec![events])
+        }
+    }
+
+    fn submit_chunks<Event: wire::Wire + Send + 'static>(
+        &self,
+        op: u8, // TB_OPERATION
+        chunks: Vec<&[Event]>,
+        permits: Vec<Option<rate_limit::Permit>>,
+    ) -> Vec<(
+        usize,
+        impl Future<Output = Result<CompletionMessage<Event>, PacketStatus>>,
+    )> {
+        // Submitted at once rather than each after the last completes, so
+        // that all are queued before returning. Their order isn't preserved.
+        let mut offset = 0;
+        let mut requests = Vec::with_capacity(chunks.len());
+        for (chunk, permit) in chunks.into_iter().zip(permits) {
+            requests.push((offset, self.submit_with_permit::<Event>(op, chunk, permit)));
+            offset += chunk.len();
+        }
+        requests
+    }
 }
 
-impl core::fmt::Display for CreateAccountResult {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match self {
-            Self::Ok => f.write_str("ok"),
-            Self::LinkedEventFailed => f.write_str("linked event failed"),
-            Self::LinkedEventChainOpen => f.write_str("linked event chain open"),
-            Self::ImportedEventExpected => f.write_str("imported event expected"),
-            Self::ImportedEventNotExpected => f.write_str("imported event not expected"),
-            Self::TimestampMustBeZero => f.write_str("timestamp must be zero"),
-            Self::ImportedEventTimestampOutOfRange => {
-                f.write_str("imported event timestamp out of range")
-            }
-            Self::ImportedEventTimestampMustNotAdvance => {
-                f.write_str("imported event timestamp must not advance")
-            }
-            Self::ReservedField => f.write_str("reserved field"),
-            Self::ReservedFlag => f.write_str("reserved flag"),
-            Self::IdMustNotBeZero => f.write_str("id must not be zero"),
-            Self::IdMustNotBeIntMax => f.write_str("id must not be int max"),
-            Self::ExistsWithDifferentFlags => f.write_str("exists with different flags"),
-            Self::ExistsWithDifferentUserData128 => {
-                f.write_str("exists with different user_data_128")
-            }
-            Self::ExistsWithDifferentUserData64 => {
-                f.write_str("exists with different user_data_64")
-            }
-            Self::ExistsWithDifferentUserData32 => {
-                f.write_str("exists with different user_data_32")
-            }
-            Self::ExistsWithDifferentLedger => f.write_str("exists with different ledger"),
-            Self::ExistsWithDifferentCode => f.write_str("exists with different code"),
-            Self::Exists => f.write_str("exists"),
-            Self::FlagsAreMutuallyExclusive => f.write_str("flags are mutually exclusive"),
-            Self::DebitsPendingMustBeZero => f.write_str("debits_pending must be zero"),
-            Self::DebitsPostedMustBeZero => f.write_str("debits_posted must be zero"),
-            Self::CreditsPendingMustBeZero => f.write_str("credits_pending must be zero"),
-            Self::CreditsPostedMustBeZero => f.write_str("credits_posted must be zero"),
-            Self::LedgerMustNotBeZero => f.write_str("ledger must not be zero"),
-            Self::CodeMustNotBeZero => f.write_str("code must not be zero"),
-            Self::ImportedEventTimestampMustNotRegress => {
-                f.write_str("imported event timestamp must not regress")
-            }
+#[cfg(feature = "std")]
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Some(close_future) = self.core.close() {
+            // NB: Rust 1.68 clippy - specifically - want's an explicit drop for this future.
+            drop(close_future);
         }
     }
 }
 
-/// The result of a single [`create_transfers`] event.
-///
-/// For the meaning of individual enum variants see the linked protocol reference.
-///
-/// See also [`CreateTransfersResult`] (note the plural), the type directly
-/// returned by `create_accunts`, and which contains an additional index for
-/// relating results with input events.
-///
-/// [`create_transfers`]: `Client::create_transfers`
-///
-/// # Protocol reference
-///
-/// [`CreateTransferResult`](https://docs.tigerbeetle.com/reference/requests/create_transfers/#result).
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-#[non_exhaustive]
-pub enum CreateTransferResult {
-    Ok,
-    LinkedEventFailed,
-    LinkedEventChainOpen,
-    ImportedEventExpected,
-    ImportedEventNotExpected,
-    TimestampMustBeZero,
-    ImportedEventTimestampOutOfRange,
-    ImportedEventTimestampMustNotAdvance,
-    ReservedFlag,
-    IdMustNotBeZero,
-    IdMustNotBeIntMax,
-    ExistsWithDifferentFlags,
-    ExistsWithDifferentPendingId,
-    ExistsWithDifferentTimeout,
-    ExistsWithDifferentDebitAccountId,
-    ExistsWithDifferentCreditAccountId,
-    ExistsWithDifferentAmount,
-    ExistsWithDifferentUserData128,
-    ExistsWithDifferentUserData64,
-    ExistsWithDifferentUserData32,
-    ExistsWithDifferentLedger,
-    ExistsWithDifferentCode,
-    Exists,
-    IdAlreadyFailed,
-    FlagsAreMutuallyExclusive,
-    DebitAccountIdMustNotBeZero,
-    DebitAccountIdMustNotBeIntMax,
-    CreditAccountIdMustNotBeZero,
-    CreditAccountIdMustNotBeIntMax,
-    AccountsMustBeDifferent,
-    PendingIdMustBeZero,
-    PendingIdMustNotBeZero,
-    PendingIdMustNotBeIntMax,
-    PendingIdMustBeDifferent,
-    TimeoutReservedForPendingTransfer,
-    ClosingTransferMustBePending,
-    LedgerMustNotBeZero,
-    CodeMustNotBeZero,
-    DebitAccountNotFound,
-    CreditAccountNotFound,
-    AccountsMustHaveTheSameLedger,
-    TransferMustHaveTheSameLedgerAsAccounts,
-    PendingTransferNotFound,
-    PendingTransferNotPending,
-    PendingTransferHasDifferentDebitAccountId,
-    PendingTransferHasDifferentCreditAccountId,
-    PendingTransferHasDifferentLedger,
-    PendingTransferHasDifferentCode,
-    ExceedsPendingTransferAmount,
-    PendingTransferHasDifferentAmount,
-    PendingTransferAlreadyPosted,
-    PendingTransferAlreadyVoided,
-    PendingTransferExpired,
-    ImportedEventTimestampMustNotRegress,
-    ImportedEventTimestampMustPostdateDebitAccount,
-    ImportedEventTimestampMustPostdateCreditAccount,
-    ImportedEventTimeoutMustBeZero,
-    DebitAccountAlreadyClosed,
-    CreditAccountAlreadyClosed,
-    OverflowsDebitsPending,
-    OverflowsCreditsPending,
-    OverflowsDebitsPosted,
-    OverflowsCreditsPosted,
-    OverflowsDebits,
-    OverflowsCredits,
-    OverflowsTimeout,
-    ExceedsCredits,
-    ExceedsDebits,
+#[cfg(feature = "std")]
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.write_str("Client")
+    }
 }
 
-/// The result of a single [`create_transfers`] event, with index.
-///
-/// [`create_transfers`]: `Client::create_transfers`
+/// Assert, at compile time, that our types have the layout of the C types in
+/// `tb_client.h`.
 ///
-/// # Protocol reference
+/// We don't actually use some of the C types at all,
+/// instead casting directly to hand-written Rust types,
+/// so a change to the upstream format must fail the build
+/// rather than silently corrupt data.
 ///
-/// [`CreateTransferResult`](https://docs.tigerbeetle.com/reference/requests/create_transfers/#result).
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct CreateTransfersResult {
-    pub index: usize,
-    pub result: CreateTransferResult,
+/// Field offsets need `core::mem::offset_of`, and are only checked when the
+/// compiler has it (see build.rs).
+macro_rules! assert_layout {
+    ($($rust:ty = $c:ty { $($field:ident),* $(,)? }),* $(,)?) => {
+        $(
+            const _: () = assert!(core::mem::size_of::<$rust>() == core::mem::size_of::<$c>());
+            const _: () = assert!(core::mem::align_of::<$rust>() == core::mem::align_of::<$c>());
+            $(
+                #[cfg(tb_offset_of)]
+                const _: () = assert!(
+                    core::mem::offset_of!($rust, $field) == core::mem::offset_of!($c, $field)
+                );
+            )*
+        )*
+    };
 }
 
-impl core::fmt::Display for CreateTransferResult {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match self {
-            Self::Ok => f.write_str("ok"),
-            Self::LinkedEventFailed => f.write_str("linked event failed"),
-            Self::LinkedEventChainOpen => f.write_str("linked event chain open"),
-            Self::ImportedEventExpected => f.write_str("imported event expected"),
-            Self::ImportedEventNotExpected => f.write_str("imported event not expected"),
-            Self::TimestampMustBeZero => f.write_str("timestamp must be zero"),
-            Self::ImportedEventTimestampOutOfRange => {
-                f.write_str("imported event timestamp out of range")
-            }
-            Self::ImportedEventTimestampMustNotAdvance => {
-                f.write_str("imported event timestamp must not advance")
-            }
-            Self::ReservedFlag => f.write_str("reserved flag"),
-            Self::IdMustNotBeZero => f.write_str("id must not be zero"),
-            Self::IdMustNotBeIntMax => f.write_str("id must not be int max"),
-            Self::ExistsWithDifferentFlags => f.write_str("exists with different flags"),
-            Self::ExistsWithDifferentPendingId => f.write_str("exists with different pending_id"),
-            Self::ExistsWithDifferentTimeout => f.write_str("exists with different timeout"),
-            Self::ExistsWithDifferentDebitAccountId => {
-                f.write_str("exists with different debit_account_id")
-            }
-            Self::ExistsWithDifferentCreditAccountId => {
-                f.write_str("exists with different credit_account_id")
-            }
-            Self::ExistsWithDifferentAmount => f.write_str("exists with different amount"),
-            Self::ExistsWithDifferentUserData128 => {
-                f.write_str("exists with different user_data_128")
-            }
-            Self::ExistsWithDifferentUserData64 => {
-                f.write_str("exists with different user_data_64")
-            }
-            Self::ExistsWithDifferentUserData32 => {
-                f.write_str("exists with different user_data_32")
-            }
-            Self::ExistsWithDifferentLedger => f.write_str("exists with different ledger"),
-            Self::ExistsWithDifferentCode => f.write_str("exists with different code"),
-            Self::Exists => f.write_str("exists"),
-            Self::IdAlreadyFailed => f.write_str("id already failed"),
-            Self::FlagsAreMutuallyExclusive => f.write_str("flags are mutually exclusive"),
-            Self::DebitAccountIdMustNotBeZero => f.write_str("debit_account_id must not be zero"),
-            Self::DebitAccountIdMustNotBeIntMax => {
-                f.write_str("debit_account_id must not be int max")
-            }
-            Self::CreditAccountIdMustNotBeZero => f.write_str("credit_account_id must not be zero"),
-            Self::CreditAccountIdMustNotBeIntMax => {
-                f.write_str("credit_account_id must not be int max")
-            }
-            Self::AccountsMustBeDifferent => f.write_str("accounts must be different"),
-            Self::PendingIdMustBeZero => f.write_str("pending_id must be zero"),
-            Self::PendingIdMustNotBeZero => f.write_str("pending_id must not be zero"),
-            Self::PendingIdMustNotBeIntMax => f.write_str("pending_id must not be int max"),
-            Self::PendingIdMustBeDifferent => f.write_str("pending_id must be different"),
-            Self::TimeoutReservedForPendingTransfer => {
-                f.write_str("timeout reserved for pending transfer")
-            }
-            Self::ClosingTransferMustBePending => f.write_str("closing transfer must be pending"),
-            Self::LedgerMustNotBeZero => f.write_str("ledger must not be zero"),
-            Self::CodeMustNotBeZero => f.write_str("code must not be zero"),
-            Self::DebitAccountNotFound => f.write_str("debit account not found"),
-            Self::CreditAccountNotFound => f.write_str("credit account not found"),
-            Self::AccountsMustHaveTheSameLedger => {
-                f.write_str("accounts must have the same ledger")
-            }
-            Self::TransferMustHaveTheSameLedgerAsAccounts => {
-                f.write_str("transfer must have the same ledger as accounts")
-            }
-            Self::PendingTransferNotFound => f.write_str("pending transfer not found"),
-            Self::PendingTransferNotPending => f.write_str("pending transfer not pending"),
-            Self::PendingTransferHasDifferentDebitAccountId => {
-                f.write_str("pending transfer has different debit_account_id")
-            }
-            Self::PendingTransferHasDifferentCreditAccountId => {
-                f.write_str("pending transfer has different credit_account_id")
-            }
-            Self::PendingTransferHasDifferentLedger => {
-                f.write_str("pending transfer has different ledger")
-            }
-            Self::PendingTransferHasDifferentCode => {
-                f.write_str("pending transfer has different code")
-            }
-            Self::ExceedsPendingTransferAmount => f.write_str("exceeds pending transfer amount"),
-            Self::PendingTransferHasDifferentAmount => {
-                f.write_str("pending transfer has different amount")
-            }
-            Self::PendingTransferAlreadyPosted => f.write_str("pending transfer already posted"),
-            Self::PendingTransferAlreadyVoided => f.write_str("pending transfer already voided"),
-            Self::PendingTransferExpired => f.write_str("pending transfer expired"),
-            Self::ImportedEventTimestampMustNotRegress => {
-                f.write_str("imported event timestamp must not regress")
-            }
-            Self::ImportedEventTimestampMustPostdateDebitAccount => {
-                f.write_str("imported event timestamp must postdate debit account")
-            }
-            Self::ImportedEventTimestampMustPostdateCreditAccount => {
-                f.write_str("imported event timestamp must postdate credit account")
-            }
-            Self::ImportedEventTimeoutMustBeZero => {
-                f.write_str("imported event timeout must be zero")
-            }
-            Self::DebitAccountAlreadyClosed => f.write_str("debit account already closed"),
-            Self::CreditAccountAlreadyClosed => f.write_str("credit account already closed"),
-            Self::OverflowsDebitsPending => f.write_str("overflows debits_pending"),
-            Self::OverflowsCreditsPending => f.write_str("overflows credits_pending"),
-            Self::OverflowsDebitsPosted => f.write_str("overflows debits_posted"),
-            Self::OverflowsCreditsPosted => f.write_str("overflows credits_posted"),
-            Self::OverflowsDebits => f.write_str("overflows debits"),
-            Self::OverflowsCredits => f.write_str("overflows credits"),
-            Self::OverflowsTimeout => f.write_str("overflows timeout"),
-            Self::ExceedsCredits => f.write_str("exceeds credits"),
-            Self::ExceedsDebits => f.write_str("exceeds debits"),
-        }
-    }
+assert_layout! {
+    Account = tbc::tb_account_t {
+        id,
+        debits_pending,
+        debits_posted,
+        credits_pending,
+        credits_posted,
+        user_data_128,
+        user_data_64,
+        user_data_32,
+        reserved,
+        ledger,
+        code,
+        flags,
+        timestamp,
+    },
+    AccountFlags = tbc::TB_ACCOUNT_FLAGS {},
+    Transfer = tbc::tb_transfer_t {
+        id,
+        debit_account_id,
+        credit_account_id,
+        amount,
+        pending_id,
+        user_data_128,
+        user_data_64,
+        user_data_32,
+        timeout,
+        ledger,
+        code,
+        flags,
+        timestamp,
+    },
+    TransferFlags = tbc::TB_TRANSFER_FLAGS {},
+    AccountFilter = tbc::tb_account_filter_t {
+        account_id,
+        user_data_128,
+        user_data_64,
+        user_data_32,
+        code,
+        reserved,
+        timestamp_min,
+        timestamp_max,
+        limit,
+        flags,
+    },
+    AccountFilterFlags = tbc::TB_ACCOUNT_FILTER_FLAGS {},
+    AccountBalance = tbc::tb_account_balance_t {
+        debits_pending,
+        debits_posted,
+        credits_pending,
+        credits_posted,
+        timestamp,
+        reserved,
+    },
+    QueryFilter = tbc::tb_query_filter_t {
+        user_data_128,
+        user_data_64,
+        user_data_32,
+        ledger,
+        code,
+        reserved,
+        timestamp_min,
+        timestamp_max,
+        limit,
+        flags,
+    },
+    QueryFilterFlags = tbc::TB_QUERY_FILTER_FLAGS {},
 }
 
 /// Errors resulting from constructing a [`Client`].
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[non_exhaustive]
 pub enum InitStatus {
@@ -1634,7 +1905,9 @@ pub enum InitStatus {
     NetworkSubsystem,
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for InitStatus {}
+#[cfg(feature = "std")]
 impl core::fmt::Display for InitStatus {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
@@ -1651,17 +1924,28 @@ impl core::fmt::Display for InitStatus {
 /// Errors that occur prior to the server processing a batch of operations.
 ///
 /// When one of these is returned as a result of a transaction request,
-/// then all operations in the request can be assumed to have not been processed.
+/// then all operations in the request can be assumed to have not been
+/// processed, except for [`ClientEvicted`](Self::ClientEvicted) and
+/// [`Timeout`](Self::Timeout), after which they may or may not have been.
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[non_exhaustive]
 pub enum PacketStatus {
     /// Too many events were submitted to a multi-event request.
     TooMuchData,
     /// The client was evicted by the server.
+    ///
+    /// The request may or may not have been executed. The client registers
+    /// a new session for later requests, and resubmits queries to it once;
+    /// writes are only resubmitted by a [`RetryPolicy`].
     ClientEvicted,
     /// The client's version is too low.
+    ///
+    /// See [`Client::check_version`].
     ClientReleaseTooLow,
     /// The client's version is too high.
+    ///
+    /// See [`Client::check_version`].
     ClientReleaseTooHigh,
     /// The client was already destructed.
     ClientShutdown,
@@ -1669,13 +1953,22 @@ pub enum PacketStatus {
     ///
     /// This should not be possible in the Rust client.
     InvalidOperation,
-    /// The operation's payload was an incorrect size.
+    /// The operation's payload was an incorrect size, or the reply was
+    /// malformed.
     ///
     /// This should not be possible in the Rust client.
     InvalidDataSize,
+    /// The request did not complete within the client's request timeout.
+    ///
+    /// The request may or may not have been executed. This status is the
+    /// client's own, and has no equivalent in `tb_client`. See
+    /// [`Client::set_request_timeout`].
+    Timeout,
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for PacketStatus {}
+#[cfg(feature = "std")]
 impl core::fmt::Display for PacketStatus {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
@@ -1686,6 +1979,45 @@ impl core::fmt::Display for PacketStatus {
             Self::ClientShutdown => f.write_str("client shutdown"),
             Self::InvalidOperation => f.write_str("invalid operation"),
             Self::InvalidDataSize => f.write_str("invalid data size"),
+            Self::Timeout => f.write_str("timeout"),
+        }
+    }
+}
+
+/// Errors returned by requests made with a deadline or cancellation token.
+///
+/// See [`with_deadline`] and [`with_cancellation`].
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ClientError {
+    /// The request failed. See [`PacketStatus`].
+    Packet(PacketStatus),
+    /// The request did not complete before its deadline.
+    Timeout,
+    /// The request was cancelled.
+    Cancelled,
+}
+
+#[cfg(feature = "std")]
+impl From<PacketStatus> for ClientError {
+    fn from(status: PacketStatus) -> ClientError {
+        match status {
+            PacketStatus::Timeout => ClientError::Timeout,
+            status => ClientError::Packet(status),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ClientError {}
+#[cfg(feature = "std")]
+impl core::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+            Self::Timeout => f.write_str("timeout"),
+            Self::Cancelled => f.write_str("cancelled"),
         }
     }
 }
@@ -1694,36 +2026,26 @@ impl core::fmt::Display for PacketStatus {
 ///
 /// Returned by [`Client::lookup_accounts`] and [`Client::lookup_transfers`]
 /// when the account or transfer does not exist.
+#[cfg(feature = "std")]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct NotFound;
 
+#[cfg(feature = "std")]
 impl std::error::Error for NotFound {}
+#[cfg(feature = "std")]
 impl core::fmt::Display for NotFound {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.write_str("not found")
     }
 }
 
-/// A utility type for representing reserved bytes in structs.
-///
-/// This type is instantiated with [`Default::default`] and typically
-/// does not need to be used directly.
-#[repr(transparent)]
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-pub struct Reserved<const N: usize>([u8; N]);
-
-impl<const N: usize> Default for Reserved<N> {
-    fn default() -> Reserved<N> {
-        Reserved([0; N])
-    }
-}
-
+#[cfg(feature = "std")]
 fn create_packet<Event>(
     op: u8, // TB_OPERATION
     events: &[Event],
 ) -> (Box<tbc::tb_packet_t>, Receiver<CompletionMessage<Event>>)
 where
-    Event: Copy + 'static,
+    Event: wire::Wire + 'static,
 {
     let (tx, rx) = channel::<CompletionMessage<Event>>();
     let callback: Box<OnCompletion> = Box::new(Box::new(
@@ -1746,7 +2068,7 @@ where
                 packet,
                 _timestamp: timestamp,
                 result,
-                _events: events,
+                events,
             });
         },
     ));
@@ -1771,49 +2093,131 @@ where
     (packet, rx)
 }
 
-fn handle_message<CEvent, CResult>(
+/// Combine the results of a `create_accounts` request that may have been split.
+#[cfg(feature = "std")]
+async fn create_accounts_results<Request>(
+    requests: Result<Vec<(usize, Request)>, PacketStatus>,
+) -> Result<Vec<CreateAccountsResult>, PacketStatus>
+where
+    Request: Future<Output = Result<CompletionMessage<Account>, PacketStatus>>,
+{
+    let mut results = Vec::new();
+    for (offset, request) in requests? {
+        let msg = request.await?;
+
+        let responses =
+            wire::decode_create_accounts_results(message_result(&msg)?, msg.events.len())
+                .ok_or(PacketStatus::InvalidDataSize)?;
+
+        results.extend(responses.into_iter().map(|(index, result)| {
+            let result = CreateAccountsResult {
+                index: offset + index,
+                result,
+            };
+            telemetry::record_event_result("create_accounts", &result.result);
+            result
+        }));
+    }
+
+    Ok(results)
+}
+
+/// Combine the results of a `create_transfers` request that may have been split.
+#[cfg(feature = "std")]
+async fn create_transfers_results<Request>(
+    requests: Result<Vec<(usize, Request)>, PacketStatus>,
+) -> Result<Vec<CreateTransfersResult>, PacketStatus>
+where
+    Request: Future<Output = Result<CompletionMessage<Transfer>, PacketStatus>>,
+{
+    let mut results = Vec::new();
+    for (offset, request) in requests? {
+        let msg = request.await?;
+
+        let responses =
+            wire::decode_create_transfers_results(message_result(&msg)?, msg.events.len())
+                .ok_or(PacketStatus::InvalidDataSize)?;
+
+        results.extend(responses.into_iter().map(|(index, result)| {
+            let result = CreateTransfersResult {
+                index: offset + index,
+                result,
+            };
+            telemetry::record_event_result("create_transfers", &result.result);
+            result
+        }));
+    }
+
+    Ok(results)
+}
+
+/// The results of a request, or its status if it failed.
+///
+/// A reply that is not a whole number of results fails with
+/// [`PacketStatus::InvalidDataSize`].
+#[cfg(feature = "std")]
+fn handle_message<CEvent, CResult: wire::Wire>(
     msg: &CompletionMessage<CEvent>,
-) -> Result<&[CResult], PacketStatus> {
-    let packet = &msg.packet.0;
-    let result = &msg.result;
+) -> Result<Vec<CResult>, PacketStatus> {
+    wire::decode(message_result(msg)?).ok_or(PacketStatus::InvalidDataSize)
+}
 
+/// The reply of a request, or its status if it failed.
+#[cfg(feature = "std")]
+fn message_result<CEvent>(msg: &CompletionMessage<CEvent>) -> Result<&[u8], PacketStatus> {
+    let packet = &msg.packet.0;
     if packet.status != tbc::TB_PACKET_STATUS_TB_PACKET_OK {
         return Err(packet.status.into());
     }
+    Ok(&msg.result)
+}
 
-    let result = unsafe {
-        if !result.is_empty() {
-            std::slice::from_raw_parts(
-                result.as_ptr() as *const CResult,
-                result
-                    .len()
-                    .checked_div(mem::size_of::<CResult>())
-                    .expect("div"),
-            )
-        } else {
-            &[]
-        }
-    };
+#[cfg(feature = "std")]
+fn operation_name(op: u8) -> &'static str {
+    match op {
+        tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS => "create_accounts",
+        tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS => "create_transfers",
+        tbc::TB_OPERATION_TB_OPERATION_LOOKUP_ACCOUNTS => "lookup_accounts",
+        tbc::TB_OPERATION_TB_OPERATION_LOOKUP_TRANSFERS => "lookup_transfers",
+        tbc::TB_OPERATION_TB_OPERATION_GET_ACCOUNT_TRANSFERS => "get_account_transfers",
+        tbc::TB_OPERATION_TB_OPERATION_GET_ACCOUNT_BALANCES => "get_account_balances",
+        tbc::TB_OPERATION_TB_OPERATION_QUERY_ACCOUNTS => "query_accounts",
+        tbc::TB_OPERATION_TB_OPERATION_QUERY_TRANSFERS => "query_transfers",
+        _ => "unknown",
+    }
+}
 
-    Ok(result)
+/// Whether `op` only reads, so can be resubmitted without effect.
+#[cfg(feature = "std")]
+fn is_query(op: u8) -> bool {
+    !matches!(
+        op,
+        tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS
+            | tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS
+    )
 }
 
 // Thread-sendable wrapper for the owned packet.
+#[cfg(feature = "std")]
 struct Packet(Box<tbc::tb_packet_t>);
 
 // Safety: after completion, zig no longer touches the packet; we own it exclusively.
+#[cfg(feature = "std")]
 unsafe impl Send for Packet {}
 
+#[cfg(feature = "std")]
 struct CompletionMessage<E> {
     _context: usize,
     packet: Packet,
     _timestamp: u64,
     result: Vec<u8>,
-    _events: Vec<E>,
+    events: Vec<E>,
 }
 
+#[cfg(feature = "std")]
 type OnCompletion = Box<dyn FnOnce(usize, *mut tbc::tb_packet_t, u64, *const u8, u32)>;
 
+#[cfg(feature = "std")]
 extern "C" fn on_completion(
     context: usize,
     packet: *mut tbc::tb_packet_t,
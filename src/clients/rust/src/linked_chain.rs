@@ -0,0 +1,349 @@
+use super::*;
+
+/// A builder for chains of linked events.
+///
+/// Events in a linked chain succeed or fail together. TigerBeetle marks a
+/// chain by setting the `linked` flag on every event but the last one; this
+/// builder sets and clears those flags so the caller doesn't have to.
+///
+/// Chains may be built from either [`Account`]s or [`Transfer`]s.
+///
+/// # Example
+///
+/// ```
+/// use tigerbeetle as tb;
+///
+/// # fn example() -> Result<(), tb::LinkedChainError> {
+/// let transfer = tb::Transfer {
+///     debit_account_id: 1,
+///     credit_account_id: 2,
+///     amount: 10,
+///     ledger: 1,
+///     code: 1,
+///     ..Default::default()
+/// };
+///
+/// let transfers = tb::LinkedChain::new()
+///     .add(tb::Transfer { id: tb::id(), ..transfer })
+///     .add(tb::Transfer { id: tb::id(), ..transfer })
+///     .build()?;
+///
+/// assert!(transfers[0].flags.contains(tb::TransferFlags::Linked));
+/// assert!(!transfers[1].flags.contains(tb::TransferFlags::Linked));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Protocol reference
+///
+/// [Linked Events](https://docs.tigerbeetle.com/coding/linked-events/).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LinkedChain<Event> {
+    events: Vec<Event>,
+}
+
+impl<Event: Linkable> LinkedChain<Event> {
+    /// Create an empty chain.
+    pub fn new() -> LinkedChain<Event> {
+        LinkedChain { events: Vec::new() }
+    }
+
+    /// Append an event to the end of the chain.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, event: Event) -> LinkedChain<Event> {
+        self.push(event);
+        self
+    }
+
+    /// Append an event to the end of the chain, in place.
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// The number of events in the chain.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns `true` if the chain has no events.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Set the `linked` flags and return the events, ready for submission.
+    ///
+    /// The `linked` flag is set on every event but the last, and cleared on
+    /// the last, regardless of how the flags were set on the input events.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`LinkedChainError::Empty`] if there are no events, and
+    /// [`LinkedChainError::TooLong`] if there are more than [`BATCH_MAX`]
+    /// events, since a chain must be submitted within a single request.
+    pub fn build(self) -> Result<Vec<Event>, LinkedChainError> {
+        let mut events = self.events;
+
+        if events.is_empty() {
+            return Err(LinkedChainError::Empty);
+        }
+        if events.len() > BATCH_MAX {
+            return Err(LinkedChainError::TooLong {
+                len: events.len(),
+                max: BATCH_MAX,
+            });
+        }
+
+        let last_index = events.len() - 1;
+        for (index, event) in events.iter_mut().enumerate() {
+            event.set_linked(index != last_index);
+        }
+
+        Ok(events)
+    }
+}
+
+/// Events that may be linked into a [`LinkedChain`].
+///
+/// This trait is sealed and implemented for [`Account`] and [`Transfer`].
+pub trait Linkable: Copy + sealed::Sealed {
+    #[doc(hidden)]
+    fn is_linked(&self) -> bool;
+    #[doc(hidden)]
+    fn set_linked(&mut self, linked: bool);
+}
+
+impl Linkable for Account {
+    fn is_linked(&self) -> bool {
+        self.flags.contains(AccountFlags::Linked)
+    }
+
+    fn set_linked(&mut self, linked: bool) {
+        self.flags.set(AccountFlags::Linked, linked);
+    }
+}
+
+impl Linkable for Transfer {
+    fn is_linked(&self) -> bool {
+        self.flags.contains(TransferFlags::Linked)
+    }
+
+    fn set_linked(&mut self, linked: bool) {
+        self.flags.set(TransferFlags::Linked, linked);
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for super::Account {}
+    impl Sealed for super::Transfer {}
+}
+
+/// Errors resulting from building a [`LinkedChain`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum LinkedChainError {
+    /// The chain has no events.
+    Empty,
+    /// The chain has more events than fit in a single request.
+    TooLong { len: usize, max: usize },
+}
+
+impl std::error::Error for LinkedChainError {}
+impl core::fmt::Display for LinkedChainError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Empty => f.write_str("linked chain is empty"),
+            Self::TooLong { len, max } => {
+                write!(f, "linked chain of {len} events exceeds maximum of {max}")
+            }
+        }
+    }
+}
+
+/// The event that caused a linked chain to fail.
+///
+/// When any event in a chain fails, every other event in the chain fails
+/// with `LinkedEventFailed`. This type identifies the event that failed
+/// for some other reason, which is the cause of the chain's failure.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct LinkedChainFailure<Result> {
+    /// The index of the failed event in the submitted events.
+    pub index: usize,
+    /// The result of the failed event.
+    pub result: Result,
+}
+
+impl LinkedChainFailure<CreateAccountResult> {
+    /// Find the cause of failure in the results of submitting a chain of accounts.
+    ///
+    /// Returns `None` if there are no failures.
+    pub fn from_create_accounts_results(
+        results: &[CreateAccountsResult],
+    ) -> Option<LinkedChainFailure<CreateAccountResult>> {
+        chain_failure(
+            results.iter().map(|r| (r.index, r.result)),
+            CreateAccountResult::LinkedEventFailed,
+        )
+    }
+}
+
+impl LinkedChainFailure<CreateTransferResult> {
+    /// Find the cause of failure in the results of submitting a chain of transfers.
+    ///
+    /// Returns `None` if there are no failures.
+    pub fn from_create_transfers_results(
+        results: &[CreateTransfersResult],
+    ) -> Option<LinkedChainFailure<CreateTransferResult>> {
+        chain_failure(
+            results.iter().map(|r| (r.index, r.result)),
+            CreateTransferResult::LinkedEventFailed,
+        )
+    }
+}
+
+fn chain_failure<Result: Copy + Eq>(
+    mut results: impl Iterator<Item = (usize, Result)>,
+    linked_event_failed: Result,
+) -> Option<LinkedChainFailure<Result>> {
+    let (index, result) = results.find(|(_, result)| *result != linked_event_failed)?;
+    Some(LinkedChainFailure { index, result })
+}
+
+/// Split events into chunks of at most `batch_max` events,
+/// without splitting any linked chain across chunks.
+///
+/// Returns `None` if a linked chain is longer than `batch_max`.
+pub(crate) fn split_at_chain_boundaries<Event: Linkable>(
+    events: &[Event],
+    batch_max: usize,
+) -> Option<Vec<&[Event]>> {
+    assert!(batch_max > 0);
+
+    let mut chunks = Vec::new();
+    let mut rest = events;
+    while rest.len() > batch_max {
+        // Cut after the last event in the window that closes a chain.
+        let chunk_len = rest[..batch_max]
+            .iter()
+            .rposition(|event| !event.is_linked())?
+            + 1;
+        let (chunk, rest_next) = rest.split_at(chunk_len);
+        chunks.push(chunk);
+        rest = rest_next;
+    }
+    chunks.push(rest);
+
+    Some(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_sets_linked_on_all_but_last() {
+        let transfers = LinkedChain::new()
+            .add(Transfer::default())
+            .add(Transfer::default())
+            .add(Transfer {
+                flags: TransferFlags::Linked | TransferFlags::Pending,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(transfers.len(), 3);
+        assert_eq!(transfers[0].flags, TransferFlags::Linked);
+        assert_eq!(transfers[1].flags, TransferFlags::Linked);
+        assert_eq!(transfers[2].flags, TransferFlags::Pending);
+    }
+
+    #[test]
+    fn build_single_event_is_not_linked() {
+        let accounts = LinkedChain::new()
+            .add(Account {
+                flags: AccountFlags::Linked,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(accounts[0].flags, AccountFlags::None);
+    }
+
+    #[test]
+    fn build_errors() {
+        assert_eq!(
+            LinkedChain::<Account>::new().build(),
+            Err(LinkedChainError::Empty)
+        );
+
+        let mut chain = LinkedChain::new();
+        for _ in 0..=BATCH_MAX {
+            chain.push(Transfer::default());
+        }
+        assert_eq!(
+            chain.build(),
+            Err(LinkedChainError::TooLong {
+                len: BATCH_MAX + 1,
+                max: BATCH_MAX,
+            })
+        );
+    }
+
+    #[test]
+    fn failure_finds_cause() {
+        let results = [
+            CreateTransfersResult {
+                index: 0,
+                result: CreateTransferResult::LinkedEventFailed,
+            },
+            CreateTransfersResult {
+                index: 1,
+                result: CreateTransferResult::ExceedsCredits,
+            },
+            CreateTransfersResult {
+                index: 2,
+                result: CreateTransferResult::LinkedEventFailed,
+            },
+        ];
+
+        assert_eq!(
+            LinkedChainFailure::from_create_transfers_results(&results),
+            Some(LinkedChainFailure {
+                index: 1,
+                result: CreateTransferResult::ExceedsCredits,
+            })
+        );
+        assert_eq!(LinkedChainFailure::from_create_accounts_results(&[]), None);
+    }
+
+    #[test]
+    fn split_respects_chains() {
+        let linked = Transfer {
+            flags: TransferFlags::Linked,
+            ..Default::default()
+        };
+        let single = Transfer::default();
+
+        let events = [single; 3];
+        let chunks = split_at_chain_boundaries(&events, 3).unwrap();
+        assert_eq!(chunks, [&events[..]]);
+
+        let events = [single, single, single, single, single];
+        let chunks = split_at_chain_boundaries(&events, 2).unwrap();
+        let chunk_lens: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(chunk_lens, [2, 2, 1]);
+
+        // A chain of three starting at index 1 moves to the second chunk.
+        let events = [single, linked, linked, single, single];
+        let chunks = split_at_chain_boundaries(&events, 3).unwrap();
+        let chunk_lens: Vec<usize> = chunks.iter().map(|c| c.len()).collect();
+        assert_eq!(chunk_lens, [1, 3, 1]);
+
+        // A chain longer than the batch can't be split.
+        let events = [linked, linked, linked, single];
+        assert_eq!(split_at_chain_boundaries(&events, 3), None);
+    }
+}
@@ -0,0 +1,487 @@
+use super::*;
+
+use std::collections::HashMap;
+
+/// A client for several TigerBeetle clusters, sharded by ledger.
+///
+/// Each cluster is assigned a set of ledgers. Accounts and transfers are
+/// created in the cluster that owns their ledger, and queries for a single
+/// ledger go to that cluster. Lookups, and queries that aren't restricted to
+/// a ledger, are sent to every cluster and their results merged.
+///
+/// Because transfers can only move amounts between accounts in the same
+/// ledger, every transfer is executed entirely within one cluster. However,
+/// linked chains must not span clusters, since no cluster could apply the
+/// chain atomically.
+///
+/// Transfers are routed by their own `ledger` field, so post-pending and
+/// void-pending transfers must set it, rather than leaving it zero to
+/// inherit the ledger of the pending transfer.
+///
+/// # Example
+///
+/// ```no_run
+/// use tigerbeetle as tb;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let client = tb::MultiClusterClient::new()
+///     .cluster(tb::Client::new(0, "10.0.0.1:3000")?, &[1, 2])
+///     .cluster(tb::Client::new(1, "10.0.1.1:3000")?, &[3]);
+///
+/// let account = tb::Account {
+///     id: tb::id(),
+///     ledger: 3,
+///     code: 1,
+///     ..Default::default()
+/// };
+/// // Created in cluster 1.
+/// let results = client.create_accounts(&[account]).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct MultiClusterClient {
+    clusters: Vec<Client>,
+    ledgers: HashMap<u32, usize>,
+}
+
+impl MultiClusterClient {
+    /// Create a client with no clusters.
+    pub fn new() -> MultiClusterClient {
+        MultiClusterClient::default()
+    }
+
+    /// Add a cluster, which owns `ledgers`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of `ledgers` is already owned by another cluster.
+    pub fn cluster(mut self, client: Client, ledgers: &[u32]) -> MultiClusterClient {
+        let index = self.clusters.len();
+        for &ledger in ledgers {
+            let owner = self.ledgers.insert(ledger, index);
+            assert!(
+                owner.is_none(),
+                "ledger {ledger} is assigned to two clusters"
+            );
+        }
+        self.clusters.push(client);
+        self
+    }
+
+    /// The client for the cluster that owns `ledger`.
+    pub fn client_for_ledger(&self, ledger: u32) -> Option<&Client> {
+        self.ledgers
+            .get(&ledger)
+            .map(|&index| &self.clusters[index])
+    }
+
+    /// Create one or more accounts, each in the cluster that owns its ledger.
+    ///
+    /// The results are as for [`Client::create_accounts`], with indexes
+    /// relative to `events`.
+    ///
+    /// # Errors
+    ///
+    /// Nothing is submitted if an event's ledger is not owned by any cluster,
+    /// or a linked chain spans clusters.
+    pub async fn create_accounts(
+        &self,
+        events: &[Account],
+    ) -> Result<Vec<CreateAccountsResult>, MultiClusterError> {
+        let requests: Vec<_> = self
+            .route(events, |event| event.ledger)?
+            .into_iter()
+            .map(|(index, shard)| {
+                (
+                    shard.indexes,
+                    self.clusters[index].create_accounts(&shard.events),
+                )
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for (indexes, request) in requests {
+            results.extend(
+                request
+                    .await?
+                    .into_iter()
+                    .map(|result| CreateAccountsResult {
+                        index: indexes[result.index],
+                        ..result
+                    }),
+            );
+        }
+        results.sort_by_key(|result| result.index);
+        Ok(results)
+    }
+
+    /// Create one or more transfers, each in the cluster that owns its ledger.
+    ///
+    /// The results are as for [`Client::create_transfers`], with indexes
+    /// relative to `events`.
+    ///
+    /// # Errors
+    ///
+    /// As for [`MultiClusterClient::create_accounts`].
+    pub async fn create_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<Vec<CreateTransfersResult>, MultiClusterError> {
+        let requests: Vec<_> = self
+            .route(events, |event| event.ledger)?
+            .into_iter()
+            .map(|(index, shard)| {
+                (
+                    shard.indexes,
+                    self.clusters[index].create_transfers(&shard.events),
+                )
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for (indexes, request) in requests {
+            results.extend(
+                request
+                    .await?
+                    .into_iter()
+                    .map(|result| CreateTransfersResult {
+                        index: indexes[result.index],
+                        ..result
+                    }),
+            );
+        }
+        results.sort_by_key(|result| result.index);
+        Ok(results)
+    }
+
+    /// Query individual accounts in every cluster.
+    ///
+    /// As with [`Client::lookup_accounts`], accounts are returned in the
+    /// order of `events`, omitting those that don't exist.
+    pub async fn lookup_accounts(&self, events: &[u128]) -> Result<Vec<Account>, PacketStatus> {
+        let requests: Vec<_> = self
+            .clusters
+            .iter()
+            .map(|client| client.lookup_accounts(events))
+            .collect();
+
+        let mut found = HashMap::new();
+        for request in requests {
+            found.extend(
+                request
+                    .await?
+                    .into_iter()
+                    .map(|account| (account.id, account)),
+            );
+        }
+        Ok(events
+            .iter()
+            .filter_map(|id| found.get(id).copied())
+            .collect())
+    }
+
+    /// Query individual transfers in every cluster.
+    ///
+    /// As with [`Client::lookup_transfers`], transfers are returned in the
+    /// order of `events`, omitting those that don't exist.
+    pub async fn lookup_transfers(&self, events: &[u128]) -> Result<Vec<Transfer>, PacketStatus> {
+        let requests: Vec<_> = self
+            .clusters
+            .iter()
+            .map(|client| client.lookup_transfers(events))
+            .collect();
+
+        let mut found = HashMap::new();
+        for request in requests {
+            found.extend(
+                request
+                    .await?
+                    .into_iter()
+                    .map(|transfer| (transfer.id, transfer)),
+            );
+        }
+        Ok(events
+            .iter()
+            .filter_map(|id| found.get(id).copied())
+            .collect())
+    }
+
+    /// Query multiple transfers for a single account, in every cluster.
+    ///
+    /// See [`Client::get_account_transfers`].
+    pub async fn get_account_transfers(
+        &self,
+        event: AccountFilter,
+    ) -> Result<Vec<Transfer>, PacketStatus> {
+        let requests: Vec<_> = self
+            .clusters
+            .iter()
+            .map(|client| client.get_account_transfers(event))
+            .collect();
+
+        // The account exists in at most one cluster.
+        let mut results = Vec::new();
+        for request in requests {
+            results.extend(request.await?);
+        }
+        Ok(results)
+    }
+
+    /// Query historical account balances for a single account, in every
+    /// cluster.
+    ///
+    /// See [`Client::get_account_balances`].
+    pub async fn get_account_balances(
+        &self,
+        event: AccountFilter,
+    ) -> Result<Vec<AccountBalance>, PacketStatus> {
+        let requests: Vec<_> = self
+            .clusters
+            .iter()
+            .map(|client| client.get_account_balances(event))
+            .collect();
+
+        let mut results = Vec::new();
+        for request in requests {
+            results.extend(request.await?);
+        }
+        Ok(results)
+    }
+
+    /// Query multiple accounts related by fields and timestamps.
+    ///
+    /// If the filter's `ledger` is set the query goes to the cluster that
+    /// owns it, and otherwise to every cluster, in which case the results are
+    /// merged in timestamp order and truncated to the filter's `limit`.
+    ///
+    /// See [`Client::query_accounts`].
+    pub async fn query_accounts(&self, event: QueryFilter) -> Result<Vec<Account>, PacketStatus> {
+        let requests: Vec<_> = self
+            .query_clusters(event)
+            .map(|client| client.query_accounts(event))
+            .collect();
+
+        let mut results = Vec::new();
+        for request in requests {
+            results.extend(request.await?);
+        }
+        Ok(merge_query_results(results, event, |account| {
+            account.timestamp
+        }))
+    }
+
+    /// Query multiple transfers related by fields and timestamps.
+    ///
+    /// Queries are routed as for [`MultiClusterClient::query_accounts`].
+    ///
+    /// See [`Client::query_transfers`].
+    pub async fn query_transfers(&self, event: QueryFilter) -> Result<Vec<Transfer>, PacketStatus> {
+        let requests: Vec<_> = self
+            .query_clusters(event)
+            .map(|client| client.query_transfers(event))
+            .collect();
+
+        let mut results = Vec::new();
+        for request in requests {
+            results.extend(request.await?);
+        }
+        Ok(merge_query_results(results, event, |transfer| {
+            transfer.timestamp
+        }))
+    }
+
+    /// Close every cluster's client and asynchronously wait for completion.
+    ///
+    /// See [`Client::close`].
+    pub async fn close(self) {
+        let closes: Vec<_> = self.clusters.into_iter().map(Client::close).collect();
+        for close in closes {
+            close.await;
+        }
+    }
+
+    /// The clusters that may hold results for a query.
+    fn query_clusters(&self, event: QueryFilter) -> impl Iterator<Item = &Client> {
+        let owner = match event.ledger {
+            0 => None,
+            ledger => Some(self.ledgers.get(&ledger).copied()),
+        };
+        self.clusters
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| match owner {
+                None => true,
+                Some(owner) => owner == Some(*index),
+            })
+            .map(|(_, client)| client)
+    }
+
+    /// Partition events into one shard per cluster, keeping linked chains
+    /// together.
+    fn route<Event: Linkable>(
+        &self,
+        events: &[Event],
+        ledger: impl Fn(&Event) -> u32,
+    ) -> Result<HashMap<usize, Shard<Event>>, MultiClusterError> {
+        let mut shards: HashMap<usize, Shard<Event>> = HashMap::new();
+        let mut chain_cluster = None;
+        for (index, event) in events.iter().enumerate() {
+            let ledger = ledger(event);
+            let cluster = *self
+                .ledgers
+                .get(&ledger)
+                .ok_or(MultiClusterError::LedgerNotMapped { ledger })?;
+            if chain_cluster.map_or(false, |chain_cluster| chain_cluster != cluster) {
+                return Err(MultiClusterError::LinkedChainSpansClusters { index });
+            }
+            chain_cluster = if event.is_linked() {
+                Some(cluster)
+            } else {
+                None
+            };
+
+            let shard = shards.entry(cluster).or_insert_with(|| Shard {
+                indexes: Vec::new(),
+                events: Vec::new(),
+            });
+            shard.indexes.push(index);
+            shard.events.push(*event);
+        }
+        Ok(shards)
+    }
+}
+
+impl fmt::Debug for MultiClusterClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("MultiClusterClient")
+            .field("clusters", &self.clusters.len())
+            .finish()
+    }
+}
+
+/// The events submitted to one cluster, with their indexes in the
+/// original events.
+struct Shard<Event> {
+    indexes: Vec<usize>,
+    events: Vec<Event>,
+}
+
+fn merge_query_results<T>(
+    mut results: Vec<T>,
+    event: QueryFilter,
+    timestamp: impl Fn(&T) -> u64,
+) -> Vec<T> {
+    if event.flags.contains(QueryFilterFlags::Reversed) {
+        results.sort_by_key(|result| std::cmp::Reverse(timestamp(result)));
+    } else {
+        results.sort_by_key(timestamp);
+    }
+    if event.limit != 0 {
+        results.truncate(usize::try_from(event.limit).expect("usize"));
+    }
+    results
+}
+
+/// Errors resulting from requests made with a [`MultiClusterClient`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum MultiClusterError {
+    /// An event's ledger is not owned by any cluster.
+    LedgerNotMapped { ledger: u32 },
+    /// The linked chain containing the event at `index` spans clusters.
+    LinkedChainSpansClusters { index: usize },
+    /// A request failed. See [`PacketStatus`].
+    Packet(PacketStatus),
+}
+
+impl From<PacketStatus> for MultiClusterError {
+    fn from(status: PacketStatus) -> MultiClusterError {
+        MultiClusterError::Packet(status)
+    }
+}
+
+impl std::error::Error for MultiClusterError {}
+impl core::fmt::Display for MultiClusterError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::LedgerNotMapped { ledger } => {
+                write!(f, "ledger {ledger} is not assigned to a cluster")
+            }
+            Self::LinkedChainSpansClusters { index } => {
+                write!(f, "linked chain at event {index} spans clusters")
+            }
+            Self::Packet(status) => core::fmt::Display::fmt(status, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(ledger: u32, flags: AccountFlags) -> Account {
+        Account {
+            ledger,
+            flags,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn route_by_ledger() {
+        let client = MultiClusterClient {
+            clusters: Vec::new(),
+            ledgers: HashMap::from([(1, 0), (2, 1), (3, 1)]),
+        };
+
+        let events = [
+            account(2, AccountFlags::None),
+            account(1, AccountFlags::Linked),
+            account(1, AccountFlags::None),
+            account(3, AccountFlags::None),
+        ];
+        let shards = client.route(&events, |event| event.ledger).unwrap();
+        assert_eq!(shards[&0].indexes, [1, 2]);
+        assert_eq!(shards[&1].indexes, [0, 3]);
+
+        assert_eq!(
+            client
+                .route(&[account(4, AccountFlags::None)], |event| event.ledger)
+                .err(),
+            Some(MultiClusterError::LedgerNotMapped { ledger: 4 })
+        );
+
+        let events = [
+            account(2, AccountFlags::Linked),
+            account(3, AccountFlags::Linked),
+            account(1, AccountFlags::None),
+        ];
+        assert_eq!(
+            client.route(&events, |event| event.ledger).err(),
+            Some(MultiClusterError::LinkedChainSpansClusters { index: 2 })
+        );
+    }
+
+    #[test]
+    fn merge_query_results_in_timestamp_order() {
+        let filter = QueryFilter {
+            limit: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            merge_query_results(vec![4, 1, 3, 2], filter, |&t| t),
+            [1, 2, 3]
+        );
+
+        let filter = QueryFilter {
+            limit: 3,
+            flags: QueryFilterFlags::Reversed,
+            ..Default::default()
+        };
+        assert_eq!(
+            merge_query_results(vec![4, 1, 3, 2], filter, |&t| t),
+            [4, 3, 2]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use super::*;
+
+use futures_core::Stream;
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// The filter of a range query, which can be advanced past a page of
+/// results.
+pub(crate) trait PageFilter: Copy {
+    /// The filter, with the maximum page size if its limit is zero.
+    fn with_page_size(self) -> Self;
+
+    fn limit(&self) -> u32;
+
+    /// The filter for the results after the one with `timestamp`, in the
+    /// filter's order.
+    fn after(self, timestamp: u64) -> Self;
+}
+
+/// A result of a range query.
+pub(crate) trait Timestamped {
+    fn timestamp(&self) -> u64;
+}
+
+macro_rules! page_filter {
+    ($filter:ty, $reversed:expr) => {
+        impl PageFilter for $filter {
+            fn with_page_size(self) -> Self {
+                Self {
+                    limit: match self.limit {
+                        0 => BATCH_MAX as u32,
+                        limit => limit,
+                    },
+                    ..self
+                }
+            }
+
+            fn limit(&self) -> u32 {
+                self.limit
+            }
+
+            fn after(self, timestamp: u64) -> Self {
+                if self.flags.contains($reversed) {
+                    Self {
+                        timestamp_max: timestamp - 1,
+                        ..self
+                    }
+                } else {
+                    Self {
+                        timestamp_min: timestamp + 1,
+                        ..self
+                    }
+                }
+            }
+        }
+    };
+}
+
+page_filter!(AccountFilter, AccountFilterFlags::Reversed);
+page_filter!(QueryFilter, QueryFilterFlags::Reversed);
+
+macro_rules! timestamped {
+    ($($result:ty),*) => {
+        $(impl Timestamped for $result {
+            fn timestamp(&self) -> u64 {
+                self.timestamp
+            }
+        })*
+    };
+}
+
+timestamped!(Account, Transfer, AccountBalance);
+
+/// The pages of the results of a filter, fetched one at a time.
+pub(crate) struct Pages<F> {
+    filter: F,
+    done: bool,
+}
+
+impl<F: PageFilter> Pages<F> {
+    pub(crate) fn new(filter: F) -> Pages<F> {
+        Pages {
+            filter: filter.with_page_size(),
+            done: false,
+        }
+    }
+
+    /// The filter of the next page, or `None` after the last.
+    pub(crate) fn filter(&self) -> Option<F> {
+        (!self.done).then_some(self.filter)
+    }
+
+    /// Record the page returned for [`Pages::filter`].
+    ///
+    /// A page smaller than the filter's limit is the last.
+    pub(crate) fn fetched<T: Timestamped>(&mut self, page: &[T]) {
+        self.done = page.len() < self.filter.limit() as usize;
+        if let Some(last) = page.last() {
+            self.filter = self.filter.after(last.timestamp());
+        }
+    }
+
+    /// Fetch the next page, or `None` after the last.
+    pub(crate) async fn next<'a, T: Timestamped>(
+        &mut self,
+        query: impl FnOnce(F) -> BoxFuture<'a, Result<Vec<T>, PacketStatus>>,
+    ) -> Result<Option<Vec<T>>, PacketStatus> {
+        let filter = match self.filter() {
+            Some(filter) => filter,
+            None => return Ok(None),
+        };
+        let page = query(filter).await?;
+        self.fetched(&page);
+        Ok(Some(page))
+    }
+}
+
+/// Fetch every page of the results of `filter`.
+pub(crate) async fn all<'a, F: PageFilter, T: Timestamped>(
+    filter: F,
+    query: impl Fn(F) -> BoxFuture<'a, Result<Vec<T>, PacketStatus>>,
+) -> Result<Vec<T>, PacketStatus> {
+    let mut pages = Pages::new(filter);
+    let mut results = Vec::new();
+    while let Some(page) = pages.next(&query).await? {
+        results.extend(page);
+    }
+    Ok(results)
+}
+
+/// A stream of the transfers matching a filter, fetched a page at a time.
+/// See [`Client::query_transfers_paged`].
+pub struct PagedTransfers<'a> {
+    client: &'a Client,
+    pages: Pages<QueryFilter>,
+    request: Option<BoxFuture<'static, Result<Vec<Transfer>, PacketStatus>>>,
+    page: VecDeque<Transfer>,
+}
+
+impl Client {
+    /// Query all transfers matching `filter`, in as many requests as
+    /// needed.
+    ///
+    /// The stream requests a page of `filter.limit` transfers, or the
+    /// maximum if it is zero, and yields them one at a time. Once it has
+    /// yielded a page, it requests the transfers after the page's last, by
+    /// advancing `filter.timestamp_min`, or `filter.timestamp_max` if the
+    /// filter is reversed. It ends after a page smaller than the limit.
+    ///
+    /// Pages are only requested while the stream is polled. A failed
+    /// request yields its error, and polling again retries it.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use tigerbeetle as tb;
+    ///
+    /// # async fn example(client: &tb::Client) -> Result<(), tb::PacketStatus> {
+    /// let filter = tb::QueryFilter {
+    ///     ledger: 840,
+    ///     ..Default::default()
+    /// };
+    /// let mut transfers = client.query_transfers_paged(filter);
+    /// let mut total = 0;
+    /// while let Some(transfer) = transfers.next().await {
+    ///     total += transfer?.amount;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn query_transfers_paged(&self, filter: QueryFilter) -> PagedTransfers<'_> {
+        PagedTransfers {
+            client: self,
+            pages: Pages::new(filter),
+            request: None,
+            page: VecDeque::new(),
+        }
+    }
+}
+
+impl Stream for PagedTransfers<'_> {
+    type Item = Result<Transfer, PacketStatus>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(transfer) = this.page.pop_front() {
+                return Poll::Ready(Some(Ok(transfer)));
+            }
+            if this.request.is_none() {
+                let filter = match this.pages.filter() {
+                    Some(filter) => filter,
+                    None => return Poll::Ready(None),
+                };
+                this.request = Some(Box::pin(this.client.query_transfers(filter)));
+            }
+            let request = this.request.as_mut().expect("requested");
+            let result = match request.as_mut().poll(cx) {
+                Poll::Ready(result) => result,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.request = None;
+            match result {
+                Ok(page) => {
+                    this.pages.fetched(&page);
+                    this.page.extend(page);
+                }
+                Err(status) => return Poll::Ready(Some(Err(status))),
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for PagedTransfers<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("PagedTransfers")
+            .field("filter", &self.pages.filter())
+            .finish_non_exhaustive()
+    }
+}
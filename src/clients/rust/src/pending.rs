@@ -0,0 +1,195 @@
+use super::*;
+
+use std::collections::HashMap;
+
+impl Transfer {
+    /// The cluster timestamp at which a pending transfer expires.
+    ///
+    /// A pending transfer with a `timeout` expires `timeout` seconds after
+    /// it was created, at which point the cluster voids it. Returns `None`
+    /// if the transfer is not pending, has no timeout, or has no `timestamp`
+    /// because it was not returned by the cluster.
+    ///
+    /// Cluster timestamps are nanoseconds since the UNIX epoch, and closely
+    /// track the replicas' wall clocks.
+    pub fn expires_at(&self) -> Option<u64> {
+        if !self.flags.contains(TransferFlags::Pending) || self.timeout == 0 || self.timestamp == 0
+        {
+            return None;
+        }
+        Some(
+            self.timestamp
+                .saturating_add(u64::from(self.timeout) * 1_000_000_000),
+        )
+    }
+}
+
+/// Pending transfers held by the application, such as those awaiting
+/// approval, tracked by when they expire.
+///
+/// Transfers must have been returned by the cluster, e.g. by
+/// [`Client::lookup_transfers`], so that they have a `timestamp`.
+///
+/// # Example
+///
+/// ```no_run
+/// use tigerbeetle as tb;
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+///
+/// # async fn example(client: &tb::Client, pending_ids: &[u128]) -> Result<(), tb::PacketStatus> {
+/// let mut pending = tb::PendingTransfers::new();
+/// for transfer in client.lookup_transfers(pending_ids).await? {
+///     pending.insert(transfer);
+/// }
+///
+/// // Void anything that would expire in the next minute, rather than let
+/// // it expire mid-approval.
+/// let soon = SystemTime::now() + Duration::from_secs(60);
+/// let soon = soon.duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+/// let voids = pending.void_expiring_before(soon);
+/// let results = client.create_transfers(&voids).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PendingTransfers {
+    transfers: HashMap<u128, Transfer>,
+}
+
+impl PendingTransfers {
+    pub fn new() -> PendingTransfers {
+        PendingTransfers::default()
+    }
+
+    /// Track a pending transfer, replacing any with the same id.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the transfer is not pending, or has no `timestamp`.
+    pub fn insert(&mut self, transfer: Transfer) {
+        assert!(transfer.flags.contains(TransferFlags::Pending));
+        assert_ne!(transfer.timestamp, 0);
+        self.transfers.insert(transfer.id, transfer);
+    }
+
+    /// Stop tracking a pending transfer, e.g. once it is posted or voided.
+    pub fn remove(&mut self, id: u128) -> Option<Transfer> {
+        self.transfers.remove(&id)
+    }
+
+    pub fn get(&self, id: u128) -> Option<&Transfer> {
+        self.transfers.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.transfers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+
+    /// The pending transfers that expire before `timestamp`, soonest first.
+    ///
+    /// Transfers without a timeout never expire.
+    pub fn expiring_before(&self, timestamp: u64) -> Vec<Transfer> {
+        let mut expiring: Vec<Transfer> = self
+            .transfers
+            .values()
+            .filter(|transfer| {
+                let expires_at = transfer.expires_at();
+                expires_at.is_some() && expires_at < Some(timestamp)
+            })
+            .copied()
+            .collect();
+        expiring.sort_by_key(|transfer| (transfer.expires_at(), transfer.id));
+        expiring
+    }
+
+    /// Transfers that void each pending transfer expiring before
+    /// `timestamp`, soonest first.
+    ///
+    /// Transfers are given new ids with [`id`]. The pending transfers remain
+    /// tracked until [removed](PendingTransfers::remove).
+    pub fn void_expiring_before(&self, timestamp: u64) -> Vec<Transfer> {
+        self.expiring_before(timestamp)
+            .iter()
+            .map(|pending| Transfer::void_pending_transfer(id(), pending.id))
+            .collect()
+    }
+
+    /// Transfers that post the full amount of each pending transfer expiring
+    /// before `timestamp`, soonest first.
+    ///
+    /// Transfers are given new ids with [`id`]. The pending transfers remain
+    /// tracked until [removed](PendingTransfers::remove).
+    pub fn post_expiring_before(&self, timestamp: u64) -> Vec<Transfer> {
+        self.expiring_before(timestamp)
+            .iter()
+            .map(|pending| Transfer::post_pending_transfer(id(), pending.id, AMOUNT_MAX))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn pending(id: u128, timestamp: u64, timeout: u32) -> Transfer {
+        Transfer {
+            id,
+            timestamp,
+            timeout,
+            flags: TransferFlags::Pending,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expires_at() {
+        assert_eq!(pending(1, 100, 60).expires_at(), Some(100 + 60 * SECOND));
+        assert_eq!(pending(1, 100, 0).expires_at(), None);
+        assert_eq!(pending(1, 0, 60).expires_at(), None);
+        assert_eq!(
+            Transfer {
+                flags: TransferFlags::empty(),
+                ..pending(1, 100, 60)
+            }
+            .expires_at(),
+            None
+        );
+    }
+
+    #[test]
+    fn expiring_before() {
+        let mut transfers = PendingTransfers::new();
+        transfers.insert(pending(1, 10 * SECOND, 30));
+        transfers.insert(pending(2, 10 * SECOND, 10));
+        transfers.insert(pending(3, 10 * SECOND, 0));
+        transfers.insert(pending(4, 10 * SECOND, 60));
+
+        let ids = |transfers: Vec<Transfer>| -> Vec<u128> {
+            transfers.iter().map(|transfer| transfer.id).collect()
+        };
+        assert_eq!(ids(transfers.expiring_before(10 * SECOND)), []);
+        assert_eq!(ids(transfers.expiring_before(41 * SECOND)), [2, 1]);
+
+        let voids = transfers.void_expiring_before(41 * SECOND);
+        assert_eq!(
+            voids.iter().map(|void| void.pending_id).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert!(voids
+            .iter()
+            .all(|void| void.flags == TransferFlags::VoidPendingTransfer));
+
+        let posts = transfers.post_expiring_before(41 * SECOND);
+        assert!(posts.iter().all(|post| post.amount == AMOUNT_MAX));
+
+        transfers.remove(2);
+        assert_eq!(ids(transfers.expiring_before(u64::MAX)), [1, 4]);
+        assert_eq!(transfers.len(), 3);
+    }
+}
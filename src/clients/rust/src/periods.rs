@@ -0,0 +1,483 @@
+//! Closing accounting periods.
+//!
+//! At the end of an accounting period the balances of the profit and loss
+//! accounts, income and expenses, are rolled into retained earnings, so that
+//! the next period starts from zero. [`close`] computes each account's
+//! posted balance at the end of the period, from its balance history, and
+//! creates a linked chain of closing transfers into the retained earnings
+//! account. It then creates a marker account recording the close, so a
+//! period is closed once:
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//! use tb::ledger::ChartOfAccounts;
+//! use tb::periods::{self, PeriodClose};
+//!
+//! # async fn example(client: &tb::Client, chart: &ChartOfAccounts, period_end: u64) -> Result<(), periods::PeriodError> {
+//! let close = PeriodClose::new(2024_12, period_end, "equity:retained_earnings", 900)
+//!     .accounts("income:")
+//!     .accounts("expenses:");
+//!
+//! let entries = periods::preview(client, chart, &close).await?;
+//! for transfer in &entries.transfers {
+//!     println!("{} -> {}: {}", transfer.debit_account_id, transfer.credit_account_id, transfer.amount);
+//! }
+//! periods::close(client, chart, &close).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The profit and loss accounts must have [`AccountFlags::History`], and
+//! be on the ledger of the retained earnings account. Only posted amounts
+//! are closed; transfers pending at the end of the period are closed with
+//! the period in which they are posted.
+
+use crate::id128;
+use crate::ledger::ChartOfAccounts;
+use crate::paging;
+use crate::{
+    Account, AccountFilter, AccountFilterFlags, AccountFlags, CreateAccountResult,
+    CreateTransferResult, PacketStatus, TigerBeetleClient, Transfer, TransferFlags,
+};
+
+use core::fmt;
+
+/// The close of an accounting period.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PeriodClose {
+    id: u128,
+    period_end: u64,
+    retained_earnings: String,
+    prefixes: Vec<String>,
+    code: u16,
+}
+
+impl PeriodClose {
+    /// A close of the period ending at the timestamp `period_end`,
+    /// inclusive, into the account named `retained_earnings`.
+    ///
+    /// `id` identifies the close: it is the id of the marker account, the
+    /// `user_data_128` of the closing transfers, and the seed of their ids,
+    /// so that closing again creates the same transfers. The marker account
+    /// and the closing transfers have `code`, which must not be used by
+    /// other transfers of the closed accounts.
+    pub fn new(id: u128, period_end: u64, retained_earnings: &str, code: u16) -> PeriodClose {
+        PeriodClose {
+            id,
+            period_end,
+            retained_earnings: retained_earnings.to_owned(),
+            prefixes: Vec::new(),
+            code,
+        }
+    }
+
+    /// Close the accounts of the chart whose names start with `prefix`,
+    /// such as `"income:"`.
+    pub fn accounts(mut self, prefix: &str) -> PeriodClose {
+        self.prefixes.push(prefix.to_owned());
+        self
+    }
+
+    pub fn id(&self) -> u128 {
+        self.id
+    }
+
+    pub fn period_end(&self) -> u64 {
+        self.period_end
+    }
+}
+
+/// The transfers and marker account of a period's close.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ClosingEntries {
+    /// A linked chain of a transfer for each account with a balance,
+    /// between it and the retained earnings account.
+    pub transfers: Vec<Transfer>,
+    /// The account recording the close. It has the close's id, the period
+    /// end as its `user_data_64`, and is closed, so it can't be used.
+    pub marker: Account,
+}
+
+/// The closing entries of a period, without creating them.
+///
+/// # Errors
+///
+/// As [`close`], apart from [`PeriodError::AlreadyClosed`] and
+/// [`PeriodError::Failed`].
+pub async fn preview(
+    client: &dyn TigerBeetleClient,
+    chart: &ChartOfAccounts,
+    close: &PeriodClose,
+) -> Result<ClosingEntries, PeriodError> {
+    let retained_earnings = chart
+        .get(&close.retained_earnings)
+        .ok_or_else(|| PeriodError::AccountUndefined(close.retained_earnings.clone()))?;
+    let ledger = retained_earnings.ledger;
+
+    let names: Vec<&String> = chart
+        .accounts
+        .keys()
+        .filter(|name| {
+            **name != close.retained_earnings
+                && close
+                    .prefixes
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix.as_str()))
+        })
+        .collect();
+    let ids: Vec<u128> = names.iter().map(|name| chart.accounts[*name].id).collect();
+    let mut accounts = Vec::new();
+    for chunk in ids.chunks(crate::BATCH_MAX) {
+        accounts.extend(client.lookup_accounts(chunk).await?);
+    }
+
+    let mut transfers = Vec::new();
+    for name in names {
+        // Accounts that haven't been created have no balance.
+        let account = match accounts.iter().find(|a| a.id == chart.accounts[name].id) {
+            Some(account) => account,
+            None => continue,
+        };
+        if account.ledger != ledger {
+            return Err(PeriodError::LedgerMismatch(name.clone()));
+        }
+        if !account.flags.contains(AccountFlags::History) {
+            return Err(PeriodError::HistoryMissing(name.clone()));
+        }
+
+        let (debits, credits) = balance(client, close, account.id).await?;
+        let (debit_account_id, credit_account_id, amount) = if credits > debits {
+            (account.id, retained_earnings.id, credits - debits)
+        } else if debits > credits {
+            (retained_earnings.id, account.id, debits - credits)
+        } else {
+            continue;
+        };
+        transfers.push(Transfer {
+            id: id128::derive(close.id, account.id),
+            debit_account_id,
+            credit_account_id,
+            amount,
+            user_data_128: close.id,
+            user_data_64: close.period_end,
+            ledger,
+            code: close.code,
+            flags: TransferFlags::Linked,
+            ..Default::default()
+        });
+    }
+    if let Some(last) = transfers.last_mut() {
+        last.flags.remove(TransferFlags::Linked);
+    }
+
+    Ok(ClosingEntries {
+        transfers,
+        marker: Account {
+            id: close.id,
+            user_data_64: close.period_end,
+            ledger,
+            code: close.code,
+            flags: AccountFlags::Closed,
+            ..Default::default()
+        },
+    })
+}
+
+/// Close a period: create its closing transfers, then its marker account.
+///
+/// Closing is safe to retry: if the closing transfers were created, but
+/// not the marker account, they aren't created again. Close periods in
+/// order; a period may be closed before or after the end of the next one.
+///
+/// # Errors
+///
+/// Returns [`PeriodError::AlreadyClosed`] if the marker account exists,
+/// [`PeriodError::AccountUndefined`] if the retained earnings account isn't
+/// in the chart, [`PeriodError::LedgerMismatch`] or
+/// [`PeriodError::HistoryMissing`] if an account can't be closed,
+/// [`PeriodError::Failed`] if the cluster rejected the closing entries,
+/// and [`PeriodError::Packet`] if a request failed.
+pub async fn close(
+    client: &dyn TigerBeetleClient,
+    chart: &ChartOfAccounts,
+    close: &PeriodClose,
+) -> Result<ClosingEntries, PeriodError> {
+    if closed(client, close.id).await?.is_some() {
+        return Err(PeriodError::AlreadyClosed);
+    }
+    let entries = preview(client, chart, close).await?;
+
+    // The transfers are linked, so all or none of them exist.
+    let created = match entries.transfers.first() {
+        Some(first) => !client.lookup_transfers(&[first.id]).await?.is_empty(),
+        None => true,
+    };
+    if !created {
+        let results = client.create_transfers(&entries.transfers).await?;
+        if let Some(result) = results
+            .iter()
+            .find(|result| result.result != CreateTransferResult::LinkedEventFailed)
+            .or_else(|| results.first())
+        {
+            return Err(PeriodError::Failed(format!(
+                "transfer {}: {}",
+                entries.transfers[result.index].id, result.result
+            )));
+        }
+    }
+
+    let results = client
+        .create_accounts(std::slice::from_ref(&entries.marker))
+        .await?;
+    match results.first().map(|result| result.result) {
+        None => Ok(entries),
+        // Closed concurrently.
+        Some(CreateAccountResult::Exists) => Err(PeriodError::AlreadyClosed),
+        Some(result) => Err(PeriodError::Failed(format!("marker account: {result}"))),
+    }
+}
+
+/// The end of the period closed with `id`, or `None` if it isn't closed.
+pub async fn closed(client: &dyn TigerBeetleClient, id: u128) -> Result<Option<u64>, PacketStatus> {
+    let accounts = client.lookup_accounts(&[id]).await?;
+    Ok(accounts.first().map(|marker| marker.user_data_64))
+}
+
+/// The posted debits and credits of `account` to close.
+///
+/// This is its balance at the end of the period, plus the closing
+/// transfers of earlier periods that were created after it ended.
+async fn balance(
+    client: &dyn TigerBeetleClient,
+    close: &PeriodClose,
+    account: u128,
+) -> Result<(u128, u128), PacketStatus> {
+    let filter = AccountFilter {
+        account_id: account,
+        timestamp_max: close.period_end,
+        limit: 1,
+        flags: AccountFilterFlags::Debits
+            | AccountFilterFlags::Credits
+            | AccountFilterFlags::Reversed,
+        ..Default::default()
+    };
+    let (mut debits, mut credits) = match client.get_account_balances(filter).await?.first() {
+        Some(balance) => (balance.debits_posted, balance.credits_posted),
+        None => (0, 0),
+    };
+
+    let filter = AccountFilter {
+        account_id: account,
+        code: close.code,
+        timestamp_min: close.period_end.saturating_add(1),
+        flags: AccountFilterFlags::Debits | AccountFilterFlags::Credits,
+        ..Default::default()
+    };
+    let later = paging::all(filter, |filter| client.get_account_transfers(filter)).await?;
+    for transfer in later {
+        if transfer.user_data_64 >= close.period_end {
+            continue;
+        }
+        if transfer.debit_account_id == account {
+            debits += transfer.amount;
+        } else {
+            credits += transfer.amount;
+        }
+    }
+    Ok((debits, credits))
+}
+
+/// Errors closing a period.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PeriodError {
+    /// The period's marker account exists.
+    AlreadyClosed,
+    /// The account isn't in the chart of accounts.
+    AccountUndefined(String),
+    /// The account isn't on the ledger of the retained earnings account.
+    LedgerMismatch(String),
+    /// The account doesn't have [`AccountFlags::History`], so its balance
+    /// at the end of the period is unknown.
+    HistoryMissing(String),
+    /// The cluster rejected a closing transfer or the marker account.
+    Failed(String),
+    /// A request failed.
+    Packet(PacketStatus),
+}
+
+impl From<PacketStatus> for PeriodError {
+    fn from(status: PacketStatus) -> PeriodError {
+        PeriodError::Packet(status)
+    }
+}
+
+impl std::error::Error for PeriodError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Packet(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PeriodError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AlreadyClosed => f.write_str("period already closed"),
+            Self::AccountUndefined(name) => write!(f, "account {name} undefined"),
+            Self::LedgerMismatch(name) => {
+                write!(f, "account {name} is not on the retained earnings ledger")
+            }
+            Self::HistoryMissing(name) => write!(f, "account {name} has no balance history"),
+            Self::Failed(error) => write!(f, "close failed: {error}"),
+            Self::Packet(status) => fmt::Display::fmt(status, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::AccountDefinition;
+    use crate::InMemoryClient;
+
+    use futures::executor::block_on;
+
+    fn chart() -> ChartOfAccounts {
+        let mut chart = ChartOfAccounts::new();
+        for (name, id) in [
+            ("assets:cash", 1),
+            ("equity:retained_earnings", 2),
+            ("income:sales", 3),
+            ("expenses:rent", 4),
+            ("expenses:travel", 5),
+        ] {
+            chart.insert(
+                name,
+                AccountDefinition {
+                    id,
+                    ledger: 840,
+                    code: 10,
+                    flags: AccountFlags::History,
+                },
+            );
+        }
+        chart
+    }
+
+    fn transfer(client: &InMemoryClient, id: u128, debit: u128, credit: u128, amount: u128) {
+        let transfer = Transfer {
+            id,
+            debit_account_id: debit,
+            credit_account_id: credit,
+            amount,
+            ledger: 840,
+            code: 1,
+            ..Default::default()
+        };
+        assert!(block_on(client.create_transfers(&[transfer]))
+            .unwrap()
+            .is_empty());
+    }
+
+    fn balance(client: &InMemoryClient, id: u128) -> i128 {
+        let account = block_on(client.lookup_accounts(&[id])).unwrap()[0];
+        account.credits_posted as i128 - account.debits_posted as i128
+    }
+
+    #[test]
+    fn close() {
+        let client = InMemoryClient::new();
+        let chart = chart();
+        let accounts: Vec<Account> = chart
+            .accounts
+            .values()
+            .map(|definition| definition.to_account())
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+
+        transfer(&client, 1, 1, 3, 1000);
+        transfer(&client, 2, 4, 1, 300);
+        let period_end = block_on(client.lookup_transfers(&[2])).unwrap()[0].timestamp;
+        // After the end of the period.
+        transfer(&client, 3, 1, 3, 50);
+
+        let january = PeriodClose::new(100, period_end, "equity:retained_earnings", 900)
+            .accounts("income:")
+            .accounts("expenses:");
+        let preview = block_on(super::preview(&client, &chart, &january)).unwrap();
+        let legs: Vec<_> = preview
+            .transfers
+            .iter()
+            .map(|t| (t.debit_account_id, t.credit_account_id, t.amount, t.flags))
+            .collect();
+        assert_eq!(
+            legs,
+            [
+                (2, 4, 300, TransferFlags::Linked),
+                (3, 2, 1000, TransferFlags::empty()),
+            ]
+        );
+        assert_eq!(block_on(closed(&client, 100)), Ok(None));
+
+        let entries = block_on(super::close(&client, &chart, &january)).unwrap();
+        assert_eq!(entries, preview);
+        assert_eq!(balance(&client, 2), 700);
+        assert_eq!(balance(&client, 3), 50);
+        assert_eq!(balance(&client, 4), 0);
+        assert_eq!(block_on(closed(&client, 100)), Ok(Some(period_end)));
+        assert_eq!(
+            block_on(super::close(&client, &chart, &january)),
+            Err(PeriodError::AlreadyClosed)
+        );
+
+        // The next period closes only its own activity.
+        transfer(&client, 4, 5, 1, 20);
+        let period_end = block_on(client.lookup_transfers(&[4])).unwrap()[0].timestamp;
+        let february = PeriodClose::new(101, period_end, "equity:retained_earnings", 900)
+            .accounts("income:")
+            .accounts("expenses:");
+        block_on(super::close(&client, &chart, &february)).unwrap();
+        assert_eq!(balance(&client, 2), 730);
+        assert_eq!(balance(&client, 3), 0);
+        assert_eq!(balance(&client, 5), 0);
+    }
+
+    #[test]
+    fn close_invalid() {
+        let client = InMemoryClient::new();
+        let mut chart = chart();
+        chart.insert(
+            "income:interest",
+            AccountDefinition {
+                id: 6,
+                ledger: 840,
+                code: 10,
+                flags: AccountFlags::empty(),
+            },
+        );
+        let accounts: Vec<Account> = chart
+            .accounts
+            .values()
+            .map(|definition| definition.to_account())
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+
+        let close = PeriodClose::new(100, u64::MAX - 1, "equity:retained_earnings", 900)
+            .accounts("income:");
+        assert_eq!(
+            block_on(super::preview(&client, &chart, &close)),
+            Err(PeriodError::HistoryMissing("income:interest".to_owned()))
+        );
+        let close = PeriodClose::new(100, u64::MAX - 1, "equity:missing", 900);
+        assert_eq!(
+            block_on(super::preview(&client, &chart, &close)),
+            Err(PeriodError::AccountUndefined("equity:missing".to_owned()))
+        );
+    }
+}
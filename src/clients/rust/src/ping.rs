@@ -0,0 +1,55 @@
+use super::*;
+
+impl Client {
+    /// Check connectivity to the cluster, returning the round-trip time.
+    ///
+    /// The cluster has no dedicated ping operation, so this submits an empty
+    /// `lookup_accounts` request, which the cluster replies to without
+    /// reading any state. Unlike other requests it is never retried, and it
+    /// isn't recorded by the `metrics` or `otel` features, so it is suitable
+    /// for health checks.
+    ///
+    /// Requests are sent to the primary replica, so the round-trip time is
+    /// that of the primary, or of whichever replica relays the request to it.
+    /// Latency to individual replicas is not available.
+    ///
+    /// Like any request, a ping waits until a session is registered, and
+    /// doesn't time out. Use [`with_deadline`] to bound it.
+    ///
+    /// # Errors
+    ///
+    /// Returns the [`PacketStatus`] of the failed request.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example(client: &tb::Client) {
+    /// match tb::with_deadline(client.ping(), Duration::from_secs(1)).await {
+    ///     Ok(round_trip) => println!("healthy: {round_trip:?}"),
+    ///     Err(error) => println!("unhealthy: {error}"),
+    /// }
+    /// # }
+    /// ```
+    pub fn ping(&self) -> impl Future<Output = Result<Duration, PacketStatus>> {
+        let session = self.core.session();
+        let started = Instant::now();
+        let (packet, rx) =
+            create_packet::<u128>(tbc::TB_OPERATION_TB_OPERATION_LOOKUP_ACCOUNTS, &[]);
+        session.submit(packet);
+
+        async move {
+            // Hold the session until the reply, as other requests do.
+            let _session = session;
+            let msg = rx.await.expect("channel");
+            let status = msg.packet.0.status;
+            if status == tbc::TB_PACKET_STATUS_TB_PACKET_OK {
+                Ok(started.elapsed())
+            } else {
+                Err(status.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use super::*;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A pool of TigerBeetle clients.
+///
+/// The server processes one request at a time per client session, so a
+/// single [`Client`] will queue concurrent requests. `ClientPool` holds
+/// multiple sessions to the same cluster and distributes requests across
+/// them round-robin, allowing several requests to be in flight at once.
+///
+/// Each client replaces its session if the cluster evicts it, as a single
+/// [`Client`] does.
+///
+/// Note that the number of sessions the cluster will accept is limited, and
+/// that a pool only benefits applications which issue many concurrent requests.
+/// Because a request may go to any session, there is no ordering between
+/// concurrent requests submitted to a pool.
+///
+/// A pool whose clients are configured, e.g. with a retry policy or rate
+/// limit, is created with [`ClientBuilder::build_pool`].
+///
+/// # Example
+///
+/// ```no_run
+/// use tigerbeetle as tb;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let pool = tb::ClientPool::new(0, "127.0.0.1:3000", 4)?;
+///
+/// let accounts = pool.lookup_accounts(&[1, 2]).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ClientPool {
+    clients: Vec<Client>,
+    next: AtomicUsize,
+}
+
+impl ClientPool {
+    /// Create a pool of `size` clients.
+    ///
+    /// See [`Client::new`] for the format of `addresses`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn new(cluster_id: u128, addresses: &str, size: usize) -> Result<ClientPool, InitStatus> {
+        assert!(size > 0, "client pool size must be greater than zero");
+
+        let mut clients = Vec::with_capacity(size);
+        for _ in 0..size {
+            clients.push(Client::new(cluster_id, addresses)?);
+        }
+        Ok(ClientPool::from_clients(clients))
+    }
+
+    pub(crate) fn from_clients(clients: Vec<Client>) -> ClientPool {
+        assert!(
+            !clients.is_empty(),
+            "client pool size must be greater than zero"
+        );
+        ClientPool {
+            clients,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The number of sessions in the pool.
+    pub fn size(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Create one or more accounts.
+    ///
+    /// See [`Client::create_accounts`]. As with a client, the request is
+    /// queued for submission prior to return of this function.
+    pub fn create_accounts(
+        &self,
+        events: &[Account],
+    ) -> impl Future<Output = Result<Vec<CreateAccountsResult>, PacketStatus>> {
+        self.client().create_accounts(events)
+    }
+
+    /// Create one or more transfers.
+    ///
+    /// See [`Client::create_transfers`].
+    pub fn create_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>> {
+        self.client().create_transfers(events)
+    }
+
+    /// Query individual accounts.
+    ///
+    /// See [`Client::lookup_accounts`].
+    pub fn lookup_accounts(
+        &self,
+        events: &[u128],
+    ) -> impl Future<Output = Result<Vec<Account>, PacketStatus>> {
+        self.client().lookup_accounts(events)
+    }
+
+    /// Query individual transfers.
+    ///
+    /// See [`Client::lookup_transfers`].
+    pub fn lookup_transfers(
+        &self,
+        events: &[u128],
+    ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
+        self.client().lookup_transfers(events)
+    }
+
+    /// Query multiple transfers for a single account.
+    ///
+    /// See [`Client::get_account_transfers`].
+    pub fn get_account_transfers(
+        &self,
+        event: AccountFilter,
+    ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
+        self.client().get_account_transfers(event)
+    }
+
+    /// Query historical account balances for a single account.
+    ///
+    /// See [`Client::get_account_balances`].
+    pub fn get_account_balances(
+        &self,
+        event: AccountFilter,
+    ) -> impl Future<Output = Result<Vec<AccountBalance>, PacketStatus>> {
+        self.client().get_account_balances(event)
+    }
+
+    /// Query multiple accounts related by fields and timestamps.
+    ///
+    /// See [`Client::query_accounts`].
+    pub fn query_accounts(
+        &self,
+        event: QueryFilter,
+    ) -> impl Future<Output = Result<Vec<Account>, PacketStatus>> {
+        self.client().query_accounts(event)
+    }
+
+    /// Query multiple transfers related by fields and timestamps.
+    ///
+    /// See [`Client::query_transfers`].
+    pub fn query_transfers(
+        &self,
+        event: QueryFilter,
+    ) -> impl Future<Output = Result<Vec<Transfer>, PacketStatus>> {
+        self.client().query_transfers(event)
+    }
+
+    /// The client for the next request, round-robin.
+    fn client(&self) -> &Client {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        &self.clients[index]
+    }
+}
+
+impl fmt::Debug for ClientPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ClientPool")
+            .field("size", &self.clients.len())
+            .finish()
+    }
+}
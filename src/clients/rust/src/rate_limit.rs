@@ -0,0 +1,356 @@
+use super::*;
+
+use futures_channel::oneshot::{Receiver, Sender};
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Limits on the rate of a [`Client`]'s requests.
+///
+/// Events are limited with a token bucket: the bucket holds up to
+/// [`burst`](RateLimit::burst) tokens and is refilled at
+/// [`events_per_second`](RateLimit::events_per_second), and each request
+/// takes a token per event. The number of requests in flight can also be
+/// capped; a request is in flight from its submission until its future
+/// completes or is dropped.
+///
+/// A request that exceeds a limit is submitted by its future once it is
+/// within the limits, and is not submitted if the future is dropped first.
+/// Configure a client's limits with [`Client::set_rate_limit`] or
+/// [`ClientBuilder::rate_limit`]. To fail instead of waiting, use
+/// [`Client::try_create_accounts`] and [`Client::try_create_transfers`].
+///
+/// `tb_client` itself doesn't limit concurrency: each request's packet is
+/// allocated by the Rust client, so there is no packet pool to size, and
+/// requests beyond the one the cluster processes at a time are queued
+/// without bound. [`requests_in_flight_max`](RateLimit::requests_in_flight_max)
+/// is the client's concurrency limit.
+///
+/// # Example
+///
+/// ```no_run
+/// use tigerbeetle as tb;
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut client = tb::Client::new(0, "127.0.0.1:3000")?;
+/// client.set_rate_limit(
+///     tb::RateLimit::new()
+///         .events_per_second(10_000)
+///         .requests_in_flight_max(4),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct RateLimit {
+    events_per_second: Option<u32>,
+    burst: Option<u32>,
+    requests_in_flight_max: Option<usize>,
+}
+
+impl RateLimit {
+    /// No limits.
+    pub fn new() -> RateLimit {
+        RateLimit::default()
+    }
+
+    /// Limit the average rate of events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `events_per_second` is zero.
+    pub fn events_per_second(mut self, events_per_second: u32) -> RateLimit {
+        assert!(
+            events_per_second > 0,
+            "events_per_second must be greater than zero"
+        );
+        self.events_per_second = Some(events_per_second);
+        self
+    }
+
+    /// Set the number of events that may be submitted at once after a quiet
+    /// period. Defaults to a second's worth of events.
+    ///
+    /// A request with more events than the burst is submitted once the
+    /// bucket is full, after which the bucket is in debt until it refills.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `burst` is zero.
+    pub fn burst(mut self, burst: u32) -> RateLimit {
+        assert!(burst > 0, "burst must be greater than zero");
+        self.burst = Some(burst);
+        self
+    }
+
+    /// Limit the number of requests in flight.
+    ///
+    /// Requests with more than [`BATCH_MAX`] events are split, and each part
+    /// counts as a request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `requests_in_flight_max` is zero.
+    pub fn requests_in_flight_max(mut self, requests_in_flight_max: usize) -> RateLimit {
+        assert!(
+            requests_in_flight_max > 0,
+            "requests_in_flight_max must be greater than zero"
+        );
+        self.requests_in_flight_max = Some(requests_in_flight_max);
+        self
+    }
+
+    fn burst_tokens(&self) -> u32 {
+        self.burst.or(self.events_per_second).unwrap_or(0)
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.events_per_second.is_none() && self.requests_in_flight_max.is_none()
+    }
+}
+
+/// The error returned when a request can't be submitted without exceeding
+/// a [`RateLimit`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WouldBlock;
+
+impl std::error::Error for WouldBlock {}
+impl core::fmt::Display for WouldBlock {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.write_str("rate limit exceeded")
+    }
+}
+
+/// The state of a client's [`RateLimit`].
+pub(crate) struct Limiter {
+    limit: RateLimit,
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    /// May be negative after a request larger than the burst.
+    tokens: f64,
+    refilled: Instant,
+    in_flight: usize,
+    /// Requests waiting for another to leave flight.
+    waiters: VecDeque<Sender<()>>,
+}
+
+/// Admission of a request; dropping it takes the request out of flight.
+pub(crate) struct Permit {
+    limiter: Arc<Limiter>,
+}
+
+impl Limiter {
+    /// Returns `None` if `limit` has no limits.
+    pub(crate) fn new(limit: RateLimit) -> Option<Arc<Limiter>> {
+        if limit.is_unlimited() {
+            return None;
+        }
+        Some(Arc::new(Limiter {
+            limit,
+            state: Mutex::new(LimiterState {
+                tokens: f64::from(limit.burst_tokens()),
+                refilled: Instant::now(),
+                in_flight: 0,
+                waiters: VecDeque::new(),
+            }),
+        }))
+    }
+
+    /// Admit a request of each of `events` at once, or none of them.
+    pub(crate) fn try_acquire(
+        self: &Arc<Limiter>,
+        events: &[usize],
+    ) -> Result<Vec<Permit>, WouldBlock> {
+        let mut state = self.state.lock().expect("rate limiter");
+        match self.admit(&mut state, events) {
+            Admission::Admitted => Ok(events
+                .iter()
+                .map(|_| Permit {
+                    limiter: Arc::clone(self),
+                })
+                .collect()),
+            Admission::RetryAfter(_) | Admission::InFlight => Err(WouldBlock),
+        }
+    }
+
+    /// Wait until a request of `events` is admitted.
+    pub(crate) async fn acquire(self: Arc<Limiter>, events: usize) -> Permit {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().expect("rate limiter");
+                match self.admit(&mut state, &[events]) {
+                    Admission::Admitted => {
+                        return Permit {
+                            limiter: Arc::clone(&self),
+                        }
+                    }
+                    Admission::RetryAfter(delay) => {
+                        // This request may have been woken for a slot in
+                        // flight that it isn't taking yet, so pass it on.
+                        self.wake_next(&mut state);
+                        Err(delay)
+                    }
+                    Admission::InFlight => {
+                        let (tx, rx) = channel();
+                        state.waiters.push_back(tx);
+                        Ok(Waiter {
+                            limiter: Arc::clone(&self),
+                            rx,
+                        })
+                    }
+                }
+            };
+            match wait {
+                Err(delay) => timer::sleep(delay).await,
+                Ok(mut waiter) => {
+                    let _ = (&mut waiter.rx).await;
+                }
+            }
+        }
+    }
+
+    /// Wake the first waiter that is still waiting, if there is room in
+    /// flight.
+    fn wake_next(&self, state: &mut LimiterState) {
+        if let Some(requests_in_flight_max) = self.limit.requests_in_flight_max {
+            if state.in_flight >= requests_in_flight_max {
+                return;
+            }
+        }
+        while let Some(waiter) = state.waiters.pop_front() {
+            if waiter.send(()).is_ok() {
+                break;
+            }
+        }
+    }
+
+    fn admit(&self, state: &mut LimiterState, events: &[usize]) -> Admission {
+        if let Some(requests_in_flight_max) = self.limit.requests_in_flight_max {
+            if state.in_flight + events.len() > requests_in_flight_max {
+                return Admission::InFlight;
+            }
+        }
+
+        if let Some(events_per_second) = self.limit.events_per_second {
+            let now = Instant::now();
+            let burst = f64::from(self.limit.burst_tokens());
+            let refill =
+                now.duration_since(state.refilled).as_secs_f64() * f64::from(events_per_second);
+            state.tokens = (state.tokens + refill).min(burst);
+            state.refilled = now;
+
+            let events_total = events.iter().sum::<usize>() as f64;
+            let needed = events_total.min(burst);
+            if state.tokens < needed {
+                let deficit = needed - state.tokens;
+                return Admission::RetryAfter(Duration::from_secs_f64(
+                    deficit / f64::from(events_per_second),
+                ));
+            }
+            state.tokens -= events_total;
+        }
+
+        state.in_flight += events.len();
+        Admission::Admitted
+    }
+}
+
+enum Admission {
+    Admitted,
+    /// Not enough tokens, until after the delay.
+    RetryAfter(Duration),
+    /// Too many requests in flight.
+    InFlight,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().expect("rate limiter");
+        state.in_flight -= 1;
+        self.limiter.wake_next(&mut state);
+    }
+}
+
+/// A request waiting for another to leave flight.
+struct Waiter {
+    limiter: Arc<Limiter>,
+    rx: Receiver<()>,
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        // If the request was woken but is cancelled before taking the slot,
+        // wake the next instead. Closing under the lock orders this with
+        // `wake_next`, which skips closed waiters.
+        let mut state = self.limiter.state.lock().expect("rate limiter");
+        self.rx.close();
+        if let Ok(Some(())) = self.rx.try_recv() {
+            self.limiter.wake_next(&mut state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn unlimited() {
+        assert!(Limiter::new(RateLimit::new()).is_none());
+    }
+
+    #[test]
+    fn events_per_second() {
+        let limiter = Limiter::new(RateLimit::new().events_per_second(1_000).burst(10)).unwrap();
+
+        assert_eq!(limiter.try_acquire(&[4, 6]).map(|p| p.len()), Ok(2));
+        assert_eq!(limiter.try_acquire(&[5]).err(), Some(WouldBlock));
+
+        // Larger than the burst, admitted once the bucket refills.
+        let started = Instant::now();
+        drop(block_on(Arc::clone(&limiter).acquire(20)));
+        assert!(started.elapsed() >= Duration::from_millis(10));
+        assert_eq!(limiter.try_acquire(&[1]).err(), Some(WouldBlock));
+    }
+
+    #[test]
+    fn requests_in_flight_max() {
+        let limiter = Limiter::new(RateLimit::new().requests_in_flight_max(2)).unwrap();
+
+        let permits = limiter.try_acquire(&[1, 1]).unwrap();
+        assert_eq!(limiter.try_acquire(&[1]).err(), Some(WouldBlock));
+
+        let waiter = std::thread::spawn({
+            let limiter = Arc::clone(&limiter);
+            move || drop(block_on(limiter.acquire(1)))
+        });
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!waiter.is_finished());
+
+        drop(permits);
+        waiter.join().unwrap();
+        assert_eq!(limiter.try_acquire(&[1, 1]).map(|p| p.len()), Ok(2));
+    }
+
+    #[test]
+    fn cancelled_waiter() {
+        let limiter = Limiter::new(RateLimit::new().requests_in_flight_max(1)).unwrap();
+        let permit = limiter.try_acquire(&[1]).unwrap();
+
+        block_on(async {
+            let mut first = Box::pin(Arc::clone(&limiter).acquire(1));
+            let mut second = Box::pin(Arc::clone(&limiter).acquire(1));
+            assert!(futures::poll!(first.as_mut()).is_pending());
+            assert!(futures::poll!(second.as_mut()).is_pending());
+
+            // The first is woken, but cancelled before it takes the slot.
+            drop(permit);
+            drop(first);
+            assert!(futures::poll!(second.as_mut()).is_ready());
+        });
+    }
+}
@@ -0,0 +1,455 @@
+//! Helpers for common multi-transfer patterns.
+//!
+//! Each recipe builds the transfers of a pattern from the
+//! [TigerBeetle recipes](https://docs.tigerbeetle.com/coding/recipes/),
+//! linked so that the cluster applies them atomically. Transfers are given
+//! new ids with [`id`].
+
+use crate::{id, Transfer, TransferFlags};
+
+/// A transfer from one account to many, through a control account.
+///
+/// The source account is debited the total once, and each destination is
+/// credited its amount from the control account, so the source's history
+/// shows a single debit for the whole payment.
+///
+/// # Example
+///
+/// ```
+/// use tigerbeetle as tb;
+/// use tb::recipes::{Destination, OneToMany};
+///
+/// // Split a 100.00 payment between a merchant and the platform's fee.
+/// let payment = OneToMany {
+///     source_account_id: 1,
+///     control_account_id: 2,
+///     total: 10_000,
+///     destinations: vec![
+///         Destination { account_id: 3, amount: 9_700 },
+///         Destination { account_id: 4, amount: 300 },
+///     ],
+///     ledger: 840,
+///     code: 10,
+///     ..Default::default()
+/// };
+/// let transfers = payment.transfers()?;
+/// assert_eq!(transfers.len(), 3);
+/// # Ok::<(), tb::recipes::RecipeError>(())
+/// ```
+///
+/// # Protocol reference
+///
+/// [Multi-Debit, Multi-Credit Transfers](https://docs.tigerbeetle.com/coding/recipes/multi-debit-credit-transfers/).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct OneToMany {
+    pub source_account_id: u128,
+    /// The account the total passes through. Its balance is zero once the
+    /// transfers are applied.
+    pub control_account_id: u128,
+    /// The amount debited from the source, which must equal the sum of the
+    /// destinations' amounts.
+    pub total: u128,
+    pub destinations: Vec<Destination>,
+    pub ledger: u32,
+    pub code: u16,
+    /// Set on each transfer, to find the transfers of one payment.
+    pub user_data_128: u128,
+}
+
+/// An account credited by a [`OneToMany`] transfer.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Destination {
+    pub account_id: u128,
+    pub amount: u128,
+}
+
+impl OneToMany {
+    /// The linked transfers: the total from the source to the control
+    /// account, followed by a transfer from the control account to each
+    /// destination, in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecipeError`] if there are no destinations, a
+    /// destination's amount is zero, or the amounts don't sum to the total.
+    pub fn transfers(&self) -> Result<Vec<Transfer>, RecipeError> {
+        if self.destinations.is_empty() {
+            return Err(RecipeError::DestinationsEmpty);
+        }
+        let mut sum: u128 = 0;
+        for (index, destination) in self.destinations.iter().enumerate() {
+            if destination.amount == 0 {
+                return Err(RecipeError::AmountZero { index });
+            }
+            sum = sum
+                .checked_add(destination.amount)
+                .ok_or(RecipeError::TotalMismatch)?;
+        }
+        if sum != self.total {
+            return Err(RecipeError::TotalMismatch);
+        }
+
+        let transfer = |debit_account_id, credit_account_id, amount| Transfer {
+            id: id(),
+            debit_account_id,
+            credit_account_id,
+            amount,
+            user_data_128: self.user_data_128,
+            ledger: self.ledger,
+            code: self.code,
+            flags: TransferFlags::Linked,
+            ..Default::default()
+        };
+        let mut transfers = Vec::with_capacity(self.destinations.len() + 1);
+        transfers.push(transfer(
+            self.source_account_id,
+            self.control_account_id,
+            self.total,
+        ));
+        for destination in &self.destinations {
+            transfers.push(transfer(
+                self.control_account_id,
+                destination.account_id,
+                destination.amount,
+            ));
+        }
+        unlink_last(&mut transfers);
+        Ok(transfers)
+    }
+}
+
+/// A currency exchange between accounts on two ledgers.
+///
+/// Each ledger holds one currency, and a transfer can't cross ledgers, so an
+/// exchange is two transfers through liquidity accounts held by the
+/// exchanging party: the source pays into the liquidity account on its
+/// ledger, and the liquidity account on the destination ledger pays the
+/// destination. See [`currency_exchange`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct CurrencyExchange {
+    pub source_account_id: u128,
+    pub source_liquidity_account_id: u128,
+    pub source_ledger: u32,
+    pub destination_account_id: u128,
+    pub destination_liquidity_account_id: u128,
+    pub destination_ledger: u32,
+    /// The amount debited from the source, in the source ledger's units.
+    pub amount: u128,
+    pub rate: ExchangeRate,
+    /// An amount in the source ledger's units charged to the source in
+    /// addition to `amount`, paid to the source liquidity account.
+    pub spread: u128,
+    pub code: u16,
+    /// Set on each transfer, to find the transfers of one exchange.
+    pub user_data_128: u128,
+}
+
+/// The destination units received per source unit, as a fraction of
+/// ledger units.
+///
+/// For example, with USD in cents and EUR in cents at 0.92 EUR per USD, the
+/// rate is `92 / 100`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct ExchangeRate {
+    pub numerator: u128,
+    pub denominator: u128,
+}
+
+/// The transfers of a [`CurrencyExchange`].
+///
+/// Keep the ids of the legs to look up the exchange later, e.g. with
+/// [`Client::lookup_transfers`](crate::Client::lookup_transfers).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ExchangeTransfers {
+    /// From the source to the source liquidity account, on the source
+    /// ledger.
+    pub source_leg: Transfer,
+    /// From the destination liquidity account to the destination, on the
+    /// destination ledger.
+    pub destination_leg: Transfer,
+    /// The spread, if any, from the source to the source liquidity account.
+    pub spread: Option<Transfer>,
+}
+
+impl ExchangeTransfers {
+    /// The linked transfers to submit: the source leg, the destination leg,
+    /// and the spread, if any.
+    pub fn transfers(&self) -> Vec<Transfer> {
+        let mut transfers = vec![self.source_leg, self.destination_leg];
+        transfers.extend(self.spread);
+        unlink_last(&mut transfers);
+        transfers
+    }
+}
+
+/// Build the transfers of a currency exchange.
+///
+/// The destination amount is `amount * numerator / denominator`, rounded
+/// down.
+///
+/// # Errors
+///
+/// Returns [`RecipeError::RateInvalid`] if either part of the rate is zero,
+/// [`RecipeError::AmountZero`] if the amount or the converted amount is zero,
+/// and [`RecipeError::AmountOverflow`] if the conversion overflows.
+///
+/// # Example
+///
+/// ```
+/// use tigerbeetle as tb;
+/// use tb::recipes::{currency_exchange, CurrencyExchange, ExchangeRate};
+///
+/// // 100.00 USD to EUR at 0.92, with USD and EUR in cents.
+/// let exchange = currency_exchange(&CurrencyExchange {
+///     source_account_id: 1,
+///     source_liquidity_account_id: 2,
+///     source_ledger: 840,
+///     destination_account_id: 3,
+///     destination_liquidity_account_id: 4,
+///     destination_ledger: 978,
+///     amount: 10_000,
+///     rate: ExchangeRate { numerator: 92, denominator: 100 },
+///     code: 10,
+///     ..Default::default()
+/// })?;
+/// assert_eq!(exchange.destination_leg.amount, 9_200);
+/// assert_eq!(exchange.transfers().len(), 2);
+/// # Ok::<(), tb::recipes::RecipeError>(())
+/// ```
+///
+/// # Protocol reference
+///
+/// [Currency Exchange](https://docs.tigerbeetle.com/coding/recipes/currency-exchange/).
+pub fn currency_exchange(exchange: &CurrencyExchange) -> Result<ExchangeTransfers, RecipeError> {
+    let rate = exchange.rate;
+    if rate.numerator == 0 || rate.denominator == 0 {
+        return Err(RecipeError::RateInvalid);
+    }
+    if exchange.amount == 0 {
+        return Err(RecipeError::AmountZero { index: 0 });
+    }
+    let amount_destination = exchange
+        .amount
+        .checked_mul(rate.numerator)
+        .ok_or(RecipeError::AmountOverflow)?
+        / rate.denominator;
+    if amount_destination == 0 {
+        return Err(RecipeError::AmountZero { index: 1 });
+    }
+
+    let transfer = |debit_account_id, credit_account_id, amount, ledger| Transfer {
+        id: id(),
+        debit_account_id,
+        credit_account_id,
+        amount,
+        user_data_128: exchange.user_data_128,
+        ledger,
+        code: exchange.code,
+        flags: TransferFlags::Linked,
+        ..Default::default()
+    };
+    Ok(ExchangeTransfers {
+        source_leg: transfer(
+            exchange.source_account_id,
+            exchange.source_liquidity_account_id,
+            exchange.amount,
+            exchange.source_ledger,
+        ),
+        destination_leg: transfer(
+            exchange.destination_liquidity_account_id,
+            exchange.destination_account_id,
+            amount_destination,
+            exchange.destination_ledger,
+        ),
+        spread: (exchange.spread != 0).then(|| {
+            transfer(
+                exchange.source_account_id,
+                exchange.source_liquidity_account_id,
+                exchange.spread,
+                exchange.source_ledger,
+            )
+        }),
+    })
+}
+
+fn unlink_last(transfers: &mut [Transfer]) {
+    if let Some(last) = transfers.last_mut() {
+        last.flags.remove(TransferFlags::Linked);
+    }
+}
+
+/// Errors resulting from building the transfers of a recipe.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum RecipeError {
+    /// A [`OneToMany`] transfer has no destinations.
+    DestinationsEmpty,
+    /// The amount of the destination at `index` is zero. For a
+    /// [`CurrencyExchange`], index 0 is the source amount and index 1 the
+    /// converted destination amount.
+    AmountZero { index: usize },
+    /// The destinations' amounts don't sum to the total.
+    TotalMismatch,
+    /// A part of an [`ExchangeRate`] is zero.
+    RateInvalid,
+    /// Converting an amount overflows a `u128`.
+    AmountOverflow,
+}
+
+impl std::error::Error for RecipeError {}
+impl core::fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::DestinationsEmpty => f.write_str("no destinations"),
+            Self::AmountZero { index } => write!(f, "destination {index} has a zero amount"),
+            Self::TotalMismatch => f.write_str("amounts don't sum to the total"),
+            Self::RateInvalid => f.write_str("exchange rate invalid"),
+            Self::AmountOverflow => f.write_str("amount overflows"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_to_many(total: u128, amounts: &[u128]) -> OneToMany {
+        OneToMany {
+            source_account_id: 1,
+            control_account_id: 2,
+            total,
+            destinations: amounts
+                .iter()
+                .enumerate()
+                .map(|(index, &amount)| Destination {
+                    account_id: 10 + index as u128,
+                    amount,
+                })
+                .collect(),
+            ledger: 3,
+            code: 4,
+            user_data_128: 5,
+        }
+    }
+
+    #[test]
+    fn one_to_many_transfers() {
+        let transfers = one_to_many(10, &[7, 3]).transfers().unwrap();
+        let legs: Vec<_> = transfers
+            .iter()
+            .map(|t| {
+                (
+                    t.debit_account_id,
+                    t.credit_account_id,
+                    t.amount,
+                    t.flags.contains(TransferFlags::Linked),
+                )
+            })
+            .collect();
+        assert_eq!(
+            legs,
+            [(1, 2, 10, true), (2, 10, 7, true), (2, 11, 3, false)]
+        );
+        assert!(transfers
+            .iter()
+            .all(|t| (t.ledger, t.code, t.user_data_128) == (3, 4, 5)));
+    }
+
+    #[test]
+    fn one_to_many_invalid() {
+        let error = |total, amounts: &[u128]| one_to_many(total, amounts).transfers().unwrap_err();
+
+        assert_eq!(error(0, &[]), RecipeError::DestinationsEmpty);
+        assert_eq!(error(1, &[1, 0]), RecipeError::AmountZero { index: 1 });
+        assert_eq!(error(10, &[7, 2]), RecipeError::TotalMismatch);
+        assert_eq!(error(10, &[u128::MAX, 1]), RecipeError::TotalMismatch);
+    }
+
+    #[test]
+    fn currency_exchange_transfers() {
+        let exchange = CurrencyExchange {
+            source_account_id: 1,
+            source_liquidity_account_id: 2,
+            source_ledger: 840,
+            destination_account_id: 3,
+            destination_liquidity_account_id: 4,
+            destination_ledger: 978,
+            amount: 1_001,
+            rate: ExchangeRate {
+                numerator: 92,
+                denominator: 100,
+            },
+            spread: 5,
+            code: 10,
+            user_data_128: 6,
+        };
+        let transfers = currency_exchange(&exchange).unwrap();
+        assert_eq!(transfers.destination_leg.amount, 920);
+
+        let legs: Vec<_> = transfers
+            .transfers()
+            .iter()
+            .map(|t| {
+                (
+                    t.debit_account_id,
+                    t.credit_account_id,
+                    t.amount,
+                    t.ledger,
+                    t.flags.contains(TransferFlags::Linked),
+                )
+            })
+            .collect();
+        assert_eq!(
+            legs,
+            [
+                (1, 2, 1_001, 840, true),
+                (4, 3, 920, 978, true),
+                (1, 2, 5, 840, false)
+            ]
+        );
+
+        let exchange = CurrencyExchange {
+            spread: 0,
+            ..exchange
+        };
+        let transfers = currency_exchange(&exchange).unwrap();
+        assert_eq!(transfers.spread, None);
+        assert!(!transfers.transfers()[1]
+            .flags
+            .contains(TransferFlags::Linked));
+
+        let error = |exchange| currency_exchange(&exchange).unwrap_err();
+        let rate = |numerator, denominator| ExchangeRate {
+            numerator,
+            denominator,
+        };
+        assert_eq!(
+            error(CurrencyExchange {
+                rate: rate(1, 0),
+                ..exchange
+            }),
+            RecipeError::RateInvalid
+        );
+        assert_eq!(
+            error(CurrencyExchange {
+                amount: 0,
+                ..exchange
+            }),
+            RecipeError::AmountZero { index: 0 }
+        );
+        assert_eq!(
+            error(CurrencyExchange {
+                rate: rate(1, 10_000),
+                ..exchange
+            }),
+            RecipeError::AmountZero { index: 1 }
+        );
+        assert_eq!(
+            error(CurrencyExchange {
+                rate: rate(u128::MAX, 1),
+                ..exchange
+            }),
+            RecipeError::AmountOverflow
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Reconciliation of external statements against the cluster.
+//!
+//! A statement, such as a bank's record of an account's movements, is a
+//! list of the transfers that are expected to exist. It can be read from
+//! CSV with [`bulk::read_transfers`](crate::bulk::read_transfers), or from
+//! JSON Lines with the `jsonl` feature's `io` module.
+//!
+//! The statement is compared with the transfers the cluster returns for a
+//! filter, matched by a [`MatchKey`], and each difference is reported:
+//!
+//! ```no_run
+//! use std::fs::File;
+//! use std::io::BufReader;
+//! use tigerbeetle as tb;
+//! use tb::bulk::{self, Field, Mapping};
+//! use tb::reconcile::{self, MatchKey};
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), Box<dyn std::error::Error>> {
+//! // reference,amount
+//! // 4711,12.50
+//! let mapping = Mapping::new(tb::Transfer::default())
+//!     .column("reference", Field::UserData128)
+//!     .column("amount", Field::Amount)
+//!     .amount_scale(2);
+//! let statement = bulk::read_transfers(&mapping, BufReader::new(File::open("statement.csv")?))?;
+//!
+//! let filter = tb::AccountFilter {
+//!     account_id: 1,
+//!     timestamp_min: 1_700_000_000_000_000_000,
+//!     timestamp_max: 1_700_086_400_000_000_000,
+//!     flags: tb::AccountFilterFlags::Debits | tb::AccountFilterFlags::Credits,
+//!     ..Default::default()
+//! };
+//! let report =
+//!     reconcile::account_transfers(client, filter, &statement, MatchKey::UserData128).await?;
+//! for transfer in &report.missing {
+//!     println!("missing: {}", transfer.user_data_128);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::paging;
+use crate::{AccountFilter, PacketStatus, QueryFilter, TigerBeetleClient, Transfer};
+
+use std::collections::{HashMap, VecDeque};
+
+/// The field that identifies a statement's transfer in the cluster.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum MatchKey {
+    Id,
+    UserData128,
+    UserData64,
+    UserData32,
+}
+
+impl MatchKey {
+    fn of(self, transfer: &Transfer) -> u128 {
+        match self {
+            MatchKey::Id => transfer.id,
+            MatchKey::UserData128 => transfer.user_data_128,
+            MatchKey::UserData64 => transfer.user_data_64.into(),
+            MatchKey::UserData32 => transfer.user_data_32.into(),
+        }
+    }
+}
+
+/// A statement's transfer and the cluster's transfer it matched, which
+/// differ.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Mismatch {
+    /// The transfer in the statement.
+    pub expected: Transfer,
+    /// The transfer in the cluster.
+    pub actual: Transfer,
+}
+
+impl Mismatch {
+    /// Whether the amounts differ.
+    pub fn amount_differs(&self) -> bool {
+        self.expected.amount != self.actual.amount
+    }
+
+    /// Whether an account the statement specifies differs.
+    pub fn accounts_differ(&self) -> bool {
+        !accounts_match(&self.expected, &self.actual)
+    }
+}
+
+/// The differences between a statement and the cluster.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Report {
+    /// The number of transfers that matched exactly.
+    pub matched: usize,
+    /// The statement's transfers that aren't in the cluster.
+    pub missing: Vec<Transfer>,
+    /// The cluster's transfers that aren't in the statement.
+    pub extra: Vec<Transfer>,
+    /// Transfers in both that differ.
+    pub mismatched: Vec<Mismatch>,
+}
+
+impl Report {
+    /// Whether the statement and the cluster agree.
+    pub fn is_reconciled(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// Compare `statement` with the transfers `actual`.
+///
+/// Transfers are matched by `key`. A matched pair differs if their amounts
+/// differ, or if the statement's transfer has a debit or credit account
+/// that differs; zero accounts aren't compared, since statements often
+/// don't record the other side. When several transfers have the same key,
+/// each is matched with one of the other side's, preferring one that
+/// doesn't differ.
+pub fn compare(statement: &[Transfer], actual: &[Transfer], key: MatchKey) -> Report {
+    let mut candidates: HashMap<u128, VecDeque<&Transfer>> = HashMap::new();
+    for transfer in actual {
+        candidates
+            .entry(key.of(transfer))
+            .or_default()
+            .push_back(transfer);
+    }
+
+    let mut report = Report::default();
+    for expected in statement {
+        let found = match candidates.get_mut(&key.of(expected)) {
+            Some(found) if !found.is_empty() => found,
+            _ => {
+                report.missing.push(*expected);
+                continue;
+            }
+        };
+        let same = found.iter().position(|actual| !differs(expected, actual));
+        let actual = found
+            .remove(same.unwrap_or(0))
+            .expect("a candidate was found");
+        if same.is_some() {
+            report.matched += 1;
+        } else {
+            report.mismatched.push(Mismatch {
+                expected: *expected,
+                actual: *actual,
+            });
+        }
+    }
+
+    // Report extras in the order they were created.
+    let mut extra: Vec<&Transfer> = candidates.into_values().flatten().collect();
+    extra.sort_by_key(|transfer| transfer.timestamp);
+    report.extra = extra.into_iter().copied().collect();
+    report
+}
+
+fn differs(expected: &Transfer, actual: &Transfer) -> bool {
+    expected.amount != actual.amount || !accounts_match(expected, actual)
+}
+
+fn accounts_match(expected: &Transfer, actual: &Transfer) -> bool {
+    (expected.debit_account_id == 0 || expected.debit_account_id == actual.debit_account_id)
+        && (expected.credit_account_id == 0
+            || expected.credit_account_id == actual.credit_account_id)
+}
+
+/// Reconcile `statement` with the transfers of an account matching
+/// `filter`, e.g. those within the statement's period.
+///
+/// All pages of the query are fetched; `filter.limit` sets the page size,
+/// or the maximum if it is zero.
+pub async fn account_transfers(
+    client: &dyn TigerBeetleClient,
+    filter: AccountFilter,
+    statement: &[Transfer],
+    key: MatchKey,
+) -> Result<Report, PacketStatus> {
+    let actual = paging::all(filter, |filter| client.get_account_transfers(filter)).await?;
+    Ok(compare(statement, &actual, key))
+}
+
+/// Reconcile `statement` with the transfers matching `filter`.
+///
+/// All pages of the query are fetched; `filter.limit` sets the page size,
+/// or the maximum if it is zero.
+pub async fn query_transfers(
+    client: &dyn TigerBeetleClient,
+    filter: QueryFilter,
+    statement: &[Transfer],
+    key: MatchKey,
+) -> Result<Report, PacketStatus> {
+    let actual = paging::all(filter, |filter| client.query_transfers(filter)).await?;
+    Ok(compare(statement, &actual, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Account, AccountFilterFlags, InMemoryClient};
+
+    use futures::executor::block_on;
+
+    fn transfer(id: u128, reference: u128, amount: u128) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount,
+            user_data_128: reference,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn differences() {
+        let actual = [
+            transfer(1, 10, 100),
+            transfer(2, 20, 200),
+            transfer(3, 30, 300),
+            transfer(4, 30, 301),
+        ];
+        let statement = [
+            Transfer {
+                debit_account_id: 0,
+                credit_account_id: 0,
+                ..transfer(0, 10, 100)
+            },
+            transfer(0, 20, 250),
+            // Matched with the second transfer with the same reference.
+            transfer(0, 30, 301),
+            transfer(0, 40, 400),
+        ];
+        let report = compare(&statement, &actual, MatchKey::UserData128);
+        assert_eq!(report.matched, 2);
+        assert_eq!(report.missing, [statement[3]]);
+        assert_eq!(report.extra, [actual[2]]);
+        assert_eq!(
+            report.mismatched,
+            [Mismatch {
+                expected: statement[1],
+                actual: actual[1],
+            }]
+        );
+        assert!(report.mismatched[0].amount_differs());
+        assert!(!report.mismatched[0].accounts_differ());
+        assert!(!report.is_reconciled());
+
+        let report = compare(&actual, &actual, MatchKey::Id);
+        assert!(report.is_reconciled());
+        assert_eq!(report.matched, 4);
+    }
+
+    #[test]
+    fn pages() {
+        let client = InMemoryClient::new();
+        let accounts: Vec<Account> = (1..=2)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+        let transfers: Vec<Transfer> = (1..=10).map(|id| transfer(id, id, 5)).collect();
+        assert!(block_on(client.create_transfers(&transfers))
+            .unwrap()
+            .is_empty());
+
+        let mut statement = transfers.clone();
+        statement.remove(4);
+        statement[0].amount = 6;
+        for reversed in [false, true] {
+            let filter = AccountFilter {
+                account_id: 1,
+                limit: 3,
+                flags: AccountFilterFlags::Debits
+                    | if reversed {
+                        AccountFilterFlags::Reversed
+                    } else {
+                        AccountFilterFlags::empty()
+                    },
+                ..Default::default()
+            };
+            let report =
+                block_on(account_transfers(&client, filter, &statement, MatchKey::Id)).unwrap();
+            assert_eq!(report.matched, 8);
+            assert_eq!(report.extra.len(), 1);
+            assert_eq!(report.extra[0].id, 5);
+            assert_eq!(report.mismatched[0].actual.id, 1);
+        }
+
+        let filter = QueryFilter {
+            ledger: 1,
+            limit: 4,
+            ..Default::default()
+        };
+        let report = block_on(query_transfers(
+            &client,
+            filter,
+            &transfers,
+            MatchKey::UserData128,
+        ))
+        .unwrap();
+        assert!(report.is_reconciled());
+        assert_eq!(report.matched, 10);
+    }
+}
@@ -0,0 +1,270 @@
+use super::*;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A policy for retrying requests that fail with a [`PacketStatus`].
+///
+/// Retries are applied transparently by the [`Client`] to every request,
+/// with exponentially increasing delays between attempts. Configure a
+/// client's policy with [`Client::set_retry_policy`].
+///
+/// Retrying is safe for all TigerBeetle operations: queries have no effect,
+/// and events that were already applied by a previous attempt are reported
+/// by the retry as `Exists`.
+///
+/// By default clients use [`RetryPolicy::none`]. [`RetryPolicy::new`]
+/// creates a policy that retries requests that fail because the client's
+/// session was evicted, establishing a new session for the retry.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use tigerbeetle as tb;
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let mut client = tb::Client::new(0, "127.0.0.1:3000")?;
+/// client.set_retry_policy(
+///     tb::RetryPolicy::new()
+///         .max_attempts(10)
+///         .base_delay(Duration::from_millis(50))
+///         .on_retry(|retry| {
+///             eprintln!(
+///                 "retrying {} after {}: attempt {} in {:?}",
+///                 retry.operation, retry.status, retry.attempt, retry.delay,
+///             );
+///         }),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: bool,
+    retryable: Vec<PacketStatus>,
+    on_retry: Option<OnRetry>,
+}
+
+type OnRetry = Arc<dyn Fn(&Retry) + Send + Sync>;
+
+/// A description of a retry, passed to [`RetryPolicy::on_retry`] callbacks.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Retry {
+    /// The name of the operation being retried, e.g. `"create_transfers"`.
+    pub operation: &'static str,
+    /// The number of the upcoming attempt, starting at 2 for the first retry.
+    pub attempt: u32,
+    /// The status that caused the previous attempt to fail.
+    pub status: PacketStatus,
+    /// How long the client will wait before the next attempt.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that retries evicted requests up to 5 times.
+    ///
+    /// Delays start at 100 milliseconds, double with each attempt up to a
+    /// maximum of 5 seconds, and are randomized with full jitter.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+            retryable: vec![PacketStatus::ClientEvicted],
+            on_retry: None,
+        }
+    }
+
+    /// A policy that never retries.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::new()
+        }
+    }
+
+    /// Set the maximum number of attempts, including the first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is zero.
+    pub fn max_attempts(mut self, max_attempts: u32) -> RetryPolicy {
+        assert!(max_attempts > 0, "max_attempts must be greater than zero");
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Set the delay before the first retry.
+    pub fn base_delay(mut self, base_delay: Duration) -> RetryPolicy {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Set the upper bound on the delay between attempts.
+    pub fn max_delay(mut self, max_delay: Duration) -> RetryPolicy {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Enable or disable jitter.
+    ///
+    /// With jitter enabled each delay is chosen uniformly at random between
+    /// zero and the exponential delay, so that many clients failing at once
+    /// don't retry in lockstep.
+    pub fn jitter(mut self, jitter: bool) -> RetryPolicy {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Set the statuses that cause a request to be retried.
+    ///
+    /// [`PacketStatus::ClientShutdown`] is never retried.
+    pub fn retry_on(mut self, statuses: &[PacketStatus]) -> RetryPolicy {
+        self.retryable = statuses.to_vec();
+        self
+    }
+
+    /// Set a callback invoked before each retry, e.g. for logging.
+    ///
+    /// The callback is invoked from whichever thread polls the request future.
+    pub fn on_retry(mut self, on_retry: impl Fn(&Retry) + Send + Sync + 'static) -> RetryPolicy {
+        self.on_retry = Some(Arc::new(on_retry));
+        self
+    }
+
+    /// Decide whether to retry after `attempt` failed with `status`,
+    /// and if so, how long to wait first.
+    pub(crate) fn next_retry(
+        &self,
+        operation: &'static str,
+        attempt: u32,
+        status: PacketStatus,
+    ) -> Option<Retry> {
+        if attempt >= self.max_attempts
+            || status == PacketStatus::ClientShutdown
+            || !self.retryable.contains(&status)
+        {
+            return None;
+        }
+
+        Some(Retry {
+            operation,
+            attempt: attempt + 1,
+            status,
+            delay: self.delay(attempt),
+        })
+    }
+
+    pub(crate) fn notify(&self, retry: &Retry) {
+        if let Some(on_retry) = &self.on_retry {
+            on_retry(retry);
+        }
+    }
+
+    /// The delay after `attempt` failed, where the first attempt is 1.
+    fn delay(&self, attempt: u32) -> Duration {
+        assert!(attempt > 0);
+        let exponent = (attempt - 1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        if self.jitter {
+            let delay_nanos = u64::try_from(delay.as_nanos()).unwrap_or(u64::MAX);
+            Duration::from_nanos(time_based_id::random_u64() % delay_nanos.saturating_add(1))
+        } else {
+            delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy::none()
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .field("retryable", &self.retryable)
+            .field("on_retry", &self.on_retry.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delays_grow_exponentially_up_to_max() {
+        let policy = RetryPolicy::new()
+            .max_attempts(10)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(false);
+
+        let delays: Vec<Duration> = (1..10)
+            .map(|attempt| {
+                policy
+                    .next_retry("lookup_accounts", attempt, PacketStatus::ClientEvicted)
+                    .unwrap()
+                    .delay
+            })
+            .collect();
+
+        assert_eq!(delays[0], Duration::from_millis(100));
+        assert_eq!(delays[1], Duration::from_millis(200));
+        assert_eq!(delays[2], Duration::from_millis(400));
+        assert_eq!(delays[3], Duration::from_millis(800));
+        assert!(delays[4..].iter().all(|&d| d == Duration::from_secs(1)));
+
+        assert_eq!(
+            policy.next_retry("lookup_accounts", 10, PacketStatus::ClientEvicted),
+            None
+        );
+    }
+
+    #[test]
+    fn jitter_stays_below_delay() {
+        let policy = RetryPolicy::new().base_delay(Duration::from_millis(10));
+        for _ in 0..100 {
+            let retry = policy
+                .next_retry("lookup_accounts", 1, PacketStatus::ClientEvicted)
+                .unwrap();
+            assert!(retry.delay <= Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn only_retryable_statuses_are_retried() {
+        let policy = RetryPolicy::new()
+            .retry_on(&[PacketStatus::ClientEvicted, PacketStatus::ClientShutdown]);
+
+        assert!(policy
+            .next_retry("create_transfers", 1, PacketStatus::ClientEvicted)
+            .is_some());
+        assert!(policy
+            .next_retry("create_transfers", 1, PacketStatus::TooMuchData)
+            .is_none());
+        assert!(policy
+            .next_retry("create_transfers", 1, PacketStatus::ClientShutdown)
+            .is_none());
+        assert!(RetryPolicy::none()
+            .next_retry("create_transfers", 1, PacketStatus::ClientEvicted)
+            .is_none());
+    }
+}
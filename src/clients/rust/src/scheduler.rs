@@ -0,0 +1,402 @@
+//! Periodic interest and fee accruals.
+//!
+//! A [`Scheduler`] holds [`AccrualRule`]s, each of which accrues interest on
+//! an account's balance, or a fixed fee, with a transfer between two
+//! accounts each period. [`Scheduler::run`] computes the period's accrual
+//! transfers and creates them in batches. The id of each transfer is
+//! derived from its rule and the period, so running a period again, e.g.
+//! after a crash, doesn't accrue twice:
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//! use tb::ledger::Side;
+//! use tb::scheduler::{Accrual, AccrualRule, DayCount, Period, Scheduler};
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), tb::scheduler::AccrualError> {
+//! // 5% a year on the balance of savings account 10, paid from account 1.
+//! let scheduler = Scheduler::new().rule(AccrualRule {
+//!     id: 100,
+//!     account: 10,
+//!     balance: Side::Credit,
+//!     accrual: Accrual::Interest {
+//!         rate_ppm: 50_000,
+//!         day_count: DayCount::Actual365,
+//!     },
+//!     debit_account_id: 1,
+//!     credit_account_id: 10,
+//!     ledger: 840,
+//!     code: 50,
+//! });
+//!
+//! // Each day, numbered from the Unix epoch.
+//! let summary = scheduler.run(client, Period::new(20_000, 1)).await?;
+//! println!("{} accrued, {} already accrued", summary.created, summary.existing);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::id128;
+use crate::ledger::Side;
+use crate::{
+    Account, CreateTransferResult, CreateTransfersResult, PacketStatus, TigerBeetleClient,
+    Transfer, BATCH_MAX,
+};
+
+use core::fmt;
+
+/// A rule accruing an amount each period.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct AccrualRule {
+    /// Identifies the rule. The accrual transfers have it as their
+    /// `user_data_128`, and their ids are derived from it, so it must be
+    /// unique among rules.
+    pub id: u128,
+    /// The account whose balance interest accrues on.
+    pub account: u128,
+    /// The side of the account's balance: `Credit` for credits less debits,
+    /// as for a deposit, or `Debit` for debits less credits, as for a loan.
+    /// Interest doesn't accrue on a balance on the other side.
+    pub balance: Side,
+    pub accrual: Accrual,
+    pub debit_account_id: u128,
+    pub credit_account_id: u128,
+    pub ledger: u32,
+    pub code: u16,
+}
+
+/// The amount a rule accrues each period.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Accrual {
+    /// Simple interest on the account's posted balance, at an annual rate
+    /// in parts per million, so 5% is `50_000`. The amount is rounded down
+    /// to the ledger's smallest unit.
+    Interest { rate_ppm: u32, day_count: DayCount },
+    /// A fixed amount each period.
+    Fee(u128),
+}
+
+/// The convention for the fraction of a year in a period.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum DayCount {
+    /// The period's days over 365.
+    Actual365,
+    /// The period's days over 360.
+    Actual360,
+}
+
+impl DayCount {
+    fn year_days(self) -> u128 {
+        match self {
+            Self::Actual365 => 365,
+            Self::Actual360 => 360,
+        }
+    }
+}
+
+/// An accrual period.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Period {
+    /// Identifies the period, such as the number of the day or month. The
+    /// accrual transfers have it as their `user_data_64`, and their ids are
+    /// derived from it.
+    pub index: u64,
+    /// The number of days interest accrues for.
+    pub days: u32,
+}
+
+impl Period {
+    pub fn new(index: u64, days: u32) -> Period {
+        Period { index, days }
+    }
+}
+
+/// The outcome of [`Scheduler::run`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct AccrualSummary {
+    pub created: usize,
+    /// Accruals of the period that were already created.
+    pub existing: usize,
+    /// Rules that accrued nothing, because the amount rounded down to zero
+    /// or their account wasn't found.
+    pub skipped: usize,
+    /// The ids of the rules whose transfers the cluster rejected, and why.
+    pub failed: Vec<(u128, CreateTransferResult)>,
+}
+
+/// Accrual rules, run each period.
+///
+/// See the [module documentation](self).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Scheduler {
+    rules: Vec<AccrualRule>,
+}
+
+impl Scheduler {
+    pub fn new() -> Scheduler {
+        Scheduler::default()
+    }
+
+    /// Add a rule.
+    pub fn rule(mut self, rule: AccrualRule) -> Scheduler {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn rules(&self) -> &[AccrualRule] {
+        &self.rules
+    }
+
+    /// The accrual transfers of `period`, without creating them.
+    ///
+    /// Interest accrues on the current balances of the rules' accounts.
+    /// Rules that accrue nothing have no transfer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AccrualError::Overflow`] if an amount exceeds a `u128`,
+    /// and [`AccrualError::Packet`] if a lookup failed.
+    pub async fn accruals(
+        &self,
+        client: &dyn TigerBeetleClient,
+        period: Period,
+    ) -> Result<Vec<Transfer>, AccrualError> {
+        let mut ids: Vec<u128> = self
+            .rules
+            .iter()
+            .filter(|rule| matches!(rule.accrual, Accrual::Interest { .. }))
+            .map(|rule| rule.account)
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let mut accounts = Vec::new();
+        for chunk in ids.chunks(BATCH_MAX) {
+            accounts.extend(client.lookup_accounts(chunk).await?);
+        }
+
+        let mut transfers = Vec::new();
+        for rule in &self.rules {
+            let amount = match rule.accrual {
+                Accrual::Fee(amount) => amount,
+                Accrual::Interest {
+                    rate_ppm,
+                    day_count,
+                } => match accounts.iter().find(|account| account.id == rule.account) {
+                    Some(account) => interest(account, rule.balance, rate_ppm, day_count, period)?,
+                    None => 0,
+                },
+            };
+            if amount == 0 {
+                continue;
+            }
+            transfers.push(Transfer {
+                id: id128::derive(rule.id, u128::from(period.index)),
+                debit_account_id: rule.debit_account_id,
+                credit_account_id: rule.credit_account_id,
+                amount,
+                user_data_128: rule.id,
+                user_data_64: period.index,
+                ledger: rule.ledger,
+                code: rule.code,
+                ..Default::default()
+            });
+        }
+        Ok(transfers)
+    }
+
+    /// Create the accrual transfers of `period`, in batches of at most
+    /// [`BATCH_MAX`] events.
+    ///
+    /// Running a period again is safe: its accruals that were created are
+    /// counted as existing, even if the balance, and so the amount, has
+    /// changed since.
+    ///
+    /// # Errors
+    ///
+    /// As [`Scheduler::accruals`]. Transfers of earlier batches may have
+    /// been created if a request fails; running the period again is safe.
+    pub async fn run(
+        &self,
+        client: &dyn TigerBeetleClient,
+        period: Period,
+    ) -> Result<AccrualSummary, AccrualError> {
+        let transfers = self.accruals(client, period).await?;
+        let mut summary = AccrualSummary {
+            created: transfers.len(),
+            skipped: self.rules.len() - transfers.len(),
+            ..Default::default()
+        };
+        for batch in transfers.chunks(BATCH_MAX) {
+            let results: Vec<CreateTransfersResult> = client.create_transfers(batch).await?;
+            for result in results {
+                summary.created -= 1;
+                match result.result {
+                    CreateTransferResult::Exists
+                    | CreateTransferResult::ExistsWithDifferentAmount => summary.existing += 1,
+                    failed => summary
+                        .failed
+                        .push((batch[result.index].user_data_128, failed)),
+                }
+            }
+        }
+        Ok(summary)
+    }
+}
+
+/// The interest on `account`'s posted balance for `period`.
+fn interest(
+    account: &Account,
+    side: Side,
+    rate_ppm: u32,
+    day_count: DayCount,
+    period: Period,
+) -> Result<u128, AccrualError> {
+    let balance = match side {
+        Side::Credit => account.credits_posted.saturating_sub(account.debits_posted),
+        Side::Debit => account.debits_posted.saturating_sub(account.credits_posted),
+    };
+    balance
+        .checked_mul(u128::from(rate_ppm))
+        .and_then(|amount| amount.checked_mul(u128::from(period.days)))
+        .map(|amount| amount / (1_000_000 * day_count.year_days()))
+        .ok_or(AccrualError::Overflow)
+}
+
+/// Errors computing or creating accruals.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum AccrualError {
+    /// An interest amount exceeds a `u128`.
+    Overflow,
+    /// A request failed.
+    Packet(PacketStatus),
+}
+
+impl From<PacketStatus> for AccrualError {
+    fn from(status: PacketStatus) -> AccrualError {
+        AccrualError::Packet(status)
+    }
+}
+
+impl std::error::Error for AccrualError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Packet(status) => Some(status),
+            Self::Overflow => None,
+        }
+    }
+}
+
+impl fmt::Display for AccrualError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Overflow => f.write_str("interest exceeds a u128"),
+            Self::Packet(status) => fmt::Display::fmt(status, f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryClient;
+
+    use futures::executor::block_on;
+
+    fn rule(id: u128, account: u128, accrual: Accrual) -> AccrualRule {
+        AccrualRule {
+            id,
+            account,
+            balance: Side::Credit,
+            accrual,
+            debit_account_id: 1,
+            credit_account_id: account,
+            ledger: 1,
+            code: 50,
+        }
+    }
+
+    #[test]
+    fn run() {
+        let client = InMemoryClient::new();
+        let accounts: Vec<Account> = (1..=3)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+        let deposit = Transfer {
+            id: 1,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 3_650_000,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        assert!(block_on(client.create_transfers(&[deposit]))
+            .unwrap()
+            .is_empty());
+
+        let interest = Accrual::Interest {
+            rate_ppm: 50_000,
+            day_count: DayCount::Actual365,
+        };
+        let scheduler = Scheduler::new()
+            .rule(rule(100, 2, interest))
+            .rule(rule(101, 3, interest))
+            .rule(rule(102, 3, Accrual::Fee(7)))
+            .rule(rule(103, 4, Accrual::Fee(7)));
+
+        let transfers = block_on(scheduler.accruals(&client, Period::new(1, 2))).unwrap();
+        let accrued: Vec<_> = transfers
+            .iter()
+            .map(|t| (t.user_data_128, t.credit_account_id, t.amount))
+            .collect();
+        assert_eq!(accrued, [(100, 2, 1000), (102, 3, 7), (103, 4, 7)]);
+
+        let summary = block_on(scheduler.run(&client, Period::new(1, 2))).unwrap();
+        assert_eq!(summary.created, 2);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(
+            summary.failed,
+            [(103, CreateTransferResult::CreditAccountNotFound)]
+        );
+
+        // Running the period again, with a different balance, accrues
+        // nothing.
+        let summary = block_on(scheduler.run(&client, Period::new(1, 2))).unwrap();
+        assert_eq!((summary.created, summary.existing), (0, 2));
+        let summary = block_on(scheduler.run(&client, Period::new(2, 1))).unwrap();
+        assert_eq!((summary.created, summary.existing), (2, 0));
+    }
+
+    #[test]
+    fn interest() {
+        let account = Account {
+            debits_posted: 100,
+            credits_posted: 36_100,
+            ..Default::default()
+        };
+        let period = Period::new(0, 30);
+        let interest =
+            |side, day_count| super::interest(&account, side, 100_000, day_count, period);
+        assert_eq!(interest(Side::Credit, DayCount::Actual360), Ok(300));
+        assert_eq!(interest(Side::Credit, DayCount::Actual365), Ok(295));
+        assert_eq!(interest(Side::Debit, DayCount::Actual365), Ok(0));
+
+        let account = Account {
+            credits_posted: u128::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::interest(&account, Side::Credit, 1, DayCount::Actual365, period),
+            Err(AccrualError::Overflow)
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Serialization of `u128` fields as strings.
+//!
+//! Many formats, including JSON as read by JavaScript and TOML, can't
+//! represent integers larger than 64 bits, so the `u128` fields of this
+//! crate's types, such as ids and amounts, are serialized as decimal strings.
+//! Deserialization also accepts `0x`-prefixed hexadecimal strings and
+//! integers.
+//!
+//! The same representations can be used for `u128` fields of other types
+//! with serde's `with` attribute, this module for decimal strings and
+//! [`hex`] for hexadecimal strings:
+//!
+//! ```
+//! # use tigerbeetle as tb;
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct PaymentRequest {
+//!     #[serde(with = "tb::serde_u128::hex")]
+//!     payment_id: u128,
+//!     transfer: tb::Transfer,
+//! }
+//!
+//! let request: PaymentRequest = serde_json::from_str(
+//!     r#"{"payment_id": "42", "transfer": {"id": "7", "amount": 100}}"#,
+//! )
+//! .unwrap();
+//! assert_eq!(request.transfer.amount, 100);
+//!
+//! let json = serde_json::to_string(&request).unwrap();
+//! assert!(json.starts_with(r#"{"payment_id":"0x2a","transfer":{"id":"7","#));
+//! ```
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Serializer;
+
+use std::fmt;
+
+/// Serialize a `u128` as a decimal string.
+pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+/// Deserialize a `u128` from a decimal or `0x`-prefixed hexadecimal string,
+/// or an integer.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+    deserializer.deserialize_any(U128Visitor)
+}
+
+/// Serialization of `u128` fields as `0x`-prefixed hexadecimal strings.
+///
+/// Deserialization accepts the same representations as the parent module.
+pub mod hex {
+    use serde::{Deserializer, Serializer};
+
+    /// Serialize a `u128` as a `0x`-prefixed lowercase hexadecimal string.
+    pub fn serialize<S: Serializer>(value: &u128, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{value:#x}"))
+    }
+
+    /// Deserialize a `u128` from a decimal or `0x`-prefixed hexadecimal
+    /// string, or an integer.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+        super::deserialize(deserializer)
+    }
+}
+
+struct U128Visitor;
+
+impl<'de> Visitor<'de> for U128Visitor {
+    type Value = u128;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a u128 as a decimal or 0x-prefixed hexadecimal string, or an integer")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<u128, E> {
+        let parsed = match value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            Some(hex) if !hex.starts_with('+') => u128::from_str_radix(hex, 16),
+            None if !value.starts_with('+') => value.parse(),
+            _ => return Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+        };
+        parsed.map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<u128, E> {
+        Ok(value.into())
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<u128, E> {
+        Ok(value)
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<u128, E> {
+        u128::try_from(value).map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+}
@@ -0,0 +1,478 @@
+use super::*;
+
+use futures_channel::oneshot::Sender;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use std::sync::{Arc, Mutex, RwLock, Weak};
+
+/// The state shared between a [`Client`] and its outstanding requests.
+///
+/// Request futures may outlive the `Client` that created them, and may need
+/// to resubmit packets after a retry delay, so they hold a reference to this
+/// instead of to the client itself.
+pub(crate) struct ClientCore {
+    connector: RwLock<Connector>,
+    session: RwLock<Arc<Session>>,
+    /// Sessions replaced by an address update, which are closed once their
+    /// in-flight requests complete.
+    retired: Mutex<Vec<Arc<Session>>>,
+    in_flight: Arc<InFlight>,
+    closed: AtomicBool,
+    on_eviction: RwLock<Option<eviction::OnEviction>>,
+}
+
+/// How to establish new sessions.
+pub(crate) enum Connector {
+    Native {
+        cluster_id: u128,
+        /// DNS names are resolved again for each new session.
+        addresses: Vec<addresses::ReplicaAddress>,
+    },
+    #[cfg(feature = "sim")]
+    Sim(Arc<sim::Network>),
+}
+
+impl ClientCore {
+    pub(crate) fn new(connector: Connector) -> Result<ClientCore, InitStatus> {
+        let in_flight = Arc::new(InFlight::default());
+        let session = Session::new(&connector, &in_flight)?;
+
+        Ok(ClientCore {
+            connector: RwLock::new(connector),
+            session: RwLock::new(Arc::new(session)),
+            retired: Mutex::new(Vec::new()),
+            in_flight,
+            closed: AtomicBool::new(false),
+            on_eviction: RwLock::new(None),
+        })
+    }
+
+    pub(crate) fn set_on_eviction(&self, on_eviction: eviction::OnEviction) {
+        *self.on_eviction.write().expect("eviction callback") = Some(on_eviction);
+    }
+
+    /// Report that `session` was evicted, once per session, returning
+    /// whether this was the first report.
+    pub(crate) fn evicted(&self, session: &Session, reason: EvictionReason) -> bool {
+        if session.evicted.swap(true, Ordering::AcqRel) {
+            return false;
+        }
+        warn!(reason = %reason, "session evicted");
+        let on_eviction = self.on_eviction.read().expect("eviction callback").clone();
+        if let Some(on_eviction) = on_eviction {
+            on_eviction(reason);
+        }
+        true
+    }
+
+    /// The current session.
+    pub(crate) fn session(&self) -> Arc<Session> {
+        self.close_retired_idle();
+        let session = self.session.read().expect("client session");
+        Arc::clone(&session)
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Wait until no packets are in flight on any of the client's sessions.
+    pub(crate) fn idle(&self) -> impl Future<Output = ()> {
+        let rx = {
+            let mut state = self.in_flight.state.lock().expect("in-flight packets");
+            if state.packets == 0 {
+                None
+            } else {
+                let (tx, rx) = channel::<Infallible>();
+                state.idle.push(tx);
+                Some(rx)
+            }
+        };
+
+        async {
+            if let Some(rx) = rx {
+                // wait for the channel to close
+                let _ = rx.await;
+            }
+        }
+    }
+
+    /// Replace an evicted session with a new one.
+    ///
+    /// Returns `false` if the client is closed or a new session could not
+    /// be created, in which case the evicted session remains current.
+    pub(crate) fn replace_session(&self, evicted: &Arc<Session>) -> bool {
+        let mut session = self.session.write().expect("client session");
+        if self.is_closed() {
+            return false;
+        }
+        if !Arc::ptr_eq(&session, evicted) {
+            // Already replaced by another request.
+            return true;
+        }
+        debug!("registering a new session");
+        let connector = self.connector.read().expect("client connector");
+        match Session::new(&connector, &self.in_flight) {
+            Ok(session_new) => {
+                let session_old = std::mem::replace(&mut *session, Arc::new(session_new));
+                drop(Session::close(&session_old));
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Replace the replica addresses and register a new session with them.
+    ///
+    /// Requests already submitted to the old session complete on it, after
+    /// which it is closed; see [`ClientCore::close_retired_idle`].
+    ///
+    /// Does nothing if the client is closed.
+    pub(crate) fn update_addresses(
+        &self,
+        addresses_new: Vec<addresses::ReplicaAddress>,
+    ) -> Result<(), InitStatus> {
+        let mut session = self.session.write().expect("client session");
+        if self.is_closed() {
+            return Ok(());
+        }
+        let mut connector = self.connector.write().expect("client connector");
+        let connector_new = match &*connector {
+            Connector::Native { cluster_id, .. } => Connector::Native {
+                cluster_id: *cluster_id,
+                addresses: addresses_new,
+            },
+            // Simulated sessions have no addresses, but are still replaced.
+            #[cfg(feature = "sim")]
+            Connector::Sim(network) => Connector::Sim(Arc::clone(network)),
+        };
+        let session_new = Session::new(&connector_new, &self.in_flight)?;
+        *connector = connector_new;
+        self.retire(std::mem::replace(&mut *session, Arc::new(session_new)));
+        Ok(())
+    }
+
+    /// Resolve the replica addresses again, and register a new session if
+    /// any of them resolve differently than for the current session.
+    pub(crate) fn refresh_addresses(&self) -> Result<(), InitStatus> {
+        let addresses = match &*self.connector.read().expect("client connector") {
+            Connector::Native { addresses, .. } => addresses.clone(),
+            #[cfg(feature = "sim")]
+            Connector::Sim(_) => return Ok(()),
+        };
+        let resolved = addresses::resolve(&addresses).map_err(|_| InitStatus::AddressInvalid)?;
+        if self.session().resolved == resolved {
+            return Ok(());
+        }
+        debug!(addresses = %resolved, "replica addresses changed");
+        self.update_addresses(addresses)
+    }
+
+    /// Call [`ClientCore::refresh_addresses`] every `interval` until the
+    /// client is closed or dropped.
+    pub(crate) fn refresh_addresses_every(core: &Arc<ClientCore>, interval: Duration) {
+        let core = Arc::downgrade(core);
+        timer::call_at(Instant::now() + interval, move || {
+            // Resolution blocks, and timer callbacks must not.
+            std::thread::spawn(move || {
+                if let Some(core) = Weak::upgrade(&core) {
+                    if !core.is_closed() {
+                        if let Err(_status) = core.refresh_addresses() {
+                            warn!(status = %_status, "refreshing replica addresses failed");
+                        }
+                        ClientCore::refresh_addresses_every(&core, interval);
+                    }
+                }
+            });
+        });
+    }
+
+    fn retire(&self, session: Arc<Session>) {
+        self.retired.lock().expect("retired sessions").push(session);
+        self.close_retired_idle();
+    }
+
+    /// Close retired sessions that no longer have requests in flight.
+    ///
+    /// In-flight requests hold a reference to their session, so a retired
+    /// session is idle once the retired list holds the only reference.
+    fn close_retired_idle(&self) {
+        let mut retired = match self.retired.try_lock() {
+            Ok(retired) => retired,
+            Err(_) => return,
+        };
+        retired.retain(|session| {
+            let idle = Arc::strong_count(session) == 1;
+            if idle {
+                drop(Session::close(session));
+            }
+            !idle
+        });
+    }
+
+    /// Close the current session, and any retired sessions.
+    ///
+    /// Returns `None` if the client was already closed.
+    pub(crate) fn close(&self) -> Option<impl Future<Output = ()>> {
+        // Hold the lock so that a concurrent replacement can't create a
+        // session after we close.
+        let session = self.session.write().expect("client session");
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+        for session_retired in self.retired.lock().expect("retired sessions").drain(..) {
+            drop(Session::close(&session_retired));
+        }
+        Some(Session::close(&session))
+    }
+}
+
+/// A single `tb_client` instance.
+pub(crate) struct Session {
+    backend: Backend,
+    /// The resolved addresses the session was registered with.
+    resolved: String,
+    in_flight: Arc<InFlight>,
+    closed: Arc<AtomicBool>,
+    evicted: AtomicBool,
+}
+
+enum Backend {
+    Native(NativeClient),
+    #[cfg(feature = "sim")]
+    Sim(Arc<sim::Network>, sim::SessionId),
+}
+
+impl Session {
+    fn new(connector: &Connector, in_flight: &Arc<InFlight>) -> Result<Session, InitStatus> {
+        match connector {
+            Connector::Native {
+                cluster_id,
+                addresses,
+            } => {
+                let addresses =
+                    addresses::resolve(addresses).map_err(|_| InitStatus::AddressInvalid)?;
+                Session::new_native(*cluster_id, addresses, in_flight)
+            }
+            #[cfg(feature = "sim")]
+            Connector::Sim(network) => Ok(Session {
+                backend: Backend::Sim(Arc::clone(network), network.connect()),
+                resolved: String::new(),
+                in_flight: Arc::clone(in_flight),
+                closed: Arc::new(AtomicBool::new(false)),
+                evicted: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    fn new_native(
+        cluster_id: u128,
+        addresses: String,
+        in_flight: &Arc<InFlight>,
+    ) -> Result<Session, InitStatus> {
+        let client = NativeClient::init(cluster_id, &addresses)?;
+        Ok(Session {
+            backend: Backend::Native(client),
+            resolved: addresses,
+            in_flight: Arc::clone(in_flight),
+            closed: Arc::new(AtomicBool::new(false)),
+            evicted: AtomicBool::new(false),
+        })
+    }
+
+    /// Submit a packet.
+    ///
+    /// If the session is already closed the packet is completed
+    /// immediately with [`PacketStatus::ClientShutdown`].
+    pub(crate) fn submit(&self, packet: Box<tbc::tb_packet_t>) {
+        let packet = self.in_flight.track(packet);
+        match &self.backend {
+            Backend::Native(client) => client.submit(packet),
+            #[cfg(feature = "sim")]
+            Backend::Sim(network, session_id) => {
+                network.submit(*session_id, &self.closed, packet);
+            }
+        }
+    }
+
+    fn native_client(&self) -> &NativeClient {
+        match &self.backend {
+            Backend::Native(client) => client,
+            #[cfg(feature = "sim")]
+            Backend::Sim(..) => unreachable!(),
+        }
+    }
+
+    /// Close the session off-thread.
+    ///
+    /// Any pending requests are completed with [`PacketStatus::ClientShutdown`].
+    /// The returned future completes once the session is closed.
+    fn close(session: &Arc<Session>) -> impl Future<Output = ()> {
+        let (tx, rx) = channel::<Infallible>();
+
+        if !session.closed.swap(true, Ordering::AcqRel) {
+            match session.backend {
+                Backend::Native(_) => {
+                    // Keep the tb_client_t allocation alive until deinit returns.
+                    let session = Arc::clone(session);
+                    std::thread::spawn(move || {
+                        // This blocks, so we're calling it offthread.
+                        session.native_client().deinit();
+                        drop(tx);
+                    });
+                }
+                // In-flight simulated requests complete with ClientShutdown
+                // when they are delivered.
+                #[cfg(feature = "sim")]
+                Backend::Sim(..) => drop(tx),
+            }
+        }
+
+        async {
+            // wait for the channel to close
+            let _ = rx.await;
+        }
+    }
+}
+
+/// Packets submitted to a client's sessions that have not yet completed.
+#[derive(Default)]
+struct InFlight {
+    state: Mutex<InFlightState>,
+}
+
+#[derive(Default)]
+struct InFlightState {
+    packets: usize,
+    /// Closed once there are no packets in flight.
+    idle: Vec<Sender<Infallible>>,
+}
+
+impl InFlight {
+    /// Count a packet as in flight until its completion callback has run.
+    fn track(self: &Arc<InFlight>, mut packet: Box<tbc::tb_packet_t>) -> Box<tbc::tb_packet_t> {
+        self.state.lock().expect("in-flight packets").packets += 1;
+
+        let in_flight = Arc::clone(self);
+        // The packet's callback, boxed by create_packet.
+        let callback = unsafe { Box::from_raw(packet.user_data as *mut OnCompletion) };
+        let callback: Box<OnCompletion> = Box::new(Box::new(
+            move |context, packet, timestamp, result_ptr, result_len| {
+                callback(context, packet, timestamp, result_ptr, result_len);
+                in_flight.complete();
+            },
+        ));
+        packet.user_data = Box::into_raw(callback) as *mut c_void;
+        packet
+    }
+
+    fn complete(&self) {
+        let mut state = self.state.lock().expect("in-flight packets");
+        state.packets -= 1;
+        if state.packets == 0 {
+            state.idle.clear();
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // Sessions are always closed before the last reference is dropped,
+        // and the closing thread holds a reference until deinit returns, so
+        // dropping a native client doesn't block.
+        assert!(self.closed.load(Ordering::Acquire));
+    }
+}
+
+/// An initialized `tb_client_t`, deinitialized and freed when dropped.
+///
+/// `tb_client` keeps a pointer to the `tb_client_t`, so it is allocated
+/// separately and never moves, and is only freed once deinit has returned.
+struct NativeClient {
+    client: ptr::NonNull<tbc::tb_client_t>,
+    deinitialized: AtomicBool,
+}
+
+// Safety: tb_client_submit and tb_client_deinit may be called from any
+// thread, concurrently; the completion callback runs on tb_client's own
+// thread and only touches the packet. The allocation is owned, and only
+// freed on drop, when no other references remain.
+unsafe impl Send for NativeClient {}
+unsafe impl Sync for NativeClient {}
+
+impl NativeClient {
+    fn init(cluster_id: u128, addresses: &str) -> Result<NativeClient, InitStatus> {
+        let client = Box::into_raw(Box::new(tbc::tb_client_t {
+            opaque: Default::default(),
+        }));
+        let status = unsafe {
+            tbc::tb_client_init(
+                client,
+                &cluster_id.to_le_bytes(),
+                addresses.as_ptr(),
+                addresses.len() as u32,
+                COMPLETION_CONTEXT,
+                Some(on_completion),
+            )
+        };
+        if status != tbc::TB_INIT_STATUS_TB_INIT_SUCCESS {
+            drop(unsafe { Box::from_raw(client) });
+            return Err(status.into());
+        }
+        Ok(NativeClient {
+            client: ptr::NonNull::new(client).expect("Box::into_raw is non-null"),
+            deinitialized: AtomicBool::new(false),
+        })
+    }
+
+    /// Submit a packet, completing it with [`PacketStatus::ClientShutdown`]
+    /// if the client was deinitialized.
+    fn submit(&self, packet: Box<tbc::tb_packet_t>) {
+        let packet = Box::into_raw(packet);
+        unsafe {
+            let status = tbc::tb_client_submit(self.client.as_ptr(), packet);
+            if status == tbc::TB_CLIENT_STATUS_TB_CLIENT_INVALID {
+                // tb_client doesn't take ownership of the packet if the
+                // client is closed, so complete it ourselves.
+                (*packet).status = tbc::TB_PACKET_STATUS_TB_PACKET_CLIENT_SHUTDOWN;
+                on_completion(COMPLETION_CONTEXT, packet, 0, ptr::null(), 0);
+            } else {
+                assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
+            }
+        }
+    }
+
+    /// Deinitialize the client, completing pending packets with
+    /// [`PacketStatus::ClientShutdown`]. Blocks until tb_client's thread has
+    /// stopped. Does nothing if already deinitialized.
+    fn deinit(&self) {
+        if self.deinitialized.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let status = unsafe { tbc::tb_client_deinit(self.client.as_ptr()) };
+        assert_eq!(status, tbc::TB_CLIENT_STATUS_TB_CLIENT_OK);
+    }
+}
+
+impl Drop for NativeClient {
+    fn drop(&mut self) {
+        self.deinit();
+        drop(unsafe { Box::from_raw(self.client.as_ptr()) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn send_sync() {
+        assert_send_sync::<NativeClient>();
+        assert_send_sync::<Session>();
+        assert_send_sync::<ClientCore>();
+        assert_send_sync::<Client>();
+        assert_send_sync::<blocking::Client>();
+    }
+}
@@ -0,0 +1,1216 @@
+//! Deterministic simulation of a TigerBeetle cluster behind a faulty network.
+//!
+//! Enabled by the `sim` feature.
+//!
+//! A [`SimulatedCluster`] executes requests with an [`InMemoryClient`], and
+//! hands out [`Client`]s whose requests reach it through a simulated network.
+//! The network injects faults: delays, which also reorder concurrent requests;
+//! lost requests and replies, which `tb_client` would retransmit; duplicated
+//! requests; and session evictions.
+//!
+//! Faults are chosen by a pseudo-random generator seeded with the cluster's
+//! seed, in the order packets are submitted, so a test that submits requests
+//! in a deterministic order experiences the same faults on every run with the
+//! same seed. Failing seeds can therefore be reproduced, in the spirit of
+//! TigerBeetle's own simulator, the VOPR.
+//!
+//! # Example
+//!
+//! ```
+//! use futures::executor::block_on;
+//! use std::time::Duration;
+//! use tigerbeetle as tb;
+//! use tb::sim::{Faults, SimulatedCluster};
+//!
+//! let cluster = SimulatedCluster::new(
+//!     42,
+//!     Faults {
+//!         delay_max: Duration::from_millis(5),
+//!         eviction: 0.1,
+//!         ..Default::default()
+//!     },
+//! );
+//!
+//! let mut client = cluster.client();
+//! client.set_retry_policy(tb::RetryPolicy::new().base_delay(Duration::from_millis(1)));
+//!
+//! let result = block_on(client.create_accounts(&[tb::Account {
+//!     id: 1,
+//!     ledger: 1,
+//!     code: 1,
+//!     ..Default::default()
+//! }]));
+//! # let _ = result;
+//! ```
+
+use super::*;
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The faults injected by a [`SimulatedCluster`]'s network.
+///
+/// Probabilities are in the range `0.0..=1.0`, and apply independently to
+/// each submitted packet.
+#[derive(Clone, Debug)]
+pub struct Faults {
+    /// The maximum delay before a request is delivered.
+    ///
+    /// Each request is delayed by a random duration up to this maximum,
+    /// so concurrent requests may complete out of order.
+    pub delay_max: Duration,
+    /// The probability that a request is lost, and retransmitted after
+    /// [`Faults::retransmit_delay`].
+    pub request_loss: f64,
+    /// The probability that a reply is lost after the request executes.
+    ///
+    /// The retransmitted request is deduplicated by the cluster, so this
+    /// only delays the reply.
+    pub reply_loss: f64,
+    /// The delay before a lost request or reply is retransmitted.
+    pub retransmit_delay: Duration,
+    /// The probability that a request is executed twice.
+    ///
+    /// The reply to the duplicate is discarded. Unlike a real cluster,
+    /// which deduplicates retransmissions, this models an application-level
+    /// resubmission, so exercises the idempotency of events.
+    pub duplication: f64,
+    /// The probability that the session is evicted before the reply.
+    ///
+    /// The request fails with [`PacketStatus::ClientEvicted`], and may or
+    /// may not have been executed. All later requests to the same session
+    /// also fail, until the client establishes a new session.
+    pub eviction: f64,
+}
+
+impl Default for Faults {
+    fn default() -> Faults {
+        Faults {
+            delay_max: Duration::ZERO,
+            request_loss: 0.0,
+            reply_loss: 0.0,
+            retransmit_delay: Duration::from_millis(10),
+            duplication: 0.0,
+            eviction: 0.0,
+        }
+    }
+}
+
+/// Counts of the faults injected by a [`SimulatedCluster`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct SimStats {
+    pub requests: u64,
+    pub requests_lost: u64,
+    pub replies_lost: u64,
+    pub duplicated: u64,
+    pub evicted: u64,
+}
+
+/// A simulated TigerBeetle cluster.
+///
+/// See the [module documentation](self).
+pub struct SimulatedCluster {
+    network: Arc<Network>,
+}
+
+impl SimulatedCluster {
+    /// # Panics
+    ///
+    /// Panics if a probability in `faults` is outside `0.0..=1.0`,
+    /// or if `request_loss` or `reply_loss` is `1.0`, which would
+    /// prevent any request from completing.
+    pub fn new(seed: u64, faults: Faults) -> SimulatedCluster {
+        for probability in [faults.duplication, faults.eviction] {
+            assert!((0.0..=1.0).contains(&probability));
+        }
+        for probability in [faults.request_loss, faults.reply_loss] {
+            assert!((0.0..1.0).contains(&probability));
+        }
+
+        SimulatedCluster {
+            network: Arc::new(Network {
+                seed,
+                faults,
+                cluster: InMemoryClient::new(),
+                state: Mutex::new(NetworkState {
+                    prng: Prng::new(seed),
+                    session_next: 0,
+                    sessions_evicted: HashSet::new(),
+                    stats: SimStats::default(),
+                }),
+            }),
+        }
+    }
+
+    /// Create a client connected to the cluster through the faulty network.
+    pub fn client(&self) -> Client {
+        let core =
+            ClientCore::new(Connector::Sim(Arc::clone(&self.network))).expect("simulated session");
+        Client::with_core(core)
+    }
+
+    /// The cluster's state, accessed directly rather than through the network.
+    ///
+    /// Useful for checking invariants at the end of a simulation.
+    pub fn state(&self) -> &InMemoryClient {
+        &self.network.cluster
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.network.seed
+    }
+
+    pub fn stats(&self) -> SimStats {
+        self.network.state().stats
+    }
+}
+
+impl fmt::Debug for SimulatedCluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("SimulatedCluster")
+            .field("seed", &self.network.seed)
+            .field("faults", &self.network.faults)
+            .finish()
+    }
+}
+
+pub(crate) type SessionId = u64;
+
+pub(crate) struct Network {
+    seed: u64,
+    faults: Faults,
+    cluster: InMemoryClient,
+    state: Mutex<NetworkState>,
+}
+
+struct NetworkState {
+    prng: Prng,
+    session_next: SessionId,
+    sessions_evicted: HashSet<SessionId>,
+    stats: SimStats,
+}
+
+/// What happens to a submitted packet.
+enum Delivery {
+    Reply { duplicate: bool },
+    Evicted { executed: bool },
+}
+
+// Thread-sendable wrapper for a packet in flight.
+struct InFlight(Box<tbc::tb_packet_t>);
+
+// Safety: the packet is owned by the network until it is completed.
+unsafe impl Send for InFlight {}
+
+impl Network {
+    fn state(&self) -> std::sync::MutexGuard<'_, NetworkState> {
+        self.state.lock().expect("simulated network")
+    }
+
+    pub(crate) fn connect(&self) -> SessionId {
+        let mut state = self.state();
+        state.session_next += 1;
+        state.session_next
+    }
+
+    /// Submit a packet, completing it once it's delivered.
+    pub(crate) fn submit(
+        self: &Arc<Self>,
+        session: SessionId,
+        closed: &Arc<AtomicBool>,
+        packet: Box<tbc::tb_packet_t>,
+    ) {
+        let (delay, delivery) = self.plan(session);
+
+        let network = Arc::clone(self);
+        let closed = Arc::clone(closed);
+        let packet = InFlight(packet);
+        timer::call_at(Instant::now() + delay, move || {
+            network.deliver(packet, &closed, delivery);
+        });
+    }
+
+    /// Choose the faults for the next packet.
+    fn plan(&self, session: SessionId) -> (Duration, Delivery) {
+        let faults = &self.faults;
+        let mut state = self.state();
+        let state = &mut *state;
+        state.stats.requests += 1;
+
+        if state.sessions_evicted.contains(&session) {
+            return (Duration::ZERO, Delivery::Evicted { executed: false });
+        }
+
+        let mut delay = state.prng.duration(faults.delay_max);
+        while state.prng.chance(faults.request_loss) {
+            state.stats.requests_lost += 1;
+            delay += faults.retransmit_delay;
+        }
+
+        if state.prng.chance(faults.eviction) {
+            state.stats.evicted += 1;
+            state.sessions_evicted.insert(session);
+            let executed = state.prng.chance(0.5);
+            return (delay, Delivery::Evicted { executed });
+        }
+
+        while state.prng.chance(faults.reply_loss) {
+            state.stats.replies_lost += 1;
+            delay += faults.retransmit_delay;
+        }
+
+        let duplicate = state.prng.chance(faults.duplication);
+        if duplicate {
+            state.stats.duplicated += 1;
+        }
+        (delay, Delivery::Reply { duplicate })
+    }
+
+    fn deliver(&self, packet: InFlight, closed: &AtomicBool, delivery: Delivery) {
+        let mut packet = packet.0;
+
+        let (status, result) = if closed.load(Ordering::Acquire) {
+            (tbc::TB_PACKET_STATUS_TB_PACKET_CLIENT_SHUTDOWN, Vec::new())
+        } else if event_count(&packet) > BATCH_MAX {
+            (tbc::TB_PACKET_STATUS_TB_PACKET_TOO_MUCH_DATA, Vec::new())
+        } else {
+            match delivery {
+                Delivery::Reply { duplicate } => {
+                    let result = self.execute(&packet);
+                    if duplicate {
+                        self.execute(&packet);
+                    }
+                    (tbc::TB_PACKET_STATUS_TB_PACKET_OK, result)
+                }
+                Delivery::Evicted { executed } => {
+                    if executed {
+                        self.execute(&packet);
+                    }
+                    (tbc::TB_PACKET_STATUS_TB_PACKET_CLIENT_EVICTED, Vec::new())
+                }
+            }
+        };
+
+        packet.status = status;
+        let packet = Box::into_raw(packet);
+        on_completion(
+            COMPLETION_CONTEXT,
+            packet,
+            0,
+            result.as_ptr(),
+            u32::try_from(result.len()).expect("result size"),
+        );
+    }
+
+    /// Execute a packet against the cluster, returning the reply body.
+    fn execute(&self, packet: &tbc::tb_packet_t) -> Vec<u8> {
+        let cluster = &self.cluster;
+        match packet.operation {
+            tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS => {
+                let results = blocking::block_on(cluster.create_accounts(&events(packet)));
+                let results: Vec<tbc::tb_create_accounts_result_t> = results
+                    .expect("results")
+                    .into_iter()
+                    .map(|result| tbc::tb_create_accounts_result_t {
+                        index: u32::try_from(result.index).expect("index"),
+                        result: result.result.into(),
+                    })
+                    .collect();
+                wire::encode(&results)
+            }
+            tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS => {
+                let results = blocking::block_on(cluster.create_transfers(&events(packet)));
+                let results: Vec<tbc::tb_create_transfers_result_t> = results
+                    .expect("results")
+                    .into_iter()
+                    .map(|result| tbc::tb_create_transfers_result_t {
+                        index: u32::try_from(result.index).expect("index"),
+                        result: result.result.into(),
+                    })
+                    .collect();
+                wire::encode(&results)
+            }
+            tbc::TB_OPERATION_TB_OPERATION_LOOKUP_ACCOUNTS => {
+                let results = blocking::block_on(cluster.lookup_accounts(&events(packet)));
+                wire::encode(&results.expect("results"))
+            }
+            tbc::TB_OPERATION_TB_OPERATION_LOOKUP_TRANSFERS => {
+                let results = blocking::block_on(cluster.lookup_transfers(&events(packet)));
+                wire::encode(&results.expect("results"))
+            }
+            tbc::TB_OPERATION_TB_OPERATION_GET_ACCOUNT_TRANSFERS => {
+                let filter = events::<AccountFilter>(packet)[0];
+                let results = blocking::block_on(cluster.get_account_transfers(filter));
+                wire::encode(&results.expect("results"))
+            }
+            tbc::TB_OPERATION_TB_OPERATION_GET_ACCOUNT_BALANCES => {
+                let filter = events::<AccountFilter>(packet)[0];
+                let results = blocking::block_on(cluster.get_account_balances(filter));
+                wire::encode(&results.expect("results"))
+            }
+            tbc::TB_OPERATION_TB_OPERATION_QUERY_ACCOUNTS => {
+                let filter = events::<QueryFilter>(packet)[0];
+                let results = blocking::block_on(cluster.query_accounts(filter));
+                wire::encode(&results.expect("results"))
+            }
+            tbc::TB_OPERATION_TB_OPERATION_QUERY_TRANSFERS => {
+                let filter = events::<QueryFilter>(packet)[0];
+                let results = blocking::block_on(cluster.query_transfers(filter));
+                wire::encode(&results.expect("results"))
+            }
+            operation => panic!("unexpected operation {operation}"),
+        }
+    }
+}
+
+/// The events of a packet created by `create_packet`.
+/// The number of events in a packet, as limited by `tb_client`.
+fn event_count(packet: &tbc::tb_packet_t) -> usize {
+    let size = match packet.operation {
+        tbc::TB_OPERATION_TB_OPERATION_CREATE_ACCOUNTS => core::mem::size_of::<Account>(),
+        tbc::TB_OPERATION_TB_OPERATION_CREATE_TRANSFERS => core::mem::size_of::<Transfer>(),
+        tbc::TB_OPERATION_TB_OPERATION_LOOKUP_ACCOUNTS
+        | tbc::TB_OPERATION_TB_OPERATION_LOOKUP_TRANSFERS => core::mem::size_of::<u128>(),
+        _ => return 1,
+    };
+    packet.data_size as usize / size
+}
+
+fn events<Event: wire::Wire>(packet: &tbc::tb_packet_t) -> Vec<Event> {
+    if packet.data_size == 0 {
+        return Vec::new();
+    }
+    // Safety: the packet's data is the events' bytes, owned by the packet.
+    let bytes =
+        unsafe { std::slice::from_raw_parts(packet.data as *const u8, packet.data_size as usize) };
+    wire::decode(bytes).expect("events")
+}
+
+/// A small deterministic pseudo-random generator (SplitMix64).
+struct Prng(u64);
+
+impl Prng {
+    fn new(seed: u64) -> Prng {
+        Prng(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        let sample = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        sample < probability
+    }
+
+    fn duration(&mut self, max: Duration) -> Duration {
+        let max_nanos = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+        if max_nanos == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos(self.next_u64() % max_nanos.saturating_add(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    fn run(seed: u64) -> (SimStats, Vec<Account>) {
+        let cluster = SimulatedCluster::new(
+            seed,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                request_loss: 0.1,
+                reply_loss: 0.1,
+                retransmit_delay: Duration::from_millis(1),
+                duplication: 0.2,
+                eviction: 0.2,
+            },
+        );
+
+        let mut client = cluster.client();
+        client.set_retry_policy(
+            RetryPolicy::new()
+                .max_attempts(100)
+                .base_delay(Duration::from_millis(1))
+                .max_delay(Duration::from_millis(1))
+                .jitter(false),
+        );
+
+        block_on(async {
+            let accounts: Vec<Account> = (1..=2)
+                .map(|id| Account {
+                    id,
+                    ledger: 1,
+                    code: 1,
+                    ..Default::default()
+                })
+                .collect();
+            let results = client.create_accounts(&accounts).await.unwrap();
+            assert!(results
+                .iter()
+                .all(|r| r.result == CreateAccountResult::Exists));
+
+            for id in 1..=20 {
+                let transfer = Transfer {
+                    id,
+                    debit_account_id: 1,
+                    credit_account_id: 2,
+                    amount: 1,
+                    ledger: 1,
+                    code: 1,
+                    ..Default::default()
+                };
+                let results = client.create_transfers(&[transfer]).await.unwrap();
+                assert!(results
+                    .iter()
+                    .all(|r| r.result == CreateTransferResult::Exists));
+            }
+        });
+
+        let accounts = block_on(cluster.state().lookup_accounts(&[1, 2])).unwrap();
+        (cluster.stats(), accounts)
+    }
+
+    #[test]
+    fn retries_are_idempotent_and_deterministic() {
+        for seed in 0..4 {
+            let (stats, accounts) = run(seed);
+            assert_eq!(accounts[0].debits_posted, 20);
+            assert_eq!(accounts[1].credits_posted, 20);
+            assert!(stats.requests >= 21);
+
+            let (stats_again, _) = run(seed);
+            assert_eq!(stats, stats_again, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn delays_reorder_concurrent_requests() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+
+        let requests: Vec<_> = (1..=10)
+            .map(|id| {
+                client.create_accounts(&[Account {
+                    id,
+                    ledger: 1,
+                    code: 1,
+                    ..Default::default()
+                }])
+            })
+            .collect();
+        for request in requests {
+            assert!(block_on(request).unwrap().is_empty());
+        }
+
+        let accounts = block_on(cluster.state().query_accounts(QueryFilter {
+            limit: 10,
+            ..Default::default()
+        }))
+        .unwrap();
+        let ids: Vec<u128> = accounts.iter().map(|a| a.id).collect();
+        assert_eq!(ids.len(), 10);
+        assert_ne!(ids, (1..=10).collect::<Vec<u128>>());
+    }
+
+    #[test]
+    fn update_addresses_completes_in_flight_requests() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+        let account = |id| Account {
+            id,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+
+        let requests: Vec<_> = (1..=10)
+            .map(|id| client.create_accounts(&[account(id)]))
+            .collect();
+        let addresses = crate::addresses::parse("3000").unwrap();
+        client.update_addresses(&addresses).unwrap();
+        let request_new = client.create_accounts(&[account(11)]);
+
+        for request in requests {
+            assert!(block_on(request).unwrap().is_empty());
+        }
+        assert!(block_on(request_new).unwrap().is_empty());
+        assert_eq!(
+            client.update_addresses(&[]),
+            Err(InitStatus::AddressInvalid)
+        );
+    }
+
+    #[test]
+    fn ping() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+
+        assert!(block_on(client.ping()).is_ok());
+        assert_eq!(cluster.stats().requests, 1);
+        assert_eq!(block_on(client.check_version()), Ok(()));
+    }
+
+    #[test]
+    fn eviction_without_retries() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                eviction: 1.0,
+                ..Default::default()
+            },
+        );
+        let mut client = cluster.client();
+        let evictions = Arc::new(Mutex::new(Vec::new()));
+        client.on_eviction({
+            let evictions = Arc::clone(&evictions);
+            move |reason| evictions.lock().unwrap().push(reason)
+        });
+
+        // Queries are resubmitted once, to a new session.
+        assert_eq!(
+            block_on(client.lookup_accounts(&[1])),
+            Err(PacketStatus::ClientEvicted)
+        );
+        assert_eq!((cluster.stats().requests, cluster.stats().evicted), (2, 2));
+
+        // Writes are not, but later requests use a new session.
+        let transfer = Transfer {
+            id: 1,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 1,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            block_on(client.create_transfers(&[transfer])),
+            Err(PacketStatus::ClientEvicted)
+        );
+        assert_eq!((cluster.stats().requests, cluster.stats().evicted), (3, 3));
+        assert_eq!(
+            *evictions.lock().unwrap(),
+            vec![EvictionReason::SessionExpired; 3]
+        );
+    }
+
+    #[test]
+    fn request_timeout_and_events() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                eviction: 1.0,
+                ..Default::default()
+            },
+        );
+        let mut client = cluster.client();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        client.on_event({
+            let events = Arc::clone(&events);
+            move |event| events.lock().unwrap().push(*event)
+        });
+        assert_eq!(
+            block_on(client.lookup_accounts(&[1])),
+            Err(PacketStatus::ClientEvicted)
+        );
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                ClientEvent::Evicted(EvictionReason::SessionExpired),
+                ClientEvent::Evicted(EvictionReason::SessionExpired),
+                ClientEvent::Failed {
+                    operation: "lookup_accounts",
+                    status: PacketStatus::ClientEvicted,
+                },
+            ]
+        );
+
+        // The timeout includes waiting for the rate limit.
+        let cluster = SimulatedCluster::new(1, Faults::default());
+        let mut client = cluster.client();
+        client.set_rate_limit(RateLimit::new().events_per_second(1));
+        client.set_request_timeout(Some(Duration::from_millis(10)));
+        events.lock().unwrap().clear();
+        client.on_event({
+            let events = Arc::clone(&events);
+            move |event| events.lock().unwrap().push(*event)
+        });
+        assert_eq!(block_on(client.lookup_accounts(&[1])), Ok(vec![]));
+        assert_eq!(
+            block_on(client.lookup_accounts(&[2])),
+            Err(PacketStatus::Timeout)
+        );
+        assert_eq!(cluster.stats().requests, 1);
+        assert_eq!(
+            *events.lock().unwrap(),
+            [ClientEvent::Failed {
+                operation: "lookup_accounts",
+                status: PacketStatus::Timeout,
+            }]
+        );
+    }
+
+    #[test]
+    fn blocking() {
+        let cluster = SimulatedCluster::new(1, Faults::default());
+        let client = crate::blocking::Client::from(cluster.client());
+        let spec = |id| AccountSpec {
+            id,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        let summary = client.ensure_accounts(&[spec(1), spec(2)]).unwrap();
+        assert_eq!(summary.created, [1, 2]);
+        let transfer = |id| Transfer {
+            id,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 1,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        let transfers: Vec<Transfer> = (1..=3).map(transfer).collect();
+        assert!(client.validate_transfers(&transfers).unwrap().is_empty());
+        client.create_transfers_checked(&transfers).unwrap();
+        assert_eq!(
+            client.try_create_transfers(&[transfer(1)]),
+            Ok(Ok(vec![CreateTransfersResult {
+                index: 0,
+                result: CreateTransferResult::Exists,
+            }]))
+        );
+
+        let filter = QueryFilter {
+            ledger: 1,
+            limit: 2,
+            ..Default::default()
+        };
+        let ids: Vec<u128> = client
+            .query_transfers_paged(filter)
+            .map(|transfer| transfer.unwrap().id)
+            .collect();
+        assert_eq!(ids, [1, 2, 3]);
+    }
+
+    #[test]
+    fn validation() {
+        let cluster = SimulatedCluster::new(1, Faults::default());
+        let mut client = cluster.client();
+        let transfer = Transfer {
+            id: 1,
+            debit_account_id: 1,
+            credit_account_id: 1,
+            amount: 1,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+
+        match block_on(client.create_transfers_checked(&[transfer])) {
+            Err(CreateTransfersError::Invalid(invalid)) => {
+                assert_eq!(
+                    invalid[0].result,
+                    CreateTransferResult::AccountsMustBeDifferent
+                );
+            }
+            result => panic!("{result:?}"),
+        }
+        assert_eq!(cluster.stats().requests, 0);
+
+        client.set_validation(false);
+        match block_on(client.create_transfers_checked(&[transfer])) {
+            Err(CreateTransfersError::Failed(failed)) => {
+                assert_eq!(
+                    failed[0].result,
+                    CreateTransferResult::AccountsMustBeDifferent
+                );
+            }
+            result => panic!("{result:?}"),
+        }
+        assert_eq!(cluster.stats().requests, 1);
+
+        client.set_duplicate_id_detection(true);
+        let transfers = [transfer, transfer];
+        match block_on(client.create_transfers_checked(&transfers)) {
+            Err(CreateTransfersError::DuplicateIds(duplicates)) => {
+                assert_eq!((duplicates[0].index, duplicates[0].first_index), (1, 0));
+            }
+            result => panic!("{result:?}"),
+        }
+        assert_eq!(cluster.stats().requests, 1);
+    }
+
+    #[test]
+    fn drain_and_close() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(20),
+                ..Default::default()
+            },
+        );
+        let account = |id| Account {
+            id,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+
+        let client = cluster.client();
+        let requests: Vec<_> = (1..=10)
+            .map(|id| client.create_accounts(&[account(id)]))
+            .collect();
+        // Dropped without awaiting, but still completed.
+        drop(client.create_accounts(&[account(11)]));
+        assert_eq!(
+            block_on(client.drain_and_close(Duration::from_secs(10))),
+            Ok(())
+        );
+        for request in requests {
+            assert!(block_on(request).unwrap().is_empty());
+        }
+        assert_eq!(
+            block_on(cluster.client().lookup_accounts(&[11]))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let client = cluster.client();
+        let request = client.create_accounts(&[account(12)]);
+        assert_eq!(
+            block_on(client.drain_and_close(Duration::ZERO)),
+            Err(ClientError::Timeout)
+        );
+        assert_eq!(block_on(request), Err(PacketStatus::ClientShutdown));
+    }
+
+    #[test]
+    fn rate_limit() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                ..Default::default()
+            },
+        );
+        let mut client = cluster.client();
+        client.set_rate_limit(RateLimit::new().requests_in_flight_max(1));
+        let account = |id| Account {
+            id,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+
+        let first = client.try_create_accounts(&[account(1)]).unwrap();
+        assert!(client.try_create_accounts(&[account(2)]).is_err());
+
+        // Submitted once the first request completes.
+        let second = client.create_accounts(&[account(2)]);
+        assert_eq!(cluster.stats().requests, 1);
+        assert!(block_on(first).unwrap().is_empty());
+        assert!(block_on(second).unwrap().is_empty());
+        assert_eq!(cluster.stats().requests, 2);
+
+        let third = client.try_create_accounts(&[account(3)]).unwrap();
+        assert!(block_on(third).unwrap().is_empty());
+    }
+
+    #[test]
+    fn bulk_import() {
+        use crate::bulk::{self, Field, Mapping};
+
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+        let accounts: Vec<Account> = (1..=2)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+
+        let mapping = Mapping::new(Transfer {
+            debit_account_id: 1,
+            credit_account_id: 2,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        })
+        .column("id", Field::Id)
+        .column("amount", Field::Amount)
+        .column("flags", Field::Flags)
+        .amount_scale(2);
+        let csv = "id,amount,flags\n\
+                   1,1.50,\n\
+                   2,2.00,Linked\n\
+                   3,x,\n\
+                   4,1,\n\
+                   0,1,\n";
+
+        let mut report = Vec::new();
+        let summary = block_on(bulk::import_transfers(
+            &client,
+            &mapping,
+            csv.as_bytes(),
+            &mut report,
+        ))
+        .unwrap();
+        assert_eq!(
+            (
+                summary.rows,
+                summary.created,
+                summary.existing,
+                summary.failed
+            ),
+            (5, 2, 0, 3)
+        );
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "row,id,error\n\
+             3,3,amount: amount invalid\n\
+             2,2,another row of the linked chain is invalid\n\
+             5,0,id must not be zero\n"
+        );
+        let transfers = block_on(client.lookup_transfers(&[1, 4])).unwrap();
+        assert_eq!(
+            transfers.iter().map(|t| t.amount).collect::<Vec<_>>(),
+            [150, 100]
+        );
+
+        // Importing again only reports the rows that failed.
+        let summary = block_on(bulk::import_transfers(
+            &client,
+            &mapping,
+            csv.as_bytes(),
+            std::io::sink(),
+        ))
+        .unwrap();
+        assert_eq!(
+            (summary.created, summary.existing, summary.failed),
+            (0, 2, 3)
+        );
+    }
+
+    #[test]
+    fn watch_balances() {
+        use futures::StreamExt;
+
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+        let accounts: Vec<Account> = (1..=2)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+
+        let mut watch = client.watch_balances(&[1, 2, 3], Duration::from_millis(1));
+        let mut next = || block_on(watch.next()).unwrap().unwrap();
+        let first = [next(), next()];
+        assert_eq!(first.map(|update| update.account.id), [1, 2]);
+        assert!(first.iter().all(|update| update.previous.is_none()));
+
+        let transfer = Transfer {
+            id: 1,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 10,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        assert!(block_on(client.create_transfers(&[transfer]))
+            .unwrap()
+            .is_empty());
+        // Unchanged accounts aren't updated again.
+        let updates = [next(), next()];
+        assert_eq!(updates.map(|update| update.account.id), [1, 2]);
+        assert_eq!(updates[0].account.debits_posted, 10);
+        assert_eq!(updates[0].previous.unwrap().debits_posted, 0);
+        assert_eq!(updates[1].account.credits_posted, 10);
+    }
+
+    #[test]
+    fn query_transfers_paged() {
+        use futures::StreamExt;
+
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+        let accounts: Vec<Account> = (1..=2)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+        let transfers: Vec<Transfer> = (1..=10)
+            .map(|id| Transfer {
+                id,
+                debit_account_id: 1,
+                credit_account_id: 2,
+                amount: 1,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert!(block_on(client.create_transfers(&transfers))
+            .unwrap()
+            .is_empty());
+
+        for (limit, flags, ids) in [
+            (
+                3,
+                QueryFilterFlags::empty(),
+                (1..=10).collect::<Vec<u128>>(),
+            ),
+            (5, QueryFilterFlags::Reversed, (1..=10).rev().collect()),
+            (0, QueryFilterFlags::empty(), (1..=10).collect()),
+        ] {
+            let filter = QueryFilter {
+                ledger: 1,
+                limit,
+                flags,
+                ..Default::default()
+            };
+            let found: Vec<u128> = block_on(
+                client
+                    .query_transfers_paged(filter)
+                    .map(|transfer| transfer.unwrap().id)
+                    .collect(),
+            );
+            assert_eq!(found, ids);
+        }
+    }
+
+    #[test]
+    fn validate_transfers() {
+        let cluster = SimulatedCluster::new(
+            1,
+            Faults {
+                delay_max: Duration::from_millis(2),
+                ..Default::default()
+            },
+        );
+        let client = cluster.client();
+        let account = |id, ledger, flags| Account {
+            id,
+            ledger,
+            code: 1,
+            flags,
+            ..Default::default()
+        };
+        let accounts = [
+            account(1, 1, AccountFlags::DebitsMustNotExceedCredits),
+            account(2, 1, AccountFlags::empty()),
+            account(3, 2, AccountFlags::empty()),
+        ];
+        assert!(block_on(client.create_accounts(&accounts))
+            .unwrap()
+            .is_empty());
+        let transfer = |id, debit_account_id, credit_account_id, amount| Transfer {
+            id,
+            debit_account_id,
+            credit_account_id,
+            amount,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        assert!(block_on(client.create_transfers(&[transfer(1, 2, 1, 100)]))
+            .unwrap()
+            .is_empty());
+
+        let events = [
+            // Each is within the limit, but not both.
+            transfer(2, 1, 2, 60),
+            transfer(3, 1, 2, 60),
+            transfer(4, 2, 3, 1),
+            Transfer {
+                flags: TransferFlags::Linked,
+                ..transfer(5, 2, 1, 1)
+            },
+            transfer(6, 2, 9, 1),
+            transfer(1, 2, 1, 100),
+        ];
+        let results: Vec<_> = block_on(client.validate_transfers(&events))
+            .unwrap()
+            .into_iter()
+            .map(|result| (result.index, result.result))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                (1, CreateTransferResult::ExceedsCredits),
+                (2, CreateTransferResult::AccountsMustHaveTheSameLedger),
+                (3, CreateTransferResult::LinkedEventFailed),
+                (4, CreateTransferResult::CreditAccountNotFound),
+                (5, CreateTransferResult::Exists),
+            ]
+        );
+        // Nothing was created.
+        assert!(block_on(client.lookup_transfers(&[2, 3, 5]))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn validate_transfers_large() {
+        let cluster = SimulatedCluster::new(1, Faults::default());
+        let client = cluster.client();
+        // More than `BATCH_MAX` accounts, and transfers with their ids and
+        // pending ids.
+        let count = BATCH_MAX / 2 + 1;
+        let accounts: Vec<Account> = (1..=2 * count as u128)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        for chunk in accounts.chunks(BATCH_MAX) {
+            assert!(block_on(client.create_accounts(chunk)).unwrap().is_empty());
+        }
+
+        // Transfers between distinct accounts, then posts of pending
+        // transfers that don't exist.
+        let mut events: Vec<Transfer> = (1..=count as u128)
+            .map(|id| Transfer {
+                id,
+                debit_account_id: 2 * id - 1,
+                credit_account_id: 2 * id,
+                amount: 1,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        events.extend((1..=count as u128).map(|id| Transfer {
+            id: 2 * BATCH_MAX as u128 + id,
+            pending_id: 3 * BATCH_MAX as u128 + id,
+            flags: TransferFlags::PostPendingTransfer,
+            ..Default::default()
+        }));
+        let results = block_on(client.validate_transfers(&events)).unwrap();
+        assert_eq!(results.len(), count);
+        assert!(results.iter().all(|result| result.index >= count
+            && result.result == CreateTransferResult::PendingTransferNotFound));
+        assert!(block_on(client.lookup_accounts(&[0; BATCH_MAX + 1])).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "webhooks")]
+    fn webhooks() {
+        use crate::webhooks::Webhooks;
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let cluster = SimulatedCluster::new(1, Faults::default());
+        let mut client = cluster.client();
+        client.set_webhooks(Webhooks::new().endpoint(&url, b"secret").unwrap());
+
+        let transfer = Transfer {
+            id: 1,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 1,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        };
+        let results = block_on(client.create_transfers(&[transfer])).unwrap();
+        assert_eq!(
+            results[0].result,
+            CreateTransferResult::DebitAccountNotFound
+        );
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut length = 0;
+        let mut line = String::new();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+            .unwrap();
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["results"][0]["transfer"]["id"], "1");
+        assert_eq!(body["results"][0]["result"], "DebitAccountNotFound");
+    }
+}
@@ -0,0 +1,119 @@
+use super::*;
+
+/// Options for [`Client::create_accounts_with_options`] and
+/// [`Client::create_transfers_with_options`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SubmitOptions {
+    /// Omit `Exists` results, as if the events had been created.
+    ///
+    /// An event that already exists with exactly the same fields is the
+    /// expected outcome of resubmitting a batch, e.g. after a timeout, so it
+    /// is usually not an error. Events that exist with _different_ fields,
+    /// such as `ExistsWithDifferentAmount`, are still reported, since they
+    /// indicate that an id was reused for a different event; see
+    /// [`CreateTransferResult::is_exists_with_different_fields`].
+    ///
+    /// Only the `Exists` result itself is omitted. When an event in a linked
+    /// chain exists, the other events in the chain still fail with
+    /// `LinkedEventFailed`.
+    pub treat_exists_as_success: bool,
+}
+
+impl Client {
+    /// Create one or more accounts, with options.
+    ///
+    /// See [`Client::create_accounts`] and [`SubmitOptions`].
+    pub fn create_accounts_with_options(
+        &self,
+        events: &[Account],
+        options: SubmitOptions,
+    ) -> impl Future<Output = Result<Vec<CreateAccountsResult>, PacketStatus>> {
+        let request = self.create_accounts(events);
+
+        async move {
+            let mut results = request.await?;
+            if options.treat_exists_as_success {
+                results.retain(|result| result.result != CreateAccountResult::Exists);
+            }
+            Ok(results)
+        }
+    }
+
+    /// Create one or more transfers, with options.
+    ///
+    /// See [`Client::create_transfers`] and [`SubmitOptions`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    ///
+    /// # async fn example(client: &tb::Client, transfers: &[tb::Transfer]) -> Result<(), tb::PacketStatus> {
+    /// let options = tb::SubmitOptions {
+    ///     treat_exists_as_success: true,
+    ///     ..Default::default()
+    /// };
+    ///
+    /// // Safe to repeat until it succeeds.
+    /// let results = client.create_transfers_with_options(transfers, options).await?;
+    /// for result in results {
+    ///     if result.result.is_exists_with_different_fields() {
+    ///         panic!("transfer id reused: {}", transfers[result.index].id);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_transfers_with_options(
+        &self,
+        events: &[Transfer],
+        options: SubmitOptions,
+    ) -> impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>> {
+        let request = self.create_transfers(events);
+
+        async move {
+            let mut results = request.await?;
+            if options.treat_exists_as_success {
+                results.retain(|result| result.result != CreateTransferResult::Exists);
+            }
+            Ok(results)
+        }
+    }
+}
+
+impl CreateAccountResult {
+    /// Returns `true` if an account with the same id exists, but with
+    /// different fields.
+    pub fn is_exists_with_different_fields(self) -> bool {
+        matches!(
+            self,
+            Self::ExistsWithDifferentFlags
+                | Self::ExistsWithDifferentUserData128
+                | Self::ExistsWithDifferentUserData64
+                | Self::ExistsWithDifferentUserData32
+                | Self::ExistsWithDifferentLedger
+                | Self::ExistsWithDifferentCode
+        )
+    }
+}
+
+impl CreateTransferResult {
+    /// Returns `true` if a transfer with the same id exists, but with
+    /// different fields.
+    pub fn is_exists_with_different_fields(self) -> bool {
+        matches!(
+            self,
+            Self::ExistsWithDifferentFlags
+                | Self::ExistsWithDifferentPendingId
+                | Self::ExistsWithDifferentTimeout
+                | Self::ExistsWithDifferentDebitAccountId
+                | Self::ExistsWithDifferentCreditAccountId
+                | Self::ExistsWithDifferentAmount
+                | Self::ExistsWithDifferentUserData128
+                | Self::ExistsWithDifferentUserData64
+                | Self::ExistsWithDifferentUserData32
+                | Self::ExistsWithDifferentLedger
+                | Self::ExistsWithDifferentCode
+        )
+    }
+}
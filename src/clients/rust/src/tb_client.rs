@@ -235,8 +235,8 @@ pub struct tb_client_t {
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct tb_packet_t {
-    pub user_data: *mut ::std::os::raw::c_void,
-    pub data: *mut ::std::os::raw::c_void,
+    pub user_data: *mut ::core::ffi::c_void,
+    pub data: *mut ::core::ffi::c_void,
     pub data_size: u32,
     pub user_tag: u16,
     pub operation: u8,
@@ -297,10 +297,10 @@ extern "C" {
         client_out: *mut tb_client_t,
         // 128-bit unsigned integer represented as a 16-byte little-endian array.
         cluster_id: *const [u8; 16],
-        address_ptr: *const ::std::os::raw::c_char,
+        address_ptr: *const u8,
         address_len: u32,
         completion_ctx: usize,
-        completion_callback: ::std::option::Option<
+        completion_callback: ::core::option::Option<
             unsafe extern "C" fn(
                 arg1: usize,
                 arg3: *mut tb_packet_t,
@@ -316,10 +316,10 @@ extern "C" {
         client_out: *mut tb_client_t,
         // 128-bit unsigned integer represented as a 16-byte little-endian array.
         cluster_id: *const [u8; 16],
-        address_ptr: *const ::std::os::raw::c_char,
+        address_ptr: *const u8,
         address_len: u32,
         completion_ctx: usize,
-        completion_callback: ::std::option::Option<
+        completion_callback: ::core::option::Option<
             unsafe extern "C" fn(
                 arg1: usize,
                 arg3: *mut tb_packet_t,
@@ -354,7 +354,7 @@ extern "C" {
 
     // Registers or unregisters the application log callback.
     pub fn register_log_callback(
-        callback: ::std::option::Option<
+        callback: ::core::option::Option<
             unsafe extern "C" fn(
                 TB_LOG_LEVEL,
                 *const u8,
@@ -0,0 +1,182 @@
+//! Client-side metrics and tracing.
+//!
+//! With the `metrics` feature the `record_*` functions record through the
+//! [`metrics`](https://docs.rs/metrics) facade, and with the `otel` feature
+//! [`Span`]s are OpenTelemetry spans. Without them they do nothing.
+//!
+//! The recorded metrics and spans are described in the crate documentation.
+
+#![cfg_attr(
+    not(all(feature = "metrics", feature = "otel")),
+    allow(unused_variables, unused_mut)
+)]
+
+use super::*;
+
+use std::time::Instant;
+
+pub(crate) fn record_request(operation: &'static str, events: usize) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!("tigerbeetle_client_requests_total", "operation" => operation)
+            .increment(1);
+        metrics::counter!("tigerbeetle_client_events_total", "operation" => operation)
+            .increment(events as u64);
+    }
+}
+
+pub(crate) fn record_completion(
+    operation: &'static str,
+    started: Instant,
+    status: Option<PacketStatus>,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::histogram!(
+            "tigerbeetle_client_request_duration_seconds",
+            "operation" => operation
+        )
+        .record(started.elapsed().as_secs_f64());
+        if let Some(status) = status {
+            metrics::counter!(
+                "tigerbeetle_client_errors_total",
+                "operation" => operation,
+                "status" => format!("{status:?}")
+            )
+            .increment(1);
+        }
+    }
+}
+
+pub(crate) fn record_event_result(operation: &'static str, result: &dyn fmt::Debug) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::counter!(
+            "tigerbeetle_client_event_results_total",
+            "operation" => operation,
+            "result" => format!("{result:?}")
+        )
+        .increment(1);
+    }
+}
+
+/// A trace span covering one client operation.
+///
+/// With the `otel` feature each span is an OpenTelemetry span named after
+/// the operation, e.g. `tb.create_transfers`, and is a child of the context
+/// that was current when the operation was called. Without it spans do
+/// nothing.
+pub(crate) struct Span {
+    #[cfg(feature = "otel")]
+    span: opentelemetry::global::BoxedSpan,
+}
+
+impl Span {
+    pub(crate) fn start(operation: &'static str, batch_size: usize) -> Span {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::{SpanKind, Tracer};
+            use opentelemetry::KeyValue;
+
+            let tracer = opentelemetry::global::tracer("tigerbeetle");
+            let span = tracer
+                .span_builder(format!("tb.{operation}"))
+                .with_kind(SpanKind::Client)
+                .with_attributes([
+                    KeyValue::new("db.system", "tigerbeetle"),
+                    KeyValue::new("db.operation", operation),
+                    KeyValue::new("tb.batch_size", batch_size as i64),
+                ])
+                .start(&tracer);
+            Span { span }
+        }
+        #[cfg(not(feature = "otel"))]
+        Span {}
+    }
+
+    /// Record the ledger of the events, if they all share one.
+    pub(crate) fn ledger(&mut self, ledgers: impl IntoIterator<Item = u32>) {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::Span as _;
+
+            let mut ledgers = ledgers.into_iter();
+            if let Some(ledger) = ledgers.next() {
+                if ledgers.all(|other| other == ledger) {
+                    self.span.set_attribute(opentelemetry::KeyValue::new(
+                        "tb.ledger",
+                        i64::from(ledger),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn end<T: Traced>(self, result: &Result<T, PacketStatus>) {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::{Span as _, Status};
+            use opentelemetry::{Array, KeyValue, StringValue, Value};
+
+            let mut span = self.span;
+            match result {
+                Ok(output) => {
+                    let codes = output.result_codes();
+                    if !codes.is_empty() {
+                        let codes = codes.into_iter().map(StringValue::from).collect();
+                        span.set_attribute(KeyValue::new(
+                            "tb.result_codes",
+                            Value::Array(Array::String(codes)),
+                        ));
+                    }
+                }
+                Err(status) => {
+                    span.set_attribute(KeyValue::new("tb.packet_status", format!("{status:?}")));
+                    span.set_status(Status::error(status.to_string()));
+                }
+            }
+            span.end();
+        }
+    }
+}
+
+/// The outcome of an operation, as recorded on its [`Span`].
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+pub(crate) trait Traced {
+    /// The distinct result codes of events that did not succeed.
+    fn result_codes(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl Traced for Vec<Account> {}
+impl Traced for Vec<Transfer> {}
+impl Traced for Vec<AccountBalance> {}
+
+impl Traced for Vec<CreateAccountsResult> {
+    fn result_codes(&self) -> Vec<String> {
+        distinct_codes(self.iter().map(|result| result.result))
+    }
+}
+
+impl Traced for Vec<CreateTransfersResult> {
+    fn result_codes(&self) -> Vec<String> {
+        distinct_codes(self.iter().map(|result| result.result))
+    }
+}
+
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+fn distinct_codes<T: fmt::Debug + Ord>(codes: impl Iterator<Item = T>) -> Vec<String> {
+    let codes: std::collections::BTreeSet<T> = codes.collect();
+    codes.iter().map(|code| format!("{code:?}")).collect()
+}
+
+/// End `span` with the outcome of `request` once it completes.
+pub(crate) async fn instrument<T: Traced>(
+    span: Span,
+    request: impl Future<Output = Result<T, PacketStatus>>,
+) -> Result<T, PacketStatus> {
+    let result = request.await;
+    span.end(&result);
+    result
+}
@@ -0,0 +1,520 @@
+//! Multi-tenancy, partitioning a cluster's accounts and transfers by tenant.
+//!
+//! A [`Tenant`] wraps a client for a single tenant, identified by a
+//! [`Partition`]: either a ledger, or a value of `user_data_64`. It stamps
+//! the tenant's identifier into every account and transfer it creates,
+//! restricts lookups and queries to the tenant's accounts and transfers, and
+//! denies transfers that involve another tenant's accounts without
+//! submitting them.
+//!
+//! A `Tenant` is itself a [`TigerBeetleClient`], so code written against the
+//! trait, such as the [`audit`](crate::audit) and
+//! [`periods`](crate::periods) modules, works within a tenant unchanged:
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//! use tb::tenant::{Partition, Tenant};
+//! use tb::TigerBeetleClient;
+//!
+//! # async fn example(client: &tb::Client) -> Result<(), tb::PacketStatus> {
+//! let tenant = Tenant::new(client, Partition::UserData64(42));
+//! let results = tenant
+//!     .create_transfers(&[tb::Transfer {
+//!         id: tb::id(),
+//!         debit_account_id: 1,
+//!         credit_account_id: 2,
+//!         amount: 100,
+//!         ledger: 1,
+//!         code: 1,
+//!         ..Default::default()
+//!     }])
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! To the tenant, other tenants' accounts and transfers don't exist: they
+//! aren't returned by lookups and queries, and a transfer between accounts
+//! of different tenants fails with `DebitAccountNotFound` or
+//! `CreditAccountNotFound`, as if the other tenant's account had not been
+//! created. Account ids are shared by all tenants, so creating an account
+//! with the id of another tenant's account still fails with one of the
+//! `Exists*` results.
+//!
+//! The partition is enforced by the client, not the cluster: clients that
+//! don't go through a `Tenant` can read and write every tenant's accounts.
+
+use crate::{
+    Account, AccountBalance, AccountFilter, BoxFuture, CreateAccountsResult, CreateTransferResult,
+    CreateTransfersResult, Linkable, PacketStatus, QueryFilter, TigerBeetleClient, Transfer,
+    BATCH_MAX,
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// How a tenant's accounts and transfers are identified.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Partition {
+    /// The tenant's accounts and transfers are on this ledger.
+    ///
+    /// Since transfers are between accounts of the same ledger, the cluster
+    /// already keeps tenants apart; the `Tenant` hides the other ledgers.
+    Ledger(u32),
+    /// The tenant's accounts and transfers have this `user_data_64`, which
+    /// is not available to the application.
+    UserData64(u64),
+}
+
+/// A client restricted to a single tenant's accounts and transfers.
+///
+/// See the [module documentation](self).
+#[derive(Copy, Clone)]
+pub struct Tenant<'a> {
+    client: &'a dyn TigerBeetleClient,
+    partition: Partition,
+}
+
+impl<'a> Tenant<'a> {
+    /// The tenant of `client` identified by `partition`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the partition's identifier is zero, since unstamped
+    /// accounts and transfers would belong to the tenant.
+    pub fn new(client: &'a dyn TigerBeetleClient, partition: Partition) -> Tenant<'a> {
+        let zero = match partition {
+            Partition::Ledger(ledger) => ledger == 0,
+            Partition::UserData64(user_data_64) => user_data_64 == 0,
+        };
+        assert!(!zero, "tenant identifier must not be zero");
+        Tenant { client, partition }
+    }
+
+    /// The partition identifying the tenant.
+    pub fn partition(&self) -> Partition {
+        self.partition
+    }
+
+    /// Whether an account or transfer with `ledger` and `user_data_64`
+    /// belongs to the tenant.
+    fn owns(&self, ledger: u32, user_data_64: u64) -> bool {
+        match self.partition {
+            Partition::Ledger(tenant) => ledger == tenant,
+            Partition::UserData64(tenant) => user_data_64 == tenant,
+        }
+    }
+
+    fn stamp(&self, ledger: &mut u32, user_data_64: &mut u64) {
+        match self.partition {
+            Partition::Ledger(tenant) => *ledger = tenant,
+            Partition::UserData64(tenant) => *user_data_64 = tenant,
+        }
+    }
+
+    /// Restrict `filter` to the tenant, or return `None` if it already
+    /// selects another tenant.
+    fn restrict(&self, mut filter: QueryFilter) -> Option<QueryFilter> {
+        match self.partition {
+            Partition::Ledger(tenant) => {
+                if filter.ledger != 0 && filter.ledger != tenant {
+                    return None;
+                }
+                filter.ledger = tenant;
+            }
+            Partition::UserData64(tenant) => {
+                if filter.user_data_64 != 0 && filter.user_data_64 != tenant {
+                    return None;
+                }
+                filter.user_data_64 = tenant;
+            }
+        }
+        Some(filter)
+    }
+
+    /// The results of transfers denied because they involve another
+    /// tenant's accounts or pending transfers, by index.
+    async fn denied(
+        &self,
+        events: &[Transfer],
+    ) -> Result<HashMap<usize, CreateTransferResult>, PacketStatus> {
+        let mut account_ids: Vec<u128> = events
+            .iter()
+            .flat_map(|transfer| [transfer.debit_account_id, transfer.credit_account_id])
+            .filter(|&id| id != 0)
+            .collect();
+        account_ids.sort_unstable();
+        account_ids.dedup();
+        let mut foreign_accounts = HashSet::new();
+        for chunk in account_ids.chunks(BATCH_MAX) {
+            for account in self.client.lookup_accounts(chunk).await? {
+                if !self.owns(account.ledger, account.user_data_64) {
+                    foreign_accounts.insert(account.id);
+                }
+            }
+        }
+
+        let mut pending_ids: Vec<u128> = events
+            .iter()
+            .map(|transfer| transfer.pending_id)
+            .filter(|&id| id != 0)
+            .collect();
+        pending_ids.sort_unstable();
+        pending_ids.dedup();
+        let mut foreign_transfers = HashSet::new();
+        for chunk in pending_ids.chunks(BATCH_MAX) {
+            for transfer in self.client.lookup_transfers(chunk).await? {
+                if !self.owns(transfer.ledger, transfer.user_data_64) {
+                    foreign_transfers.insert(transfer.id);
+                }
+            }
+        }
+
+        let mut denied = HashMap::new();
+        for (index, transfer) in events.iter().enumerate() {
+            let result = if foreign_accounts.contains(&transfer.debit_account_id) {
+                CreateTransferResult::DebitAccountNotFound
+            } else if foreign_accounts.contains(&transfer.credit_account_id) {
+                CreateTransferResult::CreditAccountNotFound
+            } else if foreign_transfers.contains(&transfer.pending_id) {
+                CreateTransferResult::PendingTransferNotFound
+            } else {
+                continue;
+            };
+            denied.insert(index, result);
+        }
+        Ok(denied)
+    }
+}
+
+impl TigerBeetleClient for Tenant<'_> {
+    /// Create accounts, stamped with the tenant's identifier.
+    ///
+    /// The identifier replaces the accounts' ledger or `user_data_64`.
+    fn create_accounts<'a>(
+        &'a self,
+        events: &'a [Account],
+    ) -> BoxFuture<'a, Result<Vec<CreateAccountsResult>, PacketStatus>> {
+        let mut events = events.to_vec();
+        for account in &mut events {
+            self.stamp(&mut account.ledger, &mut account.user_data_64);
+        }
+        Box::pin(async move { self.client.create_accounts(&events).await })
+    }
+
+    /// Create transfers, stamped with the tenant's identifier.
+    ///
+    /// The identifier replaces the transfers' ledger or `user_data_64`.
+    /// Transfers that involve another tenant's accounts or pending
+    /// transfers are not submitted, and fail as if those didn't exist, as
+    /// do the other transfers of their linked chains.
+    fn create_transfers<'a>(
+        &'a self,
+        events: &'a [Transfer],
+    ) -> BoxFuture<'a, Result<Vec<CreateTransfersResult>, PacketStatus>> {
+        let mut events = events.to_vec();
+        for transfer in &mut events {
+            self.stamp(&mut transfer.ledger, &mut transfer.user_data_64);
+        }
+        Box::pin(async move {
+            let denied = self.denied(&events).await?;
+            if denied.is_empty() {
+                return self.client.create_transfers(&events).await;
+            }
+
+            // Deny whole chains, submitting the others.
+            let mut results = Vec::new();
+            let mut submitted = Vec::new();
+            let mut indexes = Vec::new();
+            let mut start = 0;
+            while start < events.len() {
+                let end = events[start..]
+                    .iter()
+                    .position(|transfer| !transfer.is_linked())
+                    .map_or(events.len(), |offset| start + offset + 1);
+                if (start..end).any(|index| denied.contains_key(&index)) {
+                    results.extend((start..end).map(|index| {
+                        CreateTransfersResult {
+                            index,
+                            result: denied
+                                .get(&index)
+                                .copied()
+                                .unwrap_or(CreateTransferResult::LinkedEventFailed),
+                        }
+                    }));
+                } else {
+                    submitted.extend_from_slice(&events[start..end]);
+                    indexes.extend(start..end);
+                }
+                start = end;
+            }
+            if !submitted.is_empty() {
+                let submitted_results = self.client.create_transfers(&submitted).await?;
+                results.extend(
+                    submitted_results
+                        .into_iter()
+                        .map(|result| CreateTransfersResult {
+                            index: indexes[result.index],
+                            result: result.result,
+                        }),
+                );
+            }
+            results.sort_unstable_by_key(|result| result.index);
+            Ok(results)
+        })
+    }
+
+    /// Look up accounts, omitting those of other tenants.
+    fn lookup_accounts<'a>(
+        &'a self,
+        events: &'a [u128],
+    ) -> BoxFuture<'a, Result<Vec<Account>, PacketStatus>> {
+        Box::pin(async move {
+            let mut accounts = self.client.lookup_accounts(events).await?;
+            accounts.retain(|account| self.owns(account.ledger, account.user_data_64));
+            Ok(accounts)
+        })
+    }
+
+    /// Look up transfers, omitting those of other tenants.
+    fn lookup_transfers<'a>(
+        &'a self,
+        events: &'a [u128],
+    ) -> BoxFuture<'a, Result<Vec<Transfer>, PacketStatus>> {
+        Box::pin(async move {
+            let mut transfers = self.client.lookup_transfers(events).await?;
+            transfers.retain(|transfer| self.owns(transfer.ledger, transfer.user_data_64));
+            Ok(transfers)
+        })
+    }
+
+    /// Query the tenant's transfers of an account.
+    fn get_account_transfers(
+        &self,
+        mut event: AccountFilter,
+    ) -> BoxFuture<'_, Result<Vec<Transfer>, PacketStatus>> {
+        Box::pin(async move {
+            if let Partition::UserData64(tenant) = self.partition {
+                if event.user_data_64 != 0 && event.user_data_64 != tenant {
+                    return Ok(Vec::new());
+                }
+                event.user_data_64 = tenant;
+            }
+            // All of an account's transfers are on its ledger, so either
+            // all or none are retained.
+            let mut transfers = self.client.get_account_transfers(event).await?;
+            transfers.retain(|transfer| self.owns(transfer.ledger, transfer.user_data_64));
+            Ok(transfers)
+        })
+    }
+
+    /// Query the historical balances of one of the tenant's accounts.
+    fn get_account_balances(
+        &self,
+        mut event: AccountFilter,
+    ) -> BoxFuture<'_, Result<Vec<AccountBalance>, PacketStatus>> {
+        Box::pin(async move {
+            match self.partition {
+                Partition::Ledger(_) => {
+                    // Balances don't have a ledger, so check the account's.
+                    let owned = self
+                        .client
+                        .lookup_accounts(&[event.account_id])
+                        .await?
+                        .iter()
+                        .any(|account| self.owns(account.ledger, account.user_data_64));
+                    if !owned {
+                        return Ok(Vec::new());
+                    }
+                }
+                Partition::UserData64(tenant) => {
+                    if event.user_data_64 != 0 && event.user_data_64 != tenant {
+                        return Ok(Vec::new());
+                    }
+                    event.user_data_64 = tenant;
+                }
+            }
+            self.client.get_account_balances(event).await
+        })
+    }
+
+    /// Query the tenant's accounts.
+    fn query_accounts(
+        &self,
+        event: QueryFilter,
+    ) -> BoxFuture<'_, Result<Vec<Account>, PacketStatus>> {
+        Box::pin(async move {
+            match self.restrict(event) {
+                Some(event) => self.client.query_accounts(event).await,
+                None => Ok(Vec::new()),
+            }
+        })
+    }
+
+    /// Query the tenant's transfers.
+    fn query_transfers(
+        &self,
+        event: QueryFilter,
+    ) -> BoxFuture<'_, Result<Vec<Transfer>, PacketStatus>> {
+        Box::pin(async move {
+            match self.restrict(event) {
+                Some(event) => self.client.query_transfers(event).await,
+                None => Ok(Vec::new()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        AccountFilterFlags, AccountFlags, InMemoryClient, QueryFilterFlags, TransferFlags,
+    };
+
+    use futures::executor::block_on;
+
+    fn transfer(id: u128, debit_account_id: u128, credit_account_id: u128) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id,
+            credit_account_id,
+            amount: 1,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn user_data_64() {
+        let client = InMemoryClient::new();
+        let a = Tenant::new(&client, Partition::UserData64(1));
+        let b = Tenant::new(&client, Partition::UserData64(2));
+        let account = |id| Account {
+            id,
+            ledger: 1,
+            code: 1,
+            user_data_64: 99,
+            ..Default::default()
+        };
+        assert!(block_on(a.create_accounts(&[account(1), account(2)]))
+            .unwrap()
+            .is_empty());
+        assert!(block_on(b.create_accounts(&[account(3)]))
+            .unwrap()
+            .is_empty());
+        let accounts = block_on(client.lookup_accounts(&[1, 3])).unwrap();
+        assert_eq!(accounts[0].user_data_64, 1);
+        assert_eq!(accounts[1].user_data_64, 2);
+
+        let results = block_on(a.create_transfers(&[
+            transfer(1, 1, 2),
+            Transfer {
+                flags: TransferFlags::Linked,
+                ..transfer(2, 1, 2)
+            },
+            transfer(3, 1, 3),
+            transfer(4, 3, 1),
+            transfer(5, 2, 1),
+        ]))
+        .unwrap();
+        let results: Vec<_> = results.iter().map(|r| (r.index, r.result)).collect();
+        assert_eq!(
+            results,
+            [
+                (1, CreateTransferResult::LinkedEventFailed),
+                (2, CreateTransferResult::CreditAccountNotFound),
+                (3, CreateTransferResult::DebitAccountNotFound),
+            ]
+        );
+        let transfers = block_on(client.lookup_transfers(&[1, 2, 3, 4, 5])).unwrap();
+        let ids: Vec<u128> = transfers.iter().map(|transfer| transfer.id).collect();
+        assert_eq!(ids, [1, 5]);
+        assert!(transfers.iter().all(|transfer| transfer.user_data_64 == 1));
+
+        let ids = |accounts: Vec<Account>| -> Vec<u128> {
+            accounts.iter().map(|account| account.id).collect()
+        };
+        assert_eq!(ids(block_on(b.lookup_accounts(&[1, 2, 3])).unwrap()), [3]);
+        assert!(block_on(b.lookup_transfers(&[1, 5])).unwrap().is_empty());
+        let filter = QueryFilter {
+            ledger: 1,
+            limit: 10,
+            flags: QueryFilterFlags::empty(),
+            ..Default::default()
+        };
+        assert_eq!(ids(block_on(a.query_accounts(filter)).unwrap()), [1, 2]);
+        let other = QueryFilter {
+            user_data_64: 2,
+            ..filter
+        };
+        assert!(block_on(a.query_accounts(other)).unwrap().is_empty());
+
+        let filter = AccountFilter {
+            account_id: 1,
+            limit: 10,
+            flags: AccountFilterFlags::Debits | AccountFilterFlags::Credits,
+            ..Default::default()
+        };
+        assert_eq!(block_on(a.get_account_transfers(filter)).unwrap().len(), 2);
+        assert!(block_on(b.get_account_transfers(filter))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn ledger() {
+        let client = InMemoryClient::new();
+        let a = Tenant::new(&client, Partition::Ledger(1));
+        let b = Tenant::new(&client, Partition::Ledger(2));
+        let account = |id| Account {
+            id,
+            code: 1,
+            flags: AccountFlags::History,
+            ..Default::default()
+        };
+        assert!(block_on(a.create_accounts(&[account(1), account(2)]))
+            .unwrap()
+            .is_empty());
+        assert!(block_on(b.create_accounts(&[account(3)]))
+            .unwrap()
+            .is_empty());
+
+        let pending = Transfer {
+            flags: TransferFlags::Pending,
+            ..transfer(1, 1, 2)
+        };
+        assert!(block_on(a.create_transfers(&[pending])).unwrap().is_empty());
+        let post = Transfer {
+            id: 2,
+            pending_id: 1,
+            amount: u128::MAX,
+            flags: TransferFlags::PostPendingTransfer,
+            ..Default::default()
+        };
+        let results = block_on(b.create_transfers(&[post])).unwrap();
+        assert_eq!(
+            results[0].result,
+            CreateTransferResult::PendingTransferNotFound
+        );
+        assert!(block_on(a.create_transfers(&[post])).unwrap().is_empty());
+
+        let filter = AccountFilter {
+            account_id: 1,
+            limit: 10,
+            flags: AccountFilterFlags::Debits | AccountFilterFlags::Credits,
+            ..Default::default()
+        };
+        assert_eq!(block_on(a.get_account_balances(filter)).unwrap().len(), 2);
+        assert!(block_on(b.get_account_balances(filter)).unwrap().is_empty());
+        assert!(block_on(b.get_account_transfers(filter))
+            .unwrap()
+            .is_empty());
+        let filter = QueryFilter {
+            limit: 10,
+            ..Default::default()
+        };
+        assert_eq!(block_on(b.query_transfers(filter)).unwrap().len(), 0);
+        assert_eq!(block_on(a.query_transfers(filter)).unwrap().len(), 2);
+    }
+}
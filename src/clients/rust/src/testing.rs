@@ -0,0 +1,405 @@
+//! Single-node TigerBeetle clusters for integration tests.
+//!
+//! A [`TestCluster`] formats a data file in a temporary directory and runs
+//! `tigerbeetle start` on a port chosen by the OS. The replica stops, and the
+//! directory is removed, when the cluster is dropped.
+//!
+//! Requires the `testing` feature, typically as a dev-dependency:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! tigerbeetle = { version = "*", features = ["testing"] }
+//! ```
+//!
+//! # Example
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let cluster = tb::testing::TestCluster::start()?;
+//! let client = cluster.client()?;
+//!
+//! let results = futures::executor::block_on(client.create_accounts(&[tb::Account {
+//!     id: tb::id(),
+//!     ledger: 1,
+//!     code: 1,
+//!     ..Default::default()
+//! }]))?;
+//! assert!(results.is_empty());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # The `tigerbeetle` binary
+//!
+//! The binary is, in order of preference:
+//!
+//! 1. the path given to [`TestClusterBuilder::tigerbeetle`],
+//! 2. the path in the `TIGERBEETLE_BIN` environment variable,
+//! 3. `tigerbeetle` on the `PATH`,
+//! 4. or a release downloaded from GitHub with `curl` and `unzip`, and
+//!    cached in `$TIGERBEETLE_CACHE`, or `tigerbeetle` in the user's cache
+//!    directory.
+
+use crate::{Client, InitStatus};
+
+use std::env::consts::EXE_SUFFIX;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A running single-replica cluster, stopped when dropped.
+///
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct TestCluster {
+    cluster_id: u128,
+    port: u16,
+    dir: PathBuf,
+    /// The replica exits when its stdin is closed.
+    replica: Child,
+}
+
+impl TestCluster {
+    /// Start a cluster with the default [`TestClusterBuilder`].
+    pub fn start() -> Result<TestCluster, TestClusterError> {
+        TestClusterBuilder::new().start()
+    }
+
+    pub fn cluster_id(&self) -> u128 {
+        self.cluster_id
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The replica's address, for [`Client::new`].
+    pub fn address(&self) -> String {
+        format!("127.0.0.1:{}", self.port)
+    }
+
+    /// Create a client of the cluster.
+    pub fn client(&self) -> Result<Client, InitStatus> {
+        Client::new(self.cluster_id, &self.address())
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        // Closing stdin asks the replica to exit; kill it in case it is
+        // stuck, but don't fail a test over it.
+        drop(self.replica.stdin.take());
+        let _ = self.replica.kill();
+        let _ = self.replica.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A builder for configuring a [`TestCluster`].
+#[derive(Clone, Debug, Default)]
+pub struct TestClusterBuilder {
+    cluster_id: u128,
+    tigerbeetle: Option<PathBuf>,
+    version: Option<String>,
+}
+
+impl TestClusterBuilder {
+    /// Create a builder for cluster 0.
+    pub fn new() -> TestClusterBuilder {
+        TestClusterBuilder::default()
+    }
+
+    /// Set the cluster id.
+    pub fn cluster_id(mut self, cluster_id: u128) -> TestClusterBuilder {
+        self.cluster_id = cluster_id;
+        self
+    }
+
+    /// Run the `tigerbeetle` binary at `path`.
+    pub fn tigerbeetle(mut self, path: impl Into<PathBuf>) -> TestClusterBuilder {
+        self.tigerbeetle = Some(path.into());
+        self
+    }
+
+    /// Download the release `version`, such as `"0.16.60"`, if the binary
+    /// is not otherwise found. Defaults to the latest release.
+    pub fn version(mut self, version: &str) -> TestClusterBuilder {
+        self.version = Some(version.to_owned());
+        self
+    }
+
+    /// Format a data file and start the replica.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TestClusterError::Io`] if a command could not be run,
+    /// [`TestClusterError::CommandFailed`] if downloading or formatting
+    /// failed, and [`TestClusterError::StartFailed`] if the replica exited
+    /// without reporting its port.
+    pub fn start(&self) -> Result<TestCluster, TestClusterError> {
+        let tigerbeetle = self.find_tigerbeetle()?;
+
+        static CLUSTERS: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "tigerbeetle-test-{}-{}",
+            std::process::id(),
+            CLUSTERS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        let cluster = start(&tigerbeetle, self.cluster_id, &dir);
+        if cluster.is_err() {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        cluster
+    }
+
+    fn find_tigerbeetle(&self) -> Result<PathBuf, TestClusterError> {
+        if let Some(path) = &self.tigerbeetle {
+            return Ok(path.clone());
+        }
+        if let Some(path) = std::env::var_os("TIGERBEETLE_BIN") {
+            return Ok(path.into());
+        }
+        let binary = format!("tigerbeetle{EXE_SUFFIX}");
+        let on_path = std::env::var_os("PATH").and_then(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(&binary))
+                .find(|path| path.is_file())
+        });
+        if let Some(path) = on_path {
+            return Ok(path);
+        }
+
+        let version = self.version.as_deref().unwrap_or("latest");
+        let dir = cache_dir().join(version);
+        let path = dir.join(&binary);
+        if !path.is_file() {
+            download(version, &dir)?;
+        }
+        Ok(path)
+    }
+}
+
+fn start(
+    tigerbeetle: &Path,
+    cluster_id: u128,
+    dir: &Path,
+) -> Result<TestCluster, TestClusterError> {
+    let data_file = dir.join("0_0.tigerbeetle");
+    run(Command::new(tigerbeetle)
+        .arg("format")
+        .arg(format!("--cluster={cluster_id}"))
+        .args(["--replica=0", "--replica-count=1"])
+        .arg(&data_file))?;
+
+    // Address 0 asks the replica to pick a port, print it, and exit when
+    // stdin closes.
+    let mut replica = Command::new(tigerbeetle)
+        .args(["start", "--development", "--addresses=0"])
+        .arg(&data_file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut line = String::new();
+    let read = replica
+        .stdout
+        .take()
+        .map(|stdout| BufReader::new(stdout).read_line(&mut line));
+    match line.trim().parse() {
+        Ok(port) => Ok(TestCluster {
+            cluster_id,
+            port,
+            dir: dir.to_owned(),
+            replica,
+        }),
+        Err(_) => {
+            let _ = replica.kill();
+            let _ = replica.wait();
+            match read {
+                Some(Err(error)) => Err(error.into()),
+                _ => Err(TestClusterError::StartFailed),
+            }
+        }
+    }
+}
+
+/// The directory that releases are cached in.
+fn cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("TIGERBEETLE_CACHE") {
+        return dir.into();
+    }
+    let cache = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| Path::new(&home).join("Library/Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    };
+    cache.unwrap_or_else(std::env::temp_dir).join("tigerbeetle")
+}
+
+/// The URL of the release archive for this platform.
+fn release_url(version: &str) -> String {
+    let platform = match (std::env::consts::ARCH, std::env::consts::OS) {
+        (_, "macos") => "universal-macos".to_owned(),
+        (arch, os) => format!("{arch}-{os}"),
+    };
+    release_url_for(version, &platform)
+}
+
+fn release_url_for(version: &str, platform: &str) -> String {
+    let asset = format!("tigerbeetle-{platform}.zip");
+    if version == "latest" {
+        format!("https://github.com/tigerbeetle/tigerbeetle/releases/latest/download/{asset}")
+    } else {
+        format!("https://github.com/tigerbeetle/tigerbeetle/releases/download/{version}/{asset}")
+    }
+}
+
+fn download(version: &str, dir: &Path) -> Result<(), TestClusterError> {
+    std::fs::create_dir_all(dir)?;
+    let archive = dir.join("tigerbeetle.zip");
+    run(Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ])
+        .arg(&archive)
+        .arg(release_url(version)))?;
+    if cfg!(windows) {
+        run(Command::new("tar")
+            .arg("-xf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(dir))?;
+    } else {
+        run(Command::new("unzip")
+            .arg("-o")
+            .arg(&archive)
+            .arg("-d")
+            .arg(dir))?;
+    }
+    std::fs::remove_file(&archive)?;
+    Ok(())
+}
+
+/// Run a command to completion, failing if it fails.
+fn run(command: &mut Command) -> Result<(), TestClusterError> {
+    let status = command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(TestClusterError::CommandFailed {
+            command: format!("{command:?}"),
+            status,
+        })
+    }
+}
+
+/// Errors starting a [`TestCluster`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TestClusterError {
+    /// A command could not be run, or a file could not be written.
+    Io(io::Error),
+    /// A command, such as `tigerbeetle format` or the download, failed.
+    CommandFailed { command: String, status: ExitStatus },
+    /// The replica exited without reporting its port.
+    StartFailed,
+}
+
+impl From<io::Error> for TestClusterError {
+    fn from(error: io::Error) -> TestClusterError {
+        TestClusterError::Io(error)
+    }
+}
+
+impl std::error::Error for TestClusterError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::CommandFailed { .. } | Self::StartFailed => None,
+        }
+    }
+}
+
+impl core::fmt::Display for TestClusterError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Io(error) => core::fmt::Display::fmt(error, f),
+            Self::CommandFailed { command, status } => write!(f, "{command} failed: {status}"),
+            Self::StartFailed => f.write_str("tigerbeetle start exited without a port"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn release_url() {
+        assert_eq!(
+            release_url_for("0.16.60", "x86_64-linux"),
+            "https://github.com/tigerbeetle/tigerbeetle/releases/download/0.16.60/\
+             tigerbeetle-x86_64-linux.zip"
+        );
+        assert_eq!(
+            release_url_for("latest", "universal-macos"),
+            "https://github.com/tigerbeetle/tigerbeetle/releases/latest/download/\
+             tigerbeetle-universal-macos.zip"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn start_and_stop() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Stands in for tigerbeetle: format creates the data file, and start
+        // reports a port and runs until stdin closes.
+        let fake = std::env::temp_dir().join(format!("fake-tigerbeetle-{}", std::process::id()));
+        std::fs::write(
+            &fake,
+            "#!/bin/sh\n\
+             for last; do :; done\n\
+             case \"$1\" in\n\
+             format) touch \"$last\" ;;\n\
+             start) test -f \"$last\" && echo 3456 && cat > /dev/null ;;\n\
+             esac\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cluster = TestClusterBuilder::new()
+            .cluster_id(7)
+            .tigerbeetle(&fake)
+            .start()
+            .unwrap();
+        assert_eq!(cluster.port(), 3456);
+        assert_eq!(cluster.address(), "127.0.0.1:3456");
+        assert_eq!(cluster.cluster_id(), 7);
+        let dir = cluster.dir.clone();
+        assert!(dir.join("0_0.tigerbeetle").is_file());
+        drop(cluster);
+        assert!(!dir.exists());
+
+        let missing = TestClusterBuilder::new()
+            .tigerbeetle(fake.with_extension("missing"))
+            .start();
+        assert!(matches!(missing, Err(TestClusterError::Io(_))));
+        std::fs::remove_file(&fake).unwrap();
+    }
+}
@@ -161,7 +161,7 @@ fn random_u128() -> u128 {
     a | (b << 64)
 }
 
-fn random_u64() -> u64 {
+pub(crate) fn random_u64() -> u64 {
     std::hash::Hasher::finish(&std::hash::BuildHasher::build_hasher(
         &std::collections::hash_map::RandomState::new(),
     ))
@@ -0,0 +1,195 @@
+//! A minimal runtime-independent timer.
+//!
+//! The client doesn't depend on an async runtime, so delays are driven by a
+//! single lazily-spawned thread that runs callbacks, e.g. completing oneshot
+//! channels, when their deadlines pass.
+
+use futures_channel::oneshot::{channel, Receiver};
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, Once};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Returns a future that completes after `duration` has elapsed.
+///
+/// A duration too long to represent never elapses. Dropping the future
+/// before it completes removes its timer entry.
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    match Instant::now().checked_add(duration) {
+        Some(deadline) => sleep_until(deadline),
+        None => Sleep { timer: None },
+    }
+}
+
+/// Returns a future that completes once `deadline` has passed.
+pub(crate) fn sleep_until(deadline: Instant) -> Sleep {
+    let (tx, rx) = channel::<()>();
+
+    let key = call_at(deadline, move || {
+        let _ = tx.send(());
+    });
+
+    Sleep {
+        timer: Some((key, rx)),
+    }
+}
+
+/// The future returned by [`sleep`] and [`sleep_until`].
+pub(crate) struct Sleep {
+    /// The entry and its receiver, or `None` if the sleep never completes.
+    timer: Option<(TimerKey, Receiver<()>)>,
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &mut self.timer {
+            // The sender is only dropped without sending if the timer thread
+            // panicked, in which case returning early is the best we can do.
+            Some((_, rx)) => Pin::new(rx).poll(cx).map(|_| ()),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some((key, _)) = self.timer.take() {
+            cancel(key);
+        }
+    }
+}
+
+/// The key of a timer entry, for cancelling it.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) struct TimerKey {
+    deadline: Instant,
+    sequence: u64,
+}
+
+/// Run `callback` on the timer thread once `deadline` has passed, unless
+/// it is cancelled first.
+///
+/// Callbacks with equal deadlines run in the order they were scheduled.
+/// Callbacks must not block.
+pub(crate) fn call_at(deadline: Instant, callback: impl FnOnce() + Send + 'static) -> TimerKey {
+    TIMER_THREAD.call_once(|| {
+        std::thread::Builder::new()
+            .name("tigerbeetle-timer".to_owned())
+            .spawn(run_timer)
+            .expect("spawn timer thread");
+    });
+
+    let key = TimerKey {
+        deadline,
+        sequence: TIMER_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+    };
+    TIMER_QUEUE
+        .lock()
+        .expect("timer queue")
+        .get_or_insert_with(BTreeMap::new)
+        .insert(key, Box::new(callback));
+    TIMER_WAKE.notify_one();
+    key
+}
+
+/// Remove the entry with `key`, if its callback hasn't run.
+pub(crate) fn cancel(key: TimerKey) {
+    if let Some(queue) = TIMER_QUEUE.lock().expect("timer queue").as_mut() {
+        queue.remove(&key);
+    }
+}
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+static TIMER_THREAD: Once = Once::new();
+static TIMER_QUEUE: Mutex<Option<BTreeMap<TimerKey, Callback>>> = Mutex::new(None);
+static TIMER_WAKE: Condvar = Condvar::new();
+static TIMER_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+fn run_timer() {
+    let mut queue = TIMER_QUEUE.lock().expect("timer queue");
+    loop {
+        let now = Instant::now();
+
+        let mut due = Vec::new();
+        let entries = queue.get_or_insert_with(BTreeMap::new);
+        while let Some(&key) = entries.keys().next() {
+            if key.deadline > now {
+                break;
+            }
+            due.push(entries.remove(&key).expect("entry"));
+        }
+
+        if !due.is_empty() {
+            // Run callbacks without the lock, so they can schedule more.
+            drop(queue);
+            for callback in due {
+                callback();
+            }
+            queue = TIMER_QUEUE.lock().expect("timer queue");
+            continue;
+        }
+
+        queue = match entries.keys().next() {
+            Some(key) => {
+                let timeout = key.deadline.saturating_duration_since(now);
+                TIMER_WAKE
+                    .wait_timeout(queue, timeout)
+                    .expect("timer queue")
+                    .0
+            }
+            None => TIMER_WAKE.wait(queue).expect("timer queue"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn sleeps_complete_in_deadline_order() {
+        let start = Instant::now();
+
+        let long = sleep(Duration::from_millis(30));
+        let short = sleep(Duration::from_millis(10));
+
+        block_on(short);
+        let short_elapsed = start.elapsed();
+        block_on(long);
+        let long_elapsed = start.elapsed();
+
+        assert!(short_elapsed >= Duration::from_millis(10));
+        assert!(long_elapsed >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn dropped_sleeps_are_removed() {
+        let sleep = sleep(Duration::from_secs(3600));
+        let key = sleep.timer.as_ref().unwrap().0;
+        let contains = || {
+            TIMER_QUEUE
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .contains_key(&key)
+        };
+        assert!(contains());
+        drop(sleep);
+        assert!(!contains());
+
+        // A sleep past the end of time never completes, nor schedules an entry.
+        let mut forever = super::sleep(Duration::MAX);
+        assert!(forever.timer.is_none());
+        assert!(block_on(async { futures::poll!(&mut forever) }).is_pending());
+    }
+}
@@ -0,0 +1,205 @@
+//! A builder for [`Transfer`]s that checks required fields at compile time.
+//!
+//! [`TransferBuilder`] tracks which of the required fields have been set in
+//! its type parameters, each either [`Set`] or [`Unset`]. Only a builder with
+//! all of them set has a [`build`](TransferBuilder::build) method, so a
+//! forgotten account, amount, ledger or code is a compile error rather than
+//! a `*_must_not_be_zero` result from the cluster.
+//!
+//! ```
+//! use tigerbeetle as tb;
+//!
+//! let transfer = tb::Transfer::builder(tb::id())
+//!     .debit_account_id(1)
+//!     .credit_account_id(2)
+//!     .amount(100)
+//!     .ledger(840)
+//!     .code(10)
+//!     .user_data_64(42)
+//!     .build();
+//! assert_eq!(transfer.amount, 100);
+//! ```
+//!
+//! Omitting a required field fails to compile:
+//!
+//! ```compile_fail
+//! use tigerbeetle as tb;
+//!
+//! let transfer = tb::Transfer::builder(tb::id())
+//!     .debit_account_id(1)
+//!     .credit_account_id(2)
+//!     .amount(100)
+//!     .code(10)
+//!     .build(); // No ledger.
+//! ```
+//!
+//! The builder can't check the values themselves, so an explicit zero is
+//! still rejected by the cluster. Transfers that take their accounts, ledger
+//! and code from a pending transfer are built with
+//! [`Transfer::post_pending_transfer`] and [`Transfer::void_pending_transfer`]
+//! instead.
+
+use crate::{Transfer, TransferFlags};
+
+use core::marker::PhantomData;
+
+/// A required field that has been set.
+#[derive(Copy, Clone, Debug)]
+pub enum Set {}
+
+/// A required field that has not been set.
+#[derive(Copy, Clone, Debug)]
+pub enum Unset {}
+
+/// A builder for a [`Transfer`].
+///
+/// The type parameters track whether the debit account, credit account,
+/// amount, ledger and code have been set. See the
+/// [module documentation](self).
+#[derive(Copy, Clone, Debug)]
+pub struct TransferBuilder<DebitAccount, CreditAccount, Amount, Ledger, Code> {
+    transfer: Transfer,
+    state: PhantomData<(DebitAccount, CreditAccount, Amount, Ledger, Code)>,
+}
+
+impl Transfer {
+    /// Create a [`TransferBuilder`] for a transfer with id `id`.
+    pub fn builder(id: u128) -> TransferBuilder<Unset, Unset, Unset, Unset, Unset> {
+        TransferBuilder {
+            transfer: Transfer {
+                id,
+                ..Default::default()
+            },
+            state: PhantomData,
+        }
+    }
+}
+
+impl<D, C, A, L, K> TransferBuilder<D, C, A, L, K> {
+    pub fn debit_account_id(self, debit_account_id: u128) -> TransferBuilder<Set, C, A, L, K> {
+        TransferBuilder {
+            transfer: Transfer {
+                debit_account_id,
+                ..self.transfer
+            },
+            state: PhantomData,
+        }
+    }
+
+    pub fn credit_account_id(self, credit_account_id: u128) -> TransferBuilder<D, Set, A, L, K> {
+        TransferBuilder {
+            transfer: Transfer {
+                credit_account_id,
+                ..self.transfer
+            },
+            state: PhantomData,
+        }
+    }
+
+    pub fn amount(self, amount: u128) -> TransferBuilder<D, C, Set, L, K> {
+        TransferBuilder {
+            transfer: Transfer {
+                amount,
+                ..self.transfer
+            },
+            state: PhantomData,
+        }
+    }
+
+    pub fn ledger(self, ledger: u32) -> TransferBuilder<D, C, A, Set, K> {
+        TransferBuilder {
+            transfer: Transfer {
+                ledger,
+                ..self.transfer
+            },
+            state: PhantomData,
+        }
+    }
+
+    pub fn code(self, code: u16) -> TransferBuilder<D, C, A, L, Set> {
+        TransferBuilder {
+            transfer: Transfer {
+                code,
+                ..self.transfer
+            },
+            state: PhantomData,
+        }
+    }
+
+    pub fn pending_id(mut self, pending_id: u128) -> TransferBuilder<D, C, A, L, K> {
+        self.transfer.pending_id = pending_id;
+        self
+    }
+
+    pub fn user_data_128(mut self, user_data_128: u128) -> TransferBuilder<D, C, A, L, K> {
+        self.transfer.user_data_128 = user_data_128;
+        self
+    }
+
+    pub fn user_data_64(mut self, user_data_64: u64) -> TransferBuilder<D, C, A, L, K> {
+        self.transfer.user_data_64 = user_data_64;
+        self
+    }
+
+    pub fn user_data_32(mut self, user_data_32: u32) -> TransferBuilder<D, C, A, L, K> {
+        self.transfer.user_data_32 = user_data_32;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: u32) -> TransferBuilder<D, C, A, L, K> {
+        self.transfer.timeout = timeout;
+        self
+    }
+
+    /// Add `flags` to the transfer's flags.
+    pub fn flags(mut self, flags: TransferFlags) -> TransferBuilder<D, C, A, L, K> {
+        self.transfer.flags |= flags;
+        self
+    }
+}
+
+impl TransferBuilder<Set, Set, Set, Set, Set> {
+    pub fn build(self) -> Transfer {
+        self.transfer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build() {
+        let transfer = Transfer::builder(1)
+            .code(5)
+            .ledger(4)
+            .amount(3)
+            .credit_account_id(2)
+            .debit_account_id(1)
+            .flags(TransferFlags::Pending)
+            .flags(TransferFlags::Linked)
+            .timeout(60)
+            .user_data_128(6)
+            .user_data_64(7)
+            .user_data_32(8)
+            .build();
+
+        assert_eq!(
+            transfer,
+            Transfer {
+                id: 1,
+                debit_account_id: 1,
+                credit_account_id: 2,
+                amount: 3,
+                ledger: 4,
+                code: 5,
+                flags: TransferFlags::Pending | TransferFlags::Linked,
+                timeout: 60,
+                user_data_128: 6,
+                user_data_64: 7,
+                user_data_32: 8,
+                ..Default::default()
+            }
+        );
+    }
+}
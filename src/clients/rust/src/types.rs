@@ -0,0 +1,1100 @@
+use super::*;
+
+/// A TigerBeetle account.
+///
+/// # Protocol reference
+///
+/// [`Account`](https://docs.tigerbeetle.com/reference/account/).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Account {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub id: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub debits_pending: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub debits_posted: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub credits_pending: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub credits_posted: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub user_data_128: u128,
+    pub user_data_64: u64,
+    pub user_data_32: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub reserved: Reserved<4>,
+    pub ledger: u32,
+    pub code: u16,
+    pub flags: AccountFlags,
+    pub timestamp: u64,
+}
+
+bitflags! {
+    /// Bitflags for the `flags` field of [`Account`].
+    ///
+    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    ///
+    /// # Protocol reference
+    ///
+    /// [`Account.flags`](https://docs.tigerbeetle.com/reference/account/#flags).
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Default)]
+    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AccountFlags: u16 {
+        const None = 0;
+        const Linked = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_LINKED;
+        const DebitsMustNotExceedCredits = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_DEBITS_MUST_NOT_EXCEED_CREDITS;
+        const CreditsMustNotExceedDebits = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_CREDITS_MUST_NOT_EXCEED_DEBITS;
+        const History = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_HISTORY;
+        const Imported = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_IMPORTED;
+        const Closed = tbc::TB_ACCOUNT_FLAGS_TB_ACCOUNT_CLOSED;
+    }
+}
+
+/// Builder-style methods for setting individual flags.
+///
+/// Each method returns the flags with one more flag set, so flags can be
+/// combined in a chain, including in `const` contexts:
+///
+/// ```
+/// use tigerbeetle as tb;
+///
+/// const FLAGS: tb::AccountFlags = tb::AccountFlags::empty().linked().history();
+///
+/// assert_eq!(FLAGS, tb::AccountFlags::Linked | tb::AccountFlags::History);
+/// ```
+macro_rules! impl_flag_builders {
+    ($flags:ident { $($method:ident => $flag:ident),* $(,)? }) => {
+        impl $flags {
+            $(
+                #[doc = concat!("Returns these flags with [`", stringify!($flags), "::", stringify!($flag), "`] set.")]
+                #[must_use]
+                pub const fn $method(self) -> Self {
+                    self.union(Self::$flag)
+                }
+            )*
+        }
+    };
+}
+
+impl_flag_builders!(AccountFlags {
+    linked => Linked,
+    debits_must_not_exceed_credits => DebitsMustNotExceedCredits,
+    credits_must_not_exceed_debits => CreditsMustNotExceedDebits,
+    history => History,
+    imported => Imported,
+    closed => Closed,
+});
+
+/// A transfer between accounts.
+///
+/// # Protocol reference
+///
+/// [`Transfer`](https://docs.tigerbeetle.com/reference/transfer).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Transfer {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub id: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub debit_account_id: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub credit_account_id: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub amount: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub pending_id: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub user_data_128: u128,
+    pub user_data_64: u64,
+    pub user_data_32: u32,
+    pub timeout: u32,
+    pub ledger: u32,
+    pub code: u16,
+    pub flags: TransferFlags,
+    pub timestamp: u64,
+}
+
+bitflags! {
+    /// Bitflags for the `flags` field of [`Transfer`].
+    ///
+    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    ///
+    /// # Protocol reference
+    ///
+    /// [`Transfer.flags`](https://docs.tigerbeetle.com/reference/transfer/#flags).
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Default)]
+    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct TransferFlags: u16 {
+        const Linked = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_LINKED;
+        const Pending = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_PENDING;
+        const PostPendingTransfer = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_POST_PENDING_TRANSFER;
+        const VoidPendingTransfer = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_VOID_PENDING_TRANSFER;
+        const BalancingDebit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_BALANCING_DEBIT;
+        const BalancingCredit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_BALANCING_CREDIT;
+        const ClosingDebit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_CLOSING_DEBIT;
+        const ClosingCredit = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_CLOSING_CREDIT;
+        const Imported = tbc::TB_TRANSFER_FLAGS_TB_TRANSFER_IMPORTED;
+    }
+}
+
+impl_flag_builders!(TransferFlags {
+    linked => Linked,
+    pending => Pending,
+    post_pending_transfer => PostPendingTransfer,
+    void_pending_transfer => VoidPendingTransfer,
+    balancing_debit => BalancingDebit,
+    balancing_credit => BalancingCredit,
+    closing_debit => ClosingDebit,
+    closing_credit => ClosingCredit,
+    imported => Imported,
+});
+
+/// The maximum value of [`Transfer::amount`].
+///
+/// When used as the amount of a post-pending or balancing transfer
+/// it has special meaning, as described in the protocol reference.
+///
+/// # Protocol reference
+///
+/// [`Transfer.amount`](https://docs.tigerbeetle.com/reference/transfer/#amount).
+pub const AMOUNT_MAX: u128 = u128::MAX;
+
+impl Transfer {
+    /// Create a transfer that posts a pending transfer.
+    ///
+    /// The returned transfer has the [`TransferFlags::PostPendingTransfer`]
+    /// flag set and `pending_id` pointing at the pending transfer. The
+    /// account, ledger and code fields are left zeroed, in which case
+    /// TigerBeetle uses the values of the pending transfer.
+    ///
+    /// To post the full amount of the pending transfer use [`AMOUNT_MAX`]
+    /// as the `amount`; otherwise the amount must not exceed the pending
+    /// amount, and the remainder is released.
+    ///
+    /// Other fields may be set with struct update syntax:
+    ///
+    /// ```
+    /// use tigerbeetle as tb;
+    ///
+    /// # let pending_id = 1;
+    /// let post = tb::Transfer {
+    ///     user_data_64: 42,
+    ///     ..tb::Transfer::post_pending_transfer(tb::id(), pending_id, tb::AMOUNT_MAX)
+    /// };
+    /// # assert!(post.flags.contains(tb::TransferFlags::PostPendingTransfer));
+    /// ```
+    ///
+    /// # Protocol reference
+    ///
+    /// [Two-Phase Transfers](https://docs.tigerbeetle.com/coding/two-phase-transfers/).
+    pub fn post_pending_transfer(id: u128, pending_id: u128, amount: u128) -> Transfer {
+        Transfer {
+            id,
+            pending_id,
+            amount,
+            flags: TransferFlags::PostPendingTransfer,
+            ..Default::default()
+        }
+    }
+
+    /// Create a transfer that voids a pending transfer.
+    ///
+    /// The returned transfer has the [`TransferFlags::VoidPendingTransfer`]
+    /// flag set and `pending_id` pointing at the pending transfer. The full
+    /// pending amount is released.
+    ///
+    /// # Protocol reference
+    ///
+    /// [Two-Phase Transfers](https://docs.tigerbeetle.com/coding/two-phase-transfers/).
+    pub fn void_pending_transfer(id: u128, pending_id: u128) -> Transfer {
+        Transfer {
+            id,
+            pending_id,
+            flags: TransferFlags::VoidPendingTransfer,
+            ..Default::default()
+        }
+    }
+
+    /// Create a transfer of up to `amount` that is limited by the debit
+    /// account's balance.
+    ///
+    /// The returned transfer has the [`TransferFlags::BalancingDebit`] flag
+    /// set. TigerBeetle transfers the lesser of `amount` and the amount that
+    /// the debit account can be debited without its debits exceeding its
+    /// credits, so the debit account must have the
+    /// [`AccountFlags::DebitsMustNotExceedCredits`] flag for the limit to
+    /// apply. Use [`AMOUNT_MAX`] as the `amount` to transfer as much as
+    /// possible.
+    ///
+    /// The amount actually transferred is not reported by `create_transfers`;
+    /// look the transfer up to find it:
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use tigerbeetle as tb;
+    ///
+    /// # fn main() -> Result<(), tb::PacketStatus> {
+    /// # let client = tb::InMemoryClient::new();
+    /// # let source = tb::Account {
+    /// #     id: 1,
+    /// #     ledger: 1,
+    /// #     code: 1,
+    /// #     flags: tb::AccountFlags::DebitsMustNotExceedCredits,
+    /// #     ..Default::default()
+    /// # };
+    /// # let destination = tb::Account { id: 2, flags: tb::AccountFlags::None, ..source };
+    /// # block_on(client.create_accounts(&[source, destination]))?;
+    /// # let deposit = tb::Transfer {
+    /// #     id: tb::id(),
+    /// #     debit_account_id: destination.id,
+    /// #     credit_account_id: source.id,
+    /// #     amount: 70,
+    /// #     ledger: 1,
+    /// #     code: 1,
+    /// #     ..Default::default()
+    /// # };
+    /// # block_on(client.create_transfers(&[deposit]))?;
+    /// // The source account has a balance of 70.
+    /// let sweep = tb::Transfer::balancing_debit(
+    ///     tb::id(),
+    ///     source.id,
+    ///     destination.id,
+    ///     tb::AMOUNT_MAX,
+    ///     1,
+    ///     1,
+    /// );
+    /// block_on(client.create_transfers(&[sweep]))?;
+    ///
+    /// let sweep = block_on(client.lookup_transfers(&[sweep.id]))?[0];
+    /// assert_eq!(sweep.amount, 70);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Protocol reference
+    ///
+    /// [`Transfer.flags.balancing_debit`](https://docs.tigerbeetle.com/reference/transfer/#flagsbalancing_debit).
+    pub fn balancing_debit(
+        id: u128,
+        debit_account_id: u128,
+        credit_account_id: u128,
+        amount: u128,
+        ledger: u32,
+        code: u16,
+    ) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id,
+            credit_account_id,
+            amount,
+            ledger,
+            code,
+            flags: TransferFlags::BalancingDebit,
+            ..Default::default()
+        }
+    }
+
+    /// Create a transfer of up to `amount` that is limited by the credit
+    /// account's balance.
+    ///
+    /// The returned transfer has the [`TransferFlags::BalancingCredit`] flag
+    /// set. TigerBeetle transfers the lesser of `amount` and the amount that
+    /// the credit account can be credited without its credits exceeding its
+    /// debits, so the credit account must have the
+    /// [`AccountFlags::CreditsMustNotExceedDebits`] flag for the limit to
+    /// apply. Use [`AMOUNT_MAX`] as the `amount` to transfer as much as
+    /// possible.
+    ///
+    /// As with [`Transfer::balancing_debit`], look the transfer up to find
+    /// the amount actually transferred.
+    ///
+    /// # Protocol reference
+    ///
+    /// [`Transfer.flags.balancing_credit`](https://docs.tigerbeetle.com/reference/transfer/#flagsbalancing_credit).
+    pub fn balancing_credit(
+        id: u128,
+        debit_account_id: u128,
+        credit_account_id: u128,
+        amount: u128,
+        ledger: u32,
+        code: u16,
+    ) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id,
+            credit_account_id,
+            amount,
+            ledger,
+            code,
+            flags: TransferFlags::BalancingCredit,
+            ..Default::default()
+        }
+    }
+
+    /// Create a transfer that closes an account.
+    ///
+    /// The returned transfer is a pending transfer of zero amount from
+    /// `account_id` to `control_account_id`, with the
+    /// [`TransferFlags::ClosingDebit`] flag set, so that `account_id` is
+    /// closed once the transfer is created. The control account is not
+    /// closed, and must be a different account on the same ledger.
+    ///
+    /// A closed account rejects new transfers, other than voiding its
+    /// pending transfers. To reopen it, void the closing transfer with
+    /// [`Transfer::reopen_account`].
+    ///
+    /// ```
+    /// use futures::executor::block_on;
+    /// use tigerbeetle as tb;
+    ///
+    /// # fn main() -> Result<(), tb::PacketStatus> {
+    /// # let client = tb::InMemoryClient::new();
+    /// # let account = tb::Account { id: 1, ledger: 1, code: 1, ..Default::default() };
+    /// # let control = tb::Account { id: 2, ..account };
+    /// # block_on(client.create_accounts(&[account, control]))?;
+    /// let closing_id = tb::id();
+    /// let closing = tb::Transfer::close_account(closing_id, account.id, control.id, 1, 1);
+    /// block_on(client.create_transfers(&[closing]))?;
+    ///
+    /// let account = block_on(client.lookup_accounts(&[account.id]))?[0];
+    /// assert!(account.flags.contains(tb::AccountFlags::Closed));
+    ///
+    /// let reopening = tb::Transfer::reopen_account(tb::id(), closing_id);
+    /// block_on(client.create_transfers(&[reopening]))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Protocol reference
+    ///
+    /// [Close Account](https://docs.tigerbeetle.com/coding/recipes/close-account/).
+    pub fn close_account(
+        id: u128,
+        account_id: u128,
+        control_account_id: u128,
+        ledger: u32,
+        code: u16,
+    ) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id: account_id,
+            credit_account_id: control_account_id,
+            amount: 0,
+            ledger,
+            code,
+            flags: TransferFlags::Pending | TransferFlags::ClosingDebit,
+            ..Default::default()
+        }
+    }
+
+    /// Create a transfer that reopens an account closed by
+    /// [`Transfer::close_account`].
+    ///
+    /// The returned transfer voids the closing transfer, `closing_id`.
+    ///
+    /// # Protocol reference
+    ///
+    /// [Close Account](https://docs.tigerbeetle.com/coding/recipes/close-account/).
+    pub fn reopen_account(id: u128, closing_id: u128) -> Transfer {
+        Transfer::void_pending_transfer(id, closing_id)
+    }
+}
+
+/// Filter for querying transfers and historical balances.
+///
+/// # Protocol reference
+///
+/// [`AccountFilter`](https://docs.tigerbeetle.com/reference/account-filter).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AccountFilter {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub account_id: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub user_data_128: u128,
+    pub user_data_64: u64,
+    pub user_data_32: u32,
+    pub code: u16,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub reserved: Reserved<58>,
+    pub timestamp_min: u64,
+    pub timestamp_max: u64,
+    pub limit: u32,
+    pub flags: AccountFilterFlags,
+}
+
+bitflags! {
+    /// Bitflags for the `flags` field of [`AccountFilter`].
+    ///
+    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    ///
+    /// # Protocol reference
+    ///
+    /// [`AccountFilter.flags`](https://docs.tigerbeetle.com/reference/account-filter/#flags).
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Default)]
+    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct AccountFilterFlags: u32 {
+        const Debits = tbc::TB_ACCOUNT_FILTER_FLAGS_TB_ACCOUNT_FILTER_DEBITS;
+        const Credits = tbc::TB_ACCOUNT_FILTER_FLAGS_TB_ACCOUNT_FILTER_CREDITS;
+        const Reversed = tbc::TB_ACCOUNT_FILTER_FLAGS_TB_ACCOUNT_FILTER_REVERSED;
+    }
+}
+
+/// An account balance at a point in time.
+///
+/// # Protocol reference
+///
+/// [`AccountBalance`](https://docs.tigerbeetle.com/reference/account-balance/).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AccountBalance {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub debits_pending: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub debits_posted: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub credits_pending: u128,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub credits_posted: u128,
+    pub timestamp: u64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub reserved: Reserved<56>,
+}
+
+/// Parameters for querying accounts and transfers.
+///
+/// # Protocol reference
+///
+/// [`QueryFilter`](https://docs.tigerbeetle.com/reference/query-filter/).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct QueryFilter {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_u128"))]
+    pub user_data_128: u128,
+    pub user_data_64: u64,
+    pub user_data_32: u32,
+    pub ledger: u32,
+    pub code: u16,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub reserved: Reserved<6>,
+    pub timestamp_min: u64,
+    pub timestamp_max: u64,
+    pub limit: u32,
+    pub flags: QueryFilterFlags,
+}
+
+bitflags! {
+    /// Bitflags for the `flags` field of [`QueryFilter`].
+    ///
+    /// See the [`bitflags` crate](https://docs.rs/bitflags) for an explanation of Rust bitflags.
+    ///
+    /// # Protocol reference
+    ///
+    /// [`QueryFilter.flags`](https://docs.tigerbeetle.com/reference/query-filter/#flags).
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Debug, Default)]
+    #[derive(Eq, PartialEq, Ord, PartialOrd, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct QueryFilterFlags: u32 {
+        const Reversed = tbc::TB_QUERY_FILTER_FLAGS_TB_QUERY_FILTER_REVERSED;
+    }
+}
+
+/// The result of a single [`create_accounts`] event.
+///
+/// For the meaning of individual enum variants see the linked protocol reference.
+///
+/// See also [`CreateAccountsResult`] (note the plural), the type directly
+/// returned by `create_accunts`, and which contains an additional index for
+/// relating results with input events.
+///
+/// [`create_accounts`]: `Client::create_accounts`
+///
+/// # Protocol reference
+///
+/// [`CreateAccountResult`](https://docs.tigerbeetle.com/reference/requests/create_accounts/#result).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CreateAccountResult {
+    Ok,
+    LinkedEventFailed,
+    LinkedEventChainOpen,
+    ImportedEventExpected,
+    ImportedEventNotExpected,
+    TimestampMustBeZero,
+    ImportedEventTimestampOutOfRange,
+    ImportedEventTimestampMustNotAdvance,
+    ReservedField,
+    ReservedFlag,
+    IdMustNotBeZero,
+    IdMustNotBeIntMax,
+    ExistsWithDifferentFlags,
+    ExistsWithDifferentUserData128,
+    ExistsWithDifferentUserData64,
+    ExistsWithDifferentUserData32,
+    ExistsWithDifferentLedger,
+    ExistsWithDifferentCode,
+    Exists,
+    FlagsAreMutuallyExclusive,
+    DebitsPendingMustBeZero,
+    DebitsPostedMustBeZero,
+    CreditsPendingMustBeZero,
+    CreditsPostedMustBeZero,
+    LedgerMustNotBeZero,
+    CodeMustNotBeZero,
+    ImportedEventTimestampMustNotRegress,
+}
+
+/// The result of a single [`create_accounts`] event, with index.
+///
+/// [`create_accounts`]: `Client::create_accounts`
+///
+/// # Protocol reference
+///
+/// [`CreateAccountResult`](https://docs.tigerbeetle.com/reference/requests/create_accounts/#result).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CreateAccountsResult {
+    pub index: usize,
+    pub result: CreateAccountResult,
+}
+
+impl core::fmt::Display for CreateAccountResult {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Ok => f.write_str("ok"),
+            Self::LinkedEventFailed => f.write_str("linked event failed"),
+            Self::LinkedEventChainOpen => f.write_str("linked event chain open"),
+            Self::ImportedEventExpected => f.write_str("imported event expected"),
+            Self::ImportedEventNotExpected => f.write_str("imported event not expected"),
+            Self::TimestampMustBeZero => f.write_str("timestamp must be zero"),
+            Self::ImportedEventTimestampOutOfRange => {
+                f.write_str("imported event timestamp out of range")
+            }
+            Self::ImportedEventTimestampMustNotAdvance => {
+                f.write_str("imported event timestamp must not advance")
+            }
+            Self::ReservedField => f.write_str("reserved field"),
+            Self::ReservedFlag => f.write_str("reserved flag"),
+            Self::IdMustNotBeZero => f.write_str("id must not be zero"),
+            Self::IdMustNotBeIntMax => f.write_str("id must not be int max"),
+            Self::ExistsWithDifferentFlags => f.write_str("exists with different flags"),
+            Self::ExistsWithDifferentUserData128 => {
+                f.write_str("exists with different user_data_128")
+            }
+            Self::ExistsWithDifferentUserData64 => {
+                f.write_str("exists with different user_data_64")
+            }
+            Self::ExistsWithDifferentUserData32 => {
+                f.write_str("exists with different user_data_32")
+            }
+            Self::ExistsWithDifferentLedger => f.write_str("exists with different ledger"),
+            Self::ExistsWithDifferentCode => f.write_str("exists with different code"),
+            Self::Exists => f.write_str("exists"),
+            Self::FlagsAreMutuallyExclusive => f.write_str("flags are mutually exclusive"),
+            Self::DebitsPendingMustBeZero => f.write_str("debits_pending must be zero"),
+            Self::DebitsPostedMustBeZero => f.write_str("debits_posted must be zero"),
+            Self::CreditsPendingMustBeZero => f.write_str("credits_pending must be zero"),
+            Self::CreditsPostedMustBeZero => f.write_str("credits_posted must be zero"),
+            Self::LedgerMustNotBeZero => f.write_str("ledger must not be zero"),
+            Self::CodeMustNotBeZero => f.write_str("code must not be zero"),
+            Self::ImportedEventTimestampMustNotRegress => {
+                f.write_str("imported event timestamp must not regress")
+            }
+        }
+    }
+}
+
+/// The result of a single [`create_transfers`] event.
+///
+/// For the meaning of individual enum variants see the linked protocol reference.
+///
+/// See also [`CreateTransfersResult`] (note the plural), the type directly
+/// returned by `create_accunts`, and which contains an additional index for
+/// relating results with input events.
+///
+/// [`create_transfers`]: `Client::create_transfers`
+///
+/// # Protocol reference
+///
+/// [`CreateTransferResult`](https://docs.tigerbeetle.com/reference/requests/create_transfers/#result).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CreateTransferResult {
+    Ok,
+    LinkedEventFailed,
+    LinkedEventChainOpen,
+    ImportedEventExpected,
+    ImportedEventNotExpected,
+    TimestampMustBeZero,
+    ImportedEventTimestampOutOfRange,
+    ImportedEventTimestampMustNotAdvance,
+    ReservedFlag,
+    IdMustNotBeZero,
+    IdMustNotBeIntMax,
+    ExistsWithDifferentFlags,
+    ExistsWithDifferentPendingId,
+    ExistsWithDifferentTimeout,
+    ExistsWithDifferentDebitAccountId,
+    ExistsWithDifferentCreditAccountId,
+    ExistsWithDifferentAmount,
+    ExistsWithDifferentUserData128,
+    ExistsWithDifferentUserData64,
+    ExistsWithDifferentUserData32,
+    ExistsWithDifferentLedger,
+    ExistsWithDifferentCode,
+    Exists,
+    IdAlreadyFailed,
+    FlagsAreMutuallyExclusive,
+    DebitAccountIdMustNotBeZero,
+    DebitAccountIdMustNotBeIntMax,
+    CreditAccountIdMustNotBeZero,
+    CreditAccountIdMustNotBeIntMax,
+    AccountsMustBeDifferent,
+    PendingIdMustBeZero,
+    PendingIdMustNotBeZero,
+    PendingIdMustNotBeIntMax,
+    PendingIdMustBeDifferent,
+    TimeoutReservedForPendingTransfer,
+    ClosingTransferMustBePending,
+    LedgerMustNotBeZero,
+    CodeMustNotBeZero,
+    DebitAccountNotFound,
+    CreditAccountNotFound,
+    AccountsMustHaveTheSameLedger,
+    TransferMustHaveTheSameLedgerAsAccounts,
+    PendingTransferNotFound,
+    PendingTransferNotPending,
+    PendingTransferHasDifferentDebitAccountId,
+    PendingTransferHasDifferentCreditAccountId,
+    PendingTransferHasDifferentLedger,
+    PendingTransferHasDifferentCode,
+    ExceedsPendingTransferAmount,
+    PendingTransferHasDifferentAmount,
+    PendingTransferAlreadyPosted,
+    PendingTransferAlreadyVoided,
+    PendingTransferExpired,
+    ImportedEventTimestampMustNotRegress,
+    ImportedEventTimestampMustPostdateDebitAccount,
+    ImportedEventTimestampMustPostdateCreditAccount,
+    ImportedEventTimeoutMustBeZero,
+    DebitAccountAlreadyClosed,
+    CreditAccountAlreadyClosed,
+    OverflowsDebitsPending,
+    OverflowsCreditsPending,
+    OverflowsDebitsPosted,
+    OverflowsCreditsPosted,
+    OverflowsDebits,
+    OverflowsCredits,
+    OverflowsTimeout,
+    ExceedsCredits,
+    ExceedsDebits,
+}
+
+/// The result of a single [`create_transfers`] event, with index.
+///
+/// [`create_transfers`]: `Client::create_transfers`
+///
+/// # Protocol reference
+///
+/// [`CreateTransferResult`](https://docs.tigerbeetle.com/reference/requests/create_transfers/#result).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CreateTransfersResult {
+    pub index: usize,
+    pub result: CreateTransferResult,
+}
+
+impl core::fmt::Display for CreateTransferResult {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::Ok => f.write_str("ok"),
+            Self::LinkedEventFailed => f.write_str("linked event failed"),
+            Self::LinkedEventChainOpen => f.write_str("linked event chain open"),
+            Self::ImportedEventExpected => f.write_str("imported event expected"),
+            Self::ImportedEventNotExpected => f.write_str("imported event not expected"),
+            Self::TimestampMustBeZero => f.write_str("timestamp must be zero"),
+            Self::ImportedEventTimestampOutOfRange => {
+                f.write_str("imported event timestamp out of range")
+            }
+            Self::ImportedEventTimestampMustNotAdvance => {
+                f.write_str("imported event timestamp must not advance")
+            }
+            Self::ReservedFlag => f.write_str("reserved flag"),
+            Self::IdMustNotBeZero => f.write_str("id must not be zero"),
+            Self::IdMustNotBeIntMax => f.write_str("id must not be int max"),
+            Self::ExistsWithDifferentFlags => f.write_str("exists with different flags"),
+            Self::ExistsWithDifferentPendingId => f.write_str("exists with different pending_id"),
+            Self::ExistsWithDifferentTimeout => f.write_str("exists with different timeout"),
+            Self::ExistsWithDifferentDebitAccountId => {
+                f.write_str("exists with different debit_account_id")
+            }
+            Self::ExistsWithDifferentCreditAccountId => {
+                f.write_str("exists with different credit_account_id")
+            }
+            Self::ExistsWithDifferentAmount => f.write_str("exists with different amount"),
+            Self::ExistsWithDifferentUserData128 => {
+                f.write_str("exists with different user_data_128")
+            }
+            Self::ExistsWithDifferentUserData64 => {
+                f.write_str("exists with different user_data_64")
+            }
+            Self::ExistsWithDifferentUserData32 => {
+                f.write_str("exists with different user_data_32")
+            }
+            Self::ExistsWithDifferentLedger => f.write_str("exists with different ledger"),
+            Self::ExistsWithDifferentCode => f.write_str("exists with different code"),
+            Self::Exists => f.write_str("exists"),
+            Self::IdAlreadyFailed => f.write_str("id already failed"),
+            Self::FlagsAreMutuallyExclusive => f.write_str("flags are mutually exclusive"),
+            Self::DebitAccountIdMustNotBeZero => f.write_str("debit_account_id must not be zero"),
+            Self::DebitAccountIdMustNotBeIntMax => {
+                f.write_str("debit_account_id must not be int max")
+            }
+            Self::CreditAccountIdMustNotBeZero => f.write_str("credit_account_id must not be zero"),
+            Self::CreditAccountIdMustNotBeIntMax => {
+                f.write_str("credit_account_id must not be int max")
+            }
+            Self::AccountsMustBeDifferent => f.write_str("accounts must be different"),
+            Self::PendingIdMustBeZero => f.write_str("pending_id must be zero"),
+            Self::PendingIdMustNotBeZero => f.write_str("pending_id must not be zero"),
+            Self::PendingIdMustNotBeIntMax => f.write_str("pending_id must not be int max"),
+            Self::PendingIdMustBeDifferent => f.write_str("pending_id must be different"),
+            Self::TimeoutReservedForPendingTransfer => {
+                f.write_str("timeout reserved for pending transfer")
+            }
+            Self::ClosingTransferMustBePending => f.write_str("closing transfer must be pending"),
+            Self::LedgerMustNotBeZero => f.write_str("ledger must not be zero"),
+            Self::CodeMustNotBeZero => f.write_str("code must not be zero"),
+            Self::DebitAccountNotFound => f.write_str("debit account not found"),
+            Self::CreditAccountNotFound => f.write_str("credit account not found"),
+            Self::AccountsMustHaveTheSameLedger => {
+                f.write_str("accounts must have the same ledger")
+            }
+            Self::TransferMustHaveTheSameLedgerAsAccounts => {
+                f.write_str("transfer must have the same ledger as accounts")
+            }
+            Self::PendingTransferNotFound => f.write_str("pending transfer not found"),
+            Self::PendingTransferNotPending => f.write_str("pending transfer not pending"),
+            Self::PendingTransferHasDifferentDebitAccountId => {
+                f.write_str("pending transfer has different debit_account_id")
+            }
+            Self::PendingTransferHasDifferentCreditAccountId => {
+                f.write_str("pending transfer has different credit_account_id")
+            }
+            Self::PendingTransferHasDifferentLedger => {
+                f.write_str("pending transfer has different ledger")
+            }
+            Self::PendingTransferHasDifferentCode => {
+                f.write_str("pending transfer has different code")
+            }
+            Self::ExceedsPendingTransferAmount => f.write_str("exceeds pending transfer amount"),
+            Self::PendingTransferHasDifferentAmount => {
+                f.write_str("pending transfer has different amount")
+            }
+            Self::PendingTransferAlreadyPosted => f.write_str("pending transfer already posted"),
+            Self::PendingTransferAlreadyVoided => f.write_str("pending transfer already voided"),
+            Self::PendingTransferExpired => f.write_str("pending transfer expired"),
+            Self::ImportedEventTimestampMustNotRegress => {
+                f.write_str("imported event timestamp must not regress")
+            }
+            Self::ImportedEventTimestampMustPostdateDebitAccount => {
+                f.write_str("imported event timestamp must postdate debit account")
+            }
+            Self::ImportedEventTimestampMustPostdateCreditAccount => {
+                f.write_str("imported event timestamp must postdate credit account")
+            }
+            Self::ImportedEventTimeoutMustBeZero => {
+                f.write_str("imported event timeout must be zero")
+            }
+            Self::DebitAccountAlreadyClosed => f.write_str("debit account already closed"),
+            Self::CreditAccountAlreadyClosed => f.write_str("credit account already closed"),
+            Self::OverflowsDebitsPending => f.write_str("overflows debits_pending"),
+            Self::OverflowsCreditsPending => f.write_str("overflows credits_pending"),
+            Self::OverflowsDebitsPosted => f.write_str("overflows debits_posted"),
+            Self::OverflowsCreditsPosted => f.write_str("overflows credits_posted"),
+            Self::OverflowsDebits => f.write_str("overflows debits"),
+            Self::OverflowsCredits => f.write_str("overflows credits"),
+            Self::OverflowsTimeout => f.write_str("overflows timeout"),
+            Self::ExceedsCredits => f.write_str("exceeds credits"),
+            Self::ExceedsDebits => f.write_str("exceeds debits"),
+        }
+    }
+}
+
+impl CreateTransferResult {
+    /// A short explanation of the result, and of what to change for the
+    /// transfer to succeed, for showing to users.
+    ///
+    /// Unlike the [`Display`](core::fmt::Display) text, which names the
+    /// result, this describes it in terms of the transfer and its accounts.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Self::Ok => "The transfer was created.",
+            Self::LinkedEventFailed => {
+                "Another transfer in the same linked chain failed, so this one was not created."
+            }
+            Self::LinkedEventChainOpen => {
+                "The last transfer in the batch has the linked flag, so its chain is never closed. \
+                 Clear the flag on the last transfer of the chain."
+            }
+            Self::ImportedEventExpected => {
+                "The first transfer in the batch is imported, so all of them must be. \
+                 Submit imported and other transfers in separate batches."
+            }
+            Self::ImportedEventNotExpected => {
+                "The first transfer in the batch isn't imported, so none of them may be. \
+                 Submit imported and other transfers in separate batches."
+            }
+            Self::TimestampMustBeZero => {
+                "The timestamp is assigned by the cluster. Leave it zero, \
+                 or set the imported flag to supply it."
+            }
+            Self::ImportedEventTimestampOutOfRange => {
+                "The imported timestamp is out of range. Use nanoseconds since the Unix epoch."
+            }
+            Self::ImportedEventTimestampMustNotAdvance => {
+                "The imported timestamp is in the future. Imported transfers must be in the past."
+            }
+            Self::ReservedFlag => "A flag that isn't defined is set. Clear it.",
+            Self::IdMustNotBeZero => "The transfer's id is zero. Give it a unique id.",
+            Self::IdMustNotBeIntMax => {
+                "The transfer's id is the maximum value, which is reserved. \
+                 Give it a unique id."
+            }
+            Self::ExistsWithDifferentFlags
+            | Self::ExistsWithDifferentPendingId
+            | Self::ExistsWithDifferentTimeout
+            | Self::ExistsWithDifferentDebitAccountId
+            | Self::ExistsWithDifferentCreditAccountId
+            | Self::ExistsWithDifferentAmount
+            | Self::ExistsWithDifferentUserData128
+            | Self::ExistsWithDifferentUserData64
+            | Self::ExistsWithDifferentUserData32
+            | Self::ExistsWithDifferentLedger
+            | Self::ExistsWithDifferentCode => {
+                "A different transfer with the same id was already created. \
+                 Give this transfer a new id, or resubmit the original unchanged."
+            }
+            Self::Exists => {
+                "The transfer was already created by an earlier request, \
+                 so nothing was changed."
+            }
+            Self::IdAlreadyFailed => {
+                "A transfer with this id already failed with a transient error, \
+                 such as an exceeded balance limit, so the id can't be reused. \
+                 Give the transfer a new id."
+            }
+            Self::FlagsAreMutuallyExclusive => {
+                "The transfer has flags that can't be combined, such as pending with \
+                 posting or voiding a pending transfer."
+            }
+            Self::DebitAccountIdMustNotBeZero => "The transfer has no debit account. Set one.",
+            Self::DebitAccountIdMustNotBeIntMax => {
+                "The debit account id is the maximum value, which is reserved."
+            }
+            Self::CreditAccountIdMustNotBeZero => "The transfer has no credit account. Set one.",
+            Self::CreditAccountIdMustNotBeIntMax => {
+                "The credit account id is the maximum value, which is reserved."
+            }
+            Self::AccountsMustBeDifferent => {
+                "The debit and credit accounts are the same account. Choose different accounts."
+            }
+            Self::PendingIdMustBeZero => {
+                "Only transfers that post or void a pending transfer have a pending id. \
+                 Leave it zero."
+            }
+            Self::PendingIdMustNotBeZero => {
+                "The transfer posts or voids a pending transfer, \
+                 but doesn't say which. Set its pending id."
+            }
+            Self::PendingIdMustNotBeIntMax => {
+                "The pending id is the maximum value, which is reserved."
+            }
+            Self::PendingIdMustBeDifferent => {
+                "The pending id is the transfer's own id. \
+                 Set it to the id of the pending transfer."
+            }
+            Self::TimeoutReservedForPendingTransfer => {
+                "Only pending transfers can time out. \
+                 Leave the timeout zero, or set the pending flag."
+            }
+            Self::ClosingTransferMustBePending => {
+                "A transfer that closes an account must be pending, \
+                 so that voiding it can reopen the account. Set the pending flag."
+            }
+            Self::LedgerMustNotBeZero => {
+                "The transfer has no ledger. Set it to the accounts' ledger."
+            }
+            Self::CodeMustNotBeZero => "The transfer has no code. Set a code for its reason.",
+            Self::DebitAccountNotFound => {
+                "The debit account doesn't exist. Check its id, or create it first."
+            }
+            Self::CreditAccountNotFound => {
+                "The credit account doesn't exist. Check its id, or create it first."
+            }
+            Self::AccountsMustHaveTheSameLedger => {
+                "The debit and credit accounts are on different ledgers, \
+                 such as different currencies. Transfer between accounts on the same ledger."
+            }
+            Self::TransferMustHaveTheSameLedgerAsAccounts => {
+                "The transfer's ledger differs from its accounts' ledger. Use the accounts' ledger."
+            }
+            Self::PendingTransferNotFound => {
+                "The pending transfer to post or void doesn't exist. Check the pending id."
+            }
+            Self::PendingTransferNotPending => {
+                "The transfer the pending id refers to isn't pending, \
+                 so it can't be posted or voided."
+            }
+            Self::PendingTransferHasDifferentDebitAccountId => {
+                "The debit account differs from the pending transfer's. \
+                 Leave it zero, or set it to the pending transfer's."
+            }
+            Self::PendingTransferHasDifferentCreditAccountId => {
+                "The credit account differs from the pending transfer's. \
+                 Leave it zero, or set it to the pending transfer's."
+            }
+            Self::PendingTransferHasDifferentLedger => {
+                "The ledger differs from the pending transfer's. \
+                 Leave it zero, or set it to the pending transfer's."
+            }
+            Self::PendingTransferHasDifferentCode => {
+                "The code differs from the pending transfer's. \
+                 Leave it zero, or set it to the pending transfer's."
+            }
+            Self::ExceedsPendingTransferAmount => {
+                "The amount to post is more than the pending transfer reserved. \
+                 Post at most the pending amount."
+            }
+            Self::PendingTransferHasDifferentAmount => {
+                "A void must release the whole pending amount. \
+                 Leave the amount zero, or set it to the pending transfer's."
+            }
+            Self::PendingTransferAlreadyPosted => "The pending transfer was already posted.",
+            Self::PendingTransferAlreadyVoided => "The pending transfer was already voided.",
+            Self::PendingTransferExpired => {
+                "The pending transfer timed out, and its amount was released, \
+                 so it can no longer be posted or voided."
+            }
+            Self::ImportedEventTimestampMustNotRegress => {
+                "The imported timestamp is not later than that of the last transfer created. \
+                 Import transfers in the order they happened."
+            }
+            Self::ImportedEventTimestampMustPostdateDebitAccount => {
+                "The imported timestamp is earlier than the debit account was created."
+            }
+            Self::ImportedEventTimestampMustPostdateCreditAccount => {
+                "The imported timestamp is earlier than the credit account was created."
+            }
+            Self::ImportedEventTimeoutMustBeZero => {
+                "Imported pending transfers can't time out. Leave the timeout zero."
+            }
+            Self::DebitAccountAlreadyClosed => {
+                "The debit account is closed, so it can't be transferred from."
+            }
+            Self::CreditAccountAlreadyClosed => {
+                "The credit account is closed, so it can't be transferred to."
+            }
+            Self::OverflowsDebitsPending
+            | Self::OverflowsCreditsPending
+            | Self::OverflowsDebitsPosted
+            | Self::OverflowsCreditsPosted
+            | Self::OverflowsDebits
+            | Self::OverflowsCredits => {
+                "The amount would overflow an account's balance. Use a smaller amount."
+            }
+            Self::OverflowsTimeout => {
+                "The timeout would end after the latest time the cluster can represent. \
+                 Use a shorter timeout."
+            }
+            Self::ExceedsCredits => {
+                "The debit account has the flag debits_must_not_exceed_credits, \
+                 and this transfer would take its debits above its credits."
+            }
+            Self::ExceedsDebits => {
+                "The credit account has the flag credits_must_not_exceed_debits, \
+                 and this transfer would take its credits above its debits."
+            }
+        }
+    }
+}
+
+/// A utility type for representing reserved bytes in structs.
+///
+/// This type is instantiated with [`Default::default`] and typically
+/// does not need to be used directly.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Reserved<const N: usize>([u8; N]);
+
+impl<const N: usize> Default for Reserved<N> {
+    fn default() -> Reserved<N> {
+        Reserved([0; N])
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde() {
+        let transfer = Transfer {
+            id: u128::MAX,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 100,
+            ledger: 1,
+            code: 1,
+            flags: TransferFlags::Linked | TransferFlags::Pending,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(transfer).unwrap();
+        assert_eq!(json["id"], u128::MAX.to_string());
+        assert_eq!(json["amount"], "100");
+        assert_eq!(json["flags"], "Linked | Pending");
+        assert!(json.get("reserved").is_none());
+        assert_eq!(serde_json::from_value::<Transfer>(json).unwrap(), transfer);
+
+        let account: Account =
+            serde_json::from_str(r#"{"id": "0xff", "ledger": 1, "code": 10}"#).unwrap();
+        assert_eq!(
+            account,
+            Account {
+                id: 255,
+                ledger: 1,
+                code: 10,
+                ..Default::default()
+            }
+        );
+
+        let result = CreateTransfersResult {
+            index: 3,
+            result: CreateTransferResult::ExceedsCredits,
+        };
+        assert_eq!(
+            serde_json::to_string(&result).unwrap(),
+            r#"{"index":3,"result":"ExceedsCredits"}"#
+        );
+    }
+}
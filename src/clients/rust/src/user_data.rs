@@ -0,0 +1,238 @@
+//! Packing of external references into the `user_data_*` fields.
+//!
+//! TigerBeetle stores `user_data_128`, `user_data_64` and `user_data_32`
+//! without interpreting them, and can query accounts and transfers by them.
+//! The types here give them a layout, so that a reference to an order, a
+//! tenant or another system can be stored and queried:
+//!
+//! - [`Reference`] packs a string of up to 16 bytes, such as an order
+//!   number, into a `u128`.
+//! - [`TenantSequence`] packs a tenant and a sequence number, 64 bits each,
+//!   into a `u128`.
+//! - [`pack_u32_pair`] packs two `u32`s into a `u64`.
+//! - UUIDs are `u128`s already; see [`id128`](crate::id128) for parsing them.
+//!
+//! ```
+//! use tigerbeetle as tb;
+//! use tb::user_data::{self, Reference};
+//!
+//! let order = Reference::new("ORD-2024-00042").unwrap();
+//! let transfer = tb::Transfer {
+//!     user_data_128: order.into(),
+//!     ..Default::default()
+//! };
+//! assert_eq!(
+//!     Reference::try_from(transfer.user_data_128).unwrap().as_str(),
+//!     "ORD-2024-00042"
+//! );
+//!
+//! // The transfers for the order.
+//! let filter = user_data::query_filter(order, 100);
+//! assert_eq!(filter.user_data_128, transfer.user_data_128);
+//! ```
+
+use crate::{AccountFilter, AccountFilterFlags, QueryFilter};
+
+use core::fmt;
+
+/// A string of up to 16 bytes, packed into a `u128`.
+///
+/// The first byte is the most significant, and the string is padded with
+/// zero bytes, so references compare as their strings do. Strings may not
+/// contain zero bytes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Reference {
+    bytes: [u8; 16],
+    len: u8,
+}
+
+impl Reference {
+    /// Pack `reference`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UserDataError::TooLong`] if `reference` is longer than 16
+    /// bytes, and [`UserDataError::Nul`] if it contains a zero byte.
+    pub fn new(reference: &str) -> Result<Reference, UserDataError> {
+        if reference.len() > 16 {
+            return Err(UserDataError::TooLong);
+        }
+        if reference.bytes().any(|byte| byte == 0) {
+            return Err(UserDataError::Nul);
+        }
+        let mut bytes = [0; 16];
+        bytes[..reference.len()].copy_from_slice(reference.as_bytes());
+        Ok(Reference {
+            bytes,
+            len: reference.len() as u8,
+        })
+    }
+
+    /// The reference as a string.
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..usize::from(self.len)]).expect("utf-8")
+    }
+}
+
+impl From<Reference> for u128 {
+    fn from(reference: Reference) -> u128 {
+        u128::from_be_bytes(reference.bytes)
+    }
+}
+
+impl TryFrom<u128> for Reference {
+    type Error = UserDataError;
+
+    /// Unpack a reference.
+    ///
+    /// Fails with [`UserDataError::Nul`] if a zero byte precedes another
+    /// byte, and with [`UserDataError::Utf8`] if the bytes aren't UTF-8.
+    fn try_from(value: u128) -> Result<Reference, UserDataError> {
+        let bytes = value.to_be_bytes();
+        let len = bytes
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(0, |last| last + 1);
+        if bytes[..len].contains(&0) {
+            return Err(UserDataError::Nul);
+        }
+        core::str::from_utf8(&bytes[..len]).map_err(|_| UserDataError::Utf8)?;
+        Ok(Reference {
+            bytes,
+            len: len as u8,
+        })
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A tenant and a sequence number within it, packed into a `u128`.
+///
+/// The tenant is the most significant 64 bits, and the sequence the least,
+/// so values sort by tenant, then sequence.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct TenantSequence {
+    pub tenant: u64,
+    pub sequence: u64,
+}
+
+impl From<TenantSequence> for u128 {
+    fn from(value: TenantSequence) -> u128 {
+        u128::from(value.tenant) << 64 | u128::from(value.sequence)
+    }
+}
+
+impl From<u128> for TenantSequence {
+    fn from(value: u128) -> TenantSequence {
+        TenantSequence {
+            tenant: (value >> 64) as u64,
+            sequence: value as u64,
+        }
+    }
+}
+
+/// Pack two `u32`s into a `u64`, `high` in the most significant 32 bits.
+pub fn pack_u32_pair(high: u32, low: u32) -> u64 {
+    u64::from(high) << 32 | u64::from(low)
+}
+
+/// Unpack a `u64` packed by [`pack_u32_pair`].
+pub fn unpack_u32_pair(value: u64) -> (u32, u32) {
+    ((value >> 32) as u32, value as u32)
+}
+
+/// A filter for up to `limit` accounts or transfers with `user_data_128`,
+/// oldest first.
+///
+/// Other fields can be set on the result to narrow the query.
+pub fn query_filter(user_data_128: impl Into<u128>, limit: u32) -> QueryFilter {
+    QueryFilter {
+        user_data_128: user_data_128.into(),
+        limit,
+        ..Default::default()
+    }
+}
+
+/// A filter for up to `limit` of the debits and credits of `account_id`
+/// with `user_data_128`, oldest first.
+pub fn account_filter(
+    account_id: u128,
+    user_data_128: impl Into<u128>,
+    limit: u32,
+) -> AccountFilter {
+    AccountFilter {
+        account_id,
+        user_data_128: user_data_128.into(),
+        limit,
+        flags: AccountFilterFlags::Debits | AccountFilterFlags::Credits,
+        ..Default::default()
+    }
+}
+
+/// Errors packing or unpacking user data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum UserDataError {
+    /// The string is longer than the field.
+    TooLong,
+    /// The string contains a zero byte.
+    Nul,
+    /// The bytes aren't UTF-8.
+    Utf8,
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UserDataError {}
+impl fmt::Display for UserDataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooLong => f.write_str("longer than 16 bytes"),
+            Self::Nul => f.write_str("contains a zero byte"),
+            Self::Utf8 => f.write_str("not utf-8"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference() {
+        for reference in ["", "a", "ORD-2024-00042", "0123456789abcdef", "€uro"] {
+            let packed = u128::from(Reference::new(reference).unwrap());
+            assert_eq!(Reference::try_from(packed).unwrap().as_str(), reference);
+        }
+        assert_eq!(u128::from(Reference::new("").unwrap()), 0);
+        assert_eq!(
+            u128::from(Reference::new("a").unwrap()),
+            u128::from(b'a') << 120
+        );
+        assert!(
+            u128::from(Reference::new("ab").unwrap()) < u128::from(Reference::new("b").unwrap())
+        );
+
+        assert_eq!(
+            Reference::new("0123456789abcdefg"),
+            Err(UserDataError::TooLong)
+        );
+        assert_eq!(Reference::new("a\0b"), Err(UserDataError::Nul));
+        assert_eq!(Reference::try_from(1 << 120 | 1), Err(UserDataError::Nul));
+        assert_eq!(Reference::try_from(0xff << 120), Err(UserDataError::Utf8));
+    }
+
+    #[test]
+    fn tenant_sequence() {
+        let value = TenantSequence {
+            tenant: 7,
+            sequence: u64::MAX,
+        };
+        assert_eq!(u128::from(value), 7 << 64 | u128::from(u64::MAX));
+        assert_eq!(TenantSequence::from(u128::from(value)), value);
+        assert_eq!(unpack_u32_pair(pack_u32_pair(1, 2)), (1, 2));
+    }
+}
@@ -0,0 +1,391 @@
+//! Validation of events before they are submitted.
+//!
+//! Only checks that don't depend on the cluster's state are made, so an
+//! event that passes may still fail. [`Client::validate_transfers`] also
+//! checks transfers against the state of their accounts. An event that
+//! fails would also be rejected by the cluster, though with a different
+//! result if an event with the same id already exists.
+//!
+//! Events are first scanned in chunks for anything suspicious, with
+//! comparisons combined without short-circuiting so that the compiler can
+//! vectorize them. Only suspicious chunks are checked event by event, so
+//! that valid batches, the common case, cost little.
+
+use super::*;
+
+use std::collections::hash_map::{Entry, HashMap};
+
+/// The events scanned at once.
+const CHUNK: usize = 16;
+
+/// The accounts of `events` that the cluster would reject.
+pub(crate) fn invalid_accounts(events: &[Account]) -> Vec<FailedAccount> {
+    invalid(events, account_suspect, account)
+        .into_iter()
+        .map(|(index, result)| FailedAccount {
+            index,
+            account: events[index],
+            result,
+        })
+        .collect()
+}
+
+/// The transfers of `events` that the cluster would reject.
+pub(crate) fn invalid_transfers(events: &[Transfer]) -> Vec<FailedTransfer> {
+    invalid(events, transfer_suspect, transfer)
+        .into_iter()
+        .map(|(index, result)| FailedTransfer {
+            index,
+            transfer: events[index],
+            result,
+        })
+        .collect()
+}
+
+/// The events whose id is that of an earlier event.
+pub(crate) fn duplicate_ids(ids: impl ExactSizeIterator<Item = u128>) -> Vec<DuplicateId> {
+    let mut first_indexes = HashMap::with_capacity(ids.len());
+    let mut duplicates = Vec::new();
+    for (index, id) in ids.enumerate() {
+        match first_indexes.entry(id) {
+            Entry::Occupied(first) => duplicates.push(DuplicateId {
+                index,
+                first_index: *first.get(),
+                id,
+            }),
+            Entry::Vacant(first) => {
+                first.insert(index);
+            }
+        }
+    }
+    duplicates
+}
+
+fn invalid<Event, Result>(
+    events: &[Event],
+    suspect: fn(&Event) -> bool,
+    check: fn(&Event) -> Option<Result>,
+) -> Vec<(usize, Result)> {
+    let mut invalid = Vec::new();
+    for (chunk_index, chunk) in events.chunks(CHUNK).enumerate() {
+        if !chunk.iter().fold(false, |any, event| any | suspect(event)) {
+            continue;
+        }
+        for (index, event) in chunk.iter().enumerate() {
+            if let Some(result) = check(event) {
+                invalid.push((chunk_index * CHUNK + index, result));
+            }
+        }
+    }
+    invalid
+}
+
+impl Client {
+    /// The results the cluster would return for `events` if they were
+    /// created now, without creating them.
+    ///
+    /// This looks up the transfers' accounts, any existing transfers with
+    /// their ids and the pending transfers they post or void, and executes
+    /// the events against them in an [`InMemoryClient`]. Results are
+    /// reported as by [`Client::create_transfers`], including failures that
+    /// depend on the accounts, such as exceeded balance limits, ledger
+    /// mismatches and closed accounts, and those of earlier events in the
+    /// batch, such as linked chains.
+    ///
+    /// The results are a prediction: transfers created concurrently may
+    /// change the accounts before the events are submitted, and a pending
+    /// transfer is assumed to still be pending, since whether it was
+    /// posted or voided isn't looked up.
+    ///
+    /// Any number of transfers can be validated; their accounts and
+    /// transfers are looked up in batches of up to [`BATCH_MAX`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] of [`PacketStatus`] if a lookup fails.
+    pub async fn validate_transfers(
+        &self,
+        events: &[Transfer],
+    ) -> Result<Vec<CreateTransfersResult>, PacketStatus> {
+        let mut transfer_ids: Vec<u128> = events.iter().map(|t| t.id).collect();
+        transfer_ids.extend(events.iter().map(|t| t.pending_id).filter(|&id| id != 0));
+        transfer_ids.sort_unstable();
+        transfer_ids.dedup();
+        let mut transfers = Vec::new();
+        for chunk in transfer_ids.chunks(BATCH_MAX) {
+            transfers.extend(self.lookup_transfers(chunk).await?);
+        }
+
+        let mut account_ids: Vec<u128> = events
+            .iter()
+            .chain(&transfers)
+            .flat_map(|t| [t.debit_account_id, t.credit_account_id])
+            .filter(|&id| id != 0)
+            .collect();
+        account_ids.sort_unstable();
+        account_ids.dedup();
+        let mut accounts = Vec::new();
+        for chunk in account_ids.chunks(BATCH_MAX) {
+            accounts.extend(self.lookup_accounts(chunk).await?);
+        }
+
+        InMemoryClient::snapshot(&accounts, &transfers)
+            .create_transfers(events)
+            .await
+    }
+}
+
+/// Whether `account` might be invalid; true for every invalid account.
+#[inline]
+fn account_suspect(a: &Account) -> bool {
+    (a.reserved != Reserved::default())
+        | (a.flags.bits() & !AccountFlags::all().bits() != 0)
+        | (a.id == 0)
+        | (a.id == u128::MAX)
+        | (a.debits_pending | a.debits_posted | a.credits_pending | a.credits_posted != 0)
+        | (a.ledger == 0)
+        | (a.code == 0)
+        | a.flags.contains(
+            AccountFlags::DebitsMustNotExceedCredits | AccountFlags::CreditsMustNotExceedDebits,
+        )
+}
+
+/// Whether `transfer` might be invalid; true for every invalid transfer.
+#[inline]
+fn transfer_suspect(t: &Transfer) -> bool {
+    (t.flags.bits() & !TransferFlags::all().bits() != 0)
+        | (t.id == 0)
+        | (t.id == u128::MAX)
+        | (t.debit_account_id == 0)
+        | (t.debit_account_id == u128::MAX)
+        | (t.credit_account_id == 0)
+        | (t.credit_account_id == u128::MAX)
+        | (t.debit_account_id == t.credit_account_id)
+        | (t.pending_id != 0)
+        | (t.timeout != 0)
+        | t.flags
+            .intersects(TransferFlags::ClosingDebit | TransferFlags::ClosingCredit)
+        | (t.ledger == 0)
+        | (t.code == 0)
+}
+
+/// The result of the first failed check of `a`, in the cluster's order.
+fn account(a: &Account) -> Option<CreateAccountResult> {
+    use CreateAccountResult::*;
+
+    if a.reserved != Reserved::default() {
+        return Some(ReservedField);
+    }
+    if !(a.flags - AccountFlags::all()).is_empty() {
+        return Some(ReservedFlag);
+    }
+    if a.id == 0 {
+        return Some(IdMustNotBeZero);
+    }
+    if a.id == u128::MAX {
+        return Some(IdMustNotBeIntMax);
+    }
+    if a.flags.contains(
+        AccountFlags::DebitsMustNotExceedCredits | AccountFlags::CreditsMustNotExceedDebits,
+    ) {
+        return Some(FlagsAreMutuallyExclusive);
+    }
+    if a.debits_pending != 0 {
+        return Some(DebitsPendingMustBeZero);
+    }
+    if a.debits_posted != 0 {
+        return Some(DebitsPostedMustBeZero);
+    }
+    if a.credits_pending != 0 {
+        return Some(CreditsPendingMustBeZero);
+    }
+    if a.credits_posted != 0 {
+        return Some(CreditsPostedMustBeZero);
+    }
+    if a.ledger == 0 {
+        return Some(LedgerMustNotBeZero);
+    }
+    if a.code == 0 {
+        return Some(CodeMustNotBeZero);
+    }
+    None
+}
+
+/// The result of the first failed check of `t`, in the cluster's order.
+fn transfer(t: &Transfer) -> Option<CreateTransferResult> {
+    use CreateTransferResult::*;
+
+    if !(t.flags - TransferFlags::all()).is_empty() {
+        return Some(ReservedFlag);
+    }
+    if t.id == 0 {
+        return Some(IdMustNotBeZero);
+    }
+    if t.id == u128::MAX {
+        return Some(IdMustNotBeIntMax);
+    }
+    if t.flags
+        .intersects(TransferFlags::PostPendingTransfer | TransferFlags::VoidPendingTransfer)
+    {
+        // The remaining fields default to the pending transfer's.
+        return None;
+    }
+    if t.debit_account_id == 0 {
+        return Some(DebitAccountIdMustNotBeZero);
+    }
+    if t.debit_account_id == u128::MAX {
+        return Some(DebitAccountIdMustNotBeIntMax);
+    }
+    if t.credit_account_id == 0 {
+        return Some(CreditAccountIdMustNotBeZero);
+    }
+    if t.credit_account_id == u128::MAX {
+        return Some(CreditAccountIdMustNotBeIntMax);
+    }
+    if t.credit_account_id == t.debit_account_id {
+        return Some(AccountsMustBeDifferent);
+    }
+    if t.pending_id != 0 {
+        return Some(PendingIdMustBeZero);
+    }
+    if !t.flags.contains(TransferFlags::Pending) {
+        if t.timeout != 0 {
+            return Some(TimeoutReservedForPendingTransfer);
+        }
+        if t.flags
+            .intersects(TransferFlags::ClosingDebit | TransferFlags::ClosingCredit)
+        {
+            return Some(ClosingTransferMustBePending);
+        }
+    }
+    if t.ledger == 0 {
+        return Some(LedgerMustNotBeZero);
+    }
+    if t.code == 0 {
+        return Some(CodeMustNotBeZero);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(id: u128) -> Transfer {
+        Transfer {
+            id,
+            debit_account_id: 1,
+            credit_account_id: 2,
+            amount: 1,
+            ledger: 1,
+            code: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn transfers() {
+        let mut events: Vec<Transfer> = (1..=100).map(transfer).collect();
+        assert_eq!(invalid_transfers(&events), vec![]);
+
+        events[3].id = 0;
+        events[40].credit_account_id = 1;
+        events[41].flags = TransferFlags::PostPendingTransfer;
+        events[41].debit_account_id = 0;
+        events[99].timeout = 1;
+        let invalid: Vec<_> = invalid_transfers(&events)
+            .iter()
+            .map(|failed| (failed.index, failed.result))
+            .collect();
+        assert_eq!(
+            invalid,
+            vec![
+                (3, CreateTransferResult::IdMustNotBeZero),
+                (40, CreateTransferResult::AccountsMustBeDifferent),
+                (99, CreateTransferResult::TimeoutReservedForPendingTransfer),
+            ]
+        );
+    }
+
+    #[test]
+    fn accounts() {
+        let mut events: Vec<Account> = (1..=20)
+            .map(|id| Account {
+                id,
+                ledger: 1,
+                code: 1,
+                ..Default::default()
+            })
+            .collect();
+        assert_eq!(invalid_accounts(&events), vec![]);
+
+        events[19].flags =
+            AccountFlags::DebitsMustNotExceedCredits | AccountFlags::CreditsMustNotExceedDebits;
+        events[19].ledger = 0;
+        assert_eq!(
+            invalid_accounts(&events),
+            vec![FailedAccount {
+                index: 19,
+                account: events[19],
+                result: CreateAccountResult::FlagsAreMutuallyExclusive,
+            }]
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        assert_eq!(duplicate_ids([1, 2, 3].into_iter()), vec![]);
+        assert_eq!(
+            duplicate_ids([1, 2, 1, 3, 2, 1].into_iter()),
+            vec![
+                DuplicateId {
+                    index: 2,
+                    first_index: 0,
+                    id: 1
+                },
+                DuplicateId {
+                    index: 4,
+                    first_index: 1,
+                    id: 2
+                },
+                DuplicateId {
+                    index: 5,
+                    first_index: 0,
+                    id: 1
+                },
+            ]
+        );
+    }
+
+    /// Every event the in-memory cluster rejects statelessly is found.
+    #[test]
+    fn agrees_with_in_memory() {
+        let client = InMemoryClient::new();
+        let mut events = Vec::new();
+        for mutation in 0..16u128 {
+            let mut t = transfer(1_000 + mutation);
+            match mutation {
+                1 => t.id = 0,
+                2 => t.id = u128::MAX,
+                3 => t.debit_account_id = 0,
+                4 => t.credit_account_id = u128::MAX,
+                5 => t.credit_account_id = t.debit_account_id,
+                6 => t.pending_id = 7,
+                7 => t.timeout = 1,
+                8 => t.flags = TransferFlags::ClosingDebit,
+                9 => t.ledger = 0,
+                10 => t.code = 0,
+                11 => t.flags = TransferFlags::from_bits_retain(1 << 15),
+                _ => {}
+            }
+            events.push(t);
+        }
+        let results = futures::executor::block_on(client.create_transfers(&events)).unwrap();
+        for failed in invalid_transfers(&events) {
+            assert!(results
+                .iter()
+                .any(|result| result.index == failed.index && result.result == failed.result));
+        }
+        assert_eq!(invalid_transfers(&events).len(), 11);
+    }
+}
@@ -0,0 +1,126 @@
+use super::*;
+
+/// The TigerBeetle release this client was built from.
+///
+/// The client and the cluster negotiate compatibility when a session is
+/// registered: a cluster accepts clients from a range of releases around its
+/// own. A client outside that range has its requests fail with
+/// [`PacketStatus::ClientReleaseTooLow`] or
+/// [`PacketStatus::ClientReleaseTooHigh`].
+///
+/// In-tree builds report `0.0.0`.
+pub const CLIENT_RELEASE: &str = env!("CARGO_PKG_VERSION");
+
+impl Client {
+    /// Check that the cluster accepts this client's release.
+    ///
+    /// The cluster's own release isn't reported to clients, so rather than
+    /// comparing versions this makes a request, as [`ping`](Client::ping)
+    /// does, and reports whether the cluster rejected the client's release.
+    /// Call it at startup to fail with a clear error instead of on the
+    /// first request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VersionError::ClientReleaseTooLow`] if the client must be
+    /// upgraded, [`VersionError::ClientReleaseTooHigh`] if the cluster must
+    /// be, and [`VersionError::Packet`] if the request failed otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tigerbeetle as tb;
+    ///
+    /// # async fn example(client: &tb::Client) -> Result<(), tb::VersionError> {
+    /// client.check_version().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn check_version(&self) -> impl Future<Output = Result<(), VersionError>> {
+        let ping = self.ping();
+        async move {
+            let result = ping.await.map(drop).map_err(VersionError::from);
+            if matches!(
+                result,
+                Err(VersionError::ClientReleaseTooLow | VersionError::ClientReleaseTooHigh)
+            ) {
+                warn!(
+                    client_release = CLIENT_RELEASE,
+                    "the cluster rejected the client's release"
+                );
+            }
+            result
+        }
+    }
+}
+
+/// Errors from [`Client::check_version`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum VersionError {
+    /// The cluster requires a newer client.
+    ClientReleaseTooLow,
+    /// The cluster requires an older client, or must be upgraded.
+    ClientReleaseTooHigh,
+    /// The request failed for another reason.
+    Packet(PacketStatus),
+}
+
+impl From<PacketStatus> for VersionError {
+    fn from(status: PacketStatus) -> VersionError {
+        match status {
+            PacketStatus::ClientReleaseTooLow => VersionError::ClientReleaseTooLow,
+            PacketStatus::ClientReleaseTooHigh => VersionError::ClientReleaseTooHigh,
+            status => VersionError::Packet(status),
+        }
+    }
+}
+
+impl std::error::Error for VersionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Packet(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for VersionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::ClientReleaseTooLow => write!(
+                f,
+                "client release {CLIENT_RELEASE} is too old for the cluster; upgrade the client"
+            ),
+            Self::ClientReleaseTooHigh => write!(
+                f,
+                "client release {CLIENT_RELEASE} is too new for the cluster; upgrade the cluster"
+            ),
+            Self::Packet(status) => write!(f, "version check failed: {status}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_status() {
+        assert_eq!(
+            VersionError::from(PacketStatus::ClientReleaseTooLow),
+            VersionError::ClientReleaseTooLow
+        );
+        assert_eq!(
+            VersionError::from(PacketStatus::ClientReleaseTooHigh),
+            VersionError::ClientReleaseTooHigh
+        );
+        assert_eq!(
+            VersionError::from(PacketStatus::ClientEvicted),
+            VersionError::Packet(PacketStatus::ClientEvicted)
+        );
+        assert!(VersionError::ClientReleaseTooLow
+            .to_string()
+            .contains(CLIENT_RELEASE));
+    }
+}
@@ -0,0 +1,151 @@
+use super::*;
+
+use futures_core::Stream;
+
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// A change to an account observed by [`Client::watch_balances`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BalanceUpdate {
+    /// The account, with its balances when it was looked up.
+    pub account: Account,
+    /// The account when it was last looked up, or `None` the first time it
+    /// was found.
+    pub previous: Option<Account>,
+}
+
+/// A stream of [`BalanceUpdate`]s. See [`Client::watch_balances`].
+pub struct BalanceWatch<'a> {
+    client: &'a Client,
+    account_ids: Vec<u128>,
+    interval: Duration,
+    state: WatchState,
+    accounts: HashMap<u128, Account>,
+    updates: VecDeque<BalanceUpdate>,
+}
+
+enum WatchState {
+    Sleeping(BoxFuture<'static, ()>),
+    LookingUp {
+        started: Instant,
+        lookup: BoxFuture<'static, Result<Vec<Account>, PacketStatus>>,
+    },
+}
+
+impl Client {
+    /// Watch the balances of accounts, by looking them up every `interval`.
+    ///
+    /// The stream yields an update for each account when it is first found,
+    /// and then whenever it has changed since it was last looked up, which
+    /// is when its balances change or it is closed or reopened. Changes
+    /// between lookups are coalesced: an account whose balance changes and
+    /// changes back is not updated. For every change, use the `history`
+    /// account flag or change data capture instead.
+    ///
+    /// Lookups are made every `interval` from the start of the last, and
+    /// only while the stream is polled. A failed lookup yields its error,
+    /// and the watch continues after the next interval. The stream never
+    /// ends.
+    ///
+    /// Any number of accounts can be watched; more than [`BATCH_MAX`] are
+    /// looked up in multiple requests.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use std::time::Duration;
+    /// use tigerbeetle as tb;
+    ///
+    /// # async fn example(client: &tb::Client) -> Result<(), tb::PacketStatus> {
+    /// let mut watch = client.watch_balances(&[1, 2], Duration::from_secs(1));
+    /// while let Some(update) = watch.next().await {
+    ///     let account = update?.account;
+    ///     println!("{}: {}", account.id, account.credits_posted - account.debits_posted);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn watch_balances(&self, account_ids: &[u128], interval: Duration) -> BalanceWatch<'_> {
+        BalanceWatch {
+            client: self,
+            account_ids: account_ids.to_vec(),
+            interval,
+            state: look_up(self, account_ids),
+            accounts: HashMap::new(),
+            updates: VecDeque::new(),
+        }
+    }
+}
+
+fn look_up(client: &Client, account_ids: &[u128]) -> WatchState {
+    let requests: Vec<_> = account_ids
+        .chunks(BATCH_MAX)
+        .map(|ids| client.lookup_accounts(ids))
+        .collect();
+    WatchState::LookingUp {
+        started: Instant::now(),
+        lookup: Box::pin(async {
+            let mut found = Vec::new();
+            for request in requests {
+                found.extend(request.await?);
+            }
+            Ok(found)
+        }),
+    }
+}
+
+impl BalanceWatch<'_> {
+    /// Queue updates for the accounts that changed.
+    fn found(&mut self, found: Vec<Account>) {
+        for account in found {
+            let previous = self.accounts.insert(account.id, account);
+            if previous != Some(account) {
+                self.updates.push_back(BalanceUpdate { account, previous });
+            }
+        }
+    }
+}
+
+impl Stream for BalanceWatch<'_> {
+    type Item = Result<BalanceUpdate, PacketStatus>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(update) = this.updates.pop_front() {
+                return Poll::Ready(Some(Ok(update)));
+            }
+            match &mut this.state {
+                WatchState::Sleeping(sleep) => match sleep.as_mut().poll(cx) {
+                    Poll::Ready(()) => this.state = look_up(this.client, &this.account_ids),
+                    Poll::Pending => return Poll::Pending,
+                },
+                WatchState::LookingUp { started, lookup } => {
+                    let result = match lookup.as_mut().poll(cx) {
+                        Poll::Ready(result) => result,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    let next = *started + this.interval;
+                    this.state = WatchState::Sleeping(Box::pin(timer::sleep_until(next)));
+                    match result {
+                        Ok(found) => this.found(found),
+                        Err(status) => return Poll::Ready(Some(Err(status))),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for BalanceWatch<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("BalanceWatch")
+            .field("account_ids", &self.account_ids)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
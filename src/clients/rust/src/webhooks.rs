@@ -0,0 +1,759 @@
+//! Webhook notifications of created transfers.
+//!
+//! A client with [`Webhooks`] posts the results of each
+//! [`create_transfers`](crate::Client::create_transfers) request to HTTP
+//! endpoints, so that other systems, such as notification services, learn
+//! about transfers without polling:
+//!
+//! ```no_run
+//! use tigerbeetle as tb;
+//! use tb::webhooks::Webhooks;
+//!
+//! # fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let webhooks = Webhooks::new()
+//!     .endpoint("http://notifications.internal:8080/tigerbeetle", b"secret")?
+//!     .on_failure(|failure| eprintln!("webhook {}: {}", failure.url, failure.error));
+//! let client = tb::ClientBuilder::new()
+//!     .address("3000")
+//!     .webhooks(webhooks)
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each request is posted as a JSON object, with the transfers in the
+//! format of the `serde` feature and the result of each:
+//!
+//! ```json
+//! {"id": "...", "type": "transfers.created", "results": [
+//!     {"transfer": {"id": "1", ...}, "result": "Ok"},
+//!     {"transfer": {"id": "2", ...}, "result": "ExceedsCredits"}
+//! ]}
+//! ```
+//!
+//! Requests that fail with a [`PacketStatus`] aren't
+//! posted, since their transfers' results are unknown.
+//!
+//! Notifications are posted in order by a background thread, and retried
+//! with exponential backoff until the endpoint responds with a `2xx` status.
+//! A retried notification has the same `id`, and the `X-TigerBeetle-Delivery`
+//! header, so endpoints can ignore duplicates.
+//!
+//! # Signatures
+//!
+//! Each post has an `X-TigerBeetle-Signature` header of the form
+//! `t=<timestamp>,v1=<signature>`, where the timestamp is in seconds since
+//! the Unix epoch and the signature is the hexadecimal HMAC-SHA256, keyed
+//! with the endpoint's secret, of the timestamp, a `.` and the body. See
+//! [`signature`] and [`verify`].
+//!
+//! Only `http` endpoints are supported; post to `https` endpoints through a
+//! proxy that terminates TLS.
+
+use crate::{CreateTransferResult, CreateTransfersResult, PacketStatus, Transfer};
+
+use core::fmt;
+use std::future::Future;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Endpoints to post the results of created transfers to, and how.
+///
+/// See the [module documentation](self).
+#[derive(Clone)]
+pub struct Webhooks {
+    pub(crate) endpoints: Vec<Endpoint>,
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    timeout: Duration,
+    queue_capacity: usize,
+    on_failure: Option<OnFailure>,
+}
+
+type OnFailure = Arc<dyn Fn(&DeliveryFailure) + Send + Sync>;
+
+#[derive(Clone)]
+pub(crate) struct Endpoint {
+    url: String,
+    /// The host and port to connect to.
+    address: String,
+    /// The `Host` header.
+    host: String,
+    path: String,
+    secret: Vec<u8>,
+}
+
+/// A notification that could not be posted to an endpoint.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct DeliveryFailure {
+    pub url: String,
+    /// The id of the notification.
+    pub id: u128,
+    /// The number of attempts made, or zero if the notification was dropped
+    /// because the queue was full.
+    pub attempts: u32,
+    /// Why the last attempt failed.
+    pub error: String,
+}
+
+impl Webhooks {
+    /// Webhooks with no endpoints.
+    ///
+    /// Notifications are attempted up to 5 times, with delays starting at
+    /// 500 milliseconds and doubling up to 30 seconds. Each attempt times
+    /// out after 10 seconds, and up to 1024 notifications are queued.
+    pub fn new() -> Webhooks {
+        Webhooks {
+            endpoints: Vec::new(),
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            queue_capacity: 1024,
+            on_failure: None,
+        }
+    }
+
+    /// Post notifications to `url`, signed with `secret`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WebhookError::UrlInvalid`] if `url` is not an `http` URL, or
+    /// contains whitespace or control characters.
+    pub fn endpoint(mut self, url: &str, secret: &[u8]) -> Result<Webhooks, WebhookError> {
+        let invalid = || WebhookError::UrlInvalid(url.to_owned());
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (host, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        if host.is_empty() || host.contains('@') {
+            return Err(invalid());
+        }
+        // They would end the request line or a header early.
+        if rest.contains(|c: char| c.is_whitespace() || c.is_control()) {
+            return Err(invalid());
+        }
+        let has_port = match host.rfind(':') {
+            Some(index) => !host[index..].contains(']'),
+            None => false,
+        };
+        self.endpoints.push(Endpoint {
+            url: url.to_owned(),
+            address: if has_port {
+                host.to_owned()
+            } else {
+                format!("{host}:80")
+            },
+            host: host.to_owned(),
+            path: path.to_owned(),
+            secret: secret.to_vec(),
+        });
+        Ok(self)
+    }
+
+    /// The number of attempts to post each notification to an endpoint.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_attempts` is zero.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Webhooks {
+        assert!(max_attempts > 0, "max_attempts must not be zero");
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// The delay before the first retry, which doubles with each attempt.
+    pub fn base_delay(mut self, base_delay: Duration) -> Webhooks {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// The longest delay between attempts.
+    pub fn max_delay(mut self, max_delay: Duration) -> Webhooks {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// The time to connect, send a notification and receive the status.
+    pub fn timeout(mut self, timeout: Duration) -> Webhooks {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The number of notifications queued for the background thread,
+    /// beyond which they are dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queue_capacity` is zero.
+    pub fn queue_capacity(mut self, queue_capacity: usize) -> Webhooks {
+        assert!(queue_capacity > 0, "queue_capacity must not be zero");
+        self.queue_capacity = queue_capacity;
+        self
+    }
+
+    /// Call `on_failure` for each notification that could not be posted to
+    /// an endpoint, on the background thread.
+    pub fn on_failure(
+        mut self,
+        on_failure: impl Fn(&DeliveryFailure) + Send + Sync + 'static,
+    ) -> Webhooks {
+        self.on_failure = Some(Arc::new(on_failure));
+        self
+    }
+
+    /// The delay before attempt `attempt`, from 2.
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt - 2).unwrap_or(u32::MAX);
+        self.base_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    fn fail(&self, failure: DeliveryFailure) {
+        warn!(
+            url = %failure.url,
+            attempts = failure.attempts,
+            error = %failure.error,
+            "webhook delivery failed"
+        );
+        if let Some(on_failure) = &self.on_failure {
+            on_failure(&failure);
+        }
+    }
+}
+
+impl Default for Webhooks {
+    fn default() -> Webhooks {
+        Webhooks::new()
+    }
+}
+
+impl fmt::Debug for Webhooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let urls: Vec<&str> = self.endpoints.iter().map(|e| e.url.as_str()).collect();
+        f.debug_struct("Webhooks")
+            .field("endpoints", &urls)
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("timeout", &self.timeout)
+            .field("queue_capacity", &self.queue_capacity)
+            .finish()
+    }
+}
+
+/// The queue of a client's notifications, and the thread posting them.
+pub(crate) struct Emitter {
+    webhooks: Webhooks,
+    sender: Mutex<SyncSender<Notification>>,
+}
+
+struct Notification {
+    id: u128,
+    body: Vec<u8>,
+}
+
+impl Emitter {
+    /// Start posting notifications, until the emitter is dropped and its
+    /// queue is empty.
+    pub(crate) fn start(webhooks: Webhooks) -> Arc<Emitter> {
+        let (sender, receiver) = mpsc::sync_channel(webhooks.queue_capacity);
+        let worker = webhooks.clone();
+        std::thread::spawn(move || deliver(&worker, receiver));
+        Arc::new(Emitter {
+            webhooks,
+            sender: Mutex::new(sender),
+        })
+    }
+
+    /// Queue a notification of the results of `transfers`.
+    fn notify(&self, transfers: &[Transfer], results: &[CreateTransfersResult]) {
+        let id = crate::id();
+        let body = body(id, transfers, results);
+        let sent = self
+            .sender
+            .lock()
+            .expect("webhooks queue")
+            .try_send(Notification { id, body });
+        if let Err(TrySendError::Full(_)) = sent {
+            for endpoint in &self.webhooks.endpoints {
+                self.webhooks.fail(DeliveryFailure {
+                    url: endpoint.url.clone(),
+                    id,
+                    attempts: 0,
+                    error: "queue full".to_owned(),
+                });
+            }
+        }
+    }
+}
+
+/// Queue a notification of `transfers` once `results` completes, if the
+/// client has webhooks.
+pub(crate) fn notify(
+    emitter: Option<Arc<Emitter>>,
+    transfers: &[Transfer],
+    results: impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>>,
+) -> impl Future<Output = Result<Vec<CreateTransfersResult>, PacketStatus>> {
+    let notification = emitter.map(|emitter| (emitter, transfers.to_vec()));
+    async move {
+        let results = results.await;
+        if let (Some((emitter, transfers)), Ok(results)) = (&notification, &results) {
+            emitter.notify(transfers, results);
+        }
+        results
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Body<'a> {
+    #[serde(with = "crate::serde_u128")]
+    id: u128,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    results: Vec<TransferResult<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct TransferResult<'a> {
+    transfer: &'a Transfer,
+    result: CreateTransferResult,
+}
+
+fn body(id: u128, transfers: &[Transfer], results: &[CreateTransfersResult]) -> Vec<u8> {
+    let mut body = Body {
+        id,
+        kind: "transfers.created",
+        results: transfers
+            .iter()
+            .map(|transfer| TransferResult {
+                transfer,
+                result: CreateTransferResult::Ok,
+            })
+            .collect(),
+    };
+    for result in results {
+        body.results[result.index].result = result.result;
+    }
+    serde_json::to_vec(&body).expect("serializable")
+}
+
+fn deliver(webhooks: &Webhooks, receiver: Receiver<Notification>) {
+    for notification in receiver {
+        for endpoint in &webhooks.endpoints {
+            let mut attempt = 1;
+            loop {
+                match post(webhooks, endpoint, &notification) {
+                    Ok(()) => break,
+                    Err(error) if attempt == webhooks.max_attempts => {
+                        webhooks.fail(DeliveryFailure {
+                            url: endpoint.url.clone(),
+                            id: notification.id,
+                            attempts: attempt,
+                            error,
+                        });
+                        break;
+                    }
+                    Err(_) => {
+                        attempt += 1;
+                        debug!(url = %endpoint.url, attempt, "retrying webhook");
+                        std::thread::sleep(webhooks.delay(attempt));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Post a notification, failing unless the endpoint responds with a `2xx`
+/// status.
+fn post(
+    webhooks: &Webhooks,
+    endpoint: &Endpoint,
+    notification: &Notification,
+) -> Result<(), String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let mut request = format!(
+        "POST {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         X-TigerBeetle-Delivery: {}\r\n\
+         X-TigerBeetle-Signature: t={timestamp},v1={}\r\n\
+         Connection: close\r\n\r\n",
+        endpoint.path,
+        endpoint.host,
+        notification.body.len(),
+        notification.id,
+        signature(&endpoint.secret, timestamp, &notification.body),
+    )
+    .into_bytes();
+    request.extend_from_slice(&notification.body);
+
+    let error = |error: io::Error| error.to_string();
+    let mut last_error = "no address".to_owned();
+    let mut connected = None;
+    for address in endpoint.address.to_socket_addrs().map_err(error)? {
+        match TcpStream::connect_timeout(&address, webhooks.timeout) {
+            Ok(stream) => {
+                connected = Some(stream);
+                break;
+            }
+            Err(connect_error) => last_error = connect_error.to_string(),
+        }
+    }
+    let mut stream = connected.ok_or(last_error)?;
+    stream
+        .set_read_timeout(Some(webhooks.timeout))
+        .map_err(error)?;
+    stream
+        .set_write_timeout(Some(webhooks.timeout))
+        .map_err(error)?;
+    stream.write_all(&request).map_err(error)?;
+
+    let mut status = String::new();
+    BufReader::new(stream)
+        .read_line(&mut status)
+        .map_err(error)?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.len() == 3 && code.starts_with('2') => Ok(()),
+        Some(code) => Err(format!("status {code}")),
+        None => Err("invalid response".to_owned()),
+    }
+}
+
+/// The signature of a notification `body` posted at `timestamp`, in seconds
+/// since the Unix epoch, as hexadecimal.
+pub fn signature(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    let mut message = timestamp.to_string().into_bytes();
+    message.push(b'.');
+    message.extend_from_slice(body);
+    hmac_sha256(secret, &message)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Whether the `X-TigerBeetle-Signature` header `header` is a valid
+/// signature of `body`, made within `tolerance` of `now`, in seconds since
+/// the Unix epoch.
+pub fn verify(secret: &[u8], header: &str, body: &[u8], now: u64, tolerance: Duration) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<u64>().ok(),
+            Some(("v1", value)) => signatures.push(value),
+            _ => {}
+        }
+    }
+    let timestamp = match timestamp {
+        Some(timestamp) if timestamp.abs_diff(now) <= tolerance.as_secs() => timestamp,
+        _ => return false,
+    };
+    let expected = signature(secret, timestamp, body);
+    signatures.iter().any(|signature| {
+        // In constant time, so the signature can't be guessed byte by byte.
+        signature.len() == expected.len()
+            && signature
+                .bytes()
+                .zip(expected.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    })
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&sha256(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| -> Vec<u8> { block.iter().map(|b| b ^ byte).collect() };
+
+    let mut inner = pad(0x36);
+    inner.extend_from_slice(message);
+    let mut outer = pad(0x5c);
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer)
+}
+
+/// SHA-256, as specified by FIPS 180-4.
+fn sha256(message: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&(message.len() as u64 * 8).to_be_bytes());
+
+    for chunk in padded.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Errors configuring [`Webhooks`].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum WebhookError {
+    /// The URL is not an `http` URL.
+    UrlInvalid(String),
+}
+
+impl std::error::Error for WebhookError {}
+impl fmt::Display for WebhookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UrlInvalid(url) => write!(f, "invalid webhook url {url}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn sha256() {
+        assert_eq!(
+            hex(&super::sha256(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(&super::sha256(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        // RFC 4231, test cases 2 and 6.
+        assert_eq!(
+            hex(&hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn verify() {
+        let header = format!("t=100,v1={}", signature(b"secret", 100, b"{}"));
+        let tolerance = Duration::from_secs(300);
+        assert!(super::verify(b"secret", &header, b"{}", 200, tolerance));
+        assert!(!super::verify(b"secret", &header, b"{}", 500, tolerance));
+        assert!(!super::verify(b"other", &header, b"{}", 200, tolerance));
+        assert!(!super::verify(b"secret", &header, b"[]", 200, tolerance));
+        assert!(!super::verify(b"secret", "v1=00", b"{}", 200, tolerance));
+    }
+
+    #[test]
+    fn endpoint() {
+        let endpoint = |url: &str| {
+            Webhooks::new().endpoint(url, b"").map(|webhooks| {
+                let endpoint = &webhooks.endpoints[0];
+                (
+                    endpoint.address.clone(),
+                    endpoint.host.clone(),
+                    endpoint.path.clone(),
+                )
+            })
+        };
+        assert_eq!(
+            endpoint("http://example.com"),
+            Ok(("example.com:80".into(), "example.com".into(), "/".into()))
+        );
+        assert_eq!(
+            endpoint("http://[::1]:8080/hooks?a=1"),
+            Ok((
+                "[::1]:8080".into(),
+                "[::1]:8080".into(),
+                "/hooks?a=1".into()
+            ))
+        );
+        assert_eq!(endpoint("http://[::1]/").unwrap().0, "[::1]:80");
+        for url in [
+            "https://example.com",
+            "example.com",
+            "http:///x",
+            "http://a b/",
+            "http://example.com/a b",
+            "http://example.com/\r\nX-Injected: 1",
+            "http://example.com/\n",
+            "http://example.com/\0",
+        ] {
+            assert_eq!(endpoint(url), Err(WebhookError::UrlInvalid(url.to_owned())));
+        }
+    }
+
+    #[test]
+    fn deliver() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let failures = Arc::new(Mutex::new(Vec::new()));
+        let webhooks = Webhooks::new()
+            .endpoint(&url, b"secret")
+            .unwrap()
+            .base_delay(Duration::from_millis(1))
+            .max_attempts(2)
+            .on_failure({
+                let failures = Arc::clone(&failures);
+                move |failure| failures.lock().unwrap().push(failure.attempts)
+            });
+        let emitter = Emitter::start(webhooks);
+
+        let transfers = [1, 2].map(|id| Transfer {
+            id,
+            ..Default::default()
+        });
+        let results = [CreateTransfersResult {
+            index: 1,
+            result: CreateTransferResult::ExceedsCredits,
+        }];
+        emitter.notify(&transfers, &results);
+        emitter.notify(&transfers[..1], &[]);
+        drop(emitter);
+
+        // The first attempt fails, and is retried.
+        let mut requests = Vec::new();
+        for status in [
+            "500 Internal Server Error",
+            "200 OK",
+            "503 Unavailable",
+            "503 Unavailable",
+        ] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+                request.extend_from_slice(line.as_bytes());
+                if line == "\r\n" {
+                    break;
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(stream, "HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n").unwrap();
+            requests.push((String::from_utf8(request).unwrap(), body));
+        }
+
+        let (head, body) = &requests[1];
+        assert!(head.starts_with("POST /hooks HTTP/1.1\r\n"));
+        let header = head
+            .lines()
+            .find_map(|line| line.strip_prefix("X-TigerBeetle-Signature: "))
+            .unwrap();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert!(super::verify(
+            b"secret",
+            header,
+            body,
+            now,
+            Duration::from_secs(60)
+        ));
+        assert_eq!(&requests[0].1, body);
+
+        let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["type"], "transfers.created");
+        assert_eq!(body["results"][0]["transfer"]["id"], "1");
+        assert_eq!(body["results"][0]["result"], "Ok");
+        assert_eq!(body["results"][1]["result"], "ExceedsCredits");
+
+        // The second notification fails both attempts.
+        for _ in 0..100 {
+            if !failures.lock().unwrap().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*failures.lock().unwrap(), [2]);
+    }
+}
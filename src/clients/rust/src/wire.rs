@@ -0,0 +1,237 @@
+//! Conversion of protocol types to and from the bytes of requests and
+//! replies.
+//!
+//! All reinterpretation of protocol types as bytes, and back, goes through
+//! [`Wire`], whose implementations are checked against the protocol's sizes
+//! at compile time.
+//!
+//! The protocol is little-endian, and the types are sent in their native
+//! layout, so the client only supports little-endian targets.
+
+use super::*;
+
+#[cfg(target_endian = "big")]
+compile_error!("the TigerBeetle protocol is little-endian; big-endian targets are not supported");
+
+/// A type that is sent to or received from the cluster as its in-memory
+/// representation.
+///
+/// # Safety
+///
+/// The type must be `repr(C)` without padding, and every bit pattern of its
+/// size must be a valid value. Flags are bitflags, which accept unknown
+/// bits, and results are raw codes, converted after decoding.
+pub(crate) unsafe trait Wire: Copy {}
+
+/// Implement [`Wire`], asserting that each type has its protocol size, so
+/// that a layout change fails the build rather than corrupting data.
+macro_rules! wire {
+    ($($t:ty = $size:expr),* $(,)?) => {
+        $(
+            // Safety: checked below, and by the ABI tests in lib.rs.
+            unsafe impl Wire for $t {}
+            const _: () = assert!(mem::size_of::<$t>() == $size);
+            const _: () = assert!(mem::align_of::<$t>() <= 16);
+        )*
+    };
+}
+
+wire! {
+    u128 = 16,
+    Account = 128,
+    Transfer = 128,
+    AccountBalance = 128,
+    AccountFilter = 128,
+    QueryFilter = 64,
+    tbc::tb_create_accounts_result_t = 8,
+    tbc::tb_create_transfers_result_t = 8,
+}
+
+/// The bytes of `items`.
+#[cfg(feature = "std")]
+pub(crate) fn encode<T: Wire>(items: &[T]) -> Vec<u8> {
+    // Safety: `Wire` types have no padding, so every byte is initialized.
+    unsafe { std::slice::from_raw_parts(items.as_ptr() as *const u8, mem::size_of_val(items)) }
+        .to_vec()
+}
+
+/// The items of a reply, or `None` if its size is not a whole number of
+/// items.
+///
+/// The bytes need not be aligned.
+pub(crate) fn decode<T: Wire>(bytes: &[u8]) -> Option<Vec<T>> {
+    let size = mem::size_of::<T>();
+    if bytes.len() % size != 0 {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(size)
+            // Safety: the chunk is the size of `T`, and any bits are a `T`.
+            .map(|chunk| unsafe { ptr::read_unaligned(chunk.as_ptr() as *const T) })
+            .collect(),
+    )
+}
+
+/// Decode the results of a create request of `events_len` events.
+///
+/// Returns `None` if a result's index is out of range, or its code unknown,
+/// so that a malformed reply is an error rather than a panic.
+pub(crate) fn decode_create_accounts_results(
+    bytes: &[u8],
+    events_len: usize,
+) -> Option<Vec<(usize, CreateAccountResult)>> {
+    decode::<tbc::tb_create_accounts_result_t>(bytes)?
+        .iter()
+        .map(|result| {
+            let index = usize::try_from(result.index)
+                .ok()
+                .filter(|&index| index < events_len)?;
+            Some((index, CreateAccountResult::from_code(result.result)?))
+        })
+        .collect()
+}
+
+/// See [`decode_create_accounts_results`].
+pub(crate) fn decode_create_transfers_results(
+    bytes: &[u8],
+    events_len: usize,
+) -> Option<Vec<(usize, CreateTransferResult)>> {
+    decode::<tbc::tb_create_transfers_result_t>(bytes)?
+        .iter()
+        .map(|result| {
+            let index = usize::try_from(result.index)
+                .ok()
+                .filter(|&index| index < events_len)?;
+            Some((index, CreateTransferResult::from_code(result.result)?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small deterministic pseudo-random generator (SplitMix64).
+    struct Prng(u64);
+
+    impl Prng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn u128(&mut self) -> u128 {
+            (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64())
+        }
+
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next_u64() as u8).collect()
+        }
+    }
+
+    fn account(prng: &mut Prng) -> Account {
+        Account {
+            id: prng.u128(),
+            debits_pending: prng.u128(),
+            debits_posted: prng.u128(),
+            credits_pending: prng.u128(),
+            credits_posted: prng.u128(),
+            user_data_128: prng.u128(),
+            user_data_64: prng.next_u64(),
+            user_data_32: prng.next_u64() as u32,
+            ledger: prng.next_u64() as u32,
+            code: prng.next_u64() as u16,
+            flags: AccountFlags::from_bits_retain(prng.next_u64() as u16),
+            timestamp: prng.next_u64(),
+            ..Default::default()
+        }
+    }
+
+    fn transfer(prng: &mut Prng) -> Transfer {
+        Transfer {
+            id: prng.u128(),
+            debit_account_id: prng.u128(),
+            credit_account_id: prng.u128(),
+            amount: prng.u128(),
+            pending_id: prng.u128(),
+            user_data_128: prng.u128(),
+            user_data_64: prng.next_u64(),
+            user_data_32: prng.next_u64() as u32,
+            timeout: prng.next_u64() as u32,
+            ledger: prng.next_u64() as u32,
+            code: prng.next_u64() as u16,
+            flags: TransferFlags::from_bits_retain(prng.next_u64() as u16),
+            timestamp: prng.next_u64(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut prng = Prng(42);
+        for len in 0..32 {
+            let accounts: Vec<Account> = (0..len).map(|_| account(&mut prng)).collect();
+            assert_eq!(decode::<Account>(&encode(&accounts)), Some(accounts));
+
+            let transfers: Vec<Transfer> = (0..len).map(|_| transfer(&mut prng)).collect();
+            let bytes = encode(&transfers);
+            assert_eq!(bytes.len(), len * 128);
+            assert_eq!(decode::<Transfer>(&bytes), Some(transfers.clone()));
+
+            // Misaligned.
+            let mut shifted = vec![0];
+            shifted.extend_from_slice(&bytes);
+            assert_eq!(decode::<Transfer>(&shifted[1..]), Some(transfers));
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes() {
+        let mut prng = Prng(7);
+        for _ in 0..1_000 {
+            let len = (prng.next_u64() % 600) as usize;
+            let bytes = prng.bytes(len);
+            let events_len = (prng.next_u64() % 8) as usize;
+
+            assert_eq!(decode::<Account>(&bytes).is_some(), len % 128 == 0);
+            assert_eq!(decode::<AccountBalance>(&bytes).is_some(), len % 128 == 0);
+            let _ = decode_create_accounts_results(&bytes, events_len);
+            let _ = decode_create_transfers_results(&bytes, events_len);
+        }
+    }
+
+    #[test]
+    fn create_results() {
+        let results = [
+            tbc::tb_create_transfers_result_t {
+                index: 1,
+                result: CreateTransferResult::Exists.into(),
+            },
+            tbc::tb_create_transfers_result_t {
+                index: 0,
+                result: CreateTransferResult::ExceedsCredits.into(),
+            },
+        ];
+        let bytes = encode(&results);
+        assert_eq!(
+            decode_create_transfers_results(&bytes, 2),
+            Some(vec![
+                (1, CreateTransferResult::Exists),
+                (0, CreateTransferResult::ExceedsCredits)
+            ])
+        );
+        // The index is out of range.
+        assert_eq!(decode_create_transfers_results(&bytes, 1), None);
+        // The code is unknown.
+        let unknown = [tbc::tb_create_transfers_result_t {
+            index: 0,
+            result: u32::MAX,
+        }];
+        assert_eq!(decode_create_transfers_results(&encode(&unknown), 1), None);
+        // A partial result.
+        assert_eq!(decode_create_transfers_results(&bytes[1..], 2), None);
+    }
+}
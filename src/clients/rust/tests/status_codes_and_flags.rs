@@ -158,7 +158,11 @@ fn round_trip_packet_status() {
         // Success not represented in tb::PacketStatus
         &[0],
         |c_value| tb::PacketStatus::from(c_value),
-        |rust_value| u8::from(rust_value),
+        |rust_value| u8::try_from(rust_value).unwrap(),
+    );
+    assert_eq!(
+        u8::try_from(tb::PacketStatus::Timeout),
+        Err(tb::PacketStatus::Timeout)
     );
 }
 
@@ -203,3 +207,30 @@ fn round_trip_query_filter_flags() {
         |rust_value| rust_value.bits(),
     );
 }
+
+#[test]
+fn flag_builders() {
+    assert_eq!(
+        tb::AccountFlags::empty()
+            .linked()
+            .debits_must_not_exceed_credits()
+            .credits_must_not_exceed_debits()
+            .history()
+            .imported()
+            .closed(),
+        tb::AccountFlags::all(),
+    );
+    assert_eq!(
+        tb::TransferFlags::empty()
+            .linked()
+            .pending()
+            .post_pending_transfer()
+            .void_pending_transfer()
+            .balancing_debit()
+            .balancing_credit()
+            .closing_debit()
+            .closing_credit()
+            .imported(),
+        tb::TransferFlags::all(),
+    );
+}
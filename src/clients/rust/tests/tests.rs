@@ -432,7 +432,8 @@ fn client_drop_causes_shutdown_status() -> anyhow::Result<()> {
 
 #[test]
 fn too_many_events() -> anyhow::Result<()> {
-    let client = test_client()?;
+    let mut client = test_client()?;
+    client.set_batch_splitting(false);
 
     block_on(async {
         let accounts = lots_of_accounts();
@@ -444,6 +445,30 @@ fn too_many_events() -> anyhow::Result<()> {
     })
 }
 
+#[test]
+fn too_many_events_split() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let mut accounts = lots_of_accounts();
+        // Make the last event fail so we can check its index is remapped.
+        let last_index = accounts.len() - 1;
+        accounts[last_index].ledger = 0;
+
+        let results = client.create_accounts(&accounts).await?;
+
+        assert_eq!(
+            results,
+            [tb::CreateAccountsResult {
+                index: last_index,
+                result: tb::CreateAccountResult::LedgerMustNotBeZero,
+            }]
+        );
+
+        Ok(())
+    })
+}
+
 fn lots_of_accounts() -> Vec<tb::Account> {
     let mut accounts = vec![];
     let num_accounts = 10_000;
@@ -613,6 +638,235 @@ fn concurrent_requests() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn post_and_void_pending_transfers() -> anyhow::Result<()> {
+    let account_id1 = tb::id();
+    let account_id2 = tb::id();
+    let pending_id1 = tb::id();
+    let pending_id2 = tb::id();
+
+    block_on(async {
+        let client = test_client()?;
+
+        let results = client
+            .create_accounts(&[
+                tb::Account {
+                    id: account_id1,
+                    ledger: TEST_LEDGER,
+                    code: TEST_CODE,
+                    ..Default::default()
+                },
+                tb::Account {
+                    id: account_id2,
+                    ledger: TEST_LEDGER,
+                    code: TEST_CODE,
+                    ..Default::default()
+                },
+            ])
+            .await?;
+        assert_eq!(results.len(), 0);
+
+        let pending = tb::Transfer {
+            debit_account_id: account_id1,
+            credit_account_id: account_id2,
+            amount: 100,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            flags: tb::TransferFlags::Pending,
+            ..Default::default()
+        };
+        let results = client
+            .create_transfers(&[
+                tb::Transfer {
+                    id: pending_id1,
+                    ..pending
+                },
+                tb::Transfer {
+                    id: pending_id2,
+                    ..pending
+                },
+            ])
+            .await?;
+        assert_eq!(results.len(), 0);
+
+        let results = client
+            .create_transfers(&[
+                tb::Transfer::post_pending_transfer(tb::id(), pending_id1, tb::AMOUNT_MAX),
+                tb::Transfer::void_pending_transfer(tb::id(), pending_id2),
+            ])
+            .await?;
+        assert_eq!(results.len(), 0);
+
+        let accounts = client.lookup_accounts(&[account_id1, account_id2]).await?;
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].debits_pending, 0);
+        assert_eq!(accounts[0].debits_posted, 100);
+        assert_eq!(accounts[1].credits_pending, 0);
+        assert_eq!(accounts[1].credits_posted, 100);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn client_pool() -> anyhow::Result<()> {
+    let address = &format!("127.0.0.1:{}", get_test_db().port);
+    let pool = tb::ClientBuilder::new()
+        .address(address)
+        .retry_policy(tb::RetryPolicy::new())
+        .build_pool(3)?;
+    assert_eq!(pool.size(), 3);
+
+    block_on(async {
+        let ids: Vec<u128> = (0..10).map(|_| tb::id()).collect();
+        let futures = ids.iter().map(|&id| {
+            let accounts = [tb::Account {
+                id,
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                ..Default::default()
+            }];
+            let pool = &pool;
+            async move { pool.create_accounts(&accounts).await }
+        });
+        for results in futures::future::join_all(futures).await {
+            assert_eq!(results?.len(), 0);
+        }
+
+        let accounts = assert_send(pool.lookup_accounts(&ids)).await?;
+        assert_eq!(accounts.len(), ids.len());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn retry_policy() -> anyhow::Result<()> {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let retries = Arc::new(AtomicU32::new(0));
+    let mut client = test_client()?;
+    client.set_retry_policy(
+        tb::RetryPolicy::new()
+            .retry_on(&[
+                tb::PacketStatus::ClientEvicted,
+                tb::PacketStatus::ClientShutdown,
+            ])
+            .on_retry({
+                let retries = Arc::clone(&retries);
+                move |_| {
+                    retries.fetch_add(1, Ordering::Relaxed);
+                }
+            }),
+    );
+
+    block_on(async {
+        let account_id = tb::id();
+        let results = client
+            .create_accounts(&[tb::Account {
+                id: account_id,
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                ..Default::default()
+            }])
+            .await?;
+        assert_eq!(results.len(), 0);
+
+        // Shutdown is never retried.
+        let request = client.lookup_accounts(&[account_id]);
+        drop(client);
+        match request.await {
+            Ok(accounts) => assert_eq!(accounts.len(), 1),
+            Err(status) => assert_eq!(status, tb::PacketStatus::ClientShutdown),
+        }
+
+        assert_eq!(retries.load(Ordering::Relaxed), 0);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn deadline_and_cancellation() -> anyhow::Result<()> {
+    use std::time::Duration;
+
+    let client = test_client()?;
+
+    block_on(async {
+        let account_id = tb::id();
+        let results = client
+            .create_accounts_with_deadline(
+                &[tb::Account {
+                    id: account_id,
+                    ledger: TEST_LEDGER,
+                    code: TEST_CODE,
+                    ..Default::default()
+                }],
+                Duration::from_secs(60),
+            )
+            .await?;
+        assert_eq!(results.len(), 0);
+
+        let token = tb::CancellationToken::new();
+        token.cancel();
+        let result = tb::with_cancellation(client.lookup_accounts(&[account_id]), &token).await;
+        match result {
+            Ok(accounts) => assert_eq!(accounts.len(), 1),
+            Err(error) => assert_eq!(error, tb::ClientError::Cancelled),
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn blocking_client() -> anyhow::Result<()> {
+    let address = &format!("127.0.0.1:{}", get_test_db().port);
+    let client = tb::blocking::Client::new(0, address)?;
+
+    let account_id1 = tb::id();
+    let account_id2 = tb::id();
+    let results = client.create_accounts(&[
+        tb::Account {
+            id: account_id1,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        },
+        tb::Account {
+            id: account_id2,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        },
+    ])?;
+    assert_eq!(results.len(), 0);
+
+    let transfer_id = tb::id();
+    let results = client.create_transfers(&[tb::Transfer {
+        id: transfer_id,
+        debit_account_id: account_id1,
+        credit_account_id: account_id2,
+        amount: 10,
+        ledger: TEST_LEDGER,
+        code: TEST_CODE,
+        ..Default::default()
+    }])?;
+    assert_eq!(results.len(), 0);
+
+    let accounts = client.lookup_accounts(&[account_id1, account_id2])?;
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0].debits_posted, 10);
+    assert_eq!(accounts[1].credits_posted, 10);
+
+    let transfers = client.lookup_transfers(&[transfer_id])?;
+    assert_eq!(transfers.len(), 1);
+
+    client.close();
+
+    Ok(())
+}
+
 // A potentially suprising behavior, documented in the crate docs.
 #[test]
 fn client_drop_loses_pending_transactions() -> anyhow::Result<()> {
@@ -1359,3 +1613,524 @@ fn example_lookup_transfers() -> Result<(), Box<dyn std::error::Error>> {
         Ok(())
     })
 }
+
+#[test]
+fn import_accounts_and_transfers() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        // Imported timestamps must be in the past, and later than any
+        // existing object's, so start from the current cluster time.
+        let probe = tb::Account {
+            id: tb::id(),
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let _ = client.create_accounts(&[probe]).await?;
+        let probe = client.lookup_accounts(&[probe.id]).await?;
+        let timestamp_base = probe[0].timestamp;
+
+        let accounts = [
+            tb::Account {
+                id: tb::id(),
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                timestamp: timestamp_base + 1,
+                ..Default::default()
+            },
+            tb::Account {
+                id: tb::id(),
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                timestamp: timestamp_base + 2,
+                ..Default::default()
+            },
+        ];
+        let results = client.import_accounts(&accounts).await?;
+        assert_eq!(results, []);
+
+        let transfers = [tb::Transfer {
+            id: tb::id(),
+            debit_account_id: accounts[0].id,
+            credit_account_id: accounts[1].id,
+            amount: 10,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            timestamp: timestamp_base + 3,
+            ..Default::default()
+        }];
+        let results = client.import_transfers(&transfers).await?;
+        assert_eq!(results, []);
+
+        let transfers_found = client.lookup_transfers(&[transfers[0].id]).await?;
+        assert_eq!(transfers_found[0].timestamp, timestamp_base + 3);
+        assert!(transfers_found[0]
+            .flags
+            .contains(tb::TransferFlags::Imported));
+
+        let result = client.import_accounts(&[accounts[1], accounts[0]]).await;
+        assert_eq!(
+            result,
+            Err(tb::ImportError::TimestampMustIncrease { index: 1 })
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn close_and_reopen_account() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let account = tb::Account {
+            id: tb::id(),
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let control = tb::Account {
+            id: tb::id(),
+            ..account
+        };
+        let results = client.create_accounts(&[account, control]).await?;
+        assert_eq!(results, []);
+
+        let closing_id = tb::id();
+        let closing =
+            tb::Transfer::close_account(closing_id, account.id, control.id, TEST_LEDGER, TEST_CODE);
+        let results = client.create_transfers(&[closing]).await?;
+        assert_eq!(results, []);
+
+        let accounts = client.lookup_accounts(&[account.id, control.id]).await?;
+        assert!(accounts[0].flags.contains(tb::AccountFlags::Closed));
+        assert!(!accounts[1].flags.contains(tb::AccountFlags::Closed));
+
+        let transfer = tb::Transfer {
+            id: tb::id(),
+            debit_account_id: account.id,
+            credit_account_id: control.id,
+            amount: 1,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let results = client.create_transfers(&[transfer]).await?;
+        assert_eq!(
+            results,
+            [tb::CreateTransfersResult {
+                index: 0,
+                result: tb::CreateTransferResult::DebitAccountAlreadyClosed,
+            }]
+        );
+
+        let reopening = tb::Transfer::reopen_account(tb::id(), closing_id);
+        let results = client.create_transfers(&[reopening]).await?;
+        assert_eq!(results, []);
+
+        let accounts = client.lookup_accounts(&[account.id]).await?;
+        assert!(!accounts[0].flags.contains(tb::AccountFlags::Closed));
+
+        let results = client.create_transfers(&[transfer]).await?;
+        assert_eq!(results, []);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn balancing_transfers() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let limited = tb::Account {
+            id: tb::id(),
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            flags: tb::AccountFlags::CreditsMustNotExceedDebits,
+            ..Default::default()
+        };
+        let other = tb::Account {
+            id: tb::id(),
+            flags: tb::AccountFlags::None,
+            ..limited
+        };
+        let results = client.create_accounts(&[limited, other]).await?;
+        assert_eq!(results, []);
+
+        let transfers = [
+            tb::Transfer {
+                id: tb::id(),
+                debit_account_id: limited.id,
+                credit_account_id: other.id,
+                amount: 100,
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                ..Default::default()
+            },
+            tb::Transfer::balancing_credit(
+                tb::id(),
+                other.id,
+                limited.id,
+                tb::AMOUNT_MAX,
+                TEST_LEDGER,
+                TEST_CODE,
+            ),
+            tb::Transfer::balancing_credit(
+                tb::id(),
+                other.id,
+                limited.id,
+                10,
+                TEST_LEDGER,
+                TEST_CODE,
+            ),
+        ];
+        let results = client.create_transfers(&transfers).await?;
+        assert_eq!(results, []);
+
+        let transfers = client
+            .lookup_transfers(&[transfers[1].id, transfers[2].id])
+            .await?;
+        assert_eq!(transfers[0].amount, 100);
+        assert_eq!(transfers[1].amount, 0);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn create_transfers_checked() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let accounts = [
+            tb::Account {
+                id: tb::id(),
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                ..Default::default()
+            },
+            tb::Account {
+                id: tb::id(),
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                ..Default::default()
+            },
+        ];
+        client.create_accounts_checked(&accounts).await?;
+
+        let transfer = tb::Transfer {
+            id: tb::id(),
+            debit_account_id: accounts[0].id,
+            credit_account_id: accounts[1].id,
+            amount: 10,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        client.create_transfers_checked(&[transfer]).await?;
+
+        let another = tb::Transfer {
+            id: tb::id(),
+            ..transfer
+        };
+        let result = client.create_transfers_checked(&[transfer, another]).await;
+        assert_eq!(
+            result,
+            Err(tb::CreateTransfersError::Failed(vec![tb::FailedTransfer {
+                index: 0,
+                transfer,
+                result: tb::CreateTransferResult::Exists,
+            }]))
+        );
+
+        Ok(())
+    })
+}
+
+#[test]
+fn treat_exists_as_success() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let account = tb::Account {
+            id: tb::id(),
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let options = tb::SubmitOptions {
+            treat_exists_as_success: true,
+        };
+
+        let results = client
+            .create_accounts_with_options(&[account], options)
+            .await?;
+        assert_eq!(results, []);
+
+        // Resubmitting the same account succeeds.
+        let results = client
+            .create_accounts_with_options(&[account], options)
+            .await?;
+        assert_eq!(results, []);
+
+        // Reusing the id for a different account does not.
+        let different = tb::Account {
+            code: TEST_CODE + 1,
+            ..account
+        };
+        let results = client
+            .create_accounts_with_options(&[different], options)
+            .await?;
+        assert_eq!(
+            results,
+            [tb::CreateAccountsResult {
+                index: 0,
+                result: tb::CreateAccountResult::ExistsWithDifferentCode,
+            }]
+        );
+        assert!(results[0].result.is_exists_with_different_fields());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn client_builder() -> anyhow::Result<()> {
+    let address = &format!("127.0.0.1:{}", get_test_db().port);
+    let client = tb::Client::builder()
+        .cluster_id_hex("0x0")
+        .address(address)
+        .batch_splitting(false)
+        .build()?;
+
+    block_on(async {
+        let results = client.lookup_accounts(&[tb::id()]).await?;
+        assert_eq!(results, []);
+
+        let result = client.create_accounts(&lots_of_accounts()).await;
+        assert_eq!(result, Err(tb::PacketStatus::TooMuchData));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn update_addresses() -> anyhow::Result<()> {
+    let client = tb::Client::builder()
+        .address(&format!("localhost:{}", get_test_db().port))
+        .address_refresh_interval(std::time::Duration::from_millis(10))
+        .build()?;
+
+    block_on(async {
+        let account_id = tb::id();
+        let request = client.create_accounts(&[tb::Account {
+            id: account_id,
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        }]);
+
+        let addresses = tb::addresses::parse(&get_test_db().port.to_string())?;
+        client.update_addresses(&addresses)?;
+        client.refresh_addresses()?;
+
+        assert_eq!(request.await?, []);
+        let accounts = client.lookup_accounts(&[account_id]).await?;
+        assert_eq!(accounts.len(), 1);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn ping() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    let round_trip = block_on(client.ping())?;
+    assert!(round_trip > std::time::Duration::ZERO);
+
+    Ok(())
+}
+
+#[test]
+fn journal_entry() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let account = tb::Account {
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let cash = tb::Account {
+            id: tb::id(),
+            ..account
+        };
+        let revenue = tb::Account {
+            id: tb::id(),
+            ..account
+        };
+        let tax = tb::Account {
+            id: tb::id(),
+            ..account
+        };
+        let results = client.create_accounts(&[cash, revenue, tax]).await?;
+        assert_eq!(results, []);
+
+        let mut accounts = tb::ledger::Accounts::new();
+        accounts.insert("cash", cash.id, TEST_LEDGER);
+        accounts.insert("revenue", revenue.id, TEST_LEDGER);
+        accounts.insert("tax", tax.id, TEST_LEDGER);
+
+        let entry = tb::ledger::JournalEntry::new(tb::id(), TEST_CODE)
+            .debit("cash", 108)
+            .credit("revenue", 100)
+            .credit("tax", 8);
+        let results = client
+            .create_transfers(&entry.to_transfers(&accounts)?)
+            .await?;
+        assert_eq!(results, []);
+
+        let balances = client
+            .lookup_accounts(&[cash.id, revenue.id, tax.id])
+            .await?;
+        assert_eq!(balances[0].debits_posted, 108);
+        assert_eq!(balances[1].credits_posted, 100);
+        assert_eq!(balances[2].credits_posted, 8);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn create_chart_of_accounts() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let mut chart = tb::ledger::ChartOfAccounts::new();
+        for name in ["assets:cash", "income:sales"] {
+            chart.insert(
+                name,
+                tb::ledger::AccountDefinition {
+                    id: tb::id(),
+                    ledger: TEST_LEDGER,
+                    code: TEST_CODE,
+                    flags: tb::AccountFlags::None,
+                },
+            );
+        }
+
+        client.create_chart_of_accounts(&chart).await?;
+        client.create_chart_of_accounts(&chart).await?;
+
+        let id = chart.get("assets:cash").unwrap().id;
+        let accounts = client.lookup_accounts(&[id]).await?;
+        assert_eq!(accounts.len(), 1);
+
+        chart.accounts.get_mut("assets:cash").unwrap().code += 1;
+        match client.create_chart_of_accounts(&chart).await {
+            Err(tb::CreateAccountsError::Failed(failed)) => {
+                assert_eq!(failed.len(), 1);
+                assert_eq!(failed[0].account.id, id);
+                assert_eq!(
+                    failed[0].result,
+                    tb::CreateAccountResult::ExistsWithDifferentCode
+                );
+            }
+            result => panic!("{result:?}"),
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn ensure_accounts() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let spec = tb::AccountSpec {
+            id: tb::id(),
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let spec_new = tb::AccountSpec {
+            id: tb::id(),
+            ..spec
+        };
+
+        let summary = client.ensure_accounts(&[spec]).await?;
+        assert_eq!(summary.created, [spec.id]);
+        assert_eq!(summary.existing, []);
+
+        let summary = client.ensure_accounts(&[spec, spec_new]).await?;
+        assert_eq!(summary.created, [spec_new.id]);
+        assert_eq!(summary.existing, [spec.id]);
+
+        let spec_changed = tb::AccountSpec {
+            user_data_32: 1,
+            ..spec
+        };
+        match client.ensure_accounts(&[spec_changed]).await {
+            Err(tb::CreateAccountsError::Failed(failed)) => assert_eq!(
+                failed[0].result,
+                tb::CreateAccountResult::ExistsWithDifferentUserData32
+            ),
+            result => panic!("{result:?}"),
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn one_to_many() -> anyhow::Result<()> {
+    let client = test_client()?;
+
+    block_on(async {
+        let accounts: Vec<tb::Account> = (0..4)
+            .map(|_| tb::Account {
+                id: tb::id(),
+                ledger: TEST_LEDGER,
+                code: TEST_CODE,
+                ..Default::default()
+            })
+            .collect();
+        let results = client.create_accounts(&accounts).await?;
+        assert_eq!(results, []);
+
+        let payment = tb::recipes::OneToMany {
+            source_account_id: accounts[0].id,
+            control_account_id: accounts[1].id,
+            total: 100,
+            destinations: vec![
+                tb::recipes::Destination {
+                    account_id: accounts[2].id,
+                    amount: 97,
+                },
+                tb::recipes::Destination {
+                    account_id: accounts[3].id,
+                    amount: 3,
+                },
+            ],
+            ledger: TEST_LEDGER,
+            code: TEST_CODE,
+            ..Default::default()
+        };
+        let results = client.create_transfers(&payment.transfers()?).await?;
+        assert_eq!(results, []);
+
+        let ids: Vec<u128> = accounts.iter().map(|account| account.id).collect();
+        let balances = client.lookup_accounts(&ids).await?;
+        assert_eq!(balances[0].debits_posted, 100);
+        assert_eq!(balances[1].debits_posted, 100);
+        assert_eq!(balances[1].credits_posted, 100);
+        assert_eq!(balances[2].credits_posted, 97);
+        assert_eq!(balances[3].credits_posted, 3);
+
+        Ok(())
+    })
+}